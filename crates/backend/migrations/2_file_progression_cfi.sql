@@ -0,0 +1 @@
+ALTER TABLE file_progression ADD COLUMN cfi TEXT;
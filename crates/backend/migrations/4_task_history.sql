@@ -0,0 +1,13 @@
+CREATE TABLE task_history (
+    id INTEGER NOT NULL UNIQUE,
+
+    name TEXT NOT NULL,
+    outcome INT NOT NULL,
+    error TEXT,
+    items_processed INTEGER NOT NULL,
+
+    started_at DATETIME NOT NULL,
+    ended_at DATETIME NOT NULL,
+
+    PRIMARY KEY("id" AUTOINCREMENT)
+);
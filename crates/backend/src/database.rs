@@ -1,9 +1,18 @@
-use sqlx::{migrate::MigrateDatabase, Sqlite, SqlitePool};
+use std::time::Duration;
+
+use sqlx::{
+    migrate::MigrateDatabase,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode},
+    Sqlite, SqlitePool,
+};
 
 use crate::Result;
 
 const DATABASE_PATH: &str = "./app/database.db";
 
+/// How long a connection waits for another one to finish writing before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub type SqlPool = sqlx::Pool<sqlx::Sqlite>;
 pub type SqlConnection = sqlx::pool::PoolConnection<sqlx::Sqlite>;
 
@@ -20,7 +29,13 @@ pub async fn init() -> Result<SqlPool> {
         debug!("Database already exists");
     }
 
-    let pool = SqlitePool::connect(DATABASE_PATH).await?;
+    // Tasks and requests write at the same time. WAL lets them read while another is writing.
+    let options = SqliteConnectOptions::new()
+        .filename(DATABASE_PATH)
+        .journal_mode(SqliteJournalMode::Wal)
+        .busy_timeout(BUSY_TIMEOUT);
+
+    let pool = SqlitePool::connect_with(options).await?;
 
     match sqlx::migrate!("./migrations").run(&pool).await {
         Ok(_) => debug!("Migration success"),
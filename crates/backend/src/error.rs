@@ -15,6 +15,7 @@ use image::ImageError;
 use lettre::address::AddressError;
 use lettre::error::Error as LettreError;
 use lettre::transport::smtp::Error as SmtpError;
+use notify::Error as NotifyError;
 use reqwest::Error as HttpError;
 use serde::de::value::Error as SerdeValueError;
 use serde_json::Error as JsonError;
@@ -145,6 +146,8 @@ pub enum Error {
     SqlxMigration(#[from] sqlx::migrate::MigrateError),
     #[error("Bcrypt Error: {0}")]
     Bcrypt(#[from] BcryptError),
+    #[error("Notify Error: {0}")]
+    Notify(#[from] NotifyError),
 
     #[error(transparent)]
     Common(#[from] CommonError),
@@ -167,6 +170,10 @@ pub enum InternalError {
     #[error("The item does not exist")]
     ItemMissing,
 
+    // Tasks
+    #[error("The task was cancelled")]
+    TaskCancelled,
+
     // SQL
     #[error("Invalid Model")]
     InvalidModel,
@@ -1,10 +1,16 @@
+use std::{io::Cursor, path::Path};
+
 use actix_files::NamedFile;
 use actix_web::{
     delete, get,
     http::header::{ContentDisposition, HeaderValue},
-    post, web,
+    post, web, Either as ActixEither, HttpResponse,
 };
 
+use bookie::{
+    epub::{EpubBook, MetadataUpdate},
+    Book,
+};
 use chrono::Utc;
 use common::{
     api::{ApiErrorResponse, DeletionResponse, WrappingResponse},
@@ -15,6 +21,7 @@ use common_local::{
     BookType, DisplayItem, ModifyValuesBy, Poster, SearchFor, SearchForBooksBy, SearchType,
 };
 use serde_qs::actix::QsQuery;
+use sqlx::SqliteConnection;
 
 use crate::{
     http::{JsonResponse, MemberCookie},
@@ -23,7 +30,7 @@ use crate::{
         BookModel, BookPersonModel, FileModel, FileProgressionModel, ImageLinkModel, LibraryModel,
         PersonModel, UploadedImageModel,
     },
-    queue_task, store_image,
+    queue_task, scanner, store_image,
     task::{self, queue_task_priority},
     Error, SqlPool, WebResult,
 };
@@ -369,6 +376,48 @@ pub async fn update_book_info(
         api::PostBookBody::Edit(edit) => {
             BookModel::edit_book_by_id(book_id, edit, &mut *db.acquire().await?).await?;
         }
+
+        api::PostBookBody::EmbedMetadata => {
+            let book = BookModel::find_one_by_id(book_id, &mut *db.acquire().await?)
+                .await?
+                .ok_or_else(|| Error::from(crate::InternalError::ItemMissing))?;
+
+            let update = create_metadata_update(&book, &mut *db.acquire().await?).await?;
+
+            for mut file in FileModel::find_by_book_id(book_id, &mut *db.acquire().await?).await? {
+                if !file.file_type.eq_ignore_ascii_case("epub") {
+                    continue;
+                }
+
+                let path = Path::new(&file.path);
+
+                // Written next to the original so the rename stays on the same file system.
+                // The temporary file is removed when dropped if anything fails before then.
+                let mut temp = tempfile::Builder::new()
+                    .suffix(".tmp")
+                    .tempfile_in(path.parent().unwrap_or_else(|| Path::new(".")))?;
+
+                EpubBook::load_from_path(path)?.write_with_metadata(&update, temp.as_file_mut())?;
+
+                // Temporary files are only readable by us.
+                temp.as_file()
+                    .set_permissions(tokio::fs::metadata(path).await?.permissions())?;
+
+                temp.persist(path).map_err(|e| e.error)?;
+
+                let meta = tokio::fs::metadata(path).await?;
+
+                file.file_size = meta.len() as i64;
+                file.modified_at = scanner::get_modified_at(&meta)?;
+                file.inode = scanner::get_inode(&meta);
+
+                if let Some(hash) = EpubBook::load_from_path(&file.path)?.compute_hash() {
+                    file.hash = hash;
+                }
+
+                file.update(&mut *db.acquire().await?).await?;
+            }
+        }
     }
 
     Ok(web::Json(WrappingResponse::okay("success")))
@@ -377,8 +426,9 @@ pub async fn update_book_info(
 #[get("/book/{id}/download")]
 pub async fn download_book(
     book_id: web::Path<BookId>,
+    query: web::Query<api::DownloadBookQuery>,
     db: web::Data<SqlPool>,
-) -> WebResult<NamedFile> {
+) -> WebResult<ActixEither<NamedFile, HttpResponse>> {
     let mut files = FileModel::find_by_book_id(*book_id, &mut *db.acquire().await?).await?;
 
     if files.is_empty() {
@@ -395,17 +445,104 @@ pub async fn download_book(
 
     let file_model = files.remove(index);
 
-    Ok(NamedFile::open_async(file_model.path)
-        .await
-        .map_err(crate::Error::from)?
-        .set_content_disposition(ContentDisposition::from_raw(
-            &HeaderValue::from_str(&format!(
-                r#"attachment; filename="{}.{}""#,
-                file_model.file_name.replace('"', ""), // Shouldn't have " in the file_name but just in-case.
-                file_model.file_type,
-            ))
-            .unwrap(),
-        )?))
+    let is_epub = file_model.file_type.eq_ignore_ascii_case("epub");
+    let is_kepub = query.kepub && is_epub;
+
+    let content_disposition = ContentDisposition::from_raw(
+        &HeaderValue::from_str(&format!(
+            r#"attachment; filename="{}.{}""#,
+            file_model.file_name.replace('"', ""), // Shouldn't have " in the file_name but just in-case.
+            if is_kepub {
+                "kepub.epub"
+            } else {
+                &file_model.file_type
+            },
+        ))
+        .unwrap(),
+    )?;
+
+    if (query.embed_metadata || is_kepub) && is_epub {
+        let update = if query.embed_metadata {
+            let book = BookModel::find_one_by_id(*book_id, &mut *db.acquire().await?)
+                .await?
+                .ok_or_else(|| Error::from(crate::InternalError::ItemMissing))?;
+
+            create_metadata_update(&book, &mut *db.acquire().await?).await?
+        } else {
+            MetadataUpdate::default()
+        };
+
+        let mut buffer = Cursor::new(Vec::new());
+        let mut epub = EpubBook::load_from_path(&file_model.path)?;
+
+        if is_kepub {
+            epub.write_kepub(&update, &mut buffer)?;
+        } else {
+            epub.write_with_metadata(&update, &mut buffer)?;
+        }
+
+        return Ok(ActixEither::Right(
+            HttpResponse::Ok()
+                .content_type("application/epub+zip")
+                .insert_header(content_disposition)
+                .body(buffer.into_inner()),
+        ));
+    }
+
+    Ok(ActixEither::Left(
+        NamedFile::open_async(file_model.path)
+            .await
+            .map_err(crate::Error::from)?
+            .set_content_disposition(content_disposition),
+    ))
+}
+
+/// Create the values to embed into the book files from the ones we have stored.
+pub(super) async fn create_metadata_update(
+    book: &BookModel,
+    db: &mut SqliteConnection,
+) -> crate::Result<MetadataUpdate> {
+    let people = PersonModel::find_by_book_id(book.id, db).await?;
+
+    let series = if let Some(parent_id) = book.parent_id {
+        BookModel::find_one_by_id(parent_id, db)
+            .await?
+            .and_then(|v| v.title)
+            .map(|title| (title, book.index.map(|v| v as f64)))
+    } else {
+        None
+    };
+
+    let cover = if let Some(path) = book.thumb_url.as_value() {
+        let path = crate::image::prefixhash_to_path(path);
+
+        let image = tokio::fs::read(path).await?;
+
+        image::guess_format(&image)
+            .ok()
+            .and_then(|v| v.extensions_str().first())
+            .map(|ext| (ext.to_string(), image))
+    } else {
+        None
+    };
+
+    let identifiers = if book.source.agent.as_ref() != "local" {
+        vec![(
+            book.source.agent.as_ref().to_string(),
+            book.source.value.clone(),
+        )]
+    } else {
+        Vec::new()
+    };
+
+    Ok(MetadataUpdate {
+        title: book.title.clone(),
+        creators: Some(people.into_iter().map(|v| v.name).collect::<Vec<_>>())
+            .filter(|v| !v.is_empty()),
+        identifiers,
+        series,
+        cover,
+    })
 }
 
 #[get("/book/{id}/posters")]
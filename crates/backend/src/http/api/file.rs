@@ -2,15 +2,23 @@ use std::io::Cursor;
 
 use actix_files::NamedFile;
 use actix_web::http::header::{ContentDisposition, HeaderValue};
-use actix_web::{delete, get, post, web, HttpResponse};
-
-use common::api::WrappingResponse;
+use actix_web::{delete, get, post, web, Either, HttpResponse};
+
+use bookie::{
+    audio::AudioBook,
+    cb::ComicBook,
+    epub::{cfi::Cfi, EpubBook, MetadataUpdate},
+    Book, BookSearch, Rendition, SanitizeOptions,
+};
+use common::api::{ApiErrorResponse, WrappingResponse};
 use common_local::api::{FileUnwrappedHeaderType, FileUnwrappedInfo};
 use common_local::{api, Chapter, FileId, Progression};
 
 use crate::http::{JsonResponse, MemberCookie};
+use crate::model::BookModel;
 use crate::model::FileModel;
 use crate::model::FileProgressionModel;
+use crate::model::LibraryModel;
 use crate::{Result, SqlPool, WebResult};
 
 const BOOK_STYLING: &str = include_str!("../../../../../app/book_stylings.css");
@@ -32,10 +40,13 @@ pub async fn load_file_resource(
     let mut book = bookie::load_from_path(&file.path)?.unwrap();
 
     let body = if res.configure_pages {
+        let sanitize = get_sanitize_options(&file, &db).await?;
+
         match book.read_path_as_bytes(
             &resource_path,
             Some(&format!("/api/file/{file_id}/res")),
             Some(&[BOOK_STYLING]),
+            Some(sanitize),
         ) {
             Ok(v) => v,
             Err(e) => {
@@ -47,7 +58,7 @@ pub async fn load_file_resource(
             }
         }
     } else {
-        match book.read_path_as_bytes(&resource_path, None, None) {
+        match book.read_path_as_bytes(&resource_path, None, None, None) {
             Ok(v) => v,
             Err(e) => {
                 error!(
@@ -64,6 +75,12 @@ pub async fn load_file_resource(
     // TODO: Determine if needed.
     if resource_path.ends_with("xhtml") {
         ok.insert_header(("Content-Type", "application/xhtml+xml"));
+    } else if resource_path.ends_with("pdf") {
+        ok.insert_header(("Content-Type", "application/pdf"));
+    } else if resource_path.ends_with("mp3") {
+        ok.insert_header(("Content-Type", "audio/mpeg"));
+    } else if resource_path.ends_with("m4a") || resource_path.ends_with("mp4") {
+        ok.insert_header(("Content-Type", "audio/mp4"));
     }
 
     Ok(ok.body(body))
@@ -101,14 +118,73 @@ pub async fn load_file_pages(
         },
     )?;
 
+    let is_fixed_layout = book.get_rendition().is_fixed_layout();
+    let sanitize = get_sanitize_options(&file, &db).await?;
+
     let mut items = Vec::new();
 
     for chap in start_chap..end_chap {
         book.set_chapter(chap);
 
+        let file_path = book.get_page_path();
+
+        // Fixed layout pages keep their own styling. They're displayed in their own frame and scaled to fit.
+        if is_fixed_layout {
+            let viewport = book.get_chapter_rendition()?.viewport;
+
+            items.push(Chapter {
+                info: FileUnwrappedInfo {
+                    header_items: vec![FileUnwrappedHeaderType {
+                        name: String::from("style"),
+                        attributes: Vec::new(),
+                        chars: Some(String::from(BOOK_STYLING)),
+                    }],
+                    header_hash: String::from("ignored"),
+                    inner_body: format!(
+                        r#"<div class="comic-strip fixed-layout-page"><iframe src="/api/file/{file_id}/res/{}" scrolling="no" {}></iframe></div>"#,
+                        file_path.display(),
+                        viewport.map_or_else(String::new, |v| format!(
+                            r#"data-width="{w}" data-height="{h}" style="width: {w}px; height: {h}px;""#,
+                            w = v.width,
+                            h = v.height,
+                        )),
+                    ),
+                },
+                file_path,
+                value: chap,
+            });
+
+            continue;
+        }
+
+        // Document pages which aren't a single image are served as their own PDF.
+        if file.is_file_type_document()
+            && file_path.extension().and_then(|v| v.to_str()) == Some("pdf")
+        {
+            items.push(Chapter {
+                info: FileUnwrappedInfo {
+                    header_items: vec![FileUnwrappedHeaderType {
+                        name: String::from("style"),
+                        attributes: Vec::new(),
+                        chars: Some(String::from(BOOK_STYLING)),
+                    }],
+                    header_hash: String::from("ignored"),
+                    inner_body: format!(
+                        r#"<div class="comic-strip"><object type="application/pdf" data="/api/file/{file_id}/res/{}" style="width: 100%; height: 100%;"></object></div>"#,
+                        file_path.display()
+                    ),
+                },
+                file_path,
+                value: chap,
+            });
+
+            continue;
+        }
+
         let body = match book.read_page_as_bytes(
             Some(&format!("/api/file/{file_id}/res")),
             Some(&[BOOK_STYLING]),
+            Some(sanitize),
         ) {
             Ok(v) => v,
             Err(e) => {
@@ -121,9 +197,7 @@ pub async fn load_file_pages(
             }
         };
 
-        let file_path = book.get_page_path();
-
-        let info = if file.is_file_type_comic() {
+        let info = if file.is_file_type_comic() || file.is_file_type_document() {
             let (width, height) = image::io::Reader::new(Cursor::new(&body))
                 .with_guessed_format()?
                 .into_dimensions()?;
@@ -163,6 +237,20 @@ pub async fn load_file_pages(
     )))
 }
 
+/// Scripts are only kept if the library the file is in allows them.
+async fn get_sanitize_options(file: &FileModel, db: &SqlPool) -> Result<SanitizeOptions> {
+    let library = LibraryModel::find_one_by_id(file.library_id, &mut *db.acquire().await?).await?;
+
+    let settings = match library {
+        Some(library) => library.parse_settings_or_default()?,
+        None => Default::default(),
+    };
+
+    Ok(SanitizeOptions {
+        allow_scripts: settings.allow_scripts,
+    })
+}
+
 // TODO: Add body requests for specifics
 #[get("/file/{id}")]
 pub async fn load_file(
@@ -172,7 +260,34 @@ pub async fn load_file(
 ) -> WebResult<JsonResponse<Option<api::GetFileByIdResponse>>> {
     Ok(web::Json(WrappingResponse::okay(
         if let Some(file) = FileModel::find_one_by_id(*file_id, &mut *db.acquire().await?).await? {
-            let mut book = bookie::load_from_path(&file.path)?.unwrap();
+            let mut has_media_overlay = false;
+            let mut rendition = Rendition::default();
+
+            let (toc, audio) = if file.is_file_type_audio_book() {
+                let mut book = AudioBook::load_from_path(&file.path)?;
+
+                let audio = api::AudioBookInfo {
+                    duration: book.duration(),
+                    narrators: book.find(BookSearch::Other("Narrator")).unwrap_or_default(),
+                    tracks: book.get_tracks().to_vec(),
+                    chapters: book.get_chapters().to_vec(),
+                };
+
+                (book.get_table_of_contents()?, Some(audio))
+            } else if file.file_type.eq_ignore_ascii_case("epub") {
+                let mut book = EpubBook::load_from_path(&file.path)?;
+
+                has_media_overlay = book.has_media_overlays();
+                rendition = book.get_rendition();
+
+                (book.get_table_of_contents()?, None)
+            } else {
+                let mut book = bookie::load_from_path(&file.path)?.unwrap();
+
+                rendition = book.get_rendition();
+
+                (book.get_table_of_contents()?, None)
+            };
 
             Some(api::GetFileByIdResponse {
                 progress: FileProgressionModel::find_one(
@@ -183,7 +298,10 @@ pub async fn load_file(
                 .await?
                 .map(|v| v.into()),
 
-                toc: book.get_table_of_contents()?.unwrap_or_default(),
+                toc: toc.unwrap_or_default(),
+                audio,
+                has_media_overlay,
+                rendition,
                 media: file.into(),
             })
         } else {
@@ -195,23 +313,194 @@ pub async fn load_file(
 #[get("/file/{id}/download")]
 pub async fn download_file(
     file_id: web::Path<FileId>,
+    query: web::Query<api::DownloadBookQuery>,
     db: web::Data<SqlPool>,
-) -> WebResult<NamedFile> {
+) -> WebResult<Either<NamedFile, HttpResponse>> {
     let file_model = FileModel::find_one_by_id(*file_id, &mut *db.acquire().await?)
         .await?
         .ok_or(crate::Error::Internal(crate::InternalError::ItemMissing))?;
 
-    Ok(NamedFile::open_async(file_model.path)
+    if query.kepub && file_model.file_type.eq_ignore_ascii_case("epub") {
+        let update = match file_model.book_id {
+            Some(book_id) if query.embed_metadata => {
+                let book = BookModel::find_one_by_id(book_id, &mut *db.acquire().await?)
+                    .await?
+                    .ok_or(crate::Error::Internal(crate::InternalError::ItemMissing))?;
+
+                super::book::create_metadata_update(&book, &mut *db.acquire().await?).await?
+            }
+            _ => MetadataUpdate::default(),
+        };
+
+        let mut buffer = Cursor::new(Vec::new());
+
+        EpubBook::load_from_path(&file_model.path)?.write_kepub(&update, &mut buffer)?;
+
+        return Ok(Either::Right(
+            HttpResponse::Ok()
+                .content_type("application/epub+zip")
+                .insert_header(ContentDisposition::from_raw(
+                    &HeaderValue::from_str(&format!(
+                        r#"attachment; filename="{}.kepub.epub""#,
+                        file_model.file_name.replace('"', ""),
+                    ))
+                    .unwrap(),
+                )?)
+                .body(buffer.into_inner()),
+        ));
+    }
+
+    // A folder of images is zipped up when it's downloaded.
+    if file_model.is_file_type_comic() && std::path::Path::new(&file_model.path).is_dir() {
+        let mut buffer = Cursor::new(Vec::new());
+
+        ComicBook::load_from_path(&file_model.path)?.write_cbz(&mut buffer)?;
+
+        return Ok(Either::Right(
+            HttpResponse::Ok()
+                .content_type("application/vnd.comicbook+zip")
+                .insert_header(ContentDisposition::from_raw(
+                    &HeaderValue::from_str(&format!(
+                        r#"attachment; filename="{}.cbz""#,
+                        file_model.file_name.replace('"', ""),
+                    ))
+                    .unwrap(),
+                )?)
+                .body(buffer.into_inner()),
+        ));
+    }
+
+    Ok(Either::Left(
+        NamedFile::open_async(file_model.path)
+            .await
+            .map_err(crate::Error::from)?
+            .set_content_disposition(ContentDisposition::from_raw(
+                &HeaderValue::from_str(&format!(
+                    r#"attachment; filename="{}.{}""#,
+                    file_model.file_name.replace('"', ""), // Shouldn't have " in the file_name but just in-case.
+                    file_model.file_type,
+                ))
+                .unwrap(),
+            )?),
+    ))
+}
+
+/// Stream an audiobook track. Range requests are handled by [`NamedFile`] so the player can seek.
+#[get("/file/{id}/audio/{track}")]
+pub async fn load_file_audio(
+    web_path: web::Path<(FileId, usize)>,
+    db: web::Data<SqlPool>,
+) -> WebResult<NamedFile> {
+    let (file_id, track) = web_path.into_inner();
+
+    let file_model = FileModel::find_one_by_id(file_id, &mut *db.acquire().await?)
+        .await?
+        .filter(|v| v.is_file_type_audio_book())
+        .ok_or(crate::Error::Internal(crate::InternalError::ItemMissing))?;
+
+    let path = bookie::audio::get_track_paths(&file_model.path)?
+        .into_iter()
+        .nth(track)
+        .ok_or(crate::Error::Internal(crate::InternalError::ItemMissing))?;
+
+    let content_type = match path.extension().and_then(|v| v.to_str()) {
+        Some("mp3") => "audio/mpeg",
+        _ => "audio/mp4",
+    };
+
+    Ok(NamedFile::open_async(path)
         .await
         .map_err(crate::Error::from)?
-        .set_content_disposition(ContentDisposition::from_raw(
-            &HeaderValue::from_str(&format!(
-                r#"attachment; filename="{}.{}""#,
-                file_model.file_name.replace('"', ""), // Shouldn't have " in the file_name but just in-case.
-                file_model.file_type,
-            ))
-            .unwrap(),
-        )?))
+        .set_content_type(content_type.parse().unwrap()))
+}
+
+/// Convert between an EPUB CFI and the chapter position the reader uses.
+#[get("/file/{id}/cfi")]
+pub async fn load_file_cfi(
+    file_id: web::Path<FileId>,
+    query: web::Query<api::GetFileCfiQuery>,
+    db: web::Data<SqlPool>,
+) -> WebResult<JsonResponse<api::ApiGetFileCfiByIdResponse>> {
+    let file_model = FileModel::find_one_by_id(*file_id, &mut *db.acquire().await?)
+        .await?
+        .filter(|v| v.file_type.eq_ignore_ascii_case("epub"))
+        .ok_or(crate::Error::Internal(crate::InternalError::ItemMissing))?;
+
+    let mut book = EpubBook::load_from_path(&file_model.path)?;
+
+    let query = query.into_inner();
+
+    let cfi = match (query.cfi, query.chapter) {
+        (Some(value), _) => value.parse::<Cfi>()?,
+        (None, Some(chapter)) => book.generate_cfi(chapter, query.char_pos)?,
+        (None, None) => return Err(ApiErrorResponse::new("Expected a cfi or chapter").into()),
+    };
+
+    let (chapter, char_pos) = book.resolve_cfi(&cfi)?;
+
+    Ok(web::Json(WrappingResponse::okay(api::FileCfi {
+        cfi: cfi.to_string(),
+        chapter,
+        char_pos,
+    })))
+}
+
+/// The Media Overlay timeline of an EPUB chapter. Used to read along with the bundled narration.
+#[get("/file/{id}/overlay/{chapter}")]
+pub async fn load_file_overlay(
+    web_path: web::Path<(FileId, usize)>,
+    db: web::Data<SqlPool>,
+) -> WebResult<JsonResponse<api::ApiGetFileOverlayByIdResponse>> {
+    let (file_id, chapter) = web_path.into_inner();
+
+    let file_model = FileModel::find_one_by_id(file_id, &mut *db.acquire().await?)
+        .await?
+        .filter(|v| v.file_type.eq_ignore_ascii_case("epub"))
+        .ok_or(crate::Error::Internal(crate::InternalError::ItemMissing))?;
+
+    let mut book = EpubBook::load_from_path(&file_model.path)?;
+
+    if chapter >= book.chapter_count() {
+        return Ok(web::Json(WrappingResponse::okay(None)));
+    }
+
+    book.set_chapter(chapter);
+
+    let Some(overlay) = book.get_media_overlay()? else {
+        return Ok(web::Json(WrappingResponse::okay(None)));
+    };
+
+    let page_path = book.get_page_path();
+
+    let clips = overlay
+        .clips
+        .into_iter()
+        .filter_map(|clip| {
+            // Only the clips narrating this chapter.
+            let (path, fragment) = clip.text.split_once('#')?;
+
+            if !page_path.ends_with(path) {
+                return None;
+            }
+
+            Some(api::MediaOverlayClip {
+                fragment: fragment.to_string(),
+                audio: clip.audio,
+                begin: clip.begin,
+                end: clip.end,
+            })
+        })
+        .collect();
+
+    Ok(web::Json(WrappingResponse::okay(Some(
+        api::MediaOverlayInfo {
+            active_class: book
+                .get_media_overlay_active_class()
+                .unwrap_or("media-overlay-active")
+                .to_string(),
+            clips,
+        },
+    ))))
 }
 
 #[get("/file/{id}/debug/{tail:.*}")]
@@ -233,7 +522,7 @@ pub async fn load_file_debug(
         } else {
             let mut book = bookie::load_from_path(&file.path)?.unwrap();
 
-            Ok(HttpResponse::Ok().body(book.read_path_as_bytes(&web_path.1, None, None)?))
+            Ok(HttpResponse::Ok().body(book.read_path_as_bytes(&web_path.1, None, None, None)?))
         }
     } else {
         Ok(HttpResponse::Ok().body("Unable to find file from ID"))
@@ -249,10 +538,15 @@ pub async fn progress_file_add(
     member: MemberCookie,
     db: web::Data<SqlPool>,
 ) -> WebResult<JsonResponse<&'static str>> {
-    if let Some(book_id) = FileModel::find_one_by_id(*file_id, &mut *db.acquire().await?)
-        .await?
-        .and_then(|v| v.book_id)
-    {
+    let file = FileModel::find_one_by_id(*file_id, &mut *db.acquire().await?).await?;
+
+    if let Some((book_id, file)) = file.and_then(|v| Some((v.book_id?, v))) {
+        let mut progress = body.into_inner();
+
+        if file.file_type.eq_ignore_ascii_case("epub") {
+            sync_progress_cfi(&file, &mut progress)?;
+        }
+
         // Check if the book already has progression. Return the progression.
         if let Some(prog) = FileProgressionModel::find_one_by_book_id(
             member.member_id(),
@@ -275,7 +569,7 @@ pub async fn progress_file_add(
             member.member_id(),
             book_id,
             *file_id,
-            body.into_inner(),
+            progress,
             &mut *db.acquire().await?,
         )
         .await?;
@@ -284,6 +578,37 @@ pub async fn progress_file_add(
     Ok(web::Json(WrappingResponse::okay("success")))
 }
 
+/// Keep the CFI and the chapter position of EPUB progress in sync.
+///
+/// A CFI from the client takes priority. It may have come from a different screen size or reading system.
+fn sync_progress_cfi(file: &FileModel, progress: &mut Progression) -> Result<()> {
+    let Progression::Ebook {
+        chapter,
+        char_pos,
+        cfi,
+        ..
+    } = progress
+    else {
+        return Ok(());
+    };
+
+    let mut book = EpubBook::load_from_path(&file.path)?;
+
+    if let Some(value) = cfi.as_deref() {
+        let (found_chapter, found_pos) = book.resolve_cfi(&value.parse()?)?;
+
+        *chapter = found_chapter as i64;
+        *char_pos = found_pos as i64;
+    } else if *chapter >= 0 && *char_pos >= 0 {
+        *cfi = Some(
+            book.generate_cfi(*chapter as usize, *char_pos as usize)?
+                .to_string(),
+        );
+    }
+
+    Ok(())
+}
+
 #[delete("/file/{id}/progress")]
 pub async fn progress_file_delete(
     file_id: web::Path<FileId>,
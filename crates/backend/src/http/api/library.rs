@@ -5,6 +5,7 @@ use common_local::{api, LibraryColl, LibraryId};
 use crate::{
     http::{JsonResponse, MemberCookie},
     model::{DirectoryModel, LibraryModel},
+    scanner::watcher::get_watch_error,
     SqlPool, WebResult,
 };
 
@@ -39,6 +40,8 @@ async fn load_library_list(
                             updated_at: lib.updated_at.timestamp_millis(),
 
                             directories: Vec::new(),
+
+                            watch_error: None,
                         })
                     } else {
                         None
@@ -83,6 +86,12 @@ async fn load_library_id(
         updated_at: model.updated_at.timestamp_millis(),
 
         directories: directories.into_iter().map(|v| v.path).collect(),
+
+        watch_error: member
+            .permissions
+            .is_owner()
+            .then(|| get_watch_error(model.id))
+            .flatten(),
     };
 
     Ok(web::Json(WrappingResponse::okay(library)))
@@ -122,6 +131,25 @@ async fn update_library_id(
         is_updated = true;
     }
 
+    if body.allow_scripts.is_some() || body.watch_directories.is_some() {
+        let mut settings = model.parse_settings_or_default()?;
+
+        if let Some(allow_scripts) = body.allow_scripts {
+            settings.allow_scripts = allow_scripts;
+        }
+
+        if let Some(watch_directories) = body.watch_directories {
+            settings.watch_directories = watch_directories;
+        }
+
+        model.settings = Some(serde_json::to_string(&settings).map_err(crate::Error::from)?);
+        is_updated = true;
+    }
+
+    let is_watch_changed = body.watch_directories.is_some()
+        || !body.remove_directories.is_empty()
+        || !body.add_directories.is_empty();
+
     if !body.remove_directories.is_empty() {
         // TODO: Don't trust that the path is correct. Also remove slashes at the end of path.
         for path in body.remove_directories {
@@ -145,5 +173,9 @@ async fn update_library_id(
         model.update(&mut *db.acquire().await?).await?;
     }
 
+    if is_watch_changed {
+        crate::scanner::watcher::refresh_library_watchers(&mut *db.acquire().await?).await?;
+    }
+
     Ok(web::Json(WrappingResponse::okay("ok")))
 }
@@ -16,6 +16,7 @@ pub mod person;
 pub mod settings;
 pub mod storage;
 pub mod task;
+pub mod trash;
 
 pub fn api_route() -> Scope<
     impl ServiceFactory<
@@ -35,6 +36,9 @@ pub fn api_route() -> Scope<
         .service(settings::save_initial_setup)
         .service(settings::get_server_settings)
         // File
+        .service(file::load_file_audio)
+        .service(file::load_file_overlay)
+        .service(file::load_file_cfi)
         .service(file::load_file_debug)
         .service(file::load_file)
         .service(file::load_file_pages)
@@ -85,6 +89,12 @@ pub fn api_route() -> Scope<
         // Task
         .service(task::get_tasks)
         .service(task::run_task)
+        .service(task::cancel_task)
+        // Trash
+        .service(trash::load_trash)
+        .service(trash::restore_trash_item)
+        .service(trash::purge_trash_item)
+        .service(trash::purge_trash)
         // Storage
         .service(storage::get_directory)
         .default_service(web::route().to(default_handler))
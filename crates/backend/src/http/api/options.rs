@@ -7,6 +7,7 @@ use crate::{
     config::{get_config, save_config, update_config},
     http::{JsonResponse, MemberCookie},
     model::{DirectoryModel, LibraryModel, NewLibraryModel},
+    scanner::watcher::{get_watch_error, refresh_library_watchers},
     SqlPool, WebResult,
 };
 
@@ -36,6 +37,11 @@ async fn load_options(
                     .into_iter()
                     .map(|v| v.path)
                     .collect(),
+                watch_error: member
+                    .permissions
+                    .is_owner()
+                    .then(|| get_watch_error(lib.id))
+                    .flatten(),
             })
             .collect(),
 
@@ -102,6 +108,8 @@ async fn update_options_add(
                     .await?;
             }
         }
+
+        refresh_library_watchers(&mut *db.acquire().await?).await?;
     }
 
     if let Some(libby_search) = libby_public_search {
@@ -141,6 +149,8 @@ async fn update_options_remove(
                 DirectoryModel::remove_by_path(&path, &mut *db.acquire().await?).await?;
             }
         }
+
+        refresh_library_watchers(&mut *db.acquire().await?).await?;
     }
 
     Ok(web::Json(WrappingResponse::okay("success")))
@@ -126,7 +126,10 @@ pub async fn save_initial_setup(
         .insert(&mut *db.acquire().await?)
         .await?;
 
-        crate::task::queue_task(crate::task::TaskLibraryScan { library_id: lib.id });
+        crate::task::queue_task(crate::task::TaskLibraryScan {
+            library_id: lib.id,
+            full_rescan: false,
+        });
 
         library_count += 1;
     }
@@ -289,6 +292,8 @@ async fn save_setup_config(mut value: SetupConfig) -> Result<()> {
         authenticators: value.authenticators,
         // TODO: Implement into setup
         searching: Default::default(),
+        trash: Default::default(),
+        watcher: Default::default(),
 
         has_admin_account: false,
         is_public_access: false,
@@ -1,15 +1,15 @@
 use actix_web::{get, post, web};
 use common::api::{ApiErrorResponse, WrappingResponse};
-use common_local::{
-    api,
-    ws::{TaskId, TaskInfo},
-};
+use common_local::{api, ws::TaskId};
 
 use crate::{
     http::{ws::RUNNING_TASKS, JsonResponse, MemberCookie},
+    model::TaskHistoryModel,
     queue_task, task, SqlPool, WebResult,
 };
 
+const QUERY_LIMIT: i64 = 100;
+
 // TODO: Actually optimize.
 #[post("/task")]
 pub async fn run_task(
@@ -26,7 +26,10 @@ pub async fn run_task(
     }
 
     if let Some(library_id) = modify.run_search {
-        queue_task(task::TaskLibraryScan { library_id });
+        queue_task(task::TaskLibraryScan {
+            library_id,
+            full_rescan: modify.full_rescan,
+        });
     }
 
     if let Some(library_id) = modify.run_metadata {
@@ -43,21 +46,55 @@ pub async fn run_task(
 
 #[get("/tasks")]
 pub async fn get_tasks(
+    query: web::Query<api::SimpleListQuery>,
     member: MemberCookie,
     db: web::Data<SqlPool>,
-) -> WebResult<JsonResponse<Vec<(TaskId, TaskInfo)>>> {
-    let member = member.fetch_or_error(&mut *db.acquire().await?).await?;
+) -> WebResult<JsonResponse<api::ApiGetTasksResponse>> {
+    let db = &mut *db.acquire().await?;
+
+    let member = member.fetch_or_error(db).await?;
 
     if !member.permissions.is_owner() {
         return Err(ApiErrorResponse::new("Not owner").into());
     }
 
-    let resp = RUNNING_TASKS
+    let running = RUNNING_TASKS
         .lock()
         .unwrap()
         .iter()
         .map(|(a, b)| (*a, b.clone()))
         .collect::<Vec<_>>();
 
-    Ok(web::Json(WrappingResponse::okay(resp)))
+    let offset = query.offset.unwrap_or(0);
+    let limit = query.limit.unwrap_or(50).min(QUERY_LIMIT);
+
+    let history = TaskHistoryModel::find_recent(offset, limit, db)
+        .await?
+        .into_iter()
+        .map(|v| v.into())
+        .collect();
+
+    Ok(web::Json(WrappingResponse::okay(api::GetTasksResponse {
+        running,
+        history,
+    })))
+}
+
+#[post("/task/{id}/cancel")]
+pub async fn cancel_task(
+    task_id: web::Path<TaskId>,
+    member: MemberCookie,
+    db: web::Data<SqlPool>,
+) -> WebResult<JsonResponse<api::ApiPostCancelTaskResponse>> {
+    let member = member.fetch_or_error(&mut *db.acquire().await?).await?;
+
+    if !member.permissions.is_owner() {
+        return Err(ApiErrorResponse::new("Not owner").into());
+    }
+
+    if !task::cancel_task(*task_id) {
+        return Err(crate::Error::from(crate::InternalError::ItemMissing).into());
+    }
+
+    Ok(web::Json(WrappingResponse::okay(String::from("success"))))
 }
@@ -0,0 +1,129 @@
+use actix_web::{delete, get, post, web};
+use common::api::{ApiErrorResponse, WrappingResponse};
+use common_local::{api, DisplayBookItem, DisplayItem, FileId};
+
+use crate::{
+    config::get_config,
+    http::{JsonResponse, MemberCookie},
+    model::{BookModel, FileModel},
+    task::purge_file,
+    SqlPool, WebResult,
+};
+
+const QUERY_LIMIT: i64 = 100;
+
+#[get("/trash")]
+async fn load_trash(
+    query: web::Query<api::SimpleListQuery>,
+    member: MemberCookie,
+    db: web::Data<SqlPool>,
+) -> WebResult<JsonResponse<api::ApiGetTrashResponse>> {
+    let db = &mut *db.acquire().await?;
+
+    let member = member.fetch_or_error(db).await?;
+
+    if !member.permissions.is_owner() {
+        return Err(ApiErrorResponse::new("Not owner").into());
+    }
+
+    let offset = query.offset.unwrap_or(0);
+    let limit = query.limit.unwrap_or(50).min(QUERY_LIMIT);
+
+    let mut items = Vec::new();
+
+    for file in FileModel::find_deleted(offset, limit, db).await? {
+        let book: Option<DisplayItem> = match file.book_id {
+            Some(book_id) => BookModel::find_one_by_id(book_id, db)
+                .await?
+                .map(|book| DisplayBookItem::from(book).into()),
+            None => None,
+        };
+
+        items.push(api::TrashItem {
+            file: file.into(),
+            book,
+        });
+    }
+
+    Ok(web::Json(WrappingResponse::okay(api::GetTrashResponse {
+        items,
+        total: FileModel::count_deleted(db).await?,
+        retention_days: get_config().trash.retention_days,
+    })))
+}
+
+#[post("/trash/{id}/restore")]
+async fn restore_trash_item(
+    file_id: web::Path<FileId>,
+    member: MemberCookie,
+    db: web::Data<SqlPool>,
+) -> WebResult<JsonResponse<&'static str>> {
+    let db = &mut *db.acquire().await?;
+
+    let member = member.fetch_or_error(db).await?;
+
+    if !member.permissions.is_owner() {
+        return Err(ApiErrorResponse::new("Not owner").into());
+    }
+
+    let file = FileModel::find_one_by_id(*file_id, db)
+        .await?
+        .ok_or_else(|| crate::Error::from(crate::InternalError::ItemMissing))?;
+
+    FileModel::restore_by_id(file.id, db).await?;
+    BookModel::sync_deleted_with_files(file.library_id, db).await?;
+
+    Ok(web::Json(WrappingResponse::okay("success")))
+}
+
+#[delete("/trash/{id}")]
+async fn purge_trash_item(
+    file_id: web::Path<FileId>,
+    member: MemberCookie,
+    db: web::Data<SqlPool>,
+) -> WebResult<JsonResponse<&'static str>> {
+    let db = &mut *db.acquire().await?;
+
+    let member = member.fetch_or_error(db).await?;
+
+    if !member.permissions.is_owner() {
+        return Err(ApiErrorResponse::new("Not owner").into());
+    }
+
+    let file = FileModel::find_one_by_id(*file_id, db)
+        .await?
+        .ok_or_else(|| crate::Error::from(crate::InternalError::ItemMissing))?;
+
+    if file.deleted_at.is_none() {
+        return Err(ApiErrorResponse::new("File is not in the trash").into());
+    }
+
+    purge_file(file, db).await?;
+
+    Ok(web::Json(WrappingResponse::okay("success")))
+}
+
+#[delete("/trash")]
+async fn purge_trash(
+    member: MemberCookie,
+    db: web::Data<SqlPool>,
+) -> WebResult<JsonResponse<&'static str>> {
+    let db = &mut *db.acquire().await?;
+
+    let member = member.fetch_or_error(db).await?;
+
+    if !member.permissions.is_owner() {
+        return Err(ApiErrorResponse::new("Not owner").into());
+    }
+
+    // Everything which was put in the trash before now.
+    let now = chrono::Utc::now().naive_utc();
+
+    for file in FileModel::find_deleted_before(now, db).await? {
+        purge_file(file, db).await?;
+    }
+
+    BookModel::delete_deleted_before(now, db).await?;
+
+    Ok(web::Json(WrappingResponse::okay("success")))
+}
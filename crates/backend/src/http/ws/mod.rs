@@ -131,6 +131,7 @@ pub fn send_message_to_clients(value: WebsocketNotification) {
                 TaskInfo {
                     name,
                     current: None,
+                    progress: None,
                 },
             );
         }
@@ -139,6 +140,7 @@ pub fn send_message_to_clients(value: WebsocketNotification) {
             id,
             type_of,
             inserting,
+            progress,
         } => {
             if let Some(info) = RUNNING_TASKS.lock().unwrap().get_mut(&id) {
                 if inserting {
@@ -146,6 +148,10 @@ pub fn send_message_to_clients(value: WebsocketNotification) {
                 } else {
                     info.current = None;
                 }
+
+                if progress.is_some() {
+                    info.progress = progress;
+                }
             }
         }
 
@@ -30,7 +30,7 @@ pub use cli::CliArgs;
 pub use database::{SqlConnection, SqlPool};
 pub use error::{Error, InternalError, Result, WebError, WebResult};
 pub use imd::IN_MEM_DB;
-pub use task::{queue_task, Task};
+pub use task::{queue_task, Task, TaskCategory};
 pub use util::*;
 
 #[actix_web::main]
@@ -57,6 +57,8 @@ async fn main() -> Result<()> {
 
     task::start_task_manager(db_data.clone());
 
+    scanner::watcher::refresh_library_watchers(&mut *db_data.acquire().await?).await?;
+
     info!(
         port = cli_args.port,
         host = cli_args.host,
@@ -39,12 +39,15 @@ impl Metadata for LocalMetadata {
                     .map(|mut v| v.remove(0))
                     .map::<Result<_>, _>(|url| {
                         Ok(vec![FoundImageLocation::FileData(
-                            book.read_path_as_bytes(&url, None, None)?,
+                            book.read_path_as_bytes(&url, None, None, None)?,
                         )])
                     })
                     .transpose()?;
 
                 let publisher = book.find(BookSearch::Publisher).map(|mut v| v.remove(0));
+                let year = book
+                    .find(BookSearch::Date)
+                    .and_then(|v| v.first()?.get(0..4)?.parse::<i64>().ok());
                 let authors = book.find(BookSearch::Creator).map(|items| {
                     items
                         .into_iter()
@@ -69,7 +72,7 @@ impl Metadata for LocalMetadata {
                         thumb_locations: thumb_file_data.unwrap_or_default(),
                         cached: BookItemCached::default(),
                         available_at: None,
-                        year: None,
+                        year,
                     },
                     authors,
                     publisher,
@@ -354,6 +354,62 @@ impl BookModel {
         Ok(res.rows_affected())
     }
 
+    pub async fn soft_delete_by_id(id: BookId, db: &mut SqliteConnection) -> Result<u64> {
+        let res =
+            sqlx::query("UPDATE book SET deleted_at = $2 WHERE id = $1 AND deleted_at IS NULL")
+                .bind(id)
+                .bind(Utc::now().naive_utc())
+                .execute(db)
+                .await?;
+
+        Ok(res.rows_affected())
+    }
+
+    /// Move the books of the library whose files are all deleted into the trash, and restore the ones with files again.
+    pub async fn sync_deleted_with_files(
+        library_id: LibraryId,
+        db: &mut SqliteConnection,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"UPDATE book SET deleted_at = $1
+            WHERE library_id = $2 AND deleted_at IS NULL
+                AND id IN (SELECT book_id FROM file WHERE deleted_at IS NOT NULL)
+                AND id NOT IN (SELECT book_id FROM file WHERE deleted_at IS NULL AND book_id IS NOT NULL)"#,
+        )
+        .bind(Utc::now().naive_utc())
+        .bind(library_id)
+        .execute(&mut *db)
+        .await?;
+
+        sqlx::query(
+            r#"UPDATE book SET deleted_at = NULL
+            WHERE library_id = $1 AND deleted_at IS NOT NULL
+                AND id IN (SELECT book_id FROM file WHERE deleted_at IS NULL)"#,
+        )
+        .bind(library_id)
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Permanently delete the books which were put in the trash before `date` and no longer have files.
+    pub async fn delete_deleted_before(
+        date: NaiveDateTime,
+        db: &mut SqliteConnection,
+    ) -> Result<u64> {
+        let res = sqlx::query(
+            r#"DELETE FROM book
+            WHERE deleted_at < $1
+                AND id NOT IN (SELECT book_id FROM file WHERE book_id IS NOT NULL)"#,
+        )
+        .bind(date)
+        .execute(db)
+        .await?;
+
+        Ok(res.rows_affected())
+    }
+
     pub async fn find_by(
         library: Option<LibraryId>,
         offset: i64,
@@ -361,25 +417,18 @@ impl BookModel {
         person_id: Option<PersonId>,
         db: &mut SqliteConnection,
     ) -> Result<Vec<Self>> {
-        let insert_where = (library.is_some() || person_id.is_some())
-            .then_some("WHERE")
-            .unwrap_or_default();
-        let insert_and = (library.is_some() && person_id.is_some())
-            .then_some("AND")
-            .unwrap_or_default();
-
         let lib_id = library
-            .map(|v| format!("library_id={v}"))
+            .map(|v| format!("AND library_id={v}"))
             .unwrap_or_default();
 
         let inner_query = person_id
             .map(|pid| {
-                format!(r#"id IN (SELECT book_id FROM book_person WHERE person_id = {pid})"#)
+                format!(r#"AND id IN (SELECT book_id FROM book_person WHERE person_id = {pid})"#)
             })
             .unwrap_or_default();
 
         let sql = format!(
-            r#"SELECT * FROM book {insert_where} {lib_id} {insert_and} {inner_query} LIMIT $1 OFFSET $2"#
+            r#"SELECT * FROM book WHERE deleted_at IS NULL {lib_id} {inner_query} LIMIT $1 OFFSET $2"#
         );
 
         let conn = sqlx::query_as(&sql);
@@ -462,6 +511,9 @@ impl BookModel {
             BookType::ComicBook as u8,
         ));
 
+        // Books in the trash
+        f_comp.push(String::from("deleted_at IS NULL "));
+
         // Library ID
         if let Some(library) = library {
             f_comp.push(format!("library_id={library} "));
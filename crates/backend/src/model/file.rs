@@ -1,4 +1,4 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use common::BookId;
 
 use common_local::{FileId, LibraryId, LibraryType, MediaItem};
@@ -23,6 +23,7 @@ pub struct NewFileModel {
 
     pub identifier: Option<String>,
     pub hash: String,
+    pub inode: Option<i64>,
 
     pub modified_at: NaiveDateTime,
     pub accessed_at: NaiveDateTime,
@@ -46,6 +47,7 @@ pub struct FileModel {
 
     pub identifier: Option<String>,
     pub hash: String,
+    pub inode: Option<i64>,
 
     pub modified_at: NaiveDateTime,
     pub accessed_at: NaiveDateTime,
@@ -92,6 +94,7 @@ impl NewFileModel {
             chapter_count: self.chapter_count,
             identifier: self.identifier,
             hash: self.hash,
+            inode: self.inode,
             modified_at: self.modified_at,
             accessed_at: self.accessed_at,
             created_at: self.created_at,
@@ -101,8 +104,8 @@ impl NewFileModel {
 
     pub async fn insert(self, db: &mut SqliteConnection) -> Result<FileModel> {
         let res = sqlx::query(
-            r#"INSERT INTO file (path, file_type, file_name, file_size, modified_at, accessed_at, created_at, identifier, hash, library_id, book_id, chapter_count)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            r#"INSERT INTO file (path, file_type, file_name, file_size, modified_at, accessed_at, created_at, identifier, hash, library_id, book_id, chapter_count, inode)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
             "#,
         )
         .bind(&self.path)
@@ -117,6 +120,7 @@ impl NewFileModel {
         .bind(self.library_id)
         .bind(self.book_id)
         .bind(self.chapter_count)
+        .bind(self.inode)
         .execute(db).await?;
 
         Ok(self.into_file(FileId::from(res.last_insert_rowid())))
@@ -128,6 +132,14 @@ impl FileModel {
         LibraryType::ComicBook.is_filetype_valid(&self.file_type)
     }
 
+    pub fn is_file_type_document(&self) -> bool {
+        LibraryType::Document.is_filetype_valid(&self.file_type)
+    }
+
+    pub fn is_file_type_audio_book(&self) -> bool {
+        LibraryType::AudioBook.is_filetype_valid(&self.file_type)
+    }
+
     pub async fn exists(path: &str, hash: &str, db: &mut SqliteConnection) -> Result<bool> {
         Ok(
             sqlx::query("SELECT EXISTS(SELECT id FROM file WHERE path = $1 OR hash = $2)")
@@ -146,7 +158,7 @@ impl FileModel {
         db: &mut SqliteConnection,
     ) -> Result<Vec<Self>> {
         Ok(sqlx::query_as(
-            "SELECT id, path, file_name, file_type, file_size, library_id, book_id, chapter_count, identifier, hash, modified_at, accessed_at, created_at, deleted_at FROM file WHERE library_id = $1 LIMIT $2 OFFSET $3"
+            "SELECT id, path, file_name, file_type, file_size, library_id, book_id, chapter_count, identifier, hash, inode, modified_at, accessed_at, created_at, deleted_at FROM file WHERE library_id = $1 LIMIT $2 OFFSET $3"
         ).bind(library).bind(limit).bind(offset).fetch_all(db).await?)
     }
 
@@ -173,7 +185,7 @@ impl FileModel {
 
     pub async fn find_by_missing_book(db: &mut SqliteConnection) -> Result<Vec<Self>> {
         Ok(sqlx::query_as(
-            "SELECT id, path, file_name, file_type, file_size, library_id, book_id, chapter_count, identifier, hash, modified_at, accessed_at, created_at, deleted_at FROM file WHERE book_id = 0 OR book_id = NULL"
+            "SELECT id, path, file_name, file_type, file_size, library_id, book_id, chapter_count, identifier, hash, inode, modified_at, accessed_at, created_at, deleted_at FROM file WHERE book_id = 0 OR book_id = NULL"
         ).fetch_all(db).await?)
     }
 
@@ -183,13 +195,55 @@ impl FileModel {
         db: &mut SqliteConnection,
     ) -> Result<Option<Self>> {
         Ok(sqlx::query_as(
-            "SELECT id, path, file_name, file_type, file_size, library_id, book_id, chapter_count, identifier, hash, modified_at, accessed_at, created_at, deleted_at FROM file WHERE path = $1 OR hash = $2"
+            "SELECT id, path, file_name, file_type, file_size, library_id, book_id, chapter_count, identifier, hash, inode, modified_at, accessed_at, created_at, deleted_at FROM file WHERE path = $1 OR hash = $2"
         ).bind(path).bind(hash).fetch_optional(db).await?)
     }
 
+    pub async fn find_by_library_id(
+        library_id: LibraryId,
+        db: &mut SqliteConnection,
+    ) -> Result<Vec<Self>> {
+        Ok(sqlx::query_as(
+            "SELECT id, path, file_name, file_type, file_size, library_id, book_id, chapter_count, identifier, hash, inode, modified_at, accessed_at, created_at, deleted_at FROM file WHERE library_id = $1 AND deleted_at IS NULL"
+        ).bind(library_id).fetch_all(db).await?)
+    }
+
+    pub async fn find_deleted(
+        offset: i64,
+        limit: i64,
+        db: &mut SqliteConnection,
+    ) -> Result<Vec<Self>> {
+        Ok(sqlx::query_as(
+            "SELECT id, path, file_name, file_type, file_size, library_id, book_id, chapter_count, identifier, hash, inode, modified_at, accessed_at, created_at, deleted_at FROM file WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC LIMIT $1 OFFSET $2"
+        ).bind(limit).bind(offset).fetch_all(db).await?)
+    }
+
+    pub async fn find_deleted_before(
+        date: NaiveDateTime,
+        db: &mut SqliteConnection,
+    ) -> Result<Vec<Self>> {
+        Ok(sqlx::query_as(
+            "SELECT id, path, file_name, file_type, file_size, library_id, book_id, chapter_count, identifier, hash, inode, modified_at, accessed_at, created_at, deleted_at FROM file WHERE deleted_at < $1"
+        ).bind(date).fetch_all(db).await?)
+    }
+
+    pub async fn count_deleted(db: &mut SqliteConnection) -> Result<i64> {
+        Ok(
+            sqlx::query_scalar("SELECT COUNT(*) FROM file WHERE deleted_at IS NOT NULL")
+                .fetch_one(db)
+                .await?,
+        )
+    }
+
+    pub async fn find_one_by_path(path: &str, db: &mut SqliteConnection) -> Result<Option<Self>> {
+        Ok(sqlx::query_as(
+            "SELECT id, path, file_name, file_type, file_size, library_id, book_id, chapter_count, identifier, hash, inode, modified_at, accessed_at, created_at, deleted_at FROM file WHERE path = $1"
+        ).bind(path).fetch_optional(db).await?)
+    }
+
     pub async fn find_one_by_id(id: FileId, db: &mut SqliteConnection) -> Result<Option<Self>> {
         Ok(sqlx::query_as(
-            "SELECT id, path, file_name, file_type, file_size, library_id, book_id, chapter_count, identifier, hash, modified_at, accessed_at, created_at, deleted_at FROM file WHERE id = $1"
+            "SELECT id, path, file_name, file_type, file_size, library_id, book_id, chapter_count, identifier, hash, inode, modified_at, accessed_at, created_at, deleted_at FROM file WHERE id = $1"
         ).bind(id).fetch_optional(db).await?)
     }
 
@@ -207,7 +261,7 @@ impl FileModel {
 
     pub async fn find_by_book_id(book_id: BookId, db: &mut SqliteConnection) -> Result<Vec<Self>> {
         Ok(sqlx::query_as(
-            "SELECT id, path, file_name, file_type, file_size, library_id, book_id, chapter_count, identifier, hash, modified_at, accessed_at, created_at, deleted_at FROM file WHERE book_id = $1"
+            "SELECT id, path, file_name, file_type, file_size, library_id, book_id, chapter_count, identifier, hash, inode, modified_at, accessed_at, created_at, deleted_at FROM file WHERE book_id = $1"
         ).bind(book_id).fetch_all(db).await?)
     }
 
@@ -217,7 +271,7 @@ impl FileModel {
         db: &mut SqliteConnection,
     ) -> Result<Vec<Self>> {
         Ok(sqlx::query_as(
-            "SELECT id, path, file_name, file_type, file_size, library_id, book_id, chapter_count, identifier, hash, modified_at, accessed_at, created_at, deleted_at FROM file WHERE hash IS NULL LIMIT $1 OFFSET $2"
+            "SELECT id, path, file_name, file_type, file_size, library_id, book_id, chapter_count, identifier, hash, inode, modified_at, accessed_at, created_at, deleted_at FROM file WHERE hash IS NULL LIMIT $1 OFFSET $2"
         ).bind(limit).bind(offset).fetch_all(db).await?)
     }
 
@@ -254,7 +308,8 @@ impl FileModel {
             r#"UPDATE file SET
                 path = $2, file_name = $3, file_type = $4, file_size = $5,
                 library_id = $6, book_id = $7, chapter_count = $8, identifier = $9,
-                modified_at = $10, accessed_at = $11, created_at = $12, deleted_at = $13
+                modified_at = $10, accessed_at = $11, created_at = $12, deleted_at = $13,
+                hash = $14, inode = $15
             WHERE id = $1"#,
         )
         .bind(self.id)
@@ -270,12 +325,75 @@ impl FileModel {
         .bind(self.accessed_at)
         .bind(self.created_at)
         .bind(self.deleted_at)
+        .bind(&self.hash)
+        .bind(self.inode)
         .execute(db)
         .await?;
 
         Ok(())
     }
 
+    /// Move the file at `from`, or every file inside of the `from` folder, to `to`.
+    pub async fn update_path_prefix(
+        from: &str,
+        to: &str,
+        db: &mut SqliteConnection,
+    ) -> Result<u64> {
+        let res = sqlx::query(
+            r#"UPDATE file SET path = $2 || substr(path, length($1) + 1)
+            WHERE path = $1 OR substr(path, 1, length($1) + 1) = $1 || '/'"#,
+        )
+        .bind(from)
+        .bind(to)
+        .execute(db)
+        .await?;
+
+        Ok(res.rows_affected())
+    }
+
+    pub async fn soft_delete_by_id(id: FileId, db: &mut SqliteConnection) -> Result<u64> {
+        let res =
+            sqlx::query("UPDATE file SET deleted_at = $2 WHERE id = $1 AND deleted_at IS NULL")
+                .bind(id)
+                .bind(Utc::now().naive_utc())
+                .execute(db)
+                .await?;
+
+        Ok(res.rows_affected())
+    }
+
+    pub async fn restore_by_id(id: FileId, db: &mut SqliteConnection) -> Result<u64> {
+        let res = sqlx::query("UPDATE file SET deleted_at = NULL WHERE id = $1")
+            .bind(id)
+            .execute(db)
+            .await?;
+
+        Ok(res.rows_affected())
+    }
+
+    pub async fn delete_by_id(id: FileId, db: &mut SqliteConnection) -> Result<u64> {
+        let res = sqlx::query("DELETE FROM file WHERE id = $1")
+            .bind(id)
+            .execute(db)
+            .await?;
+
+        Ok(res.rows_affected())
+    }
+
+    /// Mark the file at `path`, or every file inside of the `path` folder, as deleted.
+    pub async fn soft_delete_by_path(path: &str, db: &mut SqliteConnection) -> Result<u64> {
+        let res = sqlx::query(
+            r#"UPDATE file SET deleted_at = $2
+            WHERE (path = $1 OR substr(path, 1, length($1) + 1) = $1 || '/') AND deleted_at IS NULL"#,
+        )
+        .bind(path)
+        .bind(Utc::now().naive_utc())
+        .execute(db)
+        .await?;
+
+        Ok(res.rows_affected())
+    }
+
     pub async fn transfer_book_id(
         old_book_id: BookId,
         new_book_id: BookId,
@@ -2,7 +2,7 @@ use chrono::{NaiveDateTime, Utc};
 use serde::Serialize;
 use sqlx::{FromRow, SqliteConnection};
 
-use common_local::{LibraryId, LibraryType};
+use common_local::{LibraryId, LibrarySettings, LibraryType};
 
 use super::directory::DirectoryModel;
 use crate::Result;
@@ -62,6 +62,14 @@ impl NewLibraryModel {
 }
 
 impl LibraryModel {
+    pub fn parse_settings_or_default(&self) -> Result<LibrarySettings> {
+        let Some(settings) = &self.settings else {
+            return Ok(LibrarySettings::default());
+        };
+
+        Ok(serde_json::from_str(settings)?)
+    }
+
     pub async fn delete_by_id(id: LibraryId, db: &mut SqliteConnection) -> Result<u64> {
         DirectoryModel::delete_by_library_id(id, db).await?;
 
@@ -98,12 +106,16 @@ impl LibraryModel {
     pub async fn update(&mut self, db: &mut SqliteConnection) -> Result<u64> {
         self.updated_at = Utc::now().naive_utc();
 
-        let res = sqlx::query("UPDATE library SET name = $2, updated_at = $3 WHERE id = $1")
-            .bind(self.id)
-            .bind(&self.name)
-            .bind(self.updated_at)
-            .execute(db)
-            .await?;
+        let res = sqlx::query(
+            "UPDATE library SET name = $2, is_public = $3, settings = $4, updated_at = $5 WHERE id = $1",
+        )
+        .bind(self.id)
+        .bind(&self.name)
+        .bind(self.is_public)
+        .bind(&self.settings)
+        .bind(self.updated_at)
+        .execute(db)
+        .await?;
 
         Ok(res.rows_affected())
     }
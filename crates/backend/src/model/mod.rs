@@ -12,6 +12,7 @@ mod member;
 mod person;
 mod person_alt;
 mod progress;
+mod task_history;
 
 pub use self::image::*;
 pub use auth::*;
@@ -27,3 +28,4 @@ pub use member::{MemberModel, NewMemberModel};
 pub use person::*;
 pub use person_alt::*;
 pub use progress::*;
+pub use task_history::*;
@@ -22,6 +22,8 @@ pub struct FileProgressionModel {
     // Ebook
     pub page: Option<i64>, // TODO: Remove page. Change to byte pos. Most accurate since screen sizes can change.
     pub char_pos: Option<i64>,
+    // EPUB Canonical Fragment Identifier
+    pub cfi: Option<String>,
 
     // Audiobook
     pub seek_pos: Option<i64>,
@@ -41,6 +43,7 @@ impl FileProgressionModel {
                 chapter: None,
                 page: None,
                 char_pos: None,
+                cfi: None,
                 seek_pos: None,
                 updated_at: Utc::now().naive_utc(),
                 created_at: Utc::now().naive_utc(),
@@ -50,6 +53,7 @@ impl FileProgressionModel {
                 chapter,
                 page,
                 char_pos,
+                cfi,
             } => Self {
                 book_id,
                 file_id,
@@ -58,6 +62,7 @@ impl FileProgressionModel {
                 char_pos: Some(char_pos),
                 chapter: Some(chapter),
                 page: Some(page),
+                cfi,
                 seek_pos: None,
                 updated_at: Utc::now().naive_utc(),
                 created_at: Utc::now().naive_utc(),
@@ -71,6 +76,7 @@ impl FileProgressionModel {
                 chapter: Some(chapter),
                 page: None,
                 char_pos: None,
+                cfi: None,
                 seek_pos: Some(seek_pos),
                 updated_at: Utc::now().naive_utc(),
                 created_at: Utc::now().naive_utc(),
@@ -88,6 +94,7 @@ impl From<FileProgressionModel> for Progression {
                 char_pos: val.char_pos.unwrap(),
                 chapter: val.chapter.unwrap(),
                 page: val.page.unwrap(),
+                cfi: val.cfi,
             },
 
             2 => Progression::AudioBook {
@@ -112,17 +119,17 @@ impl FileProgressionModel {
 
         if Self::find_one(member_id, file_id, db).await?.is_some() {
             sqlx::query(
-                "UPDATE file_progression SET chapter = $1, char_pos = $2, page = $3, seek_pos = $4, updated_at = $5 WHERE book_id = $6 AND file_id = $7 AND user_id = $8",
+                "UPDATE file_progression SET chapter = $1, char_pos = $2, page = $3, seek_pos = $4, cfi = $5, updated_at = $6 WHERE book_id = $7 AND file_id = $8 AND user_id = $9",
             )
-            .bind(prog.chapter).bind(prog.char_pos).bind(prog.page).bind(prog.seek_pos).bind(prog.updated_at)
+            .bind(prog.chapter).bind(prog.char_pos).bind(prog.page).bind(prog.seek_pos).bind(prog.cfi).bind(prog.updated_at)
             .bind(prog.book_id).bind(prog.file_id).bind(prog.user_id)
             .execute(db).await?;
         } else {
             sqlx::query(
-                "INSERT INTO file_progression (book_id, file_id, user_id, type_of, chapter, char_pos, page, seek_pos, updated_at, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+                "INSERT INTO file_progression (book_id, file_id, user_id, type_of, chapter, char_pos, page, seek_pos, cfi, updated_at, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
             )
             .bind(prog.book_id).bind(prog.file_id).bind(prog.user_id).bind(prog.type_of).bind(prog.chapter).bind(prog.char_pos)
-            .bind(prog.page).bind(prog.seek_pos).bind(prog.updated_at).bind(prog.created_at)
+            .bind(prog.page).bind(prog.seek_pos).bind(prog.cfi).bind(prog.updated_at).bind(prog.created_at)
             .execute(db).await?;
         }
 
@@ -135,7 +142,7 @@ impl FileProgressionModel {
         db: &mut SqliteConnection,
     ) -> Result<Option<Self>> {
         Ok(sqlx::query_as(
-            "SELECT book_id, file_id, user_id, type_of, chapter, page, char_pos, seek_pos, updated_at, created_at FROM file_progression WHERE user_id = $1 AND file_id = $2"
+            "SELECT book_id, file_id, user_id, type_of, chapter, page, char_pos, cfi, seek_pos, updated_at, created_at FROM file_progression WHERE user_id = $1 AND file_id = $2"
         ).bind(member_id).bind(file_id).fetch_optional(db).await?)
     }
 
@@ -159,7 +166,7 @@ impl FileProgressionModel {
         db: &mut SqliteConnection,
     ) -> Result<Option<Self>> {
         Ok(sqlx::query_as(
-            "SELECT book_id, file_id, user_id, type_of, chapter, page, char_pos, seek_pos, updated_at, created_at FROM file_progression WHERE user_id = $1 AND book_id = $2"
+            "SELECT book_id, file_id, user_id, type_of, chapter, page, char_pos, cfi, seek_pos, updated_at, created_at FROM file_progression WHERE user_id = $1 AND book_id = $2"
         ).bind(member_id).bind(book_id).fetch_optional(db).await?)
     }
 
@@ -170,7 +177,7 @@ impl FileProgressionModel {
         db: &mut SqliteConnection,
     ) -> Result<Vec<(Self, BookModel)>> {
         let items = sqlx::query(
-            r#"SELECT file_progression.book_id, file_progression.file_id, file_progression.user_id, file_progression.type_of, file_progression.chapter, file_progression.page, file_progression.char_pos, file_progression.seek_pos, file_progression.updated_at, file_progression.created_at,
+            r#"SELECT file_progression.book_id, file_progression.file_id, file_progression.user_id, file_progression.type_of, file_progression.chapter, file_progression.page, file_progression.char_pos, file_progression.cfi, file_progression.seek_pos, file_progression.updated_at, file_progression.created_at,
                 book.id, book.library_id, book.type_of, book.parent_id, book.source, book.file_item_count, book.title, book.original_title, book.description, book.rating, book.thumb_url, book.cached, book."index", book.refreshed_at, book.created_at, book.updated_at, book.deleted_at, book.available_at, book.year
             FROM file_progression
                 JOIN book ON book.id = file_progression.book_id
@@ -196,31 +203,32 @@ impl FileProgressionModel {
                     chapter: v.try_get(4)?,
                     page: v.try_get(5)?,
                     char_pos: v.try_get(6)?,
-                    seek_pos: v.try_get(7)?,
-                    updated_at: v.try_get(8)?,
-                    created_at: v.try_get(9)?,
+                    cfi: v.try_get(7)?,
+                    seek_pos: v.try_get(8)?,
+                    updated_at: v.try_get(9)?,
+                    created_at: v.try_get(10)?,
                 };
 
                 let book = BookModel {
-                    id: v.try_get(10)?,
-                    library_id: v.try_get(11)?,
-                    type_of: v.try_get(12)?,
-                    parent_id: v.try_get(13)?,
-                    source: v.try_get(14)?,
-                    file_item_count: v.try_get(15)?,
-                    title: v.try_get(16)?,
-                    original_title: v.try_get(17)?,
-                    description: v.try_get(18)?,
-                    rating: v.try_get(19)?,
-                    thumb_url: v.try_get(20)?,
-                    cached: v.try_get(21)?,
-                    index: v.try_get(22)?,
-                    refreshed_at: v.try_get(23)?,
-                    created_at: v.try_get(24)?,
-                    updated_at: v.try_get(25)?,
-                    deleted_at: v.try_get(26)?,
-                    available_at: v.try_get(27)?,
-                    year: v.try_get(28)?,
+                    id: v.try_get(11)?,
+                    library_id: v.try_get(12)?,
+                    type_of: v.try_get(13)?,
+                    parent_id: v.try_get(14)?,
+                    source: v.try_get(15)?,
+                    file_item_count: v.try_get(16)?,
+                    title: v.try_get(17)?,
+                    original_title: v.try_get(18)?,
+                    description: v.try_get(19)?,
+                    rating: v.try_get(20)?,
+                    thumb_url: v.try_get(21)?,
+                    cached: v.try_get(22)?,
+                    index: v.try_get(23)?,
+                    refreshed_at: v.try_get(24)?,
+                    created_at: v.try_get(25)?,
+                    updated_at: v.try_get(26)?,
+                    deleted_at: v.try_get(27)?,
+                    available_at: v.try_get(28)?,
+                    year: v.try_get(29)?,
                 };
 
                 Ok((prog, book))
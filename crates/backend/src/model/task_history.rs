@@ -0,0 +1,87 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use common_local::{api::TaskHistoryItem, ws::TaskOutcome};
+use sqlx::{FromRow, SqliteConnection};
+
+use crate::Result;
+
+#[derive(Debug)]
+pub struct NewTaskHistoryModel {
+    pub name: String,
+    pub outcome: TaskOutcome,
+    pub error: Option<String>,
+    pub items_processed: i64,
+
+    pub started_at: NaiveDateTime,
+    pub ended_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TaskHistoryModel {
+    pub id: i64,
+
+    pub name: String,
+    pub outcome: TaskOutcome,
+    pub error: Option<String>,
+    pub items_processed: i64,
+
+    pub started_at: NaiveDateTime,
+    pub ended_at: NaiveDateTime,
+}
+
+impl From<TaskHistoryModel> for TaskHistoryItem {
+    fn from(val: TaskHistoryModel) -> Self {
+        TaskHistoryItem {
+            name: val.name,
+            outcome: val.outcome,
+            error: val.error,
+            items_processed: val.items_processed,
+            started_at: val.started_at,
+            ended_at: val.ended_at,
+        }
+    }
+}
+
+impl NewTaskHistoryModel {
+    pub async fn insert(self, db: &mut SqliteConnection) -> Result<TaskHistoryModel> {
+        let res = sqlx::query(
+            "INSERT INTO task_history (name, outcome, error, items_processed, started_at, ended_at) VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(&self.name)
+        .bind(self.outcome)
+        .bind(&self.error)
+        .bind(self.items_processed)
+        .bind(self.started_at)
+        .bind(self.ended_at)
+        .execute(db)
+        .await?;
+
+        Ok(TaskHistoryModel {
+            id: res.last_insert_rowid(),
+            name: self.name,
+            outcome: self.outcome,
+            error: self.error,
+            items_processed: self.items_processed,
+            started_at: self.started_at,
+            ended_at: self.ended_at,
+        })
+    }
+}
+
+impl TaskHistoryModel {
+    /// The most recently finished tasks first.
+    pub async fn find_recent(
+        offset: i64,
+        limit: i64,
+        db: &mut SqliteConnection,
+    ) -> Result<Vec<Self>> {
+        Ok(sqlx::query_as(
+            "SELECT * FROM task_history ORDER BY ended_at DESC, id DESC LIMIT $1 OFFSET $2",
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(db)
+        .await?)
+    }
+}
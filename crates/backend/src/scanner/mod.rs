@@ -1,244 +1,524 @@
 use std::{
-    collections::VecDeque,
+    collections::{HashSet, VecDeque},
     path::{Path, PathBuf},
     time::UNIX_EPOCH,
 };
 
 use crate::{
-    http::send_message_to_clients,
     metadata::{
-        get_metadata_by_source, get_metadata_from_files, openlibrary::OpenLibraryMetadata,
-        search_all_agents, Metadata, MetadataReturned,
+        get_metadata_by_source, get_metadata_from_files, local::LocalMetadata,
+        openlibrary::OpenLibraryMetadata, search_all_agents, Metadata, MetadataReturned,
     },
     model::{
         BookModel, BookPersonModel, DirectoryModel, FileModel, ImageLinkModel, LibraryModel,
         NewBookModel, NewFileModel, UploadedImageModel,
     },
     parse::{extract_comic_volume, extract_name_from_path, VolumeType},
+    task::{check_cancelled, update_task_progress},
     Result,
 };
 use bookie::BookSearch;
-use chrono::{TimeZone, Utc};
+use chrono::{NaiveDateTime, TimeZone, Utc};
 use common::parse_book_id;
 use common_local::{
-    ws::{TaskId, TaskType, WebsocketNotification},
+    ws::{TaskId, TaskType},
     BookType, LibraryId, LibraryType,
 };
 use sqlx::SqliteConnection;
 use tokio::fs;
 
-pub static WHITELISTED_FILE_TYPES: [&str; 2] = ["epub", "cbz"];
+pub mod watcher;
+
+pub static WHITELISTED_FILE_TYPES: [&str; 20] = [
+    "epub", "cbz", "cbr", "cb7", "cbt", "mobi", "prc", "azw", "azw3", "fb2", "fb2.zip", "txt",
+    "md", "markdown", "html", "htm", "pdf", "m4b", "m4a", "mp3",
+];
 
 pub async fn library_scan(
     library: &LibraryModel,
     directories: Vec<DirectoryModel>,
+    full_rescan: bool,
+    task_id: TaskId,
+    db: &mut SqliteConnection,
+) -> Result<()> {
+    let folders = directories
+        .into_iter()
+        .map(|v| (PathBuf::from(&v.path), PathBuf::from(&v.path)))
+        .collect();
+
+    scan_folders(library, folders, full_rescan, task_id, db).await
+}
+
+/// Scan a folder inside of a library directory, like one which was just moved into it.
+///
+/// The `root_dir` is the library directory the folder is inside of.
+pub async fn folder_scan(
+    library: &LibraryModel,
+    root_dir: &Path,
+    folder: &Path,
+    task_id: TaskId,
+    db: &mut SqliteConnection,
+) -> Result<()> {
+    let folders = vec![(root_dir.to_path_buf(), folder.to_path_buf())];
+
+    scan_folders(library, folders, false, task_id, db).await
+}
+
+/// Scan each folder along with the library directory it's inside of.
+async fn scan_folders(
+    library: &LibraryModel,
+    folders: Vec<(PathBuf, PathBuf)>,
+    full_rescan: bool,
     task_id: TaskId,
     db: &mut SqliteConnection,
 ) -> Result<()> {
-    if directories.is_empty() {
+    if folders.is_empty() {
         return Ok(());
     }
 
-    let mut dirs = directories
-        .into_iter()
-        .map(|v| PathBuf::from(&v.path))
-        .collect::<Vec<_>>();
-    dirs.reverse();
+    // Find everything first so we know how far along the scan is.
+    let mut entries = Vec::new();
 
-    let mut folders: VecDeque<PathBuf> = VecDeque::new();
+    // Folders whose missing files are kept. Like a library directory on a drive which isn't mounted.
+    let mut unavailable_folders = Vec::new();
+
+    for (root_dir, folder) in &folders {
+        let mut found = Vec::new();
+
+        if let Err(e) = find_entries(library.type_of, folder, &mut found).await {
+            warn!(target: "scanner", ?folder, error = ?e, "Unable to read folder. Keeping its missing files.");
+
+            unavailable_folders.push(folder);
+        } else if found.is_empty()
+            && FileModel::find_by_library_id(library.id, db)
+                .await?
+                .iter()
+                .any(|file| Path::new(&file.path).starts_with(folder))
+        {
+            warn!(target: "scanner", ?folder, "Folder is empty. Keeping its missing files.");
+
+            unavailable_folders.push(folder);
+        }
+
+        entries.extend(found.into_iter().map(|path| (root_dir, path)));
+    }
 
     let (mut checked_items, mut imported_items, mut overwritten_files) = (0, 0, 0);
+    let (mut skipped_items, mut updated_files) = (0, 0);
+
+    let mut seen_paths = HashSet::new();
+
+    let total = entries.len();
+
+    for (index, (root_dir, path)) in entries.into_iter().enumerate() {
+        // Stopping here leaves files which weren't reached yet alone.
+        check_cancelled(task_id)?;
+
+        let file_name = path
+            .file_name()
+            .map(|v| v.to_string_lossy().to_string())
+            .unwrap_or_default();
 
-    let mut inside_root_dir = dirs.pop().unwrap();
-    folders.push_front(inside_root_dir.clone());
+        update_task_progress(task_id, TaskType::LibraryScan(file_name), index + 1, total);
 
-    while let Some(dir) = folders.pop_front() {
-        if folders.is_empty() {
-            if let Some(next_dir) = dirs.pop() {
-                inside_root_dir = next_dir;
-                folders.push_front(inside_root_dir.clone());
+        let outcome = scan_file(library, root_dir, &path, full_rescan, db).await?;
+
+        if outcome != ScanOutcome::Ignored {
+            if let Some(path) = path.to_str() {
+                seen_paths.insert(path.replace('\\', "/"));
             }
         }
 
+        match outcome {
+            ScanOutcome::Ignored => continue,
+            ScanOutcome::Failed | ScanOutcome::Existing => (),
+            ScanOutcome::Skipped => skipped_items += 1,
+            ScanOutcome::Imported => imported_items += 1,
+            ScanOutcome::Updated => updated_files += 1,
+            ScanOutcome::Overwritten => overwritten_files += 1,
+        }
+
+        checked_items += 1;
+    }
+
+    // Files inside of the scanned folders which are no longer there.
+    let mut removed_files = 0;
+
+    for file in FileModel::find_by_library_id(library.id, db).await? {
+        let path = Path::new(&file.path);
+
+        if !seen_paths.contains(&file.path)
+            && folders.iter().any(|(_, folder)| path.starts_with(folder))
+            && !unavailable_folders
+                .iter()
+                .any(|folder| path.starts_with(folder))
+        {
+            info!(target: "scanner", id = ?file.id, path = file.path, "Removing Missing File");
+
+            removed_files += FileModel::soft_delete_by_id(file.id, db).await?;
+        }
+    }
+
+    BookModel::sync_deleted_with_files(library.id, db).await?;
+
+    info!("Checked {checked_items} Files, Skipped {skipped_items} Unchanged Files, Imported {imported_items} Files, Updated {updated_files} Files, Overwritten {overwritten_files} Files, Removed {removed_files} Missing Files");
+
+    Ok(())
+}
+
+/// Add everything inside of the folder to `entries`. Folders which are a single book aren't entered.
+async fn find_entries(
+    library_type: LibraryType,
+    folder: &Path,
+    entries: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let mut unscanned = VecDeque::from([folder.to_path_buf()]);
+
+    while let Some(dir) = unscanned.pop_front() {
         let mut dir = fs::read_dir(&dir).await?;
 
         while let Some(entry) = dir.next_entry().await? {
             let file_type = entry.file_type().await?;
-            let file_name = entry.file_name();
             let path = entry.path();
-            let meta = entry.metadata().await?;
-
-            if file_type.is_dir() {
-                folders.push_back(path);
-            } else if file_type.is_file() {
-                let file_name = file_name.into_string().unwrap();
-                let (file_name, file_type) = match file_name.rsplit_once('.') {
-                    Some((v1, v2)) => (v1.to_string(), v2.to_string().to_lowercase()),
-                    None => (file_name, String::new()),
-                };
-
-                if WHITELISTED_FILE_TYPES.contains(&file_type.as_str())
-                    && library.type_of.is_filetype_valid(file_type.as_str())
-                {
-                    send_message_to_clients(WebsocketNotification::update_task(
-                        task_id,
-                        TaskType::LibraryScan(file_name.clone()),
-                        true,
-                    ));
 
-                    let file_size = fs::metadata(&path).await?.len();
+            if file_type.is_dir() && !is_book_folder(library_type, &path) {
+                unscanned.push_back(path);
+            } else {
+                entries.push(path);
+            }
+        }
+    }
 
-                    checked_items += 1;
+    Ok(())
+}
 
-                    let mut book = match bookie::load_from_path(&path.to_string_lossy()) {
-                        Ok(book) => {
-                            if let Some(book) = book {
-                                book
-                            } else {
-                                error!(target: "scanner", file = ?path, "Unable to find book from path");
-                                continue;
-                            }
-                        }
+/// What happened to a file when it was scanned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanOutcome {
+    /// Not a book this library can hold.
+    Ignored,
+    /// The book couldn't be opened.
+    Failed,
+    /// Unchanged since the last scan. It wasn't opened.
+    Skipped,
+    /// Opened, but the stored file was already up to date.
+    Existing,
+    Imported,
+    Updated,
+    Overwritten,
+}
 
-                        Err(e) => {
-                            error!(target: "scanner", error = ?e, file = ?path);
-                            continue;
-                        }
-                    };
-
-                    let path = path.to_str().unwrap().replace('\\', "/");
-
-                    let Some(hash) = book.compute_hash() else {
-                        error!(target: "scanner", file = path, "Unable to compute hash");
-                        continue;
-                    };
-
-                    let chapter_count = book.chapter_count() as i64;
-
-                    // If file exists, check to see if the one currently in the database is valid.
-                    let file = if let Some(mut model) =
-                        FileModel::find_one_by_hash_or_path(&path, &hash, db).await?
-                    {
-                        // We found it by path, no need to verify anything since it exists.
-                        // Update stored model with the new one that matched the hash.
-                        // TODO: Optimize? I don't want to check the FS for EVERY SINGLE FILE.
-                        if model.path != path && tokio::fs::metadata(&model.path).await.is_err() {
-                            model.path = path;
-                            model.file_name = file_name;
-                            model.file_type = file_type;
-                            model.file_size = file_size as i64;
-                            model.library_id = library.id;
-                            model.chapter_count = chapter_count;
-                            model.hash = hash;
-
-                            model.modified_at = Utc
-                                .timestamp_millis_opt(
-                                    meta.modified()?.duration_since(UNIX_EPOCH)?.as_millis() as i64,
-                                )
-                                .unwrap()
-                                .naive_utc();
-                            model.accessed_at = Utc
-                                .timestamp_millis_opt(
-                                    meta.accessed()?.duration_since(UNIX_EPOCH)?.as_millis() as i64,
-                                )
-                                .unwrap()
-                                .naive_utc();
-                            model.created_at = Utc
-                                .timestamp_millis_opt(
-                                    meta.created()?.duration_since(UNIX_EPOCH)?.as_millis() as i64,
-                                )
-                                .unwrap()
-                                .naive_utc();
-                            model.deleted_at = None;
-
-                            info!(target: "scanner", id = ?model.id, "Overwriting Missing File");
-
-                            overwritten_files += 1;
-
-                            model.update(db).await?;
-                        }
+/// Scan a single file, or a folder which is a single book, and match it to a book.
+///
+/// The `root_dir` is the library directory the file is inside of.
+pub async fn scan_file(
+    library: &LibraryModel,
+    root_dir: &Path,
+    path: &Path,
+    full_rescan: bool,
+    db: &mut SqliteConnection,
+) -> Result<ScanOutcome> {
+    let meta = fs::symlink_metadata(path).await?;
 
-                        model
-                    } else {
-                        let identifier = if let Some(found) = book.find(BookSearch::Identifier) {
-                            let parsed = found
-                                .into_iter()
-                                .map(|v| parse_book_id(&v))
-                                .collect::<Vec<_>>();
-
-                            parsed
-                                .iter()
-                                .find_map(|v| v.as_isbn_13())
-                                .or_else(|| parsed.iter().find_map(|v| v.as_isbn_10()))
-                        } else {
-                            None
-                        };
-
-                        let file = NewFileModel {
-                            path,
-
-                            file_name,
-                            file_type,
-                            file_size: file_size as i64,
-
-                            library_id: library.id,
-                            book_id: None,
-                            chapter_count,
-
-                            identifier,
-                            hash,
-
-                            modified_at: Utc
-                                .timestamp_millis_opt(
-                                    meta.modified()?.duration_since(UNIX_EPOCH)?.as_millis() as i64,
-                                )
-                                .unwrap()
-                                .naive_utc(),
-                            accessed_at: Utc
-                                .timestamp_millis_opt(
-                                    meta.accessed()?.duration_since(UNIX_EPOCH)?.as_millis() as i64,
-                                )
-                                .unwrap()
-                                .naive_utc(),
-                            created_at: Utc
-                                .timestamp_millis_opt(
-                                    meta.created()?.duration_since(UNIX_EPOCH)?.as_millis() as i64,
-                                )
-                                .unwrap()
-                                .naive_utc(),
-                            deleted_at: None,
-                        };
-
-                        imported_items += 1;
-
-                        file.insert(db).await?
-                    };
-
-                    if file.book_id.is_none() {
-                        let file_id = file.id;
-
-                        if library.type_of == LibraryType::Book {
-                            // TODO: Run Concurrently.
-                            if let Err(e) = file_match_or_create_book(file, library.id, db).await {
-                                error!(error = ?e, "File #{file_id} file_match_or_create_metadata");
-                            }
-                        } else if let Err(e) =
-                            file_match_or_create_comic_book(file, &inside_root_dir, library.id, db)
-                                .await
-                        {
-                            error!(error = ?e, "File #{file_id} file_match_or_create_metadata");
-                        }
-                    }
+    let Some(file_name) = path.file_name().and_then(|v| v.to_str()) else {
+        return Ok(ScanOutcome::Ignored);
+    };
+
+    // A folder of audio tracks is a single audiobook.
+    let is_track_folder = meta.is_dir()
+        && library.type_of == LibraryType::AudioBook
+        && bookie::audio::is_audio_book_folder(path);
+
+    // A folder of images is a single comic book. It's downloaded as a CBZ.
+    let is_image_folder = meta.is_dir()
+        && library.type_of == LibraryType::ComicBook
+        && bookie::cb::is_image_folder(path);
+
+    let is_book_folder = is_track_folder || is_image_folder;
+
+    if !meta.is_file() && !is_book_folder {
+        return Ok(ScanOutcome::Ignored);
+    }
+
+    let file_name = file_name.to_string();
+    let (file_name, file_type) = if is_track_folder {
+        (file_name, String::from("mp3"))
+    } else if is_image_folder {
+        (file_name, String::from("cbz"))
+    } else if is_zipped_fb2(&file_name) {
+        // The only double extension we support.
+        (
+            file_name[..file_name.len() - 8].to_string(),
+            String::from("fb2.zip"),
+        )
+    } else {
+        match file_name.rsplit_once('.') {
+            Some((v1, v2)) => (v1.to_string(), v2.to_string().to_lowercase()),
+            None => (file_name, String::new()),
+        }
+    };
+
+    if !WHITELISTED_FILE_TYPES.contains(&file_type.as_str())
+        || !library.type_of.is_filetype_valid(file_type.as_str())
+        // CBR can only be read when built with the `rar` feature.
+        || (!cfg!(feature = "rar") && file_type == "cbr")
+    {
+        trace!(file = ?path, "Skipping File. Not a whitelisted file type.");
+        return Ok(ScanOutcome::Ignored);
+    }
+
+    let mut outcome = ScanOutcome::Existing;
+
+    let file_size = if is_book_folder {
+        get_folder_size(path).await?
+    } else {
+        fs::metadata(path).await?.len()
+    };
+
+    let modified_at = get_modified_at(&meta)?;
+
+    let inode = get_inode(&meta);
+
+    let Some(path) = path.to_str().map(|v| v.replace('\\', "/")) else {
+        return Ok(ScanOutcome::Ignored);
+    };
+
+    // Skip opening and hashing the file if it hasn't changed since the last scan.
+    let unchanged = if full_rescan {
+        None
+    } else {
+        FileModel::find_one_by_path(&path, db)
+            .await?
+            .filter(|model| {
+                model.file_size == file_size as i64
+                    && model.modified_at == modified_at
+                    && (model.inode.is_none() || model.inode == inode)
+            })
+    };
+
+    let file = if let Some(mut model) = unchanged {
+        outcome = ScanOutcome::Skipped;
+
+        // Files scanned before inodes were stored, restored or renamed files.
+        if (model.inode.is_none() && inode.is_some())
+            || model.deleted_at.is_some()
+            || model.file_name != file_name
+        {
+            model.inode = inode;
+            model.deleted_at = None;
+            model.file_name = file_name;
+            model.file_type = file_type;
+            model.update(db).await?;
+        }
+
+        model
+    } else {
+        let mut book = match bookie::load_from_path(&path) {
+            Ok(book) => {
+                if let Some(book) = book {
+                    book
                 } else {
-                    trace!(file = ?path, "Skipping File. Not a whitelisted file type.");
+                    error!(target: "scanner", file = ?path, "Unable to find book from path");
+                    return Ok(ScanOutcome::Failed);
                 }
             }
+
+            Err(e) => {
+                error!(target: "scanner", error = ?e, file = ?path);
+                return Ok(ScanOutcome::Failed);
+            }
+        };
+
+        let Some(hash) = book.compute_hash() else {
+            error!(target: "scanner", file = path, "Unable to compute hash");
+            return Ok(ScanOutcome::Failed);
+        };
+
+        let chapter_count = book.chapter_count() as i64;
+
+        // If file exists, check to see if the one currently in the database is valid.
+        if let Some(mut model) = FileModel::find_one_by_hash_or_path(&path, &hash, db).await? {
+            // We found it by path, no need to verify anything since it exists.
+            // Update stored model with the new one that matched the hash.
+            if model.path != path && tokio::fs::metadata(&model.path).await.is_err() {
+                model.path = path;
+                model.file_name = file_name;
+                model.file_type = file_type;
+                model.file_size = file_size as i64;
+                model.library_id = library.id;
+                model.chapter_count = chapter_count;
+                model.hash = hash;
+                model.inode = inode;
+
+                model.modified_at = modified_at;
+                model.accessed_at = Utc
+                    .timestamp_millis_opt(
+                        meta.accessed()?.duration_since(UNIX_EPOCH)?.as_millis() as i64
+                    )
+                    .unwrap()
+                    .naive_utc();
+                model.created_at = Utc
+                    .timestamp_millis_opt(
+                        meta.created()?.duration_since(UNIX_EPOCH)?.as_millis() as i64
+                    )
+                    .unwrap()
+                    .naive_utc();
+                model.deleted_at = None;
+
+                info!(target: "scanner", id = ?model.id, "Overwriting Missing File");
+
+                outcome = ScanOutcome::Overwritten;
+
+                model.update(db).await?;
+            } else if model.path == path
+                && (model.hash != hash
+                    || model.file_size != file_size as i64
+                    || model.modified_at != modified_at
+                    || model.inode != inode
+                    || model.deleted_at.is_some())
+            {
+                // The file was changed in place or restored.
+                model.file_name = file_name;
+                model.file_type = file_type;
+                model.file_size = file_size as i64;
+                model.chapter_count = chapter_count;
+                model.hash = hash;
+                model.inode = inode;
+                model.modified_at = modified_at;
+                model.deleted_at = None;
+
+                info!(target: "scanner", id = ?model.id, "Updating Changed File");
+
+                outcome = ScanOutcome::Updated;
+
+                model.update(db).await?;
+            }
+
+            model
+        } else {
+            let identifier = if let Some(found) = book.find(BookSearch::Identifier) {
+                let parsed = found
+                    .into_iter()
+                    .map(|v| parse_book_id(&v))
+                    .collect::<Vec<_>>();
+
+                parsed
+                    .iter()
+                    .find_map(|v| v.as_isbn_13())
+                    .or_else(|| parsed.iter().find_map(|v| v.as_isbn_10()))
+            } else {
+                None
+            };
+
+            let file = NewFileModel {
+                path,
+
+                file_name,
+                file_type,
+                file_size: file_size as i64,
+
+                library_id: library.id,
+                book_id: None,
+                chapter_count,
+
+                identifier,
+                hash,
+                inode,
+
+                modified_at,
+                accessed_at: Utc
+                    .timestamp_millis_opt(
+                        meta.accessed()?.duration_since(UNIX_EPOCH)?.as_millis() as i64
+                    )
+                    .unwrap()
+                    .naive_utc(),
+                created_at: Utc
+                    .timestamp_millis_opt(
+                        meta.created()?.duration_since(UNIX_EPOCH)?.as_millis() as i64
+                    )
+                    .unwrap()
+                    .naive_utc(),
+                deleted_at: None,
+            };
+
+            outcome = ScanOutcome::Imported;
+
+            file.insert(db).await?
+        }
+    };
+
+    if file.book_id.is_none() {
+        let file_id = file.id;
+
+        if matches!(
+            library.type_of,
+            LibraryType::Book | LibraryType::Document | LibraryType::AudioBook
+        ) {
+            // TODO: Run Concurrently.
+            if let Err(e) = file_match_or_create_book(file, library.id, db).await {
+                error!(error = ?e, "File #{file_id} file_match_or_create_metadata");
+            }
+        } else if let Err(e) = file_match_or_create_comic_book(file, root_dir, library.id, db).await
+        {
+            error!(error = ?e, "File #{file_id} file_match_or_create_metadata");
         }
     }
 
-    info!("Checked {checked_items} Files, Imported {imported_items} Files, Overwritten {overwritten_files} Files");
+    Ok(outcome)
+}
 
-    Ok(())
+/// A folder which is a single book. Its files aren't scanned separately.
+pub fn is_book_folder(library_type: LibraryType, path: &Path) -> bool {
+    match library_type {
+        LibraryType::AudioBook => bookie::audio::is_audio_book_folder(path),
+        LibraryType::ComicBook => bookie::cb::is_image_folder(path),
+        _ => false,
+    }
+}
+
+/// A `.fb2.zip` file. The extension is checked as bytes so the name can be sliced before it.
+fn is_zipped_fb2(file_name: &str) -> bool {
+    let bytes = file_name.as_bytes();
+
+    bytes.len() > 8 && bytes[bytes.len() - 8..].eq_ignore_ascii_case(b".fb2.zip")
+}
+
+/// The modified time of the file in milliseconds. Stored to tell if a file is unchanged.
+pub(crate) fn get_modified_at(meta: &std::fs::Metadata) -> Result<NaiveDateTime> {
+    Ok(Utc
+        .timestamp_millis_opt(meta.modified()?.duration_since(UNIX_EPOCH)?.as_millis() as i64)
+        .unwrap()
+        .naive_utc())
+}
+
+/// The inode of the file. Used alongside the size and modified time to tell if a file is unchanged.
+#[cfg(unix)]
+pub(crate) fn get_inode(meta: &std::fs::Metadata) -> Option<i64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(meta.ino() as i64)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn get_inode(_meta: &std::fs::Metadata) -> Option<i64> {
+    None
+}
+
+/// The size of the files directly inside of the folder.
+async fn get_folder_size(path: &Path) -> Result<u64> {
+    let mut size = 0;
+
+    let mut dir = fs::read_dir(path).await?;
+
+    while let Some(entry) = dir.next_entry().await? {
+        let meta = entry.metadata().await?;
+
+        if meta.is_file() {
+            size += meta.len();
+        }
+    }
+
+    Ok(size)
 }
 
 async fn file_match_or_create_book(
@@ -313,7 +593,24 @@ async fn file_match_or_create_comic_book(
         return Ok(());
     };
 
-    let stripped_book_name = extract_name_from_path(local_path);
+    // Prefer the values stored inside of the archive. Eg. ComicInfo.xml
+    let (series, number, title) = {
+        let book = bookie::load_from_path(&file.path).ok().flatten();
+
+        let find = |search| {
+            book.as_ref()
+                .and_then(|v| v.find(search))
+                .map(|mut v| v.remove(0))
+        };
+
+        (
+            find(BookSearch::Other("Series")),
+            find(BookSearch::Other("Number")).and_then(|v| v.trim().parse::<f32>().ok()),
+            find(BookSearch::Other("Title")),
+        )
+    };
+
+    let stripped_book_name = series.unwrap_or_else(|| extract_name_from_path(local_path));
 
     debug!("{stripped_book_name:?} - {local_path:?}");
 
@@ -405,7 +702,12 @@ async fn file_match_or_create_comic_book(
 
         debug!("Main Book ID: {main_book_id:?}");
 
-        let Some(volume_type) = extract_comic_volume(&file.file_name) else {
+        let volume_type = match number {
+            Some(number) => Some(VolumeType::Volume(number.trunc() as u32)),
+            None => extract_comic_volume(&file.file_name),
+        };
+
+        let Some(volume_type) = volume_type else {
             // TODO: How to handle this?
             // We don't know what volume this is.
 
@@ -453,7 +755,28 @@ async fn file_match_or_create_comic_book(
         debug!("Section Book ID: {sec_book_id:?} - Index: {book_index}");
 
         // Multiple index by 10 so we can define .5 chapters.
-        let book_index = book_index as i64 * 10;
+        let book_index =
+            book_index as i64 * 10 + number.map_or(0, |v| (v.fract() * 10.0).round() as i64);
+
+        // People and cover of this specific chapter.
+        let (local_author_ids, local_thumb) = match LocalMetadata
+            .get_metadata_from_files(std::slice::from_ref(&file))
+            .await?
+        {
+            Some(mut local) => {
+                let (_, author_ids) = local.add_or_ignore_authors_into_database(db).await?;
+
+                let thumb = local
+                    .meta
+                    .thumb_locations
+                    .into_iter()
+                    .find(|v| v.is_file_data());
+
+                (author_ids, thumb)
+            }
+
+            None => (Vec::new(), None),
+        };
 
         // Now we can officially find or create the sub book.
         let sub_book_model =
@@ -464,13 +787,23 @@ async fn file_match_or_create_comic_book(
                     book_model.type_of = BookType::ComicBookChapter;
                     book_model.parent_id = Some(sec_book_id);
                     book_model.index = Some(book_index);
-                    book_model.title = Some(format!(
-                        "{} - {}",
-                        if is_prologue { "Prologue" } else { "Chapter" },
-                        book_index / 10
-                    ));
+                    book_model.title = Some(title.unwrap_or_else(|| {
+                        format!(
+                            "{} - {}",
+                            if is_prologue { "Prologue" } else { "Chapter" },
+                            book_index / 10
+                        )
+                    }));
                     book_model.original_title = book_model.title.clone();
 
+                    if let Some(mut thumb) = local_thumb {
+                        thumb.download(db).await?;
+
+                        if let Some(thumb) = thumb.as_local_value() {
+                            book_model.thumb_url = thumb.clone();
+                        }
+                    }
+
                     book_model.insert(db).await?
                 }
             };
@@ -489,7 +822,7 @@ async fn file_match_or_create_comic_book(
             }
         }
 
-        for person_id in author_ids {
+        for person_id in author_ids.into_iter().chain(local_author_ids) {
             BookPersonModel {
                 book_id: sub_book_model.id,
                 person_id,
@@ -501,3 +834,144 @@ async fn file_match_or_create_comic_book(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use common::{Source, ThumbnailStore};
+    use common_local::BookItemCached;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::model::NewLibraryModel;
+
+    async fn insert_library(db: &mut SqliteConnection) -> Result<LibraryModel> {
+        let now = Utc::now().naive_utc();
+
+        NewLibraryModel {
+            name: String::from("Books"),
+            type_of: LibraryType::Book,
+            is_public: false,
+            settings: None,
+            scanned_at: now,
+            created_at: now,
+            updated_at: now,
+        }
+        .insert(db)
+        .await
+    }
+
+    /// A book with a single file.
+    async fn insert_file(
+        path: &str,
+        library: &LibraryModel,
+        db: &mut SqliteConnection,
+    ) -> Result<FileModel> {
+        let now = Utc::now().naive_utc();
+        let file_name = path.rsplit('/').next().unwrap_or(path).to_string();
+
+        let book = NewBookModel {
+            library_id: library.id,
+            type_of: BookType::Book,
+            parent_id: None,
+            source: Source::try_from(format!("local:{file_name}")).unwrap(),
+            file_item_count: 1,
+            title: None,
+            original_title: None,
+            description: None,
+            rating: 0.0,
+            thumb_url: ThumbnailStore::None,
+            cached: BookItemCached::default(),
+            index: None,
+            refreshed_at: now,
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+            available_at: None,
+            year: None,
+        }
+        .insert(db)
+        .await?;
+
+        NewFileModel {
+            path: path.to_string(),
+            file_name,
+            file_type: String::from("epub"),
+            file_size: 0,
+            library_id: library.id,
+            book_id: Some(book.id),
+            chapter_count: 0,
+            identifier: None,
+            hash: path.to_string(),
+            inode: None,
+            modified_at: now,
+            accessed_at: now,
+            created_at: now,
+            deleted_at: None,
+        }
+        .insert(db)
+        .await
+    }
+
+    #[tokio::test]
+    async fn remove_missing_files() -> Result<()> {
+        // A migrated in-memory database. It only lives as long as its single connection.
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await?;
+
+        sqlx::migrate!("./migrations").run(&pool).await?;
+
+        let db = &mut *pool.acquire().await?;
+
+        let temp = tempfile::tempdir()?;
+        let dir = |name: &str| temp.path().join(name).to_string_lossy().replace('\\', "/");
+
+        // Readable, with a file which isn't a book.
+        fs::create_dir(dir("full")).await?;
+        fs::write(dir("full/notes.xyz"), "").await?;
+        // Empty, like a drive which isn't mounted.
+        fs::create_dir(dir("empty")).await?;
+        // Missing entirely.
+
+        let library = insert_library(db).await?;
+        let other_library = insert_library(db).await?;
+
+        let removed = insert_file(&dir("full/a.epub"), &library, db).await?;
+        let empty = insert_file(&dir("empty/b.epub"), &library, db).await?;
+        let missing = insert_file(&dir("missing/c.epub"), &library, db).await?;
+        let other = insert_file(&dir("other/d.epub"), &other_library, db).await?;
+
+        // Deleted without syncing its book. Only the scanned library is synced.
+        FileModel::soft_delete_by_id(other.id, db).await?;
+
+        let directories = ["full", "empty", "missing"]
+            .into_iter()
+            .map(|name| DirectoryModel {
+                library_id: library.id,
+                path: dir(name),
+            })
+            .collect();
+
+        library_scan(&library, directories, false, TaskId::new(), db).await?;
+
+        for (file, is_deleted) in [(removed, true), (empty, false), (missing, false)] {
+            let file = FileModel::find_one_by_id(file.id, db).await?.unwrap();
+            assert_eq!(file.deleted_at.is_some(), is_deleted, "{}", file.path);
+
+            let book = BookModel::find_one_by_id(file.book_id.unwrap(), db)
+                .await?
+                .unwrap();
+            assert_eq!(book.deleted_at.is_some(), is_deleted, "{}", file.path);
+        }
+
+        let other_book = BookModel::find_one_by_id(other.book_id.unwrap(), db)
+            .await?
+            .unwrap();
+        assert!(other_book.deleted_at.is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,451 @@
+//! Watches the directories of libraries so new, moved and removed books are picked up
+//! without having to run a library scan.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use common_local::{LibraryId, LibraryType};
+use lazy_static::lazy_static;
+use notify::{
+    event::{ModifyKind, RenameMode},
+    ErrorKind, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use sqlx::SqliteConnection;
+
+use super::is_book_folder;
+use crate::{
+    model::{DirectoryModel, LibraryModel},
+    task::{queue_task, FileChange, TaskFileChange},
+    Error, Result,
+};
+
+/// How long a path has to go without any events before its change is queued.
+static DEBOUNCE_DURATION: Duration = Duration::from_secs(2);
+
+lazy_static! {
+    /// The watcher for each library. Dropping a watcher stops it.
+    static ref WATCHERS: Mutex<HashMap<LibraryId, RecommendedWatcher>> = Mutex::new(HashMap::new());
+
+    /// Why a library couldn't be watched. These libraries are scanned periodically instead.
+    static ref WATCH_ERRORS: Mutex<HashMap<LibraryId, String>> = Mutex::new(HashMap::new());
+
+    /// Events from every watcher are sent to a single debouncing thread.
+    static ref EVENT_SENDER: Mutex<Sender<WatchEvent>> = {
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || debounce_events(receiver));
+
+        Mutex::new(sender)
+    };
+}
+
+struct WatchEvent {
+    library_id: LibraryId,
+    library_type: LibraryType,
+    root_dir: PathBuf,
+    event: Event,
+}
+
+struct PendingChange {
+    library_id: LibraryId,
+    root_dir: PathBuf,
+    change: FileChange,
+    last_event: Instant,
+}
+
+/// Watch the directories of every library which has `watch_directories` enabled.
+///
+/// Replaces the current watchers. Called on startup and whenever a library or its directories change.
+pub async fn refresh_library_watchers(db: &mut SqliteConnection) -> Result<()> {
+    let mut watchers = HashMap::new();
+    let mut errors = HashMap::new();
+
+    for library in LibraryModel::get_all(db).await? {
+        if !library.parse_settings_or_default()?.watch_directories {
+            continue;
+        }
+
+        let directories = DirectoryModel::find_directories_by_library_id(library.id, db)
+            .await?
+            .into_iter()
+            .map(|v| PathBuf::from(v.path))
+            .collect::<Vec<_>>();
+
+        match watch_library(&library, directories) {
+            Ok(watcher) => {
+                info!(target: "watcher", library = ?library.id, "Watching Library Directories");
+
+                watchers.insert(library.id, watcher);
+            }
+
+            Err(e) => {
+                error!(target: "watcher", library = ?library.id, error = ?e, "Unable to watch library");
+
+                errors.insert(library.id, describe_watch_error(&e));
+            }
+        }
+    }
+
+    // The old watchers are dropped first so they can't report errors for the new ones.
+    *WATCHERS.lock()? = watchers;
+    *WATCH_ERRORS.lock()? = errors;
+
+    Ok(())
+}
+
+fn watch_library(library: &LibraryModel, directories: Vec<PathBuf>) -> Result<RecommendedWatcher> {
+    let library_id = library.id;
+    let library_type = library.type_of;
+
+    let sender = EVENT_SENDER.lock()?.clone();
+    let root_dirs = directories.clone();
+
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+        Ok(event) => {
+            // The library directory the event happened inside of.
+            let Some(root_dir) = event
+                .paths
+                .first()
+                .and_then(|path| root_dirs.iter().find(|root| path.starts_with(root)))
+            else {
+                return;
+            };
+
+            let _ = sender.send(WatchEvent {
+                library_id,
+                library_type,
+                root_dir: root_dir.clone(),
+                event,
+            });
+        }
+
+        Err(e) => {
+            error!(target: "watcher", library = ?library_id, error = ?e);
+
+            // Changes may have been missed. Fall back to scanning the library.
+            if let Ok(mut errors) = WATCH_ERRORS.lock() {
+                errors.insert(library_id, describe_watch_error(&Error::from(e)));
+            }
+        }
+    })?;
+
+    for dir in &directories {
+        watcher.watch(dir, RecursiveMode::Recursive)?;
+    }
+
+    Ok(watcher)
+}
+
+/// Why the library couldn't be watched, if it failed to be.
+pub fn get_watch_error(library_id: LibraryId) -> Option<String> {
+    WATCH_ERRORS.lock().ok()?.get(&library_id).cloned()
+}
+
+/// The libraries which couldn't be watched.
+pub fn get_unwatched_libraries() -> Vec<LibraryId> {
+    WATCH_ERRORS
+        .lock()
+        .map(|v| v.keys().copied().collect())
+        .unwrap_or_default()
+}
+
+fn describe_watch_error(error: &Error) -> String {
+    match error {
+        // inotify returns ENOSPC once the watch limit is reached.
+        Error::Notify(e) if matches!(e.kind, ErrorKind::MaxFilesWatch) => String::from(
+            "Too many folders are being watched. Raise fs.inotify.max_user_watches to watch this library.",
+        ),
+
+        _ => error.to_string(),
+    }
+}
+
+fn debounce_events(receiver: Receiver<WatchEvent>) {
+    let mut pending = HashMap::new();
+
+    loop {
+        match receiver.recv_timeout(DEBOUNCE_DURATION / 4) {
+            Ok(event) => add_event(&mut pending, event),
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        for PendingChange {
+            library_id,
+            root_dir,
+            change,
+            ..
+        } in take_settled(&mut pending, Instant::now())
+        {
+            debug!(target: "watcher", ?change, "Queueing File Change");
+
+            queue_task(TaskFileChange {
+                library_id,
+                root_dir,
+                change,
+            });
+        }
+    }
+}
+
+/// Remove the changes which haven't had an event for [`DEBOUNCE_DURATION`].
+fn take_settled(pending: &mut HashMap<PathBuf, PendingChange>, now: Instant) -> Vec<PendingChange> {
+    let settled = pending
+        .iter()
+        .filter(|(_, v)| now.saturating_duration_since(v.last_event) >= DEBOUNCE_DURATION)
+        .map(|(path, _)| path.clone())
+        .collect::<Vec<_>>();
+
+    settled
+        .into_iter()
+        .filter_map(|path| pending.remove(&path))
+        .collect()
+}
+
+fn add_event(pending: &mut HashMap<PathBuf, PendingChange>, event: WatchEvent) {
+    let WatchEvent {
+        library_id,
+        library_type,
+        root_dir,
+        event,
+    } = event;
+
+    let mut paths = event.paths.into_iter();
+
+    let changes = match event.kind {
+        EventKind::Create(_)
+        | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Any)
+        | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+            paths.map(FileChange::Changed).collect()
+        }
+
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            match (paths.next(), paths.next()) {
+                (Some(from), Some(to)) => vec![FileChange::Moved { from, to }],
+                _ => Vec::new(),
+            }
+        }
+
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) | EventKind::Remove(_) => {
+            paths.map(FileChange::Removed).collect()
+        }
+
+        // We don't know which side of the rename this path is.
+        EventKind::Modify(ModifyKind::Name(_)) => paths
+            .map(|path| {
+                if path.exists() {
+                    FileChange::Changed(path)
+                } else {
+                    FileChange::Removed(path)
+                }
+            })
+            .collect(),
+
+        _ => Vec::new(),
+    };
+
+    for change in changes {
+        // A change inside of a folder which is a single book is a change to the book.
+        let change = match get_book_folder(library_type, &root_dir, change.path()) {
+            Some(folder) => FileChange::Changed(folder),
+            None => change,
+        };
+
+        add_change(pending, library_id, &root_dir, change);
+    }
+}
+
+/// Merge the change with the one already pending for the same path.
+fn add_change(
+    pending: &mut HashMap<PathBuf, PendingChange>,
+    library_id: LibraryId,
+    root_dir: &Path,
+    change: FileChange,
+) {
+    let change = match change {
+        FileChange::Moved { from, to } => match pending.remove(&from).map(|v| v.change) {
+            // It was never imported from where it was created.
+            Some(FileChange::Changed(_)) => FileChange::Changed(to),
+            Some(FileChange::Moved { from, .. }) => FileChange::Moved { from, to },
+            _ => FileChange::Moved { from, to },
+        },
+
+        FileChange::Changed(path) => match pending.remove(&path).map(|v| v.change) {
+            // Keep the move so the stored file isn't imported a second time.
+            Some(FileChange::Moved { from, to }) => FileChange::Moved { from, to },
+            _ => FileChange::Changed(path),
+        },
+
+        FileChange::Removed(path) => {
+            // Moved then removed. It's stored under the path it was moved from.
+            if let Some(FileChange::Moved { from, .. }) = pending.remove(&path).map(|v| v.change) {
+                add_change(pending, library_id, root_dir, FileChange::Removed(from));
+            }
+
+            FileChange::Removed(path)
+        }
+    };
+
+    pending.insert(
+        change.path().to_path_buf(),
+        PendingChange {
+            library_id,
+            root_dir: root_dir.to_path_buf(),
+            change,
+            last_event: Instant::now(),
+        },
+    );
+}
+
+/// The folder which is a single book that the path is inside of.
+fn get_book_folder(library_type: LibraryType, root_dir: &Path, path: &Path) -> Option<PathBuf> {
+    let parent = path.parent()?;
+
+    if parent != root_dir && parent.starts_with(root_dir) && is_book_folder(library_type, parent) {
+        Some(parent.to_path_buf())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use notify::event::CreateKind;
+
+    use super::*;
+
+    fn add(pending: &mut HashMap<PathBuf, PendingChange>, change: FileChange) {
+        add_change(pending, LibraryId::from(1), Path::new("/books"), change);
+    }
+
+    fn changes(pending: &HashMap<PathBuf, PendingChange>) -> Vec<FileChange> {
+        let mut changes = pending
+            .values()
+            .map(|v| v.change.clone())
+            .collect::<Vec<_>>();
+
+        changes.sort_by(|a, b| a.path().cmp(b.path()));
+
+        changes
+    }
+
+    fn moved(from: &str, to: &str) -> FileChange {
+        FileChange::Moved {
+            from: PathBuf::from(from),
+            to: PathBuf::from(to),
+        }
+    }
+
+    #[test]
+    fn coalesce_changes() {
+        // Created then moved. It's imported from where it ended up.
+        let mut pending = HashMap::new();
+        add(&mut pending, FileChange::Changed("/books/a.epub".into()));
+        add(&mut pending, moved("/books/a.epub", "/books/b.epub"));
+        assert_eq!(
+            changes(&pending),
+            [FileChange::Changed("/books/b.epub".into())]
+        );
+
+        // Moved then modified.
+        let mut pending = HashMap::new();
+        add(&mut pending, moved("/books/a.epub", "/books/b.epub"));
+        add(&mut pending, FileChange::Changed("/books/b.epub".into()));
+        assert_eq!(changes(&pending), [moved("/books/a.epub", "/books/b.epub")]);
+
+        // Moved twice.
+        let mut pending = HashMap::new();
+        add(&mut pending, moved("/books/a.epub", "/books/b.epub"));
+        add(&mut pending, moved("/books/b.epub", "/books/c.epub"));
+        assert_eq!(changes(&pending), [moved("/books/a.epub", "/books/c.epub")]);
+
+        // Moved then removed.
+        let mut pending = HashMap::new();
+        add(&mut pending, moved("/books/a.epub", "/books/b.epub"));
+        add(&mut pending, FileChange::Removed("/books/b.epub".into()));
+        assert_eq!(
+            changes(&pending),
+            [
+                FileChange::Removed("/books/a.epub".into()),
+                FileChange::Removed("/books/b.epub".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn coalesce_events() {
+        let mut pending = HashMap::new();
+
+        let event = |event: Event| WatchEvent {
+            library_id: LibraryId::from(1),
+            library_type: LibraryType::Book,
+            root_dir: PathBuf::from("/books"),
+            event,
+        };
+
+        add_event(
+            &mut pending,
+            event(Event::new(EventKind::Create(CreateKind::File)).add_path("/books/a.epub".into())),
+        );
+
+        add_event(
+            &mut pending,
+            event(
+                Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+                    .add_path("/books/a.epub".into())
+                    .add_path("/books/b.epub".into()),
+            ),
+        );
+
+        assert_eq!(
+            changes(&pending),
+            [FileChange::Changed("/books/b.epub".into())]
+        );
+    }
+
+    #[test]
+    fn debounce() {
+        let mut pending = HashMap::new();
+        add(&mut pending, FileChange::Changed("/books/a.epub".into()));
+        add(&mut pending, FileChange::Changed("/books/b.epub".into()));
+
+        let now = Instant::now();
+
+        assert!(take_settled(&mut pending, now).is_empty());
+
+        // Only the change which hasn't had an event in a while is settled.
+        if let Some(change) = pending.get_mut(Path::new("/books/a.epub")) {
+            change.last_event = now - DEBOUNCE_DURATION;
+        }
+
+        let settled = take_settled(&mut pending, now);
+        assert_eq!(settled.len(), 1);
+        assert_eq!(
+            settled[0].change,
+            FileChange::Changed("/books/a.epub".into())
+        );
+        assert_eq!(pending.len(), 1);
+
+        // Another event restarts the wait.
+        if let Some(change) = pending.get_mut(Path::new("/books/b.epub")) {
+            change.last_event = now - DEBOUNCE_DURATION;
+        }
+
+        add(&mut pending, FileChange::Changed("/books/b.epub".into()));
+        assert!(take_settled(&mut pending, now + DEBOUNCE_DURATION / 2).is_empty());
+
+        assert_eq!(
+            take_settled(&mut pending, now + DEBOUNCE_DURATION * 2).len(),
+            1
+        );
+        assert!(pending.is_empty());
+    }
+}
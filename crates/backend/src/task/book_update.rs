@@ -17,7 +17,9 @@ use crate::{
     model::{
         BookModel, BookPersonModel, FileModel, ImageLinkModel, NewBookModel, UploadedImageModel,
     },
-    sort_by_similarity, Result, SqlPool, Task,
+    sort_by_similarity,
+    task::{check_cancelled, update_task_progress},
+    Result, SqlPool, Task, TaskCategory,
 };
 
 #[derive(Clone)]
@@ -225,10 +227,8 @@ impl Task for TaskUpdateInvalidBook {
                             // Remove old meta persons
                             BookPersonModel::delete_by_book_id(old_book_id, db).await?;
 
-                            // TODO: Change to "deleted" instead of delting from database. We will delete from database every 24 hours.
-
-                            // Remove old Metadata
-                            BookModel::delete_by_id(old_book_id, db).await?;
+                            // Move old Metadata into the trash. It's purged with the rest of the trash.
+                            BookModel::soft_delete_by_id(old_book_id, db).await?;
                         } else {
                             // Update existing metadata.
                             // TODO: Check how long it has been since we've refreshed meta: new_meta if auto-ran.
@@ -418,7 +418,9 @@ impl Task for TaskUpdateInvalidBook {
                     let books =
                         BookModel::find_by(Some(library_id), offset, LIMIT, None, db).await?;
 
-                    for book in books {
+                    for (index, book) in books.into_iter().enumerate() {
+                        check_cancelled(task_id)?;
+
                         if Utc::now()
                             .signed_duration_since(
                                 book.refreshed_at.and_local_timezone(Utc).unwrap(),
@@ -428,14 +430,15 @@ impl Task for TaskUpdateInvalidBook {
                         {
                             let book_id = book.id;
 
-                            send_message_to_clients(WebsocketNotification::update_task(
+                            update_task_progress(
                                 task_id,
                                 TaskType::UpdatingBook {
                                     id: book_id,
                                     subtitle: None,
                                 },
-                                true,
-                            ));
+                                offset as usize + index + 1,
+                                amount as usize,
+                            );
 
                             Self::update_book_by_files(book, &active_agent, db).await?;
 
@@ -461,6 +464,10 @@ impl Task for TaskUpdateInvalidBook {
     fn name(&self) -> &'static str {
         "Updating Book"
     }
+
+    fn category(&self) -> TaskCategory {
+        TaskCategory::Metadata
+    }
 }
 
 impl TaskUpdateInvalidBook {
@@ -0,0 +1,135 @@
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use common_local::{
+    ws::{TaskId, TaskType},
+    LibraryId,
+};
+use sqlx::SqliteConnection;
+use tokio::fs;
+
+use crate::{
+    model::{BookModel, FileModel, LibraryModel},
+    scanner::{folder_scan, is_book_folder, scan_file},
+    task::{check_cancelled, update_task_progress},
+    Result, SqlPool, Task, TaskCategory,
+};
+
+/// A change to a path inside of a watched library directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileChange {
+    /// Created or modified.
+    Changed(PathBuf),
+    Moved {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    Removed(PathBuf),
+}
+
+impl FileChange {
+    /// The path the change ended up at.
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Changed(path) | Self::Removed(path) => path,
+            Self::Moved { to, .. } => to,
+        }
+    }
+}
+
+pub struct TaskFileChange {
+    pub library_id: LibraryId,
+    /// The library directory the change happened inside of.
+    pub root_dir: PathBuf,
+    pub change: FileChange,
+}
+
+#[async_trait]
+impl Task for TaskFileChange {
+    async fn run(&mut self, task_id: TaskId, pool: &SqlPool) -> Result<()> {
+        let db = &mut *pool.acquire().await?;
+
+        let Some(library) = LibraryModel::find_one_by_id(self.library_id, db).await? else {
+            return Ok(());
+        };
+
+        match &self.change {
+            FileChange::Changed(path) => self.import(&library, path, task_id, db).await?,
+
+            FileChange::Moved { from, to } => {
+                let (Some(from_str), Some(to_str)) = (path_to_string(from), path_to_string(to))
+                else {
+                    return Ok(());
+                };
+
+                let moved = FileModel::update_path_prefix(&from_str, &to_str, db).await?;
+
+                debug!(target: "watcher", moved, from = from_str, to = to_str, "Moved Files");
+
+                // Updates the file name, or imports it if it wasn't stored.
+                self.import(&library, to, task_id, db).await?;
+            }
+
+            FileChange::Removed(path) => {
+                // It was replaced before the change was ran.
+                if fs::metadata(path).await.is_ok() {
+                    self.import(&library, path, task_id, db).await?;
+                } else if let Some(path_str) = path_to_string(path) {
+                    let removed = FileModel::soft_delete_by_path(&path_str, db).await?;
+
+                    debug!(target: "watcher", removed, path = path_str, "Removed Files");
+                }
+            }
+        }
+
+        BookModel::sync_deleted_with_files(library.id, db).await?;
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "File Change"
+    }
+
+    fn category(&self) -> TaskCategory {
+        TaskCategory::LibraryScan(self.library_id)
+    }
+}
+
+impl TaskFileChange {
+    async fn import(
+        &self,
+        library: &LibraryModel,
+        path: &Path,
+        task_id: TaskId,
+        db: &mut SqliteConnection,
+    ) -> Result<()> {
+        let Ok(meta) = fs::metadata(path).await else {
+            return Ok(());
+        };
+
+        if meta.is_dir() && !is_book_folder(library.type_of, path) {
+            // A new or moved folder. Scan it for the books inside of it.
+            folder_scan(library, &self.root_dir, path, task_id, db).await?;
+        } else {
+            check_cancelled(task_id)?;
+
+            if let Some(file_name) = path.file_name() {
+                update_task_progress(
+                    task_id,
+                    TaskType::LibraryScan(file_name.to_string_lossy().to_string()),
+                    1,
+                    1,
+                );
+            }
+
+            scan_file(library, &self.root_dir, path, false, db).await?;
+        }
+
+        Ok(())
+    }
+}
+
+fn path_to_string(path: &Path) -> Option<String> {
+    path.to_str().map(|v| v.replace('\\', "/"))
+}
@@ -3,11 +3,15 @@ use common_local::{ws::TaskId, LibraryId};
 
 use crate::{
     model::{DirectoryModel, LibraryModel},
-    Result, SqlPool, Task,
+    queue_task,
+    scanner::watcher::get_unwatched_libraries,
+    Result, SqlPool, Task, TaskCategory,
 };
 
 pub struct TaskLibraryScan {
     pub library_id: LibraryId,
+    /// Open and hash every file, even ones which haven't changed since the last scan.
+    pub full_rescan: bool,
 }
 
 #[async_trait]
@@ -23,7 +27,7 @@ impl Task for TaskLibraryScan {
         let directories =
             DirectoryModel::find_directories_by_library_id(self.library_id, db).await?;
 
-        crate::scanner::library_scan(&library, directories, task_id, db).await?;
+        crate::scanner::library_scan(&library, directories, self.full_rescan, task_id, db).await?;
 
         Ok(())
     }
@@ -31,4 +35,33 @@ impl Task for TaskLibraryScan {
     fn name(&self) -> &'static str {
         "Library Scan"
     }
+
+    fn category(&self) -> TaskCategory {
+        TaskCategory::LibraryScan(self.library_id)
+    }
+}
+
+/// Scans the libraries whose directories couldn't be watched so their changes are still picked up.
+pub struct TaskScanUnwatched;
+
+#[async_trait]
+impl Task for TaskScanUnwatched {
+    async fn run(&mut self, _task_id: TaskId, _pool: &SqlPool) -> Result<()> {
+        for library_id in get_unwatched_libraries() {
+            queue_task(TaskLibraryScan {
+                library_id,
+                full_rescan: false,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "Scan Unwatched Libraries"
+    }
+
+    fn category(&self) -> TaskCategory {
+        TaskCategory::Maintenance
+    }
 }
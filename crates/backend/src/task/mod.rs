@@ -8,31 +8,63 @@ use std::{
 use actix_web::web;
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use common_local::ws::{TaskId, WebsocketNotification};
+use common_local::{
+    ws::{TaskId, TaskOutcome, TaskProgress, TaskType, WebsocketNotification},
+    LibraryId,
+};
 use lazy_static::lazy_static;
 use tokio::{runtime::Runtime, time::sleep};
 
-use crate::{http::send_message_to_clients, Result, SqlPool};
+use crate::{
+    config::get_config, http::send_message_to_clients, model::NewTaskHistoryModel, Error,
+    InternalError, Result, SqlPool,
+};
 
 mod book_update;
+mod file_change;
 mod library_scan;
+mod purge_trash;
 mod update_people;
 
 pub use book_update::*;
+pub use file_change::*;
 pub use library_scan::*;
+pub use purge_trash::*;
 pub use update_people::*;
 
+/// How many tasks can run at the same time.
 pub(self) static MAX_CONCURRENT_RUNS: usize = 2;
 
+/// How many priority tasks are started in a row before a queued task gets a turn.
+pub(self) static PRIORITY_RUNS_IN_A_ROW: usize = 2;
+
 // TODO: Unused Image Deletion task.
-// TODO: A should stop boolean
 
 lazy_static! {
     /// The tasks which are currently queued.
     pub static ref TASKS_QUEUED: Mutex<VecDeque<Box<dyn Task>>> = Mutex::new(VecDeque::new());
 
+    /// The tasks which are queued with priority. Interval tasks are queued here too.
+    pub static ref TASKS_QUEUED_PRIORITY: Mutex<VecDeque<Box<dyn Task>>> = Mutex::new(VecDeque::new());
+
     /// The tasks which run in intervals.
-    static ref TASK_INTERVALS: Mutex<Vec<TaskInterval>> = Mutex::new(vec![]);
+    static ref TASK_INTERVALS: Mutex<Vec<TaskInterval>> = Mutex::new(vec![
+        TaskInterval {
+            last_ran: None,
+            is_queued: false,
+            interval: Duration::from_secs(60 * 60 * 24),
+            is_enabled: || true,
+            task: || Box::new(TaskPurgeTrash),
+        },
+        // Not ran on startup. Only once an hour has passed.
+        TaskInterval {
+            last_ran: Some(Utc::now()),
+            is_queued: false,
+            interval: Duration::from_secs(60 * 60),
+            is_enabled: || get_config().watcher.scan_unwatched,
+            task: || Box::new(TaskScanUnwatched),
+        },
+    ]);
 
     /// Currently running Tasks
     static ref TASKS_RUNNING: Mutex<Vec<TaskRunning>> = Mutex::new(Vec::new());
@@ -41,26 +73,41 @@ lazy_static! {
 struct TaskRunning {
     id: TaskId,
     name: &'static str,
+    category: TaskCategory,
     started: DateTime<Utc>,
+    /// Set when the task should stop. Tasks check it between items with [`check_cancelled`].
+    cancelled: bool,
+    items_processed: usize,
 }
 
 struct TaskInterval {
+    /// When the task last finished.
     pub last_ran: Option<DateTime<Utc>>,
+    /// Queued or running. It isn't queued again until it finishes.
+    pub is_queued: bool,
     pub interval: Duration,
+    pub is_enabled: fn() -> bool,
     pub task: fn() -> Box<dyn Task>,
 }
 
-// TODO: Implement for Concurrent task running.
-// Only 1 task can run for each category.
-// enum TaskCategory {
-//     LibraryScan(LibraryId),
-// }
+/// Only 1 task can run for each category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskCategory {
+    /// Scanning the directories of a library, or importing changes to them.
+    LibraryScan(LibraryId),
+    Metadata,
+    People,
+    /// Cleaning up, like purging the trash.
+    Maintenance,
+}
 
 #[async_trait]
 pub trait Task: Send {
     async fn run(&mut self, task_id: TaskId, pool: &SqlPool) -> Result<()>;
 
     fn name(&self) -> &'static str;
+
+    fn category(&self) -> TaskCategory;
 }
 
 pub fn queue_task<T: Task + 'static>(task: T) {
@@ -68,7 +115,51 @@ pub fn queue_task<T: Task + 'static>(task: T) {
 }
 
 pub fn queue_task_priority<T: Task + 'static>(task: T) {
-    TASKS_QUEUED.lock().unwrap().push_front(Box::new(task));
+    TASKS_QUEUED_PRIORITY
+        .lock()
+        .unwrap()
+        .push_back(Box::new(task));
+}
+
+/// Ask a running task to stop. Returns false if the task isn't running.
+pub fn cancel_task(task_id: TaskId) -> bool {
+    let mut tasks = TASKS_RUNNING.lock().unwrap();
+
+    if let Some(task) = tasks.iter_mut().find(|v| v.id == task_id) {
+        task.cancelled = true;
+        true
+    } else {
+        false
+    }
+}
+
+/// Returns [`InternalError::TaskCancelled`] if the task was asked to stop.
+pub fn check_cancelled(task_id: TaskId) -> Result<()> {
+    let tasks = TASKS_RUNNING.lock()?;
+
+    if tasks.iter().any(|v| v.id == task_id && v.cancelled) {
+        Err(InternalError::TaskCancelled.into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Send the item a task is working on along with its progress.
+pub fn update_task_progress(task_id: TaskId, type_of: TaskType, current: usize, total: usize) {
+    if let Some(task) = TASKS_RUNNING
+        .lock()
+        .unwrap()
+        .iter_mut()
+        .find(|v| v.id == task_id)
+    {
+        task.items_processed = current;
+    }
+
+    send_message_to_clients(WebsocketNotification::update_task_progress(
+        task_id,
+        type_of,
+        TaskProgress { current, total },
+    ));
 }
 
 pub fn start_task_manager(db: web::Data<SqlPool>) {
@@ -76,84 +167,413 @@ pub fn start_task_manager(db: web::Data<SqlPool>) {
         let rt = Runtime::new().unwrap();
 
         rt.block_on(async {
+            // Priority tasks started since a queued task was last started.
+            let mut priority_streak = 0;
+
             loop {
                 sleep(Duration::from_secs(1)).await;
 
-                // TODO: Should I check intervals first or manually queued first?
+                queue_interval_tasks();
+
+                while let Some((task, is_priority)) = next_task(priority_streak) {
+                    if is_priority {
+                        priority_streak += 1;
+                    } else {
+                        priority_streak = 0;
+                    }
+
+                    start_task(task, db.clone());
+                }
+            }
+        });
+    });
+}
 
-                // Used to prevent holding lock past await.
-                let task = {
-                    let now = Utc::now();
+/// Queue the interval tasks which are due with priority.
+fn queue_interval_tasks() {
+    queue_due_intervals(&mut TASK_INTERVALS.lock().unwrap(), Utc::now());
+}
 
-                    // Get the next task in interval.
-                    let mut v = TASK_INTERVALS.lock().unwrap();
+fn queue_due_intervals(intervals: &mut [TaskInterval], now: DateTime<Utc>) {
+    for interval in intervals.iter_mut() {
+        let is_due = match interval.last_ran {
+            None => true,
+            Some(d) => now
+                .signed_duration_since(d)
+                .to_std()
+                .is_ok_and(|v| v >= interval.interval),
+        };
+
+        if is_due && !interval.is_queued && (interval.is_enabled)() {
+            interval.is_queued = true;
+
+            TASKS_QUEUED_PRIORITY
+                .lock()
+                .unwrap()
+                .push_back((interval.task)());
+        }
+    }
+}
 
-                    let interval = v.iter_mut().find_map(|v| {
-                        match v.last_ran {
-                            None => {
-                                // TODO: Update last_ran AFTER we've ran the task.
-                                v.last_ran = Some(Utc::now());
+/// Called once a task finishes. The next run of an interval task is counted from when it finished.
+fn finish_interval_task(name: &'static str) {
+    finish_interval(&mut TASK_INTERVALS.lock().unwrap(), name, Utc::now());
+}
 
-                                Some((v.task)())
-                            }
+fn finish_interval(intervals: &mut [TaskInterval], name: &'static str, now: DateTime<Utc>) {
+    for interval in intervals.iter_mut() {
+        if interval.is_queued && (interval.task)().name() == name {
+            interval.is_queued = false;
+            interval.last_ran = Some(now);
+        }
+    }
+}
 
-                            Some(d)
-                                if now.signed_duration_since(d).to_std().unwrap() >= v.interval =>
-                            {
-                                // TODO: Update last_ran AFTER we've ran the task.
-                                v.last_ran = Some(Utc::now());
+/// The next task to start if there's an open slot, and if it was queued with priority.
+///
+/// Tasks whose category is already running are skipped over.
+fn next_task(priority_streak: usize) -> Option<(Box<dyn Task>, bool)> {
+    let running = TASKS_RUNNING.lock().unwrap();
+
+    if running.len() >= MAX_CONCURRENT_RUNS {
+        return None;
+    }
+
+    let is_runnable = |category: TaskCategory| !running.iter().any(|v| v.category == category);
+
+    let mut priority = TASKS_QUEUED_PRIORITY.lock().unwrap();
+    let mut queued = TASKS_QUEUED.lock().unwrap();
+
+    let priority_index = priority.iter().position(|v| is_runnable(v.category()));
+    let queued_index = queued.iter().position(|v| is_runnable(v.category()));
+
+    match (priority_index, queued_index) {
+        // Give the queued tasks a turn so they aren't stuck behind priority ones.
+        (Some(_), Some(index)) if priority_streak >= PRIORITY_RUNS_IN_A_ROW => {
+            queued.remove(index).map(|v| (v, false))
+        }
+        (Some(index), _) => priority.remove(index).map(|v| (v, true)),
+        (None, Some(index)) => queued.remove(index).map(|v| (v, false)),
+        (None, None) => None,
+    }
+}
 
-                                Some((v.task)())
-                            }
+fn start_task(task: Box<dyn Task>, db: web::Data<SqlPool>) {
+    let task_id = TaskId::default();
+    let name = task.name();
+
+    TASKS_RUNNING.lock().unwrap().push(TaskRunning {
+        id: task_id,
+        name,
+        category: task.category(),
+        started: Utc::now(),
+        cancelled: false,
+        items_processed: 0,
+    });
 
-                            _ => None,
-                        }
-                    });
+    info!(id = ?task_id, name, "Task Started");
 
-                    interval.or_else(|| TASKS_QUEUED.lock().unwrap().pop_front())
-                };
+    send_message_to_clients(WebsocketNotification::new_task(task_id, name.to_string()));
 
-                // Run the found task.
-                if let Some(mut task) = task {
-                    let start_time = Instant::now();
+    tokio::spawn(async move {
+        let (outcome, error) = run_task(task, task_id, db.get_ref().clone()).await;
 
-                    let task_id = TaskId::default();
+        send_message_to_clients(WebsocketNotification::TaskEnd(task_id));
 
-                    {
-                        let mut tasks = TASKS_RUNNING.lock().unwrap();
-                        tasks.push(TaskRunning {
-                            id: task_id,
-                            name: task.name(),
-                            started: Utc::now(),
-                        });
-                    }
+        if let Some(running) = finish_task(task_id, name) {
+            if let Err(e) = save_task_history(running, outcome, error, &db).await {
+                error!(task = name, ?e, "Unable to save Task History");
+            }
+        }
+    });
+}
 
-                    info!(id = ?task_id, name = task.name(), "Task Started");
+/// Runs the task to completion. A task which panics is counted as failed.
+async fn run_task(
+    mut task: Box<dyn Task>,
+    task_id: TaskId,
+    pool: SqlPool,
+) -> (TaskOutcome, Option<String>) {
+    let name = task.name();
+
+    // Ran separately so a task which panics is still removed from the running tasks.
+    let handle = tokio::spawn(async move {
+        let start_time = Instant::now();
+
+        match task.run(task_id, &pool).await {
+            Ok(_) => {
+                info!(
+                    name,
+                    elapsed = ?start_time.elapsed(),
+                    "Task Finished Successfully.",
+                );
+
+                (TaskOutcome::Finished, None)
+            }
 
-                    send_message_to_clients(WebsocketNotification::new_task(
-                        task_id,
-                        task.name().to_string(),
-                    ));
+            Err(Error::Internal(InternalError::TaskCancelled)) => {
+                info!(
+                    name,
+                    elapsed = ?start_time.elapsed(),
+                    "Task Cancelled.",
+                );
 
-                    match task.run(task_id, &db).await {
-                        Ok(_) => info!(
-                            name = task.name(),
-                            elapsed = ?start_time.elapsed(),
-                            "Task Finished Successfully.",
-                        ),
-                        Err(e) => error!(task = task.name(), ?e),
-                    }
+                (TaskOutcome::Cancelled, None)
+            }
 
-                    send_message_to_clients(WebsocketNotification::TaskEnd(task_id));
+            Err(e) => {
+                error!(task = name, ?e);
 
-                    {
-                        let mut tasks = TASKS_RUNNING.lock().unwrap();
-                        if let Some(index) = tasks.iter().position(|v| v.id == task_id) {
-                            tasks.remove(index);
-                        }
-                    }
-                }
+                (TaskOutcome::Failed, Some(e.to_string()))
             }
-        });
+        }
     });
+
+    match handle.await {
+        Ok(v) => v,
+
+        Err(e) => {
+            error!(task = name, ?e, "Task Panicked");
+
+            (TaskOutcome::Failed, Some(e.to_string()))
+        }
+    }
+}
+
+/// Removes the task from the running tasks.
+fn finish_task(task_id: TaskId, name: &'static str) -> Option<TaskRunning> {
+    finish_interval_task(name);
+
+    let mut tasks = TASKS_RUNNING.lock().unwrap();
+
+    tasks
+        .iter()
+        .position(|v| v.id == task_id)
+        .map(|index| tasks.remove(index))
+}
+
+async fn save_task_history(
+    running: TaskRunning,
+    outcome: TaskOutcome,
+    error: Option<String>,
+    pool: &SqlPool,
+) -> Result<()> {
+    let db = &mut *pool.acquire().await?;
+
+    NewTaskHistoryModel {
+        name: running.name.to_string(),
+        outcome,
+        error,
+        items_processed: running.items_processed as i64,
+        started_at: running.started.naive_utc(),
+        ended_at: Utc::now().naive_utc(),
+    }
+    .insert(db)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::MutexGuard;
+
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+
+    /// The task queues are shared. Tests which use them can't run at the same time.
+    static QUEUES: Mutex<()> = Mutex::new(());
+
+    struct TestTask {
+        name: &'static str,
+        category: TaskCategory,
+        panics: bool,
+    }
+
+    impl TestTask {
+        fn new(name: &'static str, category: TaskCategory) -> Self {
+            Self {
+                name,
+                category,
+                panics: false,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Task for TestTask {
+        async fn run(&mut self, _task_id: TaskId, _pool: &SqlPool) -> Result<()> {
+            if self.panics {
+                panic!("Task Panicked");
+            }
+
+            Ok(())
+        }
+
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn category(&self) -> TaskCategory {
+            self.category
+        }
+    }
+
+    fn lock_queues() -> MutexGuard<'static, ()> {
+        let guard = QUEUES.lock().unwrap_or_else(|e| e.into_inner());
+
+        TASKS_QUEUED.lock().unwrap().clear();
+        TASKS_QUEUED_PRIORITY.lock().unwrap().clear();
+        TASKS_RUNNING.lock().unwrap().clear();
+
+        guard
+    }
+
+    fn set_running(category: TaskCategory) {
+        TASKS_RUNNING.lock().unwrap().push(TaskRunning {
+            id: TaskId::new(),
+            name: "Running",
+            category,
+            started: Utc::now(),
+            cancelled: false,
+            items_processed: 0,
+        });
+    }
+
+    #[test]
+    fn priority_fairness() {
+        let _guard = lock_queues();
+
+        for name in ["priority 1", "priority 2", "priority 3"] {
+            queue_task_priority(TestTask::new(name, TaskCategory::Metadata));
+        }
+
+        for name in ["queued 1", "queued 2"] {
+            queue_task(TestTask::new(name, TaskCategory::Metadata));
+        }
+
+        // Started the same way as the task manager does.
+        let mut started = Vec::new();
+        let mut priority_streak = 0;
+
+        while let Some((task, is_priority)) = next_task(priority_streak) {
+            if is_priority {
+                priority_streak += 1;
+            } else {
+                priority_streak = 0;
+            }
+
+            started.push(task.name());
+        }
+
+        assert_eq!(PRIORITY_RUNS_IN_A_ROW, 2);
+        assert_eq!(
+            started,
+            [
+                "priority 1",
+                "priority 2",
+                "queued 1",
+                "priority 3",
+                "queued 2"
+            ]
+        );
+    }
+
+    #[test]
+    fn running_category_is_skipped() {
+        let _guard = lock_queues();
+
+        set_running(TaskCategory::Maintenance);
+
+        queue_task_priority(TestTask::new("purge", TaskCategory::Maintenance));
+        queue_task(TestTask::new(
+            "scan",
+            TaskCategory::LibraryScan(LibraryId::from(1)),
+        ));
+
+        let (task, is_priority) = next_task(0).unwrap();
+        assert_eq!(task.name(), "scan");
+        assert!(!is_priority);
+
+        // It's left queued until the running task finishes.
+        assert!(next_task(0).is_none());
+        assert_eq!(TASKS_QUEUED_PRIORITY.lock().unwrap().len(), 1);
+
+        TASKS_RUNNING.lock().unwrap().clear();
+
+        // No more than MAX_CONCURRENT_RUNS are started.
+        for _ in 0..MAX_CONCURRENT_RUNS {
+            set_running(TaskCategory::People);
+        }
+
+        assert!(next_task(0).is_none());
+    }
+
+    #[test]
+    fn interval_is_queued_once() {
+        let _guard = lock_queues();
+
+        let mut intervals = vec![TaskInterval {
+            last_ran: None,
+            is_queued: false,
+            interval: Duration::from_secs(60 * 60),
+            is_enabled: || true,
+            task: || Box::new(TestTask::new("interval", TaskCategory::Maintenance)),
+        }];
+
+        let now = Utc::now();
+
+        queue_due_intervals(&mut intervals, now);
+        queue_due_intervals(&mut intervals, now);
+
+        assert_eq!(TASKS_QUEUED_PRIORITY.lock().unwrap().len(), 1);
+
+        // Not due again until the interval passes after it finished.
+        TASKS_QUEUED_PRIORITY.lock().unwrap().clear();
+        finish_interval(&mut intervals, "interval", now);
+
+        assert!(!intervals[0].is_queued);
+        assert_eq!(intervals[0].last_ran, Some(now));
+
+        queue_due_intervals(&mut intervals, now + chrono::Duration::minutes(59));
+        assert!(TASKS_QUEUED_PRIORITY.lock().unwrap().is_empty());
+
+        queue_due_intervals(&mut intervals, now + chrono::Duration::minutes(60));
+        assert_eq!(TASKS_QUEUED_PRIORITY.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn panicking_task_is_removed() -> Result<()> {
+        let _guard = lock_queues();
+
+        let task_id = TaskId::new();
+
+        TASKS_RUNNING.lock().unwrap().push(TaskRunning {
+            id: task_id,
+            name: "panics",
+            category: TaskCategory::People,
+            started: Utc::now(),
+            cancelled: false,
+            items_processed: 0,
+        });
+
+        let mut task = TestTask::new("panics", TaskCategory::People);
+        task.panics = true;
+
+        let (outcome, error) = Runtime::new()?.block_on(async {
+            // Never connected to since the task doesn't use it.
+            let pool = SqlitePoolOptions::new().connect_lazy("sqlite::memory:")?;
+
+            Result::Ok(run_task(Box::new(task), task_id, pool).await)
+        })?;
+
+        assert_eq!(outcome, TaskOutcome::Failed);
+        assert!(error.is_some());
+
+        assert!(finish_task(task_id, "panics").is_some());
+        assert!(TASKS_RUNNING.lock().unwrap().is_empty());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,212 @@
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use common_local::ws::TaskId;
+use sqlx::SqliteConnection;
+
+use crate::{
+    config::get_config,
+    model::{BookModel, FileModel},
+    task::check_cancelled,
+    Result, SqlPool, Task, TaskCategory,
+};
+
+/// Permanently deletes the files and books which have been in the trash longer than the retention period.
+pub struct TaskPurgeTrash;
+
+#[async_trait]
+impl Task for TaskPurgeTrash {
+    async fn run(&mut self, task_id: TaskId, pool: &SqlPool) -> Result<()> {
+        let db = &mut *pool.acquire().await?;
+
+        let retention_days = get_config().trash.retention_days;
+        let purge_before = (Utc::now() - Duration::days(retention_days as i64)).naive_utc();
+
+        let files = FileModel::find_deleted_before(purge_before, db).await?;
+        let file_count = files.len();
+
+        for file in files {
+            check_cancelled(task_id)?;
+
+            purge_file(file, db).await?;
+        }
+
+        let book_count = BookModel::delete_deleted_before(purge_before, db).await?;
+
+        info!(
+            files = file_count,
+            books = book_count,
+            retention_days,
+            "Purged Trash"
+        );
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "Purge Trash"
+    }
+
+    fn category(&self) -> TaskCategory {
+        TaskCategory::Maintenance
+    }
+}
+
+/// Permanently delete a file. Its book is deleted as well if it was the last file of a book in the trash.
+pub async fn purge_file(file: FileModel, db: &mut SqliteConnection) -> Result<()> {
+    FileModel::delete_by_id(file.id, db).await?;
+
+    if let Some(book_id) = file.book_id {
+        BookModel::decrement(book_id, db).await?;
+
+        if FileModel::find_by_book_id(book_id, db).await?.is_empty() {
+            if let Some(book) = BookModel::find_one_by_id(book_id, db).await? {
+                if book.deleted_at.is_some() {
+                    BookModel::delete_by_id(book_id, db).await?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{Source, ThumbnailStore};
+    use common_local::{BookItemCached, BookType, LibraryType};
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::model::{LibraryModel, NewBookModel, NewFileModel, NewLibraryModel};
+
+    /// A library with a single book.
+    async fn insert_book(db: &mut SqliteConnection) -> Result<(LibraryModel, BookModel)> {
+        let now = Utc::now().naive_utc();
+
+        let library = NewLibraryModel {
+            name: String::from("Books"),
+            type_of: LibraryType::Book,
+            is_public: false,
+            settings: None,
+            scanned_at: now,
+            created_at: now,
+            updated_at: now,
+        }
+        .insert(db)
+        .await?;
+
+        let book = NewBookModel {
+            library_id: library.id,
+            type_of: BookType::Book,
+            parent_id: None,
+            source: Source::try_from(String::from("local:book")).unwrap(),
+            file_item_count: 2,
+            title: None,
+            original_title: None,
+            description: None,
+            rating: 0.0,
+            thumb_url: ThumbnailStore::None,
+            cached: BookItemCached::default(),
+            index: None,
+            refreshed_at: now,
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+            available_at: None,
+            year: None,
+        }
+        .insert(db)
+        .await?;
+
+        Ok((library, book))
+    }
+
+    async fn insert_file(
+        file_name: &str,
+        book: &BookModel,
+        db: &mut SqliteConnection,
+    ) -> Result<FileModel> {
+        let now = Utc::now().naive_utc();
+
+        NewFileModel {
+            path: format!("/books/{file_name}"),
+            file_name: file_name.to_string(),
+            file_type: String::from("epub"),
+            file_size: 0,
+            library_id: book.library_id,
+            book_id: Some(book.id),
+            chapter_count: 0,
+            identifier: None,
+            hash: file_name.to_string(),
+            inode: None,
+            modified_at: now,
+            accessed_at: now,
+            created_at: now,
+            deleted_at: None,
+        }
+        .insert(db)
+        .await
+    }
+
+    #[tokio::test]
+    async fn restore_and_purge() -> Result<()> {
+        // A migrated in-memory database. It only lives as long as its single connection.
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await?;
+
+        sqlx::migrate!("./migrations").run(&pool).await?;
+
+        let db = &mut *pool.acquire().await?;
+
+        let (library, book) = insert_book(db).await?;
+
+        let first = insert_file("a.epub", &book, db).await?;
+        let second = insert_file("b.epub", &book, db).await?;
+
+        // The book is only put in the trash once all of its files are.
+        FileModel::soft_delete_by_id(first.id, db).await?;
+        BookModel::sync_deleted_with_files(library.id, db).await?;
+        assert!(BookModel::find_one_by_id(book.id, db)
+            .await?
+            .unwrap()
+            .deleted_at
+            .is_none());
+
+        FileModel::soft_delete_by_id(second.id, db).await?;
+        BookModel::sync_deleted_with_files(library.id, db).await?;
+        assert!(BookModel::find_one_by_id(book.id, db)
+            .await?
+            .unwrap()
+            .deleted_at
+            .is_some());
+
+        // Restoring a file restores its book.
+        FileModel::restore_by_id(second.id, db).await?;
+        BookModel::sync_deleted_with_files(library.id, db).await?;
+        assert!(BookModel::find_one_by_id(book.id, db)
+            .await?
+            .unwrap()
+            .deleted_at
+            .is_none());
+
+        // The book is kept while it has a file which isn't in the trash.
+        let first_id = first.id;
+        purge_file(FileModel::find_one_by_id(first_id, db).await?.unwrap(), db).await?;
+        assert!(FileModel::find_one_by_id(first_id, db).await?.is_none());
+        assert!(BookModel::find_one_by_id(book.id, db).await?.is_some());
+
+        // Purging the last file of a book in the trash deletes the book.
+        FileModel::soft_delete_by_id(second.id, db).await?;
+        BookModel::sync_deleted_with_files(library.id, db).await?;
+
+        let second = FileModel::find_one_by_id(second.id, db).await?.unwrap();
+        purge_file(second, db).await?;
+        assert!(BookModel::find_one_by_id(book.id, db).await?.is_none());
+
+        Ok(())
+    }
+}
@@ -1,13 +1,14 @@
 use async_trait::async_trait;
 use chrono::Utc;
 use common::{PersonId, Source};
-use common_local::ws::TaskId;
+use common_local::ws::{TaskId, TaskType};
 use sqlx::SqliteConnection;
 
 use crate::{
     metadata::{get_person_by_source, FoundImageLocation},
     model::{PersonAltModel, PersonModel},
-    Result, SqlPool, Task,
+    task::{check_cancelled, update_task_progress},
+    Result, SqlPool, Task, TaskCategory,
 };
 
 #[derive(Clone)]
@@ -28,7 +29,7 @@ impl TaskUpdatePeople {
 
 #[async_trait]
 impl Task for TaskUpdatePeople {
-    async fn run(&mut self, _task_id: TaskId, pool: &SqlPool) -> Result<()> {
+    async fn run(&mut self, task_id: TaskId, pool: &SqlPool) -> Result<()> {
         let db = &mut *pool.acquire().await?;
 
         match self.state.clone() {
@@ -36,13 +37,13 @@ impl Task for TaskUpdatePeople {
                 let old_person = PersonModel::find_one_by_id(person_id, db).await?.unwrap();
                 let source = old_person.source.clone();
 
-                Self::overwrite_person_with_source(old_person, &source, db).await
+                Self::overwrite_person_with_source(old_person, &source, task_id, db).await
             }
 
             UpdatingPeople::UpdatePersonWithSource { person_id, source } => {
                 let old_person = PersonModel::find_one_by_id(person_id, db).await?.unwrap();
 
-                Self::overwrite_person_with_source(old_person, &source, db).await
+                Self::overwrite_person_with_source(old_person, &source, task_id, db).await
             }
         }
     }
@@ -50,12 +51,17 @@ impl Task for TaskUpdatePeople {
     fn name(&self) -> &'static str {
         "Updating Person"
     }
+
+    fn category(&self) -> TaskCategory {
+        TaskCategory::People
+    }
 }
 
 impl TaskUpdatePeople {
     pub async fn overwrite_person_with_source(
         mut old_person: PersonModel,
         source: &Source,
+        task_id: TaskId,
         db: &mut SqliteConnection,
     ) -> Result<()> {
         if let Some(new_person) = get_person_by_source(source).await? {
@@ -77,7 +83,21 @@ impl TaskUpdatePeople {
             }
 
             if let Some(alts) = new_person.other_names {
-                for name in alts {
+                let total = alts.len();
+
+                for (index, name) in alts.into_iter().enumerate() {
+                    check_cancelled(task_id)?;
+
+                    update_task_progress(
+                        task_id,
+                        TaskType::UpdatingPerson {
+                            id: old_person.id,
+                            name: old_person.name.clone(),
+                        },
+                        index + 1,
+                        total,
+                    );
+
                     // Ignore errors. Errors should just be UNIQUE constraint failed
                     if let Err(error) = (PersonAltModel {
                         person_id: old_person.id,
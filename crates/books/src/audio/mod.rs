@@ -0,0 +1,464 @@
+// For .m4b, .m4a and .mp3 files. A folder of .mp3 files is a single audiobook.
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use common_local::sort::filename_sort;
+
+mod mp3;
+mod mp4;
+
+use super::Book;
+use crate::{BookFormat, BookSearch, Error, ReadSeek, Result, SanitizeOptions, TocItem};
+pub use common_local::{AudioChapter, AudioTrack};
+
+const TRACK_EXTENSIONS: [&str; 1] = ["mp3"];
+
+const IMAGE_EXTENSIONS: [&str; 4] = ["jpg", "jpeg", "png", "webp"];
+
+/// Image names used for the cover of an audiobook folder.
+const COVER_NAMES: [&str; 3] = ["cover", "folder", "front"];
+
+/// Values read from a single audio file.
+#[derive(Debug, Default)]
+struct FileInfo {
+    /// In milliseconds.
+    duration: u64,
+    /// Eg. "Title", "Artist", "Narrator"
+    values: HashMap<&'static str, String>,
+    /// The image extension and the image.
+    cover: Option<(&'static str, Vec<u8>)>,
+    /// The title and start of each chapter in milliseconds.
+    chapters: Vec<(String, u64)>,
+}
+
+pub struct AudioBook {
+    /// The file or folder name. Empty when loaded from a reader.
+    file_name: String,
+    /// The folder the tracks are inside of. Empty if the audiobook is a single file.
+    root_dir: PathBuf,
+    /// Only set if the audiobook is a single file.
+    source: Option<Box<dyn ReadSeek>>,
+
+    tracks: Vec<AudioTrack>,
+    chapters: Vec<AudioChapter>,
+
+    values: HashMap<&'static str, String>,
+    /// The path and contents of the cover image.
+    cover: Option<(String, Vec<u8>)>,
+
+    pub chapter: usize,
+}
+
+impl AudioBook {
+    fn load_from_folder(path: &Path) -> Result<Self> {
+        let mut tracks = Vec::new();
+        let mut chapters = Vec::new();
+        let mut values = HashMap::new();
+        let mut cover = find_folder_cover(path)?;
+
+        for (index, file_name) in get_track_names(path)?.into_iter().enumerate() {
+            let info = mp3::parse(&mut File::open(path.join(&file_name))?)?;
+
+            // The first track has the audiobook metadata.
+            if index == 0 {
+                values = info.values.clone();
+            }
+
+            if cover.is_none() {
+                cover = info.cover.map(|(ext, data)| (format!("cover.{ext}"), data));
+            }
+
+            let title = info.values.get("Title").cloned().unwrap_or_else(|| {
+                file_name
+                    .rsplit_once('.')
+                    .map_or(file_name.as_str(), |v| v.0)
+                    .to_string()
+            });
+
+            chapters.push(AudioChapter {
+                title,
+                track: index,
+                start: 0,
+                end: info.duration,
+            });
+
+            tracks.push(AudioTrack {
+                path: file_name,
+                duration: info.duration,
+            });
+        }
+
+        if tracks.is_empty() {
+            return Err(Error::MissingFile(String::from("Audio Track")));
+        }
+
+        Ok(Self {
+            file_name: path
+                .file_name()
+                .map(|v| v.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            root_dir: path.to_path_buf(),
+            source: None,
+
+            tracks,
+            chapters,
+
+            values,
+            cover,
+
+            chapter: 0,
+        })
+    }
+
+    /// The length of the whole audiobook in milliseconds.
+    pub fn duration(&self) -> u64 {
+        self.tracks.iter().map(|v| v.duration).sum()
+    }
+
+    pub fn get_tracks(&self) -> &[AudioTrack] {
+        &self.tracks
+    }
+
+    pub fn get_chapters(&self) -> &[AudioChapter] {
+        &self.chapters
+    }
+
+    fn read_track(&mut self, index: usize) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+
+        if let Some(source) = self.source.as_mut() {
+            source.seek(SeekFrom::Start(0))?;
+            source.read_to_end(&mut buf)?;
+        } else {
+            File::open(self.root_dir.join(&self.tracks[index].path))?.read_to_end(&mut buf)?;
+        }
+
+        Ok(buf)
+    }
+
+    fn get_values(&self, keys: &[&str]) -> Option<Vec<String>> {
+        let value = keys.iter().find_map(|v| self.values.get(v))?;
+
+        Some(vec![value.clone()])
+    }
+
+    fn get_list(&self, keys: &[&str]) -> Option<Vec<String>> {
+        let value = keys.iter().find_map(|v| self.values.get(v))?;
+
+        let values = value
+            .split([',', ';'])
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>();
+
+        (!values.is_empty()).then_some(values)
+    }
+}
+
+impl Book for AudioBook {
+    fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let path = path.as_ref();
+
+        if path.is_dir() {
+            return Self::load_from_folder(path);
+        }
+
+        let mut this = Self::load_from_reader(File::open(path)?)?;
+
+        this.file_name = path
+            .file_name()
+            .map(|v| v.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if let Some(track) = this.tracks.first_mut() {
+            track.path = this.file_name.clone();
+        }
+
+        Ok(this)
+    }
+
+    fn load_from_reader<R: ReadSeek + 'static>(mut reader: R) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let (ext, info) = match BookFormat::detect(&mut reader)? {
+            Some(BookFormat::Mp4) => ("m4b", mp4::parse(&mut reader)?),
+            Some(BookFormat::Mp3) => ("mp3", mp3::parse(&mut reader)?),
+            _ => return Err(Error::Unsupported("Not an audio file")),
+        };
+
+        let mut chapters = info
+            .chapters
+            .iter()
+            .map(|(title, start)| AudioChapter {
+                title: title.clone(),
+                track: 0,
+                start: *start,
+                end: info.duration,
+            })
+            .collect::<Vec<_>>();
+
+        // Chapters end where the next one starts.
+        for index in 1..chapters.len() {
+            chapters[index - 1].end = chapters[index].start;
+        }
+
+        if chapters.is_empty() {
+            chapters.push(AudioChapter {
+                title: info
+                    .values
+                    .get("Title")
+                    .cloned()
+                    .unwrap_or_else(|| String::from("Chapter 1")),
+                track: 0,
+                start: 0,
+                end: info.duration,
+            });
+        }
+
+        Ok(Self {
+            file_name: String::new(),
+            root_dir: PathBuf::new(),
+            source: Some(Box::new(reader)),
+
+            tracks: vec![AudioTrack {
+                path: format!("track.{ext}"),
+                duration: info.duration,
+            }],
+            chapters,
+
+            values: info.values,
+            cover: info.cover.map(|(ext, data)| (format!("cover.{ext}"), data)),
+
+            chapter: 0,
+        })
+    }
+
+    fn compute_hash(&mut self) -> Option<String> {
+        let mut hasher = blake3::Hasher::new();
+
+        if let Some(source) = self.source.as_mut() {
+            source.seek(SeekFrom::Start(0)).ok()?;
+            io::copy(source, &mut hasher).ok()?;
+        } else {
+            for track in &self.tracks {
+                io::copy(
+                    &mut File::open(self.root_dir.join(&track.path)).ok()?,
+                    &mut hasher,
+                )
+                .ok()?;
+            }
+        }
+
+        Some(hasher.finalize().to_string())
+    }
+
+    fn get_files(&self) -> Vec<String> {
+        let mut files = self
+            .tracks
+            .iter()
+            .map(|v| v.path.clone())
+            .collect::<Vec<_>>();
+
+        if let Some((path, _)) = self.cover.as_ref() {
+            files.push(path.clone());
+        }
+
+        files
+    }
+
+    fn find(&self, search: BookSearch<'_>) -> Option<Vec<String>> {
+        match search {
+            // Track titles are usually chapter names so we prefer the album.
+            BookSearch::Title => self.get_values(&["Album", "Title"]),
+            BookSearch::Creator => self.get_list(&["AlbumArtist", "Artist"]),
+            BookSearch::Description => {
+                self.get_values(&["LongDescription", "Description", "Comment"])
+            }
+            BookSearch::Date => self.get_values(&["Date"]),
+            BookSearch::Publisher => self.get_values(&["Publisher"]),
+            BookSearch::Rights => self.get_values(&["Rights"]),
+            BookSearch::Subject => self.get_list(&["Genre"]),
+            BookSearch::CoverImage => Some(vec![self.cover.as_ref()?.0.clone()]),
+
+            // The composer is commonly used for the narrator.
+            BookSearch::Other("Narrator") => self.get_list(&["Narrator", "Composer"]),
+            BookSearch::Other(key) => self.get_values(&[key]),
+
+            _ => None,
+        }
+    }
+
+    fn get_unique_id(&self) -> Result<Cow<str>> {
+        // Loaded from a reader.
+        if self.file_name.is_empty() {
+            return Err(Error::MissingUniqueId);
+        }
+
+        Ok(Cow::Borrowed(self.file_name.as_str()))
+    }
+
+    fn get_root_file_dir(&self) -> &Path {
+        Path::new("")
+    }
+
+    fn get_page_path(&self) -> PathBuf {
+        self.chapters
+            .get(self.chapter)
+            .and_then(|v| self.tracks.get(v.track))
+            .map(|v| PathBuf::from(&v.path))
+            .unwrap_or_default()
+    }
+
+    fn read_page_raw_as_bytes(&mut self) -> Result<Vec<u8>> {
+        Err(Error::Unsupported("Audiobooks don't have pages"))
+    }
+
+    fn read_path_as_bytes(
+        &mut self,
+        path: &str,
+        _prepend_to_urls: Option<&str>,
+        _add_css: Option<&[&str]>,
+        _sanitize: Option<SanitizeOptions>,
+    ) -> Result<Vec<u8>> {
+        if let Some((_, data)) = self.cover.as_ref().filter(|v| v.0 == path) {
+            Ok(data.clone())
+        } else if let Some(index) = self.tracks.iter().position(|v| v.path == path) {
+            self.read_track(index)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    fn read_page_as_bytes(
+        &mut self,
+        _prepend_to_urls: Option<&str>,
+        _add_css: Option<&[&str]>,
+        _sanitize: Option<SanitizeOptions>,
+    ) -> Result<Vec<u8>> {
+        Err(Error::Unsupported("Audiobooks don't have pages"))
+    }
+
+    fn get_table_of_contents(&mut self) -> Result<Option<Vec<TocItem>>> {
+        Ok(Some(
+            self.chapters
+                .iter()
+                .enumerate()
+                .map(|(chapter, v)| TocItem {
+                    title: v.title.clone(),
+                    chapter,
+                    fragment: None,
+                    children: Vec::new(),
+                })
+                .collect(),
+        ))
+    }
+
+    fn chapter_count(&self) -> usize {
+        self.chapters.len()
+    }
+
+    fn set_chapter(&mut self, value: usize) -> bool {
+        if value < self.chapter_count() {
+            self.chapter = value;
+
+            true
+        } else {
+            false
+        }
+    }
+
+    fn next_chapter(&mut self) -> bool {
+        self.set_chapter(self.chapter + 1)
+    }
+
+    fn previous_chapter(&mut self) -> bool {
+        if self.chapter != 0 {
+            self.set_chapter(self.chapter - 1)
+        } else {
+            false
+        }
+    }
+
+    fn get_chapter(&self) -> usize {
+        self.chapter
+    }
+}
+
+/// Whether the folder directly contains audio tracks.
+pub fn is_audio_book_folder<P: AsRef<Path>>(path: P) -> bool {
+    get_track_names(path.as_ref()).map_or(false, |v| !v.is_empty())
+}
+
+/// The paths of an audiobook's tracks in the order they're played.
+pub fn get_track_paths<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>> {
+    let path = path.as_ref();
+
+    if path.is_dir() {
+        Ok(get_track_names(path)?
+            .into_iter()
+            .map(|v| path.join(v))
+            .collect())
+    } else {
+        Ok(vec![path.to_path_buf()])
+    }
+}
+
+fn get_track_names(path: &Path) -> Result<Vec<String>> {
+    let mut names = get_file_names(path, &TRACK_EXTENSIONS)?;
+
+    filename_sort(&mut names);
+
+    Ok(names)
+}
+
+fn find_folder_cover(path: &Path) -> Result<Option<(String, Vec<u8>)>> {
+    let mut names = get_file_names(path, &IMAGE_EXTENSIONS)?;
+
+    filename_sort(&mut names);
+
+    let name = names
+        .iter()
+        .find(|name| {
+            let stem = name.rsplit_once('.').map_or(name.as_str(), |v| v.0);
+
+            COVER_NAMES.iter().any(|v| stem.eq_ignore_ascii_case(v))
+        })
+        .or_else(|| names.first());
+
+    Ok(match name {
+        Some(name) => Some((name.clone(), fs::read(path.join(name))?)),
+        None => None,
+    })
+}
+
+fn get_file_names(path: &Path, extensions: &[&str]) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        if name.rsplit_once('.').map_or(false, |(_, ext)| {
+            extensions.contains(&ext.to_lowercase().as_str())
+        }) {
+            names.push(name);
+        }
+    }
+
+    Ok(names)
+}
@@ -0,0 +1,248 @@
+// For .mp3 files
+
+// http://www.mp3-tech.org/programmer/frame_header.html
+// https://id3.org/id3v2.4.0-frames
+
+use std::io::{Read, Seek, SeekFrom};
+
+use id3::{frame::PictureType, Tag, TagLike};
+
+use super::FileInfo;
+use crate::{Error, Result};
+
+/// Text frames and the names we store them under.
+const FRAME_NAMES: [(&str, &str); 10] = [
+    ("TIT2", "Title"),
+    ("TALB", "Album"),
+    ("TPE1", "Artist"),
+    ("TPE2", "AlbumArtist"),
+    ("TCOM", "Composer"),
+    ("TDRC", "Date"),
+    ("TYER", "Date"),
+    ("TCON", "Genre"),
+    ("TPUB", "Publisher"),
+    ("TCOP", "Rights"),
+];
+
+/// How far past the ID3 tag we look for the first frame.
+const MAX_FRAME_SEARCH: usize = 64 * 1024;
+
+/// Layer III bitrates in kbps.
+const MPEG1_BITRATES: [u64; 15] = [
+    0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+];
+const MPEG2_BITRATES: [u64; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+pub fn parse<R: Read + Seek>(reader: &mut R) -> Result<FileInfo> {
+    let mut info = FileInfo::default();
+
+    reader.seek(SeekFrom::Start(0))?;
+
+    if let Some(tag) = id3::no_tag_ok(id3::partial_tag_ok(Tag::read_from2(&mut *reader)))? {
+        read_tag(&tag, &mut info);
+    }
+
+    info.duration = read_duration(reader)?;
+
+    Ok(info)
+}
+
+fn read_tag(tag: &Tag, info: &mut FileInfo) {
+    for (id, key) in FRAME_NAMES {
+        if let Some(value) = tag
+            .get(id)
+            .and_then(|v| v.content().text())
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+        {
+            info.values.entry(key).or_insert_with(|| value.to_string());
+        }
+    }
+
+    for text in tag.extended_texts() {
+        let key = match text.description.to_lowercase().as_str() {
+            "narrator" | "narratedby" => "Narrator",
+            "description" => "Description",
+            _ => continue,
+        };
+
+        info.values.insert(key, text.value.trim().to_string());
+    }
+
+    if let Some(comment) = tag.comments().find(|v| !v.text.trim().is_empty()) {
+        info.values
+            .insert("Comment", comment.text.trim().to_string());
+    }
+
+    // Prefer the front cover.
+    let picture = tag
+        .pictures()
+        .find(|v| v.picture_type == PictureType::CoverFront)
+        .or_else(|| tag.pictures().next());
+
+    if let Some(picture) = picture {
+        let ext = match picture.mime_type.as_str() {
+            "image/png" => "png",
+            "image/gif" => "gif",
+            "image/webp" => "webp",
+            _ => "jpg",
+        };
+
+        info.cover = Some((ext, picture.data.clone()));
+    }
+
+    let mut chapters = tag.chapters().collect::<Vec<_>>();
+    chapters.sort_by_key(|v| v.start_time);
+
+    info.chapters = chapters
+        .into_iter()
+        .map(|chapter| {
+            let title = chapter
+                .frames
+                .iter()
+                .find(|v| v.id() == "TIT2")
+                .and_then(|v| v.content().text())
+                .unwrap_or(&chapter.element_id);
+
+            (title.to_string(), chapter.start_time as u64)
+        })
+        .collect();
+}
+
+/// In milliseconds.
+///
+/// Uses the frame count from the Xing or VBRI header if the file has one. Otherwise we assume a
+/// constant bitrate and use the first frame's.
+fn read_duration<R: Read + Seek>(reader: &mut R) -> Result<u64> {
+    let length = reader.seek(SeekFrom::End(0))?;
+
+    reader.seek(SeekFrom::Start(0))?;
+    Tag::skip(&mut *reader)?;
+
+    let mut start = reader.stream_position()?;
+
+    let mut data = Vec::new();
+    reader
+        .take(MAX_FRAME_SEARCH as u64)
+        .read_to_end(&mut data)?;
+
+    let Some((offset, header)) =
+        (0..data.len()).find_map(|i| Some((i, FrameHeader::parse(data.get(i..)?)?)))
+    else {
+        return Err(Error::Mp3("Unable to find the first frame"));
+    };
+
+    start += offset as u64;
+
+    let frame = &data[offset..];
+
+    // The Xing header is stored after the side information. VBRI is always at the same place.
+    let frames = [header.side_info_size() + 4, 36]
+        .into_iter()
+        .find_map(|at| match frame.get(at..at + 4)? {
+            b"Xing" | b"Info" => {
+                let flags = read_u32(frame, at + 4)?;
+                (flags & 1 == 1).then(|| read_u32(frame, at + 8)).flatten()
+            }
+            b"VBRI" => read_u32(frame, at + 14),
+            _ => None,
+        });
+
+    Ok(match frames {
+        Some(frames) => frames as u64 * header.samples_per_frame() * 1000 / header.sample_rate,
+        // A kbps bitrate is the same as bits per millisecond.
+        None => (length - start) * 8 / header.bitrate,
+    })
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// A Layer III frame header.
+struct FrameHeader {
+    is_mpeg1: bool,
+    is_mono: bool,
+    /// In kbps.
+    bitrate: u64,
+    sample_rate: u64,
+}
+
+impl FrameHeader {
+    fn parse(data: &[u8]) -> Option<Self> {
+        let &[sync, b1, b2, b3, ..] = data else {
+            return None;
+        };
+
+        // Frame sync, Layer III
+        if sync != 0xFF || b1 & 0xE0 != 0xE0 || (b1 >> 1) & 0b11 != 0b01 {
+            return None;
+        }
+
+        let version = (b1 >> 3) & 0b11;
+        let bitrate_index = (b2 >> 4) as usize;
+        let sample_rate_index = ((b2 >> 2) & 0b11) as usize;
+
+        let (bitrates, sample_rates) = match version {
+            0b11 => (MPEG1_BITRATES, [44100, 48000, 32000]),
+            0b10 => (MPEG2_BITRATES, [22050, 24000, 16000]),
+            0b00 => (MPEG2_BITRATES, [11025, 12000, 8000]),
+            _ => return None,
+        };
+
+        Some(Self {
+            is_mpeg1: version == 0b11,
+            is_mono: b3 >> 6 == 0b11,
+            bitrate: *bitrates.get(bitrate_index).filter(|v| **v != 0)?,
+            sample_rate: *sample_rates.get(sample_rate_index)?,
+        })
+    }
+
+    fn samples_per_frame(&self) -> u64 {
+        if self.is_mpeg1 {
+            1152
+        } else {
+            576
+        }
+    }
+
+    fn side_info_size(&self) -> usize {
+        match (self.is_mpeg1, self.is_mono) {
+            (true, false) => 32,
+            (true, true) | (false, false) => 17,
+            (false, true) => 9,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn xing_duration() {
+        // MPEG1 Layer III, 128kbps, 44.1kHz, stereo
+        let mut frame = vec![0xFF, 0xFB, 0x90, 0x00];
+        frame.extend_from_slice(&[0; 32]);
+        frame.extend_from_slice(b"Xing");
+        frame.extend_from_slice(&1u32.to_be_bytes());
+        // 10 seconds of frames.
+        frame.extend_from_slice(&383u32.to_be_bytes());
+        frame.resize(417, 0);
+
+        let mut file = b"ID3\x03\x00\x00\x00\x00\x00\x00".to_vec();
+        file.extend_from_slice(&frame);
+
+        let info = parse(&mut Cursor::new(&file)).unwrap();
+        assert_eq!(info.duration, 383 * 1152 * 1000 / 44100);
+
+        // Without the Xing header the bitrate is used.
+        file.truncate(10 + 4);
+        file.resize(10 + 160_000, 0);
+
+        let info = parse(&mut Cursor::new(&file)).unwrap();
+        assert_eq!(info.duration, 10_000);
+    }
+}
@@ -0,0 +1,538 @@
+// For .m4b and .m4a files
+
+// https://developer.apple.com/documentation/quicktime-file-format
+// https://developer.apple.com/documentation/quicktime-file-format/metadata_item_list_atom
+
+use std::io::{Read, Seek, SeekFrom};
+
+use super::FileInfo;
+use crate::{Error, Result};
+
+/// Item list atoms and the names we store them under.
+const ITEM_NAMES: [(&[u8; 4], &str); 13] = [
+    (b"\xA9nam", "Title"),
+    (b"\xA9alb", "Album"),
+    (b"\xA9ART", "Artist"),
+    (b"aART", "AlbumArtist"),
+    (b"\xA9wrt", "Composer"),
+    (b"\xA9nrt", "Narrator"),
+    (b"\xA9day", "Date"),
+    (b"\xA9gen", "Genre"),
+    (b"\xA9cmt", "Comment"),
+    (b"desc", "Description"),
+    (b"ldes", "LongDescription"),
+    (b"\xA9pub", "Publisher"),
+    (b"cprt", "Rights"),
+];
+
+/// Nero chapters are stored in 100 nanosecond units.
+const NERO_TIMESCALE: u64 = 10_000_000;
+
+#[derive(Default)]
+struct Track {
+    id: u32,
+    /// Track IDs referenced by the tref/chap atom.
+    chapter_refs: Vec<u32>,
+
+    timescale: u32,
+    /// (sample count, sample duration)
+    time_to_sample: Vec<(u32, u32)>,
+    /// (first chunk, samples per chunk)
+    sample_to_chunk: Vec<(u32, u32)>,
+    sample_sizes: Vec<u32>,
+    chunk_offsets: Vec<u64>,
+}
+
+pub fn parse<R: Read + Seek>(reader: &mut R) -> Result<FileInfo> {
+    let moov = read_moov(reader)?;
+
+    let mut info = FileInfo::default();
+
+    let mut tracks = Vec::new();
+    let mut nero_chapters = Vec::new();
+
+    for (name, data) in atoms(&moov) {
+        match name {
+            b"mvhd" => info.duration = read_movie_duration(data).unwrap_or_default(),
+            b"trak" => tracks.push(read_track(data)),
+            b"meta" => read_meta(data, &mut info),
+            b"udta" => {
+                for (name, data) in atoms(data) {
+                    match name {
+                        b"meta" => read_meta(data, &mut info),
+                        b"chpl" => nero_chapters = read_nero_chapters(data).unwrap_or_default(),
+                        _ => (),
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // Prefer the QuickTime chapter track. Nero chapters are written by fewer tools.
+    info.chapters = match tracks
+        .iter()
+        .flat_map(|v| v.chapter_refs.iter())
+        .find_map(|id| tracks.iter().find(|v| v.id == *id))
+    {
+        Some(track) => read_chapter_track(reader, track)?,
+        None => nero_chapters,
+    };
+
+    Ok(info)
+}
+
+/// Find the top level moov atom and read it into memory.
+fn read_moov<R: Read + Seek>(reader: &mut R) -> Result<Vec<u8>> {
+    let length = reader.seek(SeekFrom::End(0))?;
+    let mut position = reader.seek(SeekFrom::Start(0))?;
+
+    while position + 8 <= length {
+        let mut header = [0; 8];
+        reader.read_exact(&mut header)?;
+
+        let mut header_size = 8;
+        let mut size = u32::from_be_bytes(header[0..4].try_into().unwrap()) as u64;
+
+        if size == 1 {
+            let mut large = [0; 8];
+            reader.read_exact(&mut large)?;
+
+            header_size = 16;
+            size = u64::from_be_bytes(large);
+        } else if size == 0 {
+            size = length - position;
+        }
+
+        if size < header_size || position + size > length {
+            break;
+        }
+
+        if &header[4..8] == b"moov" {
+            let mut data = vec![0; (size - header_size) as usize];
+            reader.read_exact(&mut data)?;
+
+            return Ok(data);
+        }
+
+        position = reader.seek(SeekFrom::Start(position + size))?;
+    }
+
+    Err(Error::Mp4("Missing moov atom"))
+}
+
+/// Iterate over the child atoms. Stops at the first malformed atom.
+fn atoms(mut data: &[u8]) -> impl Iterator<Item = (&[u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        let mut size = read_u32(data, 0)? as usize;
+        let name: &[u8; 4] = data.get(4..8)?.try_into().ok()?;
+        let mut header_size = 8;
+
+        if size == 1 {
+            size = usize::try_from(read_u64(data, 8)?).ok()?;
+            header_size = 16;
+        } else if size == 0 {
+            size = data.len();
+        }
+
+        if size < header_size {
+            return None;
+        }
+
+        let contents = data.get(header_size..size)?;
+        data = &data[size..];
+
+        Some((name, contents))
+    })
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// In milliseconds.
+fn read_movie_duration(data: &[u8]) -> Option<u64> {
+    let (timescale, duration) = if data.first() == Some(&1) {
+        (read_u32(data, 20)?, read_u64(data, 24)?)
+    } else {
+        (read_u32(data, 12)?, read_u32(data, 16)? as u64)
+    };
+
+    (timescale != 0).then(|| duration * 1000 / timescale as u64)
+}
+
+fn read_track(data: &[u8]) -> Track {
+    let mut track = Track::default();
+
+    for (name, data) in atoms(data) {
+        match name {
+            b"tkhd" => {
+                let at = if data.first() == Some(&1) { 20 } else { 12 };
+                track.id = read_u32(data, at).unwrap_or_default();
+            }
+
+            b"tref" => {
+                for (name, data) in atoms(data) {
+                    if name == b"chap" {
+                        track
+                            .chapter_refs
+                            .extend(data.chunks_exact(4).map(|v| read_u32(v, 0).unwrap()));
+                    }
+                }
+            }
+
+            b"mdia" => {
+                for (name, data) in atoms(data) {
+                    match name {
+                        b"mdhd" => {
+                            let at = if data.first() == Some(&1) { 20 } else { 12 };
+                            track.timescale = read_u32(data, at).unwrap_or_default();
+                        }
+
+                        b"minf" => {
+                            for (_, data) in atoms(data).filter(|v| v.0 == b"stbl") {
+                                read_sample_table(data, &mut track);
+                            }
+                        }
+
+                        _ => (),
+                    }
+                }
+            }
+
+            _ => (),
+        }
+    }
+
+    track
+}
+
+fn read_sample_table(data: &[u8], track: &mut Track) {
+    // Every table starts with a version, flags and the entry count.
+    let entries = |data: &[u8], size: usize| {
+        let count = read_u32(data, 4).unwrap_or_default() as usize;
+
+        data.get(8..)
+            .unwrap_or_default()
+            .chunks_exact(size)
+            .take(count)
+            .map(|v| v.to_vec())
+            .collect::<Vec<_>>()
+    };
+
+    for (name, data) in atoms(data) {
+        match name {
+            b"stts" => {
+                track.time_to_sample = entries(data, 8)
+                    .into_iter()
+                    .map(|v| (read_u32(&v, 0).unwrap(), read_u32(&v, 4).unwrap()))
+                    .collect();
+            }
+
+            b"stsc" => {
+                track.sample_to_chunk = entries(data, 12)
+                    .into_iter()
+                    .map(|v| (read_u32(&v, 0).unwrap(), read_u32(&v, 4).unwrap()))
+                    .collect();
+            }
+
+            b"stsz" => {
+                // A non-zero sample size is used for every sample.
+                let size = read_u32(data, 4).unwrap_or_default();
+                let count = read_u32(data, 8).unwrap_or_default() as usize;
+
+                track.sample_sizes = if size != 0 {
+                    vec![size; count]
+                } else {
+                    data.get(12..)
+                        .unwrap_or_default()
+                        .chunks_exact(4)
+                        .take(count)
+                        .map(|v| read_u32(v, 0).unwrap())
+                        .collect()
+                };
+            }
+
+            b"stco" => {
+                track.chunk_offsets = entries(data, 4)
+                    .into_iter()
+                    .map(|v| read_u32(&v, 0).unwrap() as u64)
+                    .collect();
+            }
+
+            b"co64" => {
+                track.chunk_offsets = entries(data, 8)
+                    .into_iter()
+                    .map(|v| read_u64(&v, 0).unwrap())
+                    .collect();
+            }
+
+            _ => (),
+        }
+    }
+}
+
+/// Each sample of a chapter track is a text sample containing the title.
+fn read_chapter_track<R: Read + Seek>(reader: &mut R, track: &Track) -> Result<Vec<(String, u64)>> {
+    if track.timescale == 0 {
+        return Ok(Vec::new());
+    }
+
+    // Sample offsets.
+    let mut offsets = Vec::new();
+
+    for (index, &offset) in track.chunk_offsets.iter().enumerate() {
+        let chunk = index as u32 + 1;
+
+        let samples_per_chunk = track
+            .sample_to_chunk
+            .iter()
+            .rev()
+            .find(|v| v.0 <= chunk)
+            .map_or(0, |v| v.1);
+
+        let mut offset = offset;
+
+        for _ in 0..samples_per_chunk {
+            let Some(&size) = track.sample_sizes.get(offsets.len()) else {
+                break;
+            };
+
+            offsets.push((offset, size));
+            offset += size as u64;
+        }
+    }
+
+    let durations = track
+        .time_to_sample
+        .iter()
+        .flat_map(|&(count, duration)| std::iter::repeat_n(duration, count as usize));
+
+    let mut chapters = Vec::new();
+    let mut start = 0u64;
+
+    for ((offset, size), duration) in offsets.into_iter().zip(durations) {
+        let mut sample = vec![0; size as usize];
+
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut sample)?;
+
+        let length = read_u16(&sample, 0).unwrap_or_default() as usize;
+        let text = sample.get(2..2 + length).unwrap_or_default();
+
+        let title = if let Some(text) = text.strip_prefix(b"\xFE\xFF") {
+            String::from_utf16_lossy(
+                &text
+                    .chunks_exact(2)
+                    .map(|v| u16::from_be_bytes([v[0], v[1]]))
+                    .collect::<Vec<_>>(),
+            )
+        } else {
+            String::from_utf8_lossy(text).into_owned()
+        };
+
+        chapters.push((title, start * 1000 / track.timescale as u64));
+
+        start += duration as u64;
+    }
+
+    Ok(chapters)
+}
+
+fn read_nero_chapters(data: &[u8]) -> Option<Vec<(String, u64)>> {
+    // Version 1 has an extra 4 reserved bytes.
+    let mut at = if data.first() == Some(&1) { 8 } else { 4 };
+
+    let count = *data.get(at)?;
+    at += 1;
+
+    let mut chapters = Vec::new();
+
+    for _ in 0..count {
+        let start = read_u64(data, at)?;
+        let length = *data.get(at + 8)? as usize;
+        let title = data.get(at + 9..at + 9 + length)?;
+
+        chapters.push((
+            String::from_utf8_lossy(title).into_owned(),
+            start * 1000 / NERO_TIMESCALE,
+        ));
+
+        at += 9 + length;
+    }
+
+    Some(chapters)
+}
+
+fn read_meta(data: &[u8], info: &mut FileInfo) {
+    // The ISO meta atom has a version and flags. The QuickTime one doesn't.
+    let data = if data.get(4..8) == Some(b"hdlr") {
+        data
+    } else {
+        data.get(4..).unwrap_or_default()
+    };
+
+    for (name, data) in atoms(data) {
+        if name != b"ilst" {
+            continue;
+        }
+
+        for (name, data) in atoms(data) {
+            let Some((kind, value)) = atoms(data)
+                .find(|v| v.0 == b"data")
+                .and_then(|(_, data)| Some((read_u32(data, 0)? & 0x00FF_FFFF, data.get(8..)?)))
+            else {
+                continue;
+            };
+
+            if name == b"covr" {
+                let ext = match kind {
+                    14 => "png",
+                    27 => "bmp",
+                    _ => "jpg",
+                };
+
+                if info.cover.is_none() {
+                    info.cover = Some((ext, value.to_vec()));
+                }
+            } else if let Some((_, key)) = ITEM_NAMES.iter().find(|v| v.0 == name) {
+                let value = String::from_utf8_lossy(value).trim().to_string();
+
+                if kind == 1 && !value.is_empty() {
+                    info.values.insert(*key, value);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn atom(name: &[u8; 4], contents: &[u8]) -> Vec<u8> {
+        let mut data = ((contents.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(name);
+        data.extend_from_slice(contents);
+        data
+    }
+
+    fn text_item(name: &[u8; 4], value: &str) -> Vec<u8> {
+        let mut data = vec![0, 0, 0, 1, 0, 0, 0, 0];
+        data.extend_from_slice(value.as_bytes());
+        atom(name, &atom(b"data", &data))
+    }
+
+    #[test]
+    fn chapter_track_and_metadata() {
+        // Two chapter titles stored one after another in a single chunk.
+        let samples = [&b"\x00\x05Intro"[..], &b"\x00\x09Chapter 1"[..]].concat();
+
+        let mut mvhd = vec![0; 12];
+        mvhd.extend_from_slice(&1000u32.to_be_bytes());
+        mvhd.extend_from_slice(&90_000u32.to_be_bytes());
+
+        let mut tkhd = vec![0; 12];
+        tkhd.extend_from_slice(&2u32.to_be_bytes());
+
+        let mut mdhd = vec![0; 12];
+        mdhd.extend_from_slice(&600u32.to_be_bytes());
+
+        let stts = [
+            &[0, 0, 0, 0, 0, 0, 0, 1][..],
+            &[0, 0, 0, 2, 0, 0, 0x46, 0x50],
+        ]
+        .concat();
+        let stsc = [
+            &[0, 0, 0, 0, 0, 0, 0, 1][..],
+            &[0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+        ]
+        .concat();
+        let stsz = [
+            &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2][..],
+            &[0, 0, 0, 7, 0, 0, 0, 11],
+        ]
+        .concat();
+
+        let build = |offset: u32| {
+            let stco = [&[0, 0, 0, 0, 0, 0, 0, 1][..], &offset.to_be_bytes()].concat();
+
+            let stbl = [
+                atom(b"stts", &stts),
+                atom(b"stsc", &stsc),
+                atom(b"stsz", &stsz),
+                atom(b"stco", &stco),
+            ]
+            .concat();
+
+            let chapter_track = atom(
+                b"trak",
+                &[
+                    atom(b"tkhd", &tkhd),
+                    atom(
+                        b"mdia",
+                        &[atom(b"mdhd", &mdhd), atom(b"minf", &atom(b"stbl", &stbl))].concat(),
+                    ),
+                ]
+                .concat(),
+            );
+
+            let audio_track = atom(b"trak", &atom(b"tref", &atom(b"chap", &2u32.to_be_bytes())));
+
+            let ilst = [
+                text_item(b"\xA9nam", "The Book"),
+                text_item(b"\xA9ART", "Jane Doe"),
+                text_item(b"\xA9nrt", "John Smith"),
+            ]
+            .concat();
+
+            let meta = [&[0, 0, 0, 0][..], &atom(b"ilst", &ilst)].concat();
+
+            let moov = atom(
+                b"moov",
+                &[
+                    atom(b"mvhd", &mvhd),
+                    audio_track,
+                    chapter_track,
+                    atom(b"udta", &atom(b"meta", &meta)),
+                ]
+                .concat(),
+            );
+
+            [atom(b"ftyp", b"M4B \0\0\0\0"), moov].concat()
+        };
+
+        // The samples are stored after the moov atom.
+        let offset = build(0).len() as u32 + 8;
+        let file = [build(offset), atom(b"mdat", &samples)].concat();
+
+        let info = parse(&mut Cursor::new(file)).unwrap();
+
+        assert_eq!(info.duration, 90_000);
+        assert_eq!(
+            info.chapters,
+            vec![
+                (String::from("Intro"), 0),
+                (String::from("Chapter 1"), 30_000)
+            ]
+        );
+        assert_eq!(
+            info.values.get("Title").map(|v| v.as_str()),
+            Some("The Book")
+        );
+        assert_eq!(
+            info.values.get("Narrator").map(|v| v.as_str()),
+            Some("John Smith")
+        );
+    }
+}
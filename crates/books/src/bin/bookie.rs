@@ -0,0 +1,621 @@
+//! Inspect books without running the server. Eg. `bookie info --json book.epub`
+//!
+//! JSON is written one object per line so the output of multiple books can be piped into other tools.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+    process::ExitCode,
+};
+
+use bookie::{
+    epub::EpubBook, Book, BookFormat, BookSearch, Error, Rendition, Result, SanitizeOptions,
+    TocItem,
+};
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+
+#[derive(Parser, Debug)]
+#[clap(version, about, long_about = None)]
+struct CliArgs {
+    /// Print JSON instead of text.
+    #[clap(long, global = true)]
+    json: bool,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the metadata, hash and unique id.
+    Info {
+        #[clap(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Extra metadata to look for. Eg. "Narrator"
+        #[clap(long)]
+        other: Vec<String>,
+    },
+
+    /// Print the files and chapters. EPUBs include their manifest and spine.
+    Contents { path: PathBuf },
+
+    /// Print the table of contents.
+    Toc { path: PathBuf },
+
+    /// Write a resource or chapter to stdout or a file.
+    Extract {
+        path: PathBuf,
+
+        /// The path of the resource inside of the book.
+        #[clap(long, conflicts_with = "chapter", required_unless_present = "chapter")]
+        resource: Option<String>,
+
+        #[clap(long)]
+        chapter: Option<usize>,
+
+        /// Rewrite it the same way the reader receives it. Scripts are removed and urls are made relative to the book.
+        #[clap(long)]
+        rewrite: bool,
+
+        /// Prepended to the rewritten urls. Eg. "/api/file/1/res"
+        #[clap(long, requires = "rewrite")]
+        url_prefix: Option<String>,
+
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+
+    /// Check that the books can be read. Fails if any of them have errors.
+    Validate {
+        #[clap(required = true)]
+        paths: Vec<PathBuf>,
+    },
+}
+
+const SEARCHES: [(&str, BookSearch<'static>); 16] = [
+    ("title", BookSearch::Title),
+    ("identifier", BookSearch::Identifier),
+    ("language", BookSearch::Language),
+    ("contributor", BookSearch::Contributor),
+    ("coverage", BookSearch::Coverage),
+    ("cover_image", BookSearch::CoverImage),
+    ("creator", BookSearch::Creator),
+    ("date", BookSearch::Date),
+    ("description", BookSearch::Description),
+    ("format", BookSearch::Format),
+    ("publisher", BookSearch::Publisher),
+    ("relation", BookSearch::Relation),
+    ("rights", BookSearch::Rights),
+    ("source", BookSearch::Source),
+    ("subject", BookSearch::Subject),
+    ("type", BookSearch::Type),
+];
+
+#[derive(Serialize)]
+struct Info {
+    path: PathBuf,
+    format: String,
+    unique_id: Option<String>,
+    hash: Option<String>,
+    chapter_count: usize,
+    rendition: Rendition,
+    /// Only the searches which found something.
+    metadata: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Serialize)]
+struct Contents {
+    files: Vec<String>,
+    /// The path of each chapter.
+    chapters: Vec<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    manifest: Option<Vec<ManifestEntry>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spine: Option<Vec<SpineEntry>>,
+}
+
+#[derive(Serialize)]
+struct ManifestEntry {
+    id: String,
+    href: String,
+    media_type: String,
+    properties: Option<String>,
+    fallback: Option<String>,
+    media_overlay: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SpineEntry {
+    idref: String,
+    id: Option<String>,
+    linear: bool,
+    properties: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Validation {
+    path: PathBuf,
+    format: Option<String>,
+    errors: Vec<String>,
+    warnings: Vec<String>,
+}
+
+fn main() -> ExitCode {
+    let args = CliArgs::parse();
+
+    let mut out = io::stdout().lock();
+
+    let result = match args.command {
+        Command::Info { paths, other } => print_info(&paths, &other, args.json, &mut out),
+        Command::Contents { path } => print_contents(&path, args.json, &mut out),
+        Command::Toc { path } => print_toc(&path, args.json, &mut out),
+        Command::Extract {
+            path,
+            resource,
+            chapter,
+            rewrite,
+            url_prefix,
+            output,
+        } => extract(
+            &path,
+            resource.as_deref(),
+            chapter,
+            rewrite.then_some(url_prefix.as_deref()),
+            output.as_deref(),
+            &mut out,
+        ),
+        Command::Validate { paths } => validate_all(&paths, args.json, &mut out),
+    };
+
+    match result {
+        Ok(code) => code,
+        // Eg. Piped into `head`
+        Err(Error::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Load the book along with the name of its format.
+fn open(path: &Path) -> Result<(String, Box<dyn Book>)> {
+    let format = if path.is_dir() {
+        String::from("Directory")
+    } else {
+        match BookFormat::detect(&mut File::open(path)?)? {
+            Some(format) => format!("{format:?}"),
+            // Only found by their extension.
+            None => String::from("Text"),
+        }
+    };
+
+    let book = bookie::load_from_path(path)?.ok_or(Error::Unsupported("Unknown book format"))?;
+
+    Ok((format, book))
+}
+
+fn print_json<V: Serialize>(value: &V, out: &mut impl Write) -> Result<()> {
+    serde_json::to_writer(&mut *out, value).map_err(io::Error::from)?;
+    writeln!(out)?;
+
+    Ok(())
+}
+
+// Info
+
+fn print_info(
+    paths: &[PathBuf],
+    other: &[String],
+    json: bool,
+    out: &mut impl Write,
+) -> Result<ExitCode> {
+    let mut code = ExitCode::SUCCESS;
+
+    for path in paths {
+        let info = match load_info(path, other) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("{}: {e}", path.display());
+                code = ExitCode::FAILURE;
+                continue;
+            }
+        };
+
+        if json {
+            print_json(&info, out)?;
+        } else {
+            writeln!(out, "{}", info.path.display())?;
+            writeln!(out, "  format: {}", info.format)?;
+            writeln!(
+                out,
+                "  unique id: {}",
+                info.unique_id.as_deref().unwrap_or("-")
+            )?;
+            writeln!(out, "  hash: {}", info.hash.as_deref().unwrap_or("-"))?;
+            writeln!(out, "  chapters: {}", info.chapter_count)?;
+            writeln!(out, "  rendition: {:?}", info.rendition.layout)?;
+
+            for (name, values) in &info.metadata {
+                writeln!(out, "  {name}: {}", values.join(" | "))?;
+            }
+        }
+    }
+
+    Ok(code)
+}
+
+fn load_info(path: &Path, other: &[String]) -> Result<Info> {
+    let (format, mut book) = open(path)?;
+
+    let mut metadata = BTreeMap::new();
+
+    let searches = SEARCHES.into_iter().chain(
+        other
+            .iter()
+            .map(|name| (name.as_str(), BookSearch::Other(name))),
+    );
+
+    for (name, search) in searches {
+        if let Some(values) = book.find(search).filter(|v| !v.is_empty()) {
+            metadata.insert(name.to_string(), values);
+        }
+    }
+
+    Ok(Info {
+        path: path.to_path_buf(),
+        format,
+        unique_id: book.get_unique_id().ok().map(|v| v.into_owned()),
+        hash: book.compute_hash(),
+        chapter_count: book.chapter_count(),
+        rendition: book.get_rendition(),
+        metadata,
+    })
+}
+
+// Contents
+
+fn print_contents(path: &Path, json: bool, out: &mut impl Write) -> Result<ExitCode> {
+    let (format, mut book) = open(path)?;
+
+    let mut chapters = Vec::new();
+
+    for chapter in 0..book.chapter_count() {
+        book.set_chapter(chapter);
+        chapters.push(book.get_page_path());
+    }
+
+    book.set_chapter(0);
+
+    let mut contents = Contents {
+        files: book.get_files(),
+        chapters,
+        manifest: None,
+        spine: None,
+    };
+
+    if format == "Epub" {
+        let epub = EpubBook::load_from_path(path)?;
+
+        contents.manifest = Some(
+            epub.package
+                .manifest
+                .items
+                .iter()
+                .map(|v| ManifestEntry {
+                    id: v.id.clone(),
+                    href: v.href.clone(),
+                    media_type: v.media_type.clone(),
+                    properties: v.properties.clone(),
+                    fallback: v.fallback.clone(),
+                    media_overlay: v.media_overlay.clone(),
+                })
+                .collect(),
+        );
+
+        contents.spine = Some(
+            epub.package
+                .spine
+                .items
+                .iter()
+                .map(|v| SpineEntry {
+                    idref: v.idref.clone(),
+                    id: v.id.clone(),
+                    linear: v.is_linear(),
+                    properties: v.properties.clone(),
+                })
+                .collect(),
+        );
+    }
+
+    if json {
+        print_json(&contents, out)?;
+    } else {
+        writeln!(out, "Files:")?;
+        for file in &contents.files {
+            writeln!(out, "  {file}")?;
+        }
+
+        writeln!(out, "Chapters:")?;
+        for (index, path) in contents.chapters.iter().enumerate() {
+            writeln!(out, "  {index}: {}", path.display())?;
+        }
+
+        if let Some(manifest) = &contents.manifest {
+            writeln!(out, "Manifest:")?;
+            for item in manifest {
+                writeln!(out, "  {} {} ({})", item.id, item.href, item.media_type)?;
+            }
+        }
+
+        if let Some(spine) = &contents.spine {
+            writeln!(out, "Spine:")?;
+            for (index, item) in spine.iter().enumerate() {
+                writeln!(
+                    out,
+                    "  {index}: {}{}",
+                    item.idref,
+                    if item.linear { "" } else { " (non-linear)" }
+                )?;
+            }
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+// Table of Contents
+
+fn print_toc(path: &Path, json: bool, out: &mut impl Write) -> Result<ExitCode> {
+    let (_, mut book) = open(path)?;
+
+    let toc = book.get_table_of_contents()?.unwrap_or_default();
+
+    if json {
+        print_json(&toc, out)?;
+    } else {
+        fn print_items(items: &[TocItem], depth: usize, out: &mut impl Write) -> io::Result<()> {
+            for item in items {
+                writeln!(
+                    out,
+                    "{}{} -> {}{}",
+                    "  ".repeat(depth),
+                    item.title,
+                    item.chapter,
+                    item.fragment
+                        .as_deref()
+                        .map(|v| format!("#{v}"))
+                        .unwrap_or_default()
+                )?;
+
+                print_items(&item.children, depth + 1, out)?;
+            }
+
+            Ok(())
+        }
+
+        print_items(&toc, 0, out)?;
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+// Extract
+
+/// `rewrite` contains the url prefix when the contents should be rewritten.
+fn extract(
+    path: &Path,
+    resource: Option<&str>,
+    chapter: Option<usize>,
+    rewrite: Option<Option<&str>>,
+    output: Option<&Path>,
+    out: &mut impl Write,
+) -> Result<ExitCode> {
+    let (_, mut book) = open(path)?;
+
+    let data = match (resource, chapter) {
+        (Some(resource), _) => match rewrite {
+            Some(prefix) => {
+                book.read_path_as_bytes(resource, prefix, None, Some(SanitizeOptions::default()))?
+            }
+            None => book.read_path_as_bytes(resource, None, None, None)?,
+        },
+
+        (None, Some(chapter)) => {
+            if chapter >= book.chapter_count() || !book.set_chapter(chapter) {
+                return Err(Error::MissingFile(format!("Chapter {chapter}")));
+            }
+
+            match rewrite {
+                Some(prefix) => {
+                    book.read_page_as_bytes(prefix, None, Some(SanitizeOptions::default()))?
+                }
+                None => book.read_page_raw_as_bytes()?,
+            }
+        }
+
+        (None, None) => return Err(Error::MissingValueFor("resource or chapter")),
+    };
+
+    match output {
+        Some(output) => fs::write(output, data)?,
+        None => out.write_all(&data)?,
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+// Validate
+
+fn validate_all(paths: &[PathBuf], json: bool, out: &mut impl Write) -> Result<ExitCode> {
+    let mut code = ExitCode::SUCCESS;
+
+    for path in paths {
+        let validation = validate(path);
+
+        if !validation.errors.is_empty() {
+            code = ExitCode::FAILURE;
+        }
+
+        if json {
+            print_json(&validation, out)?;
+        } else if validation.errors.is_empty() && validation.warnings.is_empty() {
+            writeln!(out, "{}: ok", path.display())?;
+        } else {
+            for error in &validation.errors {
+                writeln!(out, "{}: error: {error}", path.display())?;
+            }
+
+            for warning in &validation.warnings {
+                writeln!(out, "{}: warning: {warning}", path.display())?;
+            }
+        }
+    }
+
+    Ok(code)
+}
+
+fn validate(path: &Path) -> Validation {
+    let mut validation = Validation {
+        path: path.to_path_buf(),
+        format: None,
+        errors: Vec::new(),
+        warnings: Vec::new(),
+    };
+
+    let mut book = match open(path) {
+        Ok((format, book)) => {
+            validation.format = Some(format);
+            book
+        }
+        Err(e) => {
+            validation.errors.push(format!("Unable to load: {e}"));
+            return validation;
+        }
+    };
+
+    if let Err(e) = book.get_unique_id() {
+        validation.warnings.push(format!("No unique id: {e}"));
+    }
+
+    if book.find(BookSearch::Title).is_none_or(|v| v.is_empty()) {
+        validation.warnings.push(String::from("No title"));
+    }
+
+    if book.compute_hash().is_none() {
+        validation
+            .errors
+            .push(String::from("Unable to compute hash"));
+    }
+
+    let chapter_count = book.chapter_count();
+
+    if chapter_count == 0 {
+        validation.errors.push(String::from("No chapters"));
+    }
+
+    for chapter in 0..chapter_count {
+        book.set_chapter(chapter);
+
+        match book.read_page_raw_as_bytes() {
+            Ok(v) if v.is_empty() => validation
+                .warnings
+                .push(format!("Chapter {chapter} is empty")),
+            Ok(_) => (),
+            // Eg. Audiobooks don't have pages.
+            Err(Error::Unsupported(_)) => break,
+            Err(e) => validation
+                .errors
+                .push(format!("Unable to read chapter {chapter}: {e}")),
+        }
+    }
+
+    book.set_chapter(0);
+
+    match book.get_table_of_contents() {
+        Ok(toc) => check_toc(
+            &toc.unwrap_or_default(),
+            chapter_count,
+            &mut validation.errors,
+        ),
+        Err(e) => validation
+            .errors
+            .push(format!("Unable to read the table of contents: {e}")),
+    }
+
+    if validation.format.as_deref() == Some("Epub") {
+        if let Ok(epub) = EpubBook::load_from_path(path) {
+            check_epub(&epub, &mut validation);
+        }
+    }
+
+    validation
+}
+
+fn check_toc(items: &[TocItem], chapter_count: usize, errors: &mut Vec<String>) {
+    for item in items {
+        if item.chapter >= chapter_count {
+            errors.push(format!(
+                "Table of contents item {:?} points to chapter {} of {chapter_count}",
+                item.title, item.chapter
+            ));
+        }
+
+        check_toc(&item.children, chapter_count, errors);
+    }
+}
+
+fn check_epub(book: &EpubBook, validation: &mut Validation) {
+    let manifest = &book.package.manifest;
+
+    for item in &book.package.spine.items {
+        if manifest.get_item_by_id(&item.idref).is_none() {
+            validation
+                .errors
+                .push(format!("Spine item {:?} isn't in the manifest", item.idref));
+        }
+    }
+
+    let files = book.get_files();
+
+    for item in &manifest.items {
+        // Remote resources aren't inside of the archive.
+        if item.href.contains("://") {
+            continue;
+        }
+
+        let href = urlencoding::decode(&item.href)
+            .map(|v| v.into_owned())
+            .unwrap_or_else(|_| item.href.clone());
+
+        let path = normalize_path(&book.get_root_file_dir().join(href));
+
+        if !files.contains(&path) {
+            validation.errors.push(format!(
+                "Manifest item {:?} is missing its file {path:?}",
+                item.id
+            ));
+        }
+    }
+}
+
+/// Resolve `..` and `.` in the archive path.
+fn normalize_path(path: &Path) -> String {
+    let mut parts = Vec::new();
+
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                parts.pop();
+            }
+            Component::Normal(value) => parts.push(value.to_string_lossy()),
+            _ => (),
+        }
+    }
+
+    parts.join("/")
+}
@@ -0,0 +1,61 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Seek},
+};
+
+use binstall_zip::ZipArchive;
+
+use crate::{Error, Result};
+
+/// The archive a comic book is stored inside of.
+pub trait ComicArchive: Send {
+    /// Every file path inside of the archive. Directories are excluded.
+    fn file_names(&self) -> Vec<String>;
+
+    fn read_file(&mut self, path: &str) -> Result<Vec<u8>>;
+
+    /// Visit the files in the order given.
+    fn visit_files(&mut self, paths: &[String], visit: &mut dyn FnMut(&[u8])) -> Result<()> {
+        for path in paths {
+            visit(&self.read_file(path)?);
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek + Send> ComicArchive for ZipArchive<R> {
+    fn file_names(&self) -> Vec<String> {
+        ZipArchive::file_names(self)
+            .filter(|v| !v.ends_with('/'))
+            .map(|v| v.to_string())
+            .collect()
+    }
+
+    fn read_file(&mut self, path: &str) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+
+        self.by_name(path)?.read_to_end(&mut buf)?;
+
+        Ok(buf)
+    }
+}
+
+/// Visit buffered files in the order given.
+///
+/// Used by archives which have to be read in a single pass. Eg. solid RAR or 7z archives.
+pub fn visit_buffered_files(
+    files: HashMap<String, Vec<u8>>,
+    paths: &[String],
+    visit: &mut dyn FnMut(&[u8]),
+) -> Result<()> {
+    for path in paths {
+        let contents = files
+            .get(path)
+            .ok_or_else(|| Error::MissingFile(path.clone()))?;
+
+        visit(contents);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,358 @@
+// https://anansi-project.github.io/docs/comicinfo/documentation
+// https://www.denvog.com/comet/comet-specification/
+
+use std::{collections::HashMap, io::Read};
+
+use xml::reader::XmlEvent;
+
+use crate::{BookSearch, Result};
+
+/// People who worked on the comic. Stored as comma separated values.
+const CREATOR_ROLES: [&str; 8] = [
+    "Writer",
+    "Penciller",
+    "Inker",
+    "Colorist",
+    "Letterer",
+    "CoverArtist",
+    "Editor",
+    "Translator",
+];
+
+/// Metadata stored inside of a comic archive.
+///
+/// CoMet values are stored using their ComicInfo.xml names.
+#[derive(Debug, Default)]
+pub struct ComicInfo {
+    /// Element name to value. Eg. "Series", "Number", "Writer"
+    pub values: HashMap<String, String>,
+    pub pages: Vec<ComicPageInfo>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ComicPageInfo {
+    /// Index of the image in the archive.
+    pub image: usize,
+    /// Eg. "FrontCover", "Story", "Advertisement"
+    pub type_of: Option<String>,
+    pub double_page: bool,
+    pub bookmark: Option<String>,
+
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+impl ComicInfo {
+    /// Parse a ComicInfo.xml file.
+    pub fn parse_comic_info<R: Read>(value: R) -> Result<Self> {
+        let mut this = Self::default();
+
+        let reader = xml::ParserConfig::new()
+            .trim_whitespace(true)
+            .create_reader(value);
+
+        let mut depth = 0;
+        let mut text = String::new();
+
+        for event in reader {
+            match event? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => {
+                    depth += 1;
+                    text.clear();
+
+                    if name.local_name == "Page" {
+                        let mut page = ComicPageInfo::default();
+
+                        for attr in attributes {
+                            match attr.name.local_name.as_str() {
+                                "Image" => {
+                                    page.image = attr.value.trim().parse().unwrap_or_default()
+                                }
+                                "Type" => page.type_of = Some(attr.value),
+                                "DoublePage" => {
+                                    page.double_page = attr.value.eq_ignore_ascii_case("true")
+                                }
+                                "Bookmark" => page.bookmark = Some(attr.value),
+                                "ImageWidth" => page.width = attr.value.trim().parse().ok(),
+                                "ImageHeight" => page.height = attr.value.trim().parse().ok(),
+                                _ => (),
+                            }
+                        }
+
+                        this.pages.push(page);
+                    }
+                }
+
+                XmlEvent::Characters(value) | XmlEvent::CData(value) => text.push_str(&value),
+
+                XmlEvent::EndElement { name } => {
+                    // Values are direct children of the root element.
+                    if depth == 2 && !text.trim().is_empty() {
+                        this.values.insert(name.local_name, text.trim().to_string());
+                    }
+
+                    text.clear();
+                    depth -= 1;
+                }
+
+                XmlEvent::EndDocument => break,
+
+                _ => (),
+            }
+        }
+
+        Ok(this)
+    }
+
+    /// Parse a CoMet file.
+    pub fn parse_comet<R: Read>(value: R) -> Result<Self> {
+        let mut this = Self::default();
+
+        let reader = xml::ParserConfig::new()
+            .trim_whitespace(true)
+            .create_reader(value);
+
+        let mut depth = 0;
+        let mut text = String::new();
+
+        for event in reader {
+            match event? {
+                XmlEvent::StartElement { .. } => {
+                    depth += 1;
+                    text.clear();
+                }
+
+                XmlEvent::Characters(value) | XmlEvent::CData(value) => text.push_str(&value),
+
+                XmlEvent::EndElement { name } => {
+                    let value = text.trim();
+
+                    if depth == 2 && !value.is_empty() {
+                        let key = match name.local_name.as_str() {
+                            "title" => "Title",
+                            "description" => "Summary",
+                            "series" => "Series",
+                            "issue" => "Number",
+                            "volume" => "Volume",
+                            "publisher" => "Publisher",
+                            "genre" => "Genre",
+                            "character" => "Characters",
+                            "format" => "Format",
+                            "language" => "LanguageISO",
+                            "rating" => "AgeRating",
+                            "rights" => "Rights",
+                            "identifier" => "GTIN",
+                            "pages" => "PageCount",
+                            "creator" | "writer" => "Writer",
+                            "penciller" => "Penciller",
+                            "inker" => "Inker",
+                            "colorist" => "Colorist",
+                            "letterer" => "Letterer",
+                            "coverDesigner" => "CoverArtist",
+                            "editor" => "Editor",
+                            "coverImage" => "CoverImage",
+
+                            "date" => {
+                                // YYYY-MM-DD
+                                let mut parts = value.split('-');
+
+                                for key in ["Year", "Month", "Day"] {
+                                    if let Some(part) = parts.next().filter(|v| !v.is_empty()) {
+                                        this.values.insert(key.to_string(), part.to_string());
+                                    }
+                                }
+
+                                ""
+                            }
+
+                            "readingDirection" => {
+                                if value.eq_ignore_ascii_case("rtl") {
+                                    this.values.insert(
+                                        String::from("Manga"),
+                                        String::from("YesAndRightToLeft"),
+                                    );
+                                }
+
+                                ""
+                            }
+
+                            _ => "",
+                        };
+
+                        if !key.is_empty() {
+                            // Multiple values are stored the same way ComicInfo stores them.
+                            this.values
+                                .entry(key.to_string())
+                                .and_modify(|v| {
+                                    v.push_str(", ");
+                                    v.push_str(value);
+                                })
+                                .or_insert_with(|| value.to_string());
+                        }
+                    }
+
+                    text.clear();
+                    depth -= 1;
+                }
+
+                XmlEvent::EndDocument => break,
+
+                _ => (),
+            }
+        }
+
+        Ok(this)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|v| v.as_str())
+    }
+
+    /// Read from right to left.
+    pub fn is_manga(&self) -> bool {
+        self.get("Manga") == Some("YesAndRightToLeft")
+    }
+
+    /// Index of the image marked as the front cover.
+    pub fn get_cover_image_index(&self) -> Option<usize> {
+        self.pages
+            .iter()
+            .find(|v| v.type_of.as_deref() == Some("FrontCover"))
+            .map(|v| v.image)
+    }
+
+    pub fn find(&self, search: BookSearch<'_>) -> Option<Vec<String>> {
+        let values = match search {
+            BookSearch::Title => vec![self
+                .get("Title")
+                .or_else(|| self.get("Series"))?
+                .to_string()],
+
+            BookSearch::Creator => {
+                let mut people = Vec::new();
+
+                for role in CREATOR_ROLES {
+                    for name in split_list(self.get(role)) {
+                        if !people.contains(&name) {
+                            people.push(name);
+                        }
+                    }
+                }
+
+                people
+            }
+
+            BookSearch::Date => {
+                let mut date = self.get("Year")?.to_string();
+
+                for key in ["Month", "Day"] {
+                    match self.get(key).and_then(|v| v.parse::<u32>().ok()) {
+                        Some(v) => date.push_str(&format!("-{v:02}")),
+                        None => break,
+                    }
+                }
+
+                vec![date]
+            }
+
+            BookSearch::Subject => {
+                let mut values = split_list(self.get("Genre"));
+                values.extend(split_list(self.get("Tags")));
+                values
+            }
+
+            BookSearch::Identifier => vec![self.get("GTIN")?.to_string()],
+            BookSearch::Language => vec![self.get("LanguageISO")?.to_string()],
+            BookSearch::Description => vec![self.get("Summary")?.to_string()],
+            BookSearch::Publisher => vec![self.get("Publisher")?.to_string()],
+            BookSearch::Format => vec![self.get("Format")?.to_string()],
+            BookSearch::Rights => vec![self.get("Rights")?.to_string()],
+            BookSearch::Source => split_list(self.get("Web")),
+
+            BookSearch::Other(key) => vec![self.get(key)?.to_string()],
+
+            _ => return None,
+        };
+
+        (!values.is_empty()).then_some(values)
+    }
+}
+
+fn split_list(value: Option<&str>) -> Vec<String> {
+    value
+        .unwrap_or_default()
+        .split(',')
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comic_info() {
+        let xml = br#"<?xml version="1.0" encoding="utf-8"?>
+<ComicInfo xmlns:xsd="http://www.w3.org/2001/XMLSchema" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <Title>The Beginning</Title>
+  <Series>Example</Series>
+  <Number>2.5</Number>
+  <Volume>1</Volume>
+  <Year>2019</Year>
+  <Month>3</Month>
+  <Writer>Jane Doe, John Smith</Writer>
+  <Penciller>John Smith</Penciller>
+  <LanguageISO>ja</LanguageISO>
+  <Manga>YesAndRightToLeft</Manga>
+  <Pages>
+    <Page Image="0" Type="FrontCover" ImageWidth="800" ImageHeight="1200" />
+    <Page Image="1" DoublePage="True" Bookmark="Start" />
+  </Pages>
+</ComicInfo>"#;
+
+        let info = ComicInfo::parse_comic_info(&xml[..]).unwrap();
+
+        assert_eq!(info.get("Number"), Some("2.5"));
+        assert!(info.is_manga());
+        assert_eq!(
+            info.find(BookSearch::Creator),
+            Some(vec![String::from("Jane Doe"), String::from("John Smith")])
+        );
+        assert_eq!(
+            info.find(BookSearch::Date),
+            Some(vec![String::from("2019-03")])
+        );
+        assert_eq!(info.get_cover_image_index(), Some(0));
+        assert_eq!(info.pages.len(), 2);
+        assert!(info.pages[1].double_page);
+        assert_eq!(info.pages[0].width, Some(800));
+    }
+
+    #[test]
+    fn comet() {
+        let xml = br#"<?xml version="1.0" encoding="UTF-8"?>
+<comet xmlns="http://www.denvog.com/comet/">
+  <title>The Beginning</title>
+  <series>Example</series>
+  <issue>3</issue>
+  <date>2001-02-03</date>
+  <writer>Jane Doe</writer>
+  <writer>John Smith</writer>
+  <readingDirection>rtl</readingDirection>
+</comet>"#;
+
+        let info = ComicInfo::parse_comet(&xml[..]).unwrap();
+
+        assert_eq!(info.get("Number"), Some("3"));
+        assert_eq!(info.get("Writer"), Some("Jane Doe, John Smith"));
+        assert_eq!(
+            info.find(BookSearch::Date),
+            Some(vec![String::from("2001-02-03")])
+        );
+        assert!(info.is_manga());
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::{archive::ComicArchive, IMAGE_EXTENSIONS};
+use crate::{Error, Result};
+
+/// Metadata files which may be next to the images.
+const METADATA_FILES: [&str; 2] = ["comicinfo.xml", "comet.xml"];
+
+/// A folder of images. Only the files directly inside of it are used.
+pub struct DirectoryArchive {
+    path: PathBuf,
+
+    files: Vec<String>,
+}
+
+impl DirectoryArchive {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut files = Vec::new();
+
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+
+            let name = entry.file_name().to_string_lossy().into_owned();
+
+            // Hidden files would change the hash. Eg. .DS_Store
+            if entry.file_type()?.is_file() && !is_ignored_file(&name) {
+                files.push(name);
+            }
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            files,
+        })
+    }
+}
+
+impl ComicArchive for DirectoryArchive {
+    fn file_names(&self) -> Vec<String> {
+        self.files.clone()
+    }
+
+    fn read_file(&mut self, path: &str) -> Result<Vec<u8>> {
+        // Only the listed files can be read. Prevents reading outside of the folder.
+        if !self.files.iter().any(|v| v == path) {
+            return Err(Error::MissingFile(path.to_string()));
+        }
+
+        Ok(fs::read(self.path.join(path))?)
+    }
+}
+
+/// Whether the folder is a comic made of images. It can't contain folders or other files.
+///
+/// Hidden files and comic metadata files are allowed.
+pub fn is_image_folder<P: AsRef<Path>>(path: P) -> bool {
+    let Ok(entries) = fs::read_dir(path) else {
+        return false;
+    };
+
+    let mut has_image = false;
+
+    for entry in entries {
+        let Ok(entry) = entry else {
+            return false;
+        };
+
+        let Ok(file_type) = entry.file_type() else {
+            return false;
+        };
+
+        if file_type.is_dir() {
+            return false;
+        }
+
+        let name = entry.file_name().to_string_lossy().to_lowercase();
+
+        if is_ignored_file(&name) || METADATA_FILES.contains(&name.as_str()) {
+            continue;
+        }
+
+        if name
+            .rsplit_once('.')
+            .map_or(false, |(_, ext)| IMAGE_EXTENSIONS.contains(&ext))
+        {
+            has_image = true;
+        } else {
+            return false;
+        }
+    }
+
+    has_image
+}
+
+fn is_ignored_file(name: &str) -> bool {
+    name.starts_with('.') || name.eq_ignore_ascii_case("thumbs.db")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cb::ComicBook, Book};
+
+    #[test]
+    fn image_folder() {
+        let path = std::env::temp_dir().join(format!("bookie-image-folder-{}", std::process::id()));
+        fs::create_dir_all(&path).unwrap();
+
+        for (name, contents) in [
+            ("10.jpg", "ten"),
+            ("2.jpg", "two"),
+            ("1.png", "one"),
+            (".DS_Store", "hidden"),
+        ] {
+            fs::write(path.join(name), contents).unwrap();
+        }
+
+        assert!(is_image_folder(&path));
+
+        let mut book = ComicBook::load_from_path(&path).unwrap();
+
+        assert_eq!(book.chapter_count(), 3);
+        assert_eq!(book.get_files(), ["1.png", "2.jpg", "10.jpg"]);
+        assert_eq!(book.read_page_raw_as_bytes().unwrap(), b"one");
+        assert!(book
+            .read_path_as_bytes("../x", None, None, None)
+            .unwrap()
+            .is_empty());
+
+        let hash = book.compute_hash();
+        assert_eq!(
+            ComicBook::load_from_path(&path).unwrap().compute_hash(),
+            hash
+        );
+
+        // Other files or folders make it a regular folder.
+        fs::write(path.join("notes.txt"), "").unwrap();
+        assert!(!is_image_folder(&path));
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
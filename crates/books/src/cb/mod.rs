@@ -1,18 +1,34 @@
 use std::{
     borrow::Cow,
     fs::File,
-    io::Read,
+    io::{Seek, Write},
     path::{Path, PathBuf},
 };
 
-use binstall_zip::ZipArchive;
+use binstall_zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
 use common_local::sort::filename_sort;
 
+mod archive;
+mod comic_info;
+mod directory;
+#[cfg(feature = "rar")]
+mod rar;
+mod sevenz;
+mod tar;
+
+#[cfg(feature = "rar")]
+use self::rar::RarArchive;
+use self::{
+    archive::ComicArchive, directory::DirectoryArchive, sevenz::SevenZArchive, tar::TarArchive,
+};
+
 use super::Book;
-use crate::{BookSearch, Result};
+use crate::{BookFormat, BookSearch, Error, ReadSeek, Result, SanitizeOptions};
+pub use comic_info::{ComicInfo, ComicPageInfo};
+pub use directory::is_image_folder;
 
-// TODO: Rar, 7z , TAR, ACE, ...
-//       CBR, CB7, CBT, CBA, ...
+// TODO: ACE, ...
+//       CBA, ...
 
 // TODO: Handle multiple different types of cb files.
 //  - [text content]_v01_p{00index}
@@ -21,13 +37,127 @@ use crate::{BookSearch, Result};
 
 const SEPARATORS: [char; 4] = [' ', '_', '-', '.'];
 
+const IMAGE_EXTENSIONS: [&str; 10] = [
+    "jpg", "jpeg", "png", "gif", "webp", "bmp", "avif", "jxl", "tif", "tiff",
+];
+
 pub struct ComicBook {
     file_name: String,
 
-    archive: ZipArchive<File>,
+    archive: Box<dyn ComicArchive>,
 
+    /// Every file in the archive in reading order. Each one is a page, even if it isn't an image,
+    /// so stored progress keeps pointing to the same page.
     files: Vec<String>,
     offset: usize,
+
+    info: Option<ComicInfo>,
+}
+
+impl ComicBook {
+    /// The ComicInfo.xml or CoMet metadata stored in the archive.
+    pub fn get_info(&self) -> Option<&ComicInfo> {
+        self.info.as_ref()
+    }
+
+    /// Empty archives don't have any pages.
+    fn current_page(&self) -> Result<String> {
+        self.files
+            .get(self.offset)
+            .cloned()
+            .ok_or_else(|| Error::MissingFile(format!("page {}", self.offset)))
+    }
+
+    /// Write every file into a CBZ. Eg. To download a folder of images.
+    pub fn write_cbz<W: Write + Seek>(&mut self, writer: W) -> Result<()> {
+        let mut zip = ZipWriter::new(writer);
+
+        for path in &self.files {
+            // Images are already compressed.
+            zip.start_file(
+                path.as_str(),
+                FileOptions::default().compression_method(CompressionMethod::Stored),
+            )?;
+            zip.write_all(&self.archive.read_file(path)?)?;
+        }
+
+        zip.finish()?;
+
+        Ok(())
+    }
+
+    fn read_info(&mut self) -> Result<Option<ComicInfo>> {
+        let find_file = |name: &str| {
+            self.files
+                .iter()
+                .find(|v| {
+                    v.rsplit('/')
+                        .next()
+                        .map_or(false, |v| v.eq_ignore_ascii_case(name))
+                })
+                .cloned()
+        };
+
+        if let Some(path) = find_file("ComicInfo.xml") {
+            let contents = self.archive.read_file(&path)?;
+
+            Ok(Some(ComicInfo::parse_comic_info(contents.as_slice())?))
+        } else if let Some(path) = find_file("comet.xml") {
+            let contents = self.archive.read_file(&path)?;
+
+            Ok(Some(ComicInfo::parse_comet(contents.as_slice())?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn get_cover_page(&self) -> Option<&String> {
+        if let Some(info) = self.info.as_ref() {
+            if let Some(page) = info
+                .get_cover_image_index()
+                .and_then(|i| self.files.iter().filter(|v| is_image(v)).nth(i))
+            {
+                return Some(page);
+            }
+
+            if let Some(name) = info.get("CoverImage") {
+                if let Some(page) = self.files.iter().find(|v| v.ends_with(name)) {
+                    return Some(page);
+                }
+            }
+        }
+
+        self.files.iter().find(|v| is_image(v))
+    }
+
+    /// A value for each page. Empty if the page has none.
+    fn find_page_values(&self, func: impl Fn(&ComicPageInfo) -> Option<String>) -> Vec<String> {
+        let pages = self
+            .info
+            .as_ref()
+            .map(|v| v.pages.as_slice())
+            .unwrap_or_default();
+
+        // The metadata only counts the images.
+        let mut image_index = 0;
+
+        self.files
+            .iter()
+            .map(|page| {
+                if !is_image(page) {
+                    return String::new();
+                }
+
+                image_index += 1;
+
+                pages
+                    .iter()
+                    .find(|v| v.image == image_index - 1)
+                    .and_then(&func)
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
 }
 
 // impl ComicBook {
@@ -68,37 +198,100 @@ pub struct ComicBook {
 //     }
 // }
 
-impl Book for ComicBook {
-    fn load_from_path(path: &str) -> Result<Self>
-    where
-        Self: Sized,
-    {
-        let archive = ZipArchive::new(File::open(path)?)?;
+fn is_image(path: &str) -> bool {
+    path.rsplit_once('.')
+        .is_some_and(|(_, ext)| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
 
-        let mut files = archive
-            .file_names()
-            .filter_map(|v| (!v.ends_with('/')).then(|| v.to_string()))
-            .collect::<Vec<_>>();
+impl ComicBook {
+    fn from_archive(file_name: String, archive: Box<dyn ComicArchive>) -> Self {
+        let mut files = archive.file_names();
 
         filename_sort(&mut files);
 
-        Ok(Self {
+        let mut this = Self {
             archive,
             files,
 
-            file_name: path.rsplit_once('/').unwrap().1.to_string(),
+            file_name,
             offset: 0,
-        })
+
+            info: None,
+        };
+
+        this.info = match this.read_info() {
+            Ok(v) => v,
+            Err(e) => {
+                tracing::warn!("Unable to read comic metadata: {e}");
+                None
+            }
+        };
+
+        this
+    }
+}
+
+impl Book for ComicBook {
+    fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let path = path.as_ref();
+
+        let file_name = path
+            .file_name()
+            .map(|v| v.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        if path.is_dir() {
+            return Ok(Self::from_archive(
+                file_name,
+                Box::new(DirectoryArchive::open(path)?),
+            ));
+        }
+
+        let mut file = File::open(path)?;
+
+        let archive: Box<dyn ComicArchive> = match BookFormat::detect(&mut file)? {
+            // The unrar library only works with files on disk.
+            #[cfg(feature = "rar")]
+            Some(BookFormat::Rar) => Box::new(RarArchive::open(path)?),
+            #[cfg(not(feature = "rar"))]
+            Some(BookFormat::Rar) => return Err(Error::Unsupported("RAR support is not enabled")),
+            Some(BookFormat::SevenZ) => Box::new(SevenZArchive::from_reader(Box::new(file))?),
+            Some(BookFormat::Tar) => Box::new(TarArchive::from_reader(Box::new(file))?),
+            _ => Box::new(ZipArchive::new(file)?),
+        };
+
+        Ok(Self::from_archive(file_name, archive))
+    }
+
+    fn load_from_reader<R: ReadSeek + 'static>(mut reader: R) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let archive: Box<dyn ComicArchive> = match BookFormat::detect(&mut reader)? {
+            Some(BookFormat::Rar) => {
+                return Err(Error::Unsupported(
+                    "RAR archives can only be opened from a path",
+                ))
+            }
+            Some(BookFormat::SevenZ) => Box::new(SevenZArchive::from_reader(Box::new(reader))?),
+            Some(BookFormat::Tar) => Box::new(TarArchive::from_reader(Box::new(reader))?),
+            _ => Box::new(ZipArchive::new(reader)?),
+        };
+
+        Ok(Self::from_archive(String::new(), archive))
     }
 
     fn compute_hash(&mut self) -> Option<String> {
         let mut hasher = blake3::Hasher::new();
 
-        for path in self.files.clone() {
-            if let Ok(asdf) = self.read_path_as_bytes(&path, None, None) {
-                hasher.update(&asdf);
-            }
-        }
+        self.archive
+            .visit_files(&self.files, &mut |contents| {
+                hasher.update(contents);
+            })
+            .ok()?;
 
         Some(hasher.finalize().to_string())
     }
@@ -107,11 +300,29 @@ impl Book for ComicBook {
         self.files.clone()
     }
 
-    fn find(&self, _search: BookSearch<'_>) -> Option<Vec<String>> {
-        None
+    fn find(&self, search: BookSearch<'_>) -> Option<Vec<String>> {
+        match search {
+            BookSearch::CoverImage => Some(vec![self.get_cover_page()?.clone()]),
+
+            // Per page info.
+            BookSearch::Other("PageType") => Some(self.find_page_values(|v| v.type_of.clone())),
+            BookSearch::Other("PageDoublePage") => {
+                Some(self.find_page_values(|v| Some(v.double_page.to_string())))
+            }
+            BookSearch::Other("PageBookmark") => {
+                Some(self.find_page_values(|v| v.bookmark.clone()))
+            }
+
+            _ => self.info.as_ref()?.find(search),
+        }
     }
 
     fn get_unique_id(&self) -> Result<Cow<str>> {
+        // Loaded from a reader.
+        if self.file_name.is_empty() {
+            return Err(Error::MissingUniqueId);
+        }
+
         Ok(Cow::Borrowed(self.file_name.as_str()))
     }
 
@@ -120,17 +331,16 @@ impl Book for ComicBook {
     }
 
     fn get_page_path(&self) -> PathBuf {
-        self.files[self.offset].clone().into()
+        self.files
+            .get(self.offset)
+            .map(PathBuf::from)
+            .unwrap_or_default()
     }
 
     fn read_page_raw_as_bytes(&mut self) -> Result<Vec<u8>> {
-        let mut buf = Vec::new();
-
-        self.archive
-            .by_name(&self.files[self.offset])?
-            .read_to_end(&mut buf)?;
+        let page = self.current_page()?;
 
-        Ok(buf)
+        self.archive.read_file(&page)
     }
 
     fn read_path_as_bytes(
@@ -138,13 +348,10 @@ impl Book for ComicBook {
         path: &str,
         _prepend_to_urls: Option<&str>,
         _add_css: Option<&[&str]>,
+        _sanitize: Option<SanitizeOptions>,
     ) -> Result<Vec<u8>> {
         if let Some(file) = self.files.iter().find(|v| v == &path) {
-            let mut buf = Vec::new();
-
-            self.archive.by_name(file.as_str())?.read_to_end(&mut buf)?;
-
-            Ok(buf)
+            self.archive.read_file(file)
         } else {
             Ok(Vec::new())
         }
@@ -154,14 +361,9 @@ impl Book for ComicBook {
         &mut self,
         _prepend_to_urls: Option<&str>,
         _add_css: Option<&[&str]>,
+        _sanitize: Option<SanitizeOptions>,
     ) -> Result<Vec<u8>> {
-        let mut buf = Vec::new();
-
-        self.archive
-            .by_name(&self.files[self.offset])?
-            .read_to_end(&mut buf)?;
-
-        Ok(buf)
+        self.read_page_raw_as_bytes()
     }
 
     fn chapter_count(&self) -> usize {
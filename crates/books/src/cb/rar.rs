@@ -0,0 +1,134 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use unrar::Archive;
+
+use super::archive::{visit_buffered_files, ComicArchive};
+use crate::{Error, Result};
+
+/// A RAR (v4 or v5) archive.
+///
+/// RAR archives can be solid so every read walks the archive from the beginning,
+/// only extracting the requested entries.
+pub struct RarArchive {
+    path: PathBuf,
+
+    files: Vec<String>,
+}
+
+impl RarArchive {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+
+        let mut files = Vec::new();
+
+        for entry in Archive::new(path).open_for_listing()? {
+            let entry = entry?;
+
+            if entry.is_file() {
+                files.push(entry.filename.to_string_lossy().replace('\\', "/"));
+            }
+        }
+
+        Ok(Self {
+            path: PathBuf::from(path),
+            files,
+        })
+    }
+}
+
+impl ComicArchive for RarArchive {
+    fn file_names(&self) -> Vec<String> {
+        self.files.clone()
+    }
+
+    fn read_file(&mut self, path: &str) -> Result<Vec<u8>> {
+        let mut archive = Archive::new(&self.path).open_for_processing()?;
+
+        while let Some(header) = archive.read_header()? {
+            let entry = header.entry();
+
+            if entry.is_file() && entry.filename.to_string_lossy().replace('\\', "/") == path {
+                return Ok(header.read()?.0);
+            }
+
+            archive = header.skip()?;
+        }
+
+        Err(Error::MissingFile(path.to_string()))
+    }
+
+    // Seeking to each file would decompress the archive once per file.
+    fn visit_files(&mut self, paths: &[String], visit: &mut dyn FnMut(&[u8])) -> Result<()> {
+        let wanted = paths.iter().map(String::as_str).collect::<HashSet<_>>();
+
+        let mut files = HashMap::new();
+
+        let mut archive = Archive::new(&self.path).open_for_processing()?;
+
+        while files.len() < wanted.len() {
+            let Some(header) = archive.read_header()? else {
+                break;
+            };
+
+            let path = header.entry().filename.to_string_lossy().replace('\\', "/");
+
+            archive = if header.entry().is_file() && wanted.contains(path.as_str()) {
+                let (contents, archive) = header.read()?;
+
+                files.insert(path, contents);
+
+                archive
+            } else {
+                header.skip()?
+            };
+        }
+
+        visit_buffered_files(files, paths, visit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{cb::ComicBook, Book};
+
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/comic.cbr");
+
+    #[test]
+    fn cbr() {
+        let mut book = ComicBook::load_from_path(FIXTURE).unwrap();
+
+        assert_eq!(
+            book.get_files(),
+            ["notes.txt", "pages/1.png", "pages/2.jpg"]
+        );
+        // Every file is a page so page numbers match what was stored before.
+        assert_eq!(book.chapter_count(), 3);
+        assert_eq!(book.read_page_raw_as_bytes().unwrap(), b"skip");
+
+        book.set_chapter(1);
+        assert_eq!(book.read_page_raw_as_bytes().unwrap(), b"one");
+
+        book.set_chapter(2);
+        assert_eq!(book.read_page_raw_as_bytes().unwrap(), b"two");
+
+        assert_eq!(
+            book.read_path_as_bytes("notes.txt", None, None, None)
+                .unwrap(),
+            b"skip"
+        );
+
+        // Only the requested files are read.
+        let images = [String::from("pages/1.png"), String::from("pages/2.jpg")];
+
+        let mut hashed = Vec::new();
+        book.archive
+            .visit_files(&images, &mut |v| hashed.push(v.to_vec()))
+            .unwrap();
+        assert_eq!(hashed, [b"one", b"two"]);
+
+        assert!(book.compute_hash().is_some());
+    }
+}
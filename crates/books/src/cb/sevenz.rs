@@ -0,0 +1,150 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Seek, SeekFrom},
+};
+
+use sevenz_rust::{Password, SevenZReader};
+
+use super::archive::{visit_buffered_files, ComicArchive};
+use crate::{Error, ReadSeek, Result};
+
+/// A 7-Zip archive.
+///
+/// Solid blocks have to be decompressed from their start so we open a new reader for every read.
+pub struct SevenZArchive {
+    source: Box<dyn ReadSeek>,
+    length: u64,
+
+    files: Vec<String>,
+}
+
+impl SevenZArchive {
+    pub fn from_reader(mut source: Box<dyn ReadSeek>) -> Result<Self> {
+        let length = source.seek(SeekFrom::End(0))?;
+
+        let mut this = Self {
+            source,
+            length,
+            files: Vec::new(),
+        };
+
+        this.files = this
+            .reader()?
+            .archive()
+            .files
+            .iter()
+            .filter(|v| !v.is_directory() && v.has_stream())
+            .map(|v| v.name().replace('\\', "/"))
+            .collect();
+
+        Ok(this)
+    }
+
+    fn reader(&mut self) -> Result<SevenZReader<&mut Box<dyn ReadSeek>>> {
+        self.source.seek(SeekFrom::Start(0))?;
+
+        Ok(SevenZReader::new(
+            &mut self.source,
+            self.length,
+            Password::empty(),
+        )?)
+    }
+}
+
+impl ComicArchive for SevenZArchive {
+    fn file_names(&self) -> Vec<String> {
+        self.files.clone()
+    }
+
+    fn read_file(&mut self, path: &str) -> Result<Vec<u8>> {
+        let mut found = None;
+
+        self.reader()?.for_each_entries(|entry, reader| {
+            if entry.name().replace('\\', "/") == path {
+                let mut buf = Vec::new();
+                reader.read_to_end(&mut buf)?;
+
+                found = Some(buf);
+
+                // Stop reading.
+                Ok(false)
+            } else {
+                Ok(true)
+            }
+        })?;
+
+        found.ok_or_else(|| Error::MissingFile(path.to_string()))
+    }
+
+    fn visit_files(&mut self, paths: &[String], visit: &mut dyn FnMut(&[u8])) -> Result<()> {
+        let wanted = paths.iter().map(String::as_str).collect::<HashSet<_>>();
+
+        let mut files = HashMap::new();
+
+        self.reader()?.for_each_entries(|entry, reader| {
+            let path = entry.name().replace('\\', "/");
+
+            if !entry.is_directory() && wanted.contains(path.as_str()) {
+                let mut buf = Vec::new();
+                reader.read_to_end(&mut buf)?;
+
+                files.insert(path, buf);
+            }
+
+            // Stop reading once every file was found.
+            Ok(files.len() < wanted.len())
+        })?;
+
+        visit_buffered_files(files, paths, visit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use crate::{cb::ComicBook, Book};
+
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/comic.cb7");
+
+    #[test]
+    fn cb7() {
+        let mut book = ComicBook::load_from_path(FIXTURE).unwrap();
+
+        assert_eq!(
+            book.get_files(),
+            ["notes.txt", "pages/1.png", "pages/2.jpg"]
+        );
+        // Every file is a page so page numbers match what was stored before.
+        assert_eq!(book.chapter_count(), 3);
+        assert_eq!(book.read_page_raw_as_bytes().unwrap(), b"skip");
+
+        book.set_chapter(1);
+        assert_eq!(book.read_page_raw_as_bytes().unwrap(), b"one");
+
+        book.set_chapter(2);
+        assert_eq!(book.read_page_raw_as_bytes().unwrap(), b"two");
+
+        assert_eq!(
+            book.read_path_as_bytes("notes.txt", None, None, None)
+                .unwrap(),
+            b"skip"
+        );
+
+        // Only the requested files are read, and visited in the requested order.
+        let images = [String::from("pages/2.jpg"), String::from("pages/1.png")];
+
+        let mut visited = Vec::new();
+        book.archive
+            .visit_files(&images, &mut |v| visited.push(v.to_vec()))
+            .unwrap();
+        assert_eq!(visited, [b"two", b"one"]);
+
+        let hash = book.compute_hash();
+        assert!(hash.is_some());
+
+        // Loading from a reader gives the same contents.
+        let mut book = ComicBook::load_from_reader(File::open(FIXTURE).unwrap()).unwrap();
+        assert_eq!(book.compute_hash(), hash);
+    }
+}
@@ -0,0 +1,170 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{BufReader, Read, Seek, SeekFrom},
+};
+
+use flate2::read::GzDecoder;
+use tar::Archive;
+
+use super::archive::{visit_buffered_files, ComicArchive};
+use crate::{Error, ReadSeek, Result, GZIP_MAGIC};
+
+/// A tar archive. Optionally gzip compressed.
+///
+/// Tar archives have no index so every read walks the archive from the beginning.
+pub struct TarArchive {
+    source: Box<dyn ReadSeek>,
+    is_gzip: bool,
+
+    files: Vec<String>,
+}
+
+impl TarArchive {
+    pub fn from_reader(mut source: Box<dyn ReadSeek>) -> Result<Self> {
+        let mut magic = [0; 2];
+        let is_gzip = source.read_exact(&mut magic).is_ok() && magic == GZIP_MAGIC;
+
+        let mut this = Self {
+            source,
+            is_gzip,
+            files: Vec::new(),
+        };
+
+        let mut files = Vec::new();
+        let mut archive = this.archive()?;
+
+        for entry in archive.entries()? {
+            let entry = entry?;
+
+            if entry.header().entry_type().is_file() {
+                files.push(entry.path()?.to_string_lossy().replace('\\', "/"));
+            }
+        }
+
+        drop(archive);
+        this.files = files;
+
+        Ok(this)
+    }
+
+    fn archive(&mut self) -> Result<Archive<Box<dyn Read + '_>>> {
+        self.source.seek(SeekFrom::Start(0))?;
+
+        let source = BufReader::new(&mut self.source);
+
+        let reader: Box<dyn Read> = if self.is_gzip {
+            Box::new(GzDecoder::new(source))
+        } else {
+            Box::new(source)
+        };
+
+        Ok(Archive::new(reader))
+    }
+}
+
+impl ComicArchive for TarArchive {
+    fn file_names(&self) -> Vec<String> {
+        self.files.clone()
+    }
+
+    fn read_file(&mut self, path: &str) -> Result<Vec<u8>> {
+        let mut archive = self.archive()?;
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+
+            if entry.header().entry_type().is_file()
+                && entry.path()?.to_string_lossy().replace('\\', "/") == path
+            {
+                let mut buf = Vec::new();
+                entry.read_to_end(&mut buf)?;
+
+                return Ok(buf);
+            }
+        }
+
+        Err(Error::MissingFile(path.to_string()))
+    }
+
+    fn visit_files(&mut self, paths: &[String], visit: &mut dyn FnMut(&[u8])) -> Result<()> {
+        let wanted = paths.iter().map(String::as_str).collect::<HashSet<_>>();
+
+        let mut files = HashMap::new();
+
+        let mut archive = self.archive()?;
+
+        // Entries which aren't read are skipped over when moving to the next one.
+        for entry in archive.entries()? {
+            if files.len() == wanted.len() {
+                break;
+            }
+
+            let mut entry = entry?;
+
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            let path = entry.path()?.to_string_lossy().replace('\\', "/");
+
+            if wanted.contains(path.as_str()) {
+                let mut buf = Vec::new();
+                entry.read_to_end(&mut buf)?;
+
+                files.insert(path, buf);
+            }
+        }
+
+        visit_buffered_files(files, paths, visit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{cb::ComicBook, Book};
+
+    #[test]
+    fn cbt() {
+        let mut hashes = Vec::new();
+
+        for name in ["comic.cbt", "comic-gzip.cbt"] {
+            let path = format!("{}/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+
+            let mut book = ComicBook::load_from_path(path).unwrap();
+
+            // Directories are excluded.
+            assert_eq!(
+                book.get_files(),
+                ["notes.txt", "pages/1.png", "pages/2.jpg"]
+            );
+            // Every file is a page so page numbers match what was stored before.
+            assert_eq!(book.chapter_count(), 3);
+            assert_eq!(book.read_page_raw_as_bytes().unwrap(), b"skip");
+
+            book.set_chapter(1);
+            assert_eq!(book.read_page_raw_as_bytes().unwrap(), b"one");
+
+            book.set_chapter(2);
+            assert_eq!(book.read_page_raw_as_bytes().unwrap(), b"two");
+
+            assert!(book
+                .read_path_as_bytes("missing.png", None, None, None)
+                .unwrap()
+                .is_empty());
+
+            // Only the requested files are read, and visited in the requested order.
+            let images = [String::from("pages/2.jpg"), String::from("pages/1.png")];
+
+            let mut visited = Vec::new();
+            book.archive
+                .visit_files(&images, &mut |v| visited.push(v.to_vec()))
+                .unwrap();
+            assert_eq!(visited, [b"two", b"one"]);
+
+            hashes.push(book.compute_hash().unwrap());
+        }
+
+        // Compression doesn't change the hash.
+        assert_eq!(hashes[0], hashes[1]);
+    }
+}
@@ -0,0 +1,755 @@
+// https://idpf.org/epub/linking/cfi/epub-cfi.html
+
+use std::{fmt, str::FromStr};
+
+use crate::{
+    markup::{decode_entities, parse_tag, VOID_ELEMENTS},
+    Error, Result,
+};
+
+/// An EPUB Canonical Fragment Identifier pointing to a location inside of a spine item.
+///
+/// Eg. `epubcfi(/6/4[chap01ref]!/4[body01]/10[para05]/3:10)`
+///
+/// Ranges are collapsed into their start. Spatial and temporal offsets along with text assertions are ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfi {
+    /// Index of the item in the spine.
+    pub spine_index: usize,
+    /// The id of the spine `<itemref>`.
+    pub spine_id: Option<String>,
+    /// Steps from the root element of the content document.
+    pub steps: Vec<CfiStep>,
+    /// Offset inside of the text the last step points to. Counted in UTF-16 code units.
+    pub offset: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CfiStep {
+    /// Even for elements and odd for the text between them.
+    pub index: usize,
+    /// The id assertion. Used if the document changed and the index no longer points to the element.
+    pub id: Option<String>,
+}
+
+impl Cfi {
+    /// Create the CFI for a position inside of the content document. The position is counted the same as [`Cfi::resolve`].
+    ///
+    /// Positions past the end of the text point to the end of the last text node.
+    pub fn from_position(
+        spine_index: usize,
+        spine_id: Option<String>,
+        document: &str,
+        position: usize,
+    ) -> Self {
+        let root = parse_document(document);
+
+        let mut steps = Vec::new();
+        let mut offset = None;
+
+        if let Some(body_index) = find_body(&root) {
+            let Node::Element(body) = &root.children[body_index] else {
+                unreachable!()
+            };
+
+            let mut path = vec![body_index];
+            let mut remaining = position;
+
+            let byte_offset = find_position(body, &mut remaining, &mut path).or_else(|| {
+                path.truncate(1);
+                find_last_text(body, &mut path)
+            });
+
+            if let Some(byte_offset) = byte_offset {
+                if let Some(Node::Text(text)) = node_at(&root, &path) {
+                    let mut byte_offset = byte_offset.min(text.len());
+
+                    // The reader counts bytes. Move to the start of the character.
+                    while !text.is_char_boundary(byte_offset) {
+                        byte_offset -= 1;
+                    }
+
+                    offset = Some(text[..byte_offset].encode_utf16().count());
+                }
+            }
+
+            steps = to_steps(&root, &path);
+        }
+
+        Self {
+            spine_index,
+            spine_id,
+            steps,
+            offset,
+        }
+    }
+
+    /// Resolve the CFI into a position inside of the content document.
+    ///
+    /// The position is the byte length of every text node in the `<body>` before the location. Text nodes
+    /// which only contain whitespace are skipped. It's counted the same way the reader counts its `char_pos`.
+    pub fn resolve(&self, document: &str) -> Option<usize> {
+        let root = parse_document(document);
+
+        let path = find_path(&root, &self.steps)?;
+
+        let Some(body_index) = find_body(&root) else {
+            return Some(0);
+        };
+
+        let Node::Element(body) = &root.children[body_index] else {
+            unreachable!()
+        };
+
+        // Anything outside of the body is at the start.
+        let Some((&first, rest)) = path.split_first().filter(|(&v, _)| v == body_index) else {
+            return Some(0);
+        };
+
+        let mut position = 0;
+        let mut element = body;
+        let mut node = &root.children[first];
+
+        for &index in rest {
+            position += element.children[..index]
+                .iter()
+                .map(text_len)
+                .sum::<usize>();
+
+            match element.children.get(index) {
+                Some(child) => node = child,
+                // The path points past the last child.
+                None => return Some(position),
+            }
+
+            if let Node::Element(child) = node {
+                element = child;
+            }
+        }
+
+        if let (Node::Text(text), Some(offset)) = (node, self.offset) {
+            if text_len(node) != 0 {
+                position += utf16_to_byte_offset(text, offset);
+            }
+        }
+
+        Some(position)
+    }
+}
+
+impl FromStr for Cfi {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let invalid = || Error::InvalidCfi(value.to_string());
+
+        let inner = value
+            .trim()
+            .strip_prefix("epubcfi(")
+            .and_then(|v| v.strip_suffix(')'))
+            .ok_or_else(invalid)?;
+
+        // Ranges are "parent,start,end". Only the start is used.
+        let mut parts = split_unescaped(inner, ',');
+        let mut path = parts.next().unwrap_or_default().to_string();
+
+        if let Some(start) = parts.next() {
+            path.push_str(start);
+        }
+
+        let mut indirections = split_unescaped(&path, '!');
+
+        let (package, _) =
+            parse_steps(indirections.next().unwrap_or_default()).ok_or_else(invalid)?;
+        let content = indirections.next().ok_or_else(invalid)?;
+
+        // Links into nested documents. Eg. an iframe inside of the chapter.
+        if indirections.next().is_some() {
+            return Err(Error::Unsupported("Nested CFI indirection"));
+        }
+
+        // The last package step points to the spine <itemref>.
+        let itemref = package
+            .last()
+            .filter(|v| v.index >= 2 && v.index % 2 == 0)
+            .ok_or_else(invalid)?;
+
+        let (steps, rest) = parse_steps(content).ok_or_else(invalid)?;
+
+        let offset = match rest.strip_prefix(':') {
+            Some(rest) => {
+                let end = rest
+                    .find(|v: char| !v.is_ascii_digit())
+                    .unwrap_or(rest.len());
+
+                Some(rest[..end].parse().map_err(|_| invalid())?)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            spine_index: itemref.index / 2 - 1,
+            spine_id: itemref.id.clone(),
+            steps,
+            offset,
+        })
+    }
+}
+
+impl fmt::Display for Cfi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The spine is the third child element of the <package>.
+        write!(f, "epubcfi(/6/{}", (self.spine_index + 1) * 2)?;
+
+        if let Some(id) = self.spine_id.as_deref() {
+            write!(f, "[{}]", escape(id))?;
+        }
+
+        f.write_str("!")?;
+
+        for step in &self.steps {
+            write!(f, "/{}", step.index)?;
+
+            if let Some(id) = step.id.as_deref() {
+                write!(f, "[{}]", escape(id))?;
+            }
+        }
+
+        if let Some(offset) = self.offset {
+            write!(f, ":{offset}")?;
+        }
+
+        f.write_str(")")
+    }
+}
+
+/// Parse the steps at the start of the value. Returns them along with whatever comes after.
+fn parse_steps(mut value: &str) -> Option<(Vec<CfiStep>, &str)> {
+    let mut steps = Vec::new();
+
+    while let Some(rest) = value.strip_prefix('/') {
+        let end = rest
+            .find(|v: char| !v.is_ascii_digit())
+            .unwrap_or(rest.len());
+
+        let index = rest[..end].parse().ok()?;
+        value = &rest[end..];
+
+        let mut id = None;
+
+        if let Some(rest) = value.strip_prefix('[') {
+            let (assertion, after) = take_assertion(rest)?;
+            value = after;
+
+            // Text steps only have text assertions.
+            if index % 2 == 0 {
+                id = split_unescaped(&assertion, ';')
+                    .next()
+                    .map(unescape)
+                    .filter(|v| !v.is_empty());
+            }
+        }
+
+        steps.push(CfiStep { index, id });
+    }
+
+    (!steps.is_empty()).then_some((steps, value))
+}
+
+/// Returns the contents of the assertion and the value after its closing bracket.
+fn take_assertion(value: &str) -> Option<(String, &str)> {
+    let mut escaped = false;
+
+    for (index, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '^' => escaped = true,
+            ']' => return Some((value[..index].to_string(), &value[index + 1..])),
+            _ => (),
+        }
+    }
+
+    None
+}
+
+/// Split at the character unless it's escaped or inside of an assertion.
+fn split_unescaped(value: &str, separator: char) -> impl Iterator<Item = &str> {
+    let mut parts = Vec::new();
+
+    let mut escaped = false;
+    let mut in_assertion = false;
+    let mut start = 0;
+
+    for (index, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '^' => escaped = true,
+            '[' => in_assertion = true,
+            ']' => in_assertion = false,
+            _ if c == separator && !in_assertion => {
+                parts.push(&value[start..index]);
+                start = index + c.len_utf8();
+            }
+            _ => (),
+        }
+    }
+
+    parts.push(&value[start..]);
+
+    parts.into_iter()
+}
+
+fn unescape(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '^' {
+            output.extend(chars.next());
+        } else {
+            output.push(c);
+        }
+    }
+
+    output
+}
+
+fn escape(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        if matches!(c, '^' | '[' | ']' | '(' | ')' | ',' | ';' | '=') {
+            output.push('^');
+        }
+
+        output.push(c);
+    }
+
+    output
+}
+
+fn utf16_to_byte_offset(value: &str, offset: usize) -> usize {
+    let mut units = 0;
+
+    for (index, c) in value.char_indices() {
+        if units >= offset {
+            return index;
+        }
+
+        units += c.len_utf16();
+    }
+
+    value.len()
+}
+
+// Content Document
+
+enum Node {
+    Element(Element),
+    Text(String),
+}
+
+#[derive(Default)]
+struct Element {
+    name: String,
+    id: Option<String>,
+    children: Vec<Node>,
+}
+
+impl Element {
+    fn push_text(&mut self, value: &str) {
+        if let Some(Node::Text(text)) = self.children.last_mut() {
+            text.push_str(value);
+        } else {
+            self.children.push(Node::Text(value.to_string()));
+        }
+    }
+}
+
+/// Parse the (X)HTML into its elements and text. Returns the root `<html>` element.
+///
+/// Loosely written HTML is accepted the same way as [`crate::markup::html_to_xhtml`].
+fn parse_document(input: &str) -> Element {
+    let mut stack = vec![Element::default()];
+
+    let mut rest = input;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("<!--") {
+            let end = after.find("-->").map_or(after.len(), |v| v + 3);
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix("<![CDATA[") {
+            let end = after.find("]]>").unwrap_or(after.len());
+            stack.last_mut().unwrap().push_text(&after[..end]);
+            rest = &after[(end + 3).min(after.len())..];
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            let end = rest.find('>').map_or(rest.len(), |v| v + 1);
+            rest = &rest[end..];
+        } else if let Some(after) = rest.strip_prefix("</") {
+            let end = after.find('>').map_or(after.len(), |v| v + 1);
+            let name = after[..end].trim_end_matches('>').trim().to_lowercase();
+            rest = &after[end..];
+
+            if let Some(pos) = stack
+                .iter()
+                .rposition(|v| v.name == name)
+                .filter(|&v| v != 0)
+            {
+                while stack.len() > pos {
+                    let element = stack.pop().unwrap();
+                    stack
+                        .last_mut()
+                        .unwrap()
+                        .children
+                        .push(Node::Element(element));
+                }
+            }
+        } else if rest.starts_with('<') && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic())
+        {
+            let (tag, consumed) = parse_tag(rest);
+            rest = &rest[consumed..];
+
+            let mut element = Element {
+                id: tag
+                    .attributes
+                    .iter()
+                    .find(|(name, _)| name == "id")
+                    .map(|(_, value)| decode_entities(value)),
+                name: tag.name,
+                children: Vec::new(),
+            };
+
+            if tag.self_closing || VOID_ELEMENTS.contains(&element.name.as_str()) {
+                stack
+                    .last_mut()
+                    .unwrap()
+                    .children
+                    .push(Node::Element(element));
+            } else if element.name == "script" || element.name == "style" {
+                // Raw text until the closing tag.
+                let closing = format!("</{}", element.name);
+                let end = rest
+                    .to_lowercase()
+                    .find(&closing)
+                    .filter(|&v| rest.is_char_boundary(v))
+                    .unwrap_or(rest.len());
+
+                if end != 0 {
+                    element.push_text(&rest[..end]);
+                }
+
+                rest = &rest[end..];
+                let end = rest.find('>').map_or(rest.len(), |v| v + 1);
+                rest = &rest[end..];
+
+                stack
+                    .last_mut()
+                    .unwrap()
+                    .children
+                    .push(Node::Element(element));
+            } else {
+                stack.push(element);
+            }
+        } else {
+            let first_len = rest.chars().next().map_or(1, |c| c.len_utf8());
+            let end = rest[first_len..]
+                .find('<')
+                .map_or(rest.len(), |v| v + first_len);
+            stack
+                .last_mut()
+                .unwrap()
+                .push_text(&decode_entities(&rest[..end]));
+            rest = &rest[end..];
+        }
+    }
+
+    while stack.len() > 1 {
+        let element = stack.pop().unwrap();
+        stack
+            .last_mut()
+            .unwrap()
+            .children
+            .push(Node::Element(element));
+    }
+
+    let document = stack.swap_remove(0);
+
+    // Fragments without an <html> element are used as is.
+    if document
+        .children
+        .iter()
+        .filter(|v| matches!(v, Node::Element(_)))
+        .count()
+        == 1
+    {
+        document
+            .children
+            .into_iter()
+            .find_map(|v| match v {
+                Node::Element(v) => Some(v),
+                Node::Text(_) => None,
+            })
+            .unwrap()
+    } else {
+        document
+    }
+}
+
+fn find_body(root: &Element) -> Option<usize> {
+    root.children
+        .iter()
+        .position(|v| matches!(v, Node::Element(v) if v.name == "body"))
+}
+
+/// The byte length of the text inside of the node which the reader counts.
+fn text_len(node: &Node) -> usize {
+    match node {
+        Node::Text(text) if !text.trim().is_empty() => text.len(),
+        Node::Text(_) => 0,
+        // Scripts are removed before the chapter is shown.
+        Node::Element(element) if element.name == "script" => 0,
+        Node::Element(element) => element.children.iter().map(text_len).sum(),
+    }
+}
+
+/// Find the text node which contains the position. Returns the byte offset inside of it.
+fn find_position(element: &Element, position: &mut usize, path: &mut Vec<usize>) -> Option<usize> {
+    for (index, child) in element.children.iter().enumerate() {
+        let length = text_len(child);
+
+        if length <= *position {
+            *position -= length;
+            continue;
+        }
+
+        path.push(index);
+
+        match child {
+            Node::Text(_) => return Some(*position),
+            Node::Element(child) => return find_position(child, position, path),
+        }
+    }
+
+    None
+}
+
+/// Find the last text node. Returns its length as the offset.
+fn find_last_text(element: &Element, path: &mut Vec<usize>) -> Option<usize> {
+    let (index, child) = element
+        .children
+        .iter()
+        .enumerate()
+        .rev()
+        .find(|(_, v)| text_len(v) != 0)?;
+
+    path.push(index);
+
+    match child {
+        Node::Text(text) => Some(text.len()),
+        Node::Element(child) => find_last_text(child, path),
+    }
+}
+
+fn node_at<'a>(root: &'a Element, path: &[usize]) -> Option<&'a Node> {
+    let (&last, parents) = path.split_last()?;
+
+    let mut element = root;
+
+    for &index in parents {
+        match element.children.get(index)? {
+            Node::Element(child) => element = child,
+            Node::Text(_) => return None,
+        }
+    }
+
+    element.children.get(last)
+}
+
+/// Find the path of child indexes to the element with the id.
+fn find_id(element: &Element, id: &str, path: &mut Vec<usize>) -> bool {
+    for (index, child) in element.children.iter().enumerate() {
+        if let Node::Element(child) = child {
+            path.push(index);
+
+            if child.id.as_deref() == Some(id) || find_id(child, id, path) {
+                return true;
+            }
+
+            path.pop();
+        }
+    }
+
+    false
+}
+
+/// Convert the CFI steps into child indexes.
+///
+/// A text step without any text points to the element after it.
+fn find_path(root: &Element, steps: &[CfiStep]) -> Option<Vec<usize>> {
+    let mut path = Vec::new();
+    let mut element = root;
+
+    for (step_index, step) in steps.iter().enumerate() {
+        let is_last = step_index + 1 == steps.len();
+
+        // Elements before the step.
+        let preceding = (step.index.max(1) - 1) / 2;
+
+        let mut element_count = 0;
+        let mut found = None;
+
+        for (index, child) in element.children.iter().enumerate() {
+            let is_element = matches!(child, Node::Element(_));
+
+            // Text steps point to the first child after the preceding elements.
+            if (step.index % 2 == 1 && element_count == preceding)
+                || (step.index % 2 == 0 && is_element && element_count + 1 == step.index / 2)
+            {
+                found = Some(index);
+                break;
+            }
+
+            if is_element {
+                element_count += 1;
+            }
+        }
+
+        // Text after the last element.
+        if found.is_none() && step.index % 2 == 1 && element_count == preceding {
+            found = Some(element.children.len());
+        }
+
+        let is_valid = match (
+            step.id.as_deref(),
+            found.and_then(|v| element.children.get(v)),
+        ) {
+            (Some(id), Some(Node::Element(child))) => child.id.as_deref() == Some(id),
+            (Some(_), _) => false,
+            (None, _) => found.is_some(),
+        };
+
+        if is_valid {
+            path.extend(found);
+        } else {
+            // The id assertion takes priority over the index.
+            path.clear();
+
+            if !find_id(root, step.id.as_deref()?, &mut path) {
+                return None;
+            }
+        }
+
+        match node_at(root, &path) {
+            Some(Node::Element(child)) => element = child,
+            _ if is_last => (),
+            _ => return None,
+        }
+    }
+
+    Some(path)
+}
+
+/// Convert child indexes into CFI steps.
+fn to_steps(root: &Element, path: &[usize]) -> Vec<CfiStep> {
+    let mut steps = Vec::new();
+    let mut element = root;
+
+    for &index in path {
+        let preceding = element.children[..index]
+            .iter()
+            .filter(|v| matches!(v, Node::Element(_)))
+            .count();
+
+        match &element.children[index] {
+            Node::Element(child) => {
+                steps.push(CfiStep {
+                    index: (preceding + 1) * 2,
+                    id: child.id.clone(),
+                });
+
+                element = child;
+            }
+
+            Node::Text(_) => steps.push(CfiStep {
+                index: preceding * 2 + 1,
+                id: None,
+            }),
+        }
+    }
+
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHAPTER: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head><title>Chapter One</title></head>
+<body id="body01">
+    <h1>Chapter One</h1>
+    <p>It was a <em>dark</em> and stormy night;</p>
+    <p id="para03">the rain fell in torrents&#8212;except at occasional intervals.</p>
+    <img src="map.png"/>
+</body>
+</html>"#;
+
+    #[test]
+    fn parse_and_display() {
+        let cfi: Cfi = "epubcfi(/6/4[chap01ref]!/4[body01]/10[para05]/3:10)"
+            .parse()
+            .unwrap();
+
+        assert_eq!(cfi.spine_index, 1);
+        assert_eq!(cfi.spine_id.as_deref(), Some("chap01ref"));
+        assert_eq!(cfi.steps.len(), 3);
+        assert_eq!(cfi.steps[1].id.as_deref(), Some("para05"));
+        assert_eq!(cfi.steps[2].index, 3);
+        assert_eq!(cfi.offset, Some(10));
+        assert_eq!(
+            cfi.to_string(),
+            "epubcfi(/6/4[chap01ref]!/4[body01]/10[para05]/3:10)"
+        );
+
+        // Ranges use their start. Escaped characters are kept in the id.
+        let cfi: Cfi = "epubcfi(/6/2[a^,b]!/4/2,/1:5,/1:9)".parse().unwrap();
+        assert_eq!(cfi.spine_id.as_deref(), Some("a,b"));
+        assert_eq!(cfi.steps.len(), 3);
+        assert_eq!(cfi.offset, Some(5));
+        assert_eq!(cfi.to_string(), "epubcfi(/6/2[a^,b]!/4/2/1:5)");
+
+        assert!("/6/4!/4".parse::<Cfi>().is_err());
+        assert!("epubcfi(/6/4)".parse::<Cfi>().is_err());
+    }
+
+    #[test]
+    fn generate_and_resolve() {
+        // "Chapter One" (11) + "It was a " (9) + "dark" (4) + " and stormy night;" (18)
+        let cfi = Cfi::from_position(0, Some("c1".into()), CHAPTER, 26);
+        assert_eq!(cfi.to_string(), "epubcfi(/6/2[c1]!/4[body01]/4/3:2)");
+        assert_eq!(cfi.resolve(CHAPTER), Some(26));
+
+        // The em dash is three bytes and a single UTF-16 code unit.
+        let position = 11 + 9 + 4 + 18 + 28;
+        let cfi = Cfi::from_position(0, None, CHAPTER, position);
+        assert_eq!(cfi.to_string(), "epubcfi(/6/2!/4[body01]/6[para03]/1:26)");
+        assert_eq!(cfi.resolve(CHAPTER), Some(position));
+
+        // Past the end.
+        let cfi = Cfi::from_position(0, None, CHAPTER, 10_000);
+        assert_eq!(cfi.resolve(CHAPTER), Some(11 + 9 + 4 + 18 + 59));
+
+        // The id assertion is used once the paragraph moved.
+        let moved = CHAPTER.replace("<h1>", "<p>Note</p><h1>");
+        let cfi: Cfi = "epubcfi(/6/2!/4/6[para03]/1:4)".parse().unwrap();
+        assert_eq!(cfi.resolve(&moved), Some(4 + 11 + 9 + 4 + 18 + 4));
+
+        // Elements resolve to the text before them.
+        let cfi: Cfi = "epubcfi(/6/2!/4/8)".parse().unwrap();
+        assert_eq!(cfi.resolve(CHAPTER), Some(11 + 9 + 4 + 18 + 59));
+
+        let cfi: Cfi = "epubcfi(/6/2!/4/20)".parse().unwrap();
+        assert_eq!(cfi.resolve(CHAPTER), None);
+    }
+}
@@ -5,15 +5,23 @@ use std::{
 
 use serde::{Deserialize, Serialize};
 
-use binstall_zip::ZipArchive;
+use binstall_zip::{result::ZipError, ZipArchive};
+use sha1::{Digest, Sha1};
+use xml::reader::XmlEvent;
 
 use crate::Result;
 
+/// https://www.w3.org/TR/epub-33/#sec-font-obfuscation
+const IDPF_FONT_ALGORITHM: &str = "http://www.idpf.org/2008/embedding";
+const ADOBE_FONT_ALGORITHM: &str = "http://ns.adobe.com/pdf/enc#RC";
+
 pub struct AbsContainer<R: Read + Seek> {
     pub archive: ZipArchive<R>,
 
     pub metainf_container: MetaInfContainer,
-    // encryption, manifest, metadata, rights, signature
+    /// Resources which are obfuscated or encrypted.
+    pub encryption: Option<Encryption>,
+    // manifest, metadata, rights, signature
     // https://www.w3.org/publishing/epub3/epub-ocf.html#sec-container-metainf-encryption.xml
 }
 
@@ -48,9 +56,16 @@ impl<R: Read + Seek> AbsContainer<R> {
             serde_xml_rs::from_reader(file)?
         };
 
+        let encryption = match archive.by_name("META-INF/encryption.xml") {
+            Ok(file) => Some(Encryption::parse(file)?),
+            Err(ZipError::FileNotFound) => None,
+            Err(e) => return Err(e.into()),
+        };
+
         Ok(Self {
             archive,
             metainf_container,
+            encryption,
         })
     }
 
@@ -63,6 +78,142 @@ impl<R: Read + Seek> AbsContainer<R> {
     }
 }
 
+/// META-INF/encryption.xml
+///
+/// https://www.w3.org/TR/epub-33/#sec-container-metainf-encryption.xml
+#[derive(Debug, Default)]
+pub struct Encryption {
+    pub items: Vec<EncryptedItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptedItem {
+    /// Relative to the root of the container.
+    pub path: String,
+    pub method: EncryptionMethod,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncryptionMethod {
+    /// Font obfuscation keyed with the package unique identifier.
+    IdpfFont,
+    /// Font obfuscation keyed with the UUID identifier.
+    AdobeFont,
+    /// Anything else is DRM which we're unable to read.
+    Other(String),
+}
+
+impl Encryption {
+    pub fn parse<R: Read>(value: R) -> Result<Self> {
+        let mut this = Self::default();
+
+        let mut method = None;
+
+        for event in xml::EventReader::new(value) {
+            match event? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => {
+                    let get = |name: &str| {
+                        attributes
+                            .iter()
+                            .find(|v| v.name.local_name == name)
+                            .map(|v| v.value.trim().to_string())
+                    };
+
+                    match name.local_name.as_str() {
+                        "EncryptedData" => method = None,
+
+                        "EncryptionMethod" => {
+                            method = get("Algorithm").map(|v| match v.as_str() {
+                                IDPF_FONT_ALGORITHM => EncryptionMethod::IdpfFont,
+                                ADOBE_FONT_ALGORITHM => EncryptionMethod::AdobeFont,
+                                _ => EncryptionMethod::Other(v),
+                            });
+                        }
+
+                        "CipherReference" => {
+                            if let Some(path) = get("URI") {
+                                this.items.push(EncryptedItem {
+                                    path: urlencoding::decode(&path)
+                                        .map(|v| v.into_owned())
+                                        .unwrap_or(path),
+                                    method: method
+                                        .clone()
+                                        .unwrap_or_else(|| EncryptionMethod::Other(String::new())),
+                                });
+                            }
+                        }
+
+                        _ => (),
+                    }
+                }
+
+                XmlEvent::EndDocument => break,
+
+                _ => (),
+            }
+        }
+
+        Ok(this)
+    }
+
+    pub fn get_item_by_path(&self, path: &str) -> Option<&EncryptedItem> {
+        self.items
+            .iter()
+            .find(|v| v.path == path || v.path.trim_start_matches('/') == path)
+    }
+}
+
+/// Undo IDPF font obfuscation. The first 1040 bytes are XORed with the SHA-1 of the unique identifier.
+pub fn deobfuscate_idpf_font(data: &mut [u8], unique_identifier: &str) {
+    let key = Sha1::digest(
+        unique_identifier
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '\t' | '\r' | '\n'))
+            .collect::<String>()
+            .as_bytes(),
+    );
+
+    let len = data.len().min(1040);
+    xor_with_key(&mut data[..len], &key);
+}
+
+/// Undo Adobe font obfuscation. The first 1024 bytes are XORed with the bytes of the UUID.
+///
+/// Returns false if the identifier isn't a UUID.
+pub fn deobfuscate_adobe_font(data: &mut [u8], identifier: &str) -> bool {
+    let hex = identifier
+        .trim()
+        .trim_start_matches("urn:uuid:")
+        .chars()
+        .filter(|c| *c != '-')
+        .collect::<String>();
+
+    if hex.len() != 32 {
+        return false;
+    }
+
+    let Some(key) = (0..32)
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect::<Option<Vec<_>>>()
+    else {
+        return false;
+    };
+
+    let len = data.len().min(1024);
+    xor_with_key(&mut data[..len], &key);
+
+    true
+}
+
+fn xor_with_key(data: &mut [u8], key: &[u8]) {
+    for (byte, key) in data.iter_mut().zip(key.iter().cycle()) {
+        *byte ^= key;
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MetaInfContainer {
     pub version: String,
@@ -110,3 +261,61 @@ impl<T> DerefMut for RootfilesVec<T> {
         self.0.deref_mut()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encryption_and_deobfuscation() {
+        let xml = br#"<?xml version="1.0" encoding="UTF-8"?>
+<encryption xmlns="urn:oasis:names:tc:opendocument:xmlns:container" xmlns:enc="http://www.w3.org/2001/04/xmlenc#">
+    <enc:EncryptedData>
+        <enc:EncryptionMethod Algorithm="http://www.idpf.org/2008/embedding"/>
+        <enc:CipherData><enc:CipherReference URI="OEBPS/Fonts/My%20Font.otf"/></enc:CipherData>
+    </enc:EncryptedData>
+    <enc:EncryptedData>
+        <enc:EncryptionMethod Algorithm="http://ns.adobe.com/pdf/enc#RC"/>
+        <enc:CipherData><enc:CipherReference URI="OEBPS/Fonts/other.ttf"/></enc:CipherData>
+    </enc:EncryptedData>
+    <enc:EncryptedData>
+        <enc:EncryptionMethod Algorithm="http://www.w3.org/2001/04/xmlenc#aes128-cbc"/>
+        <enc:CipherData><enc:CipherReference URI="OEBPS/Text/chapter1.xhtml"/></enc:CipherData>
+    </enc:EncryptedData>
+</encryption>"#;
+
+        let encryption = Encryption::parse(&xml[..]).unwrap();
+
+        assert_eq!(encryption.items.len(), 3);
+        assert_eq!(
+            encryption
+                .get_item_by_path("OEBPS/Fonts/My Font.otf")
+                .map(|v| &v.method),
+            Some(&EncryptionMethod::IdpfFont)
+        );
+        assert_eq!(encryption.items[1].method, EncryptionMethod::AdobeFont);
+        assert!(matches!(
+            encryption.items[2].method,
+            EncryptionMethod::Other(_)
+        ));
+
+        let font = (0..2000).map(|v| v as u8).collect::<Vec<_>>();
+
+        // Obfuscating is the same as de-obfuscating.
+        let mut data = font.clone();
+        deobfuscate_idpf_font(&mut data, "urn:uuid:12345");
+        assert_ne!(data[..1040], font[..1040]);
+        assert_eq!(data[1040..], font[1040..]);
+        deobfuscate_idpf_font(&mut data, " urn:uuid:123\n45 ");
+        assert_eq!(data, font);
+
+        let uuid = "urn:uuid:0123e4a5-6789-abcd-ef01-23456789abcd";
+        assert!(deobfuscate_adobe_font(&mut data, uuid));
+        assert_eq!(data[0], font[0] ^ 0x01);
+        assert_eq!(data[1024..], font[1024..]);
+        assert!(deobfuscate_adobe_font(&mut data, uuid));
+        assert_eq!(data, font);
+
+        assert!(!deobfuscate_adobe_font(&mut data, "isbn:12345"));
+    }
+}
@@ -138,6 +138,8 @@ pub struct NavPoint {
 
     pub nav_label: NcxLabel,
     pub content: NcxContent,
+
+    pub children: Vec<NavPoint>,
 }
 
 impl Parser for NavPoint {
@@ -151,6 +153,10 @@ impl Parser for NavPoint {
                 self.nav_label.text = child.take_first_children().unwrap().value.unwrap();
             } else if child.name.local_name == "content" {
                 self.content.src = child.take_attribute("src").unwrap();
+            } else if child.name.local_name == "navPoint" {
+                let mut point = NavPoint::default();
+                point.parse(child)?;
+                self.children.push(point);
             }
         }
 
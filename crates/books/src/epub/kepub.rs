@@ -0,0 +1,294 @@
+// https://github.com/pgaskin/kepubify/blob/master/kepub/transform.go
+// https://pgaskin.net/kepubify/ (KEPUB format notes)
+
+use std::borrow::Cow;
+
+use xml::{
+    reader::XmlEvent as ReaderEvent, writer::XmlEvent as WriterEvent, EmitterConfig, EventWriter,
+};
+
+use crate::Result;
+
+/// Elements which start a new paragraph for the `kobo.{paragraph}.{segment}` span ids.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "li",
+    "dt",
+    "dd",
+    "blockquote",
+    "div",
+    "td",
+    "th",
+    "caption",
+    "figcaption",
+    "pre",
+];
+
+/// Elements whose text isn't wrapped.
+const SKIPPED_ELEMENTS: &[&str] = &["script", "style", "svg", "math", "textarea"];
+
+/// Kobo paginates the `#book-inner` div. Without this it'd inherit margins meant for the body.
+const KOBO_STYLE: &str = "div#book-inner { margin-top: 0; margin-bottom: 0; }";
+
+/// Convert an XHTML content document into its KEPUB form.
+///
+/// Every sentence and image is wrapped in a `<span class="koboSpan">` which Kobo devices use for
+/// highlights, reading statistics and page turns. The contents of the body are placed in the
+/// `book-columns` and `book-inner` divs.
+pub fn convert_content_document(input: &[u8]) -> Result<Vec<u8>> {
+    // Already converted.
+    if contains_bytes(input, b"koboSpan") {
+        return Ok(input.to_vec());
+    }
+
+    let reader = xml::ParserConfig::new()
+        .add_entity("nbsp", "\u{a0}")
+        .add_entity("copy", "©")
+        .add_entity("reg", "®")
+        .create_reader(input);
+
+    let mut output = Vec::new();
+    let mut writer = EmitterConfig::new()
+        .perform_indent(false)
+        .create_writer(&mut output);
+
+    let mut spans = SpanCounter::default();
+
+    let mut is_inside_body = false;
+    // How deep we are inside of an element whose text isn't wrapped.
+    let mut skip_depth = 0;
+    let mut is_image_wrapped = false;
+
+    for event in reader {
+        match event? {
+            ReaderEvent::StartElement {
+                name,
+                attributes,
+                namespace,
+            } => {
+                let local_name = name.local_name.to_lowercase();
+
+                if skip_depth != 0 || SKIPPED_ELEMENTS.contains(&local_name.as_str()) {
+                    skip_depth += 1;
+                } else if is_inside_body && BLOCK_ELEMENTS.contains(&local_name.as_str()) {
+                    spans.next_paragraph();
+                } else if is_inside_body && local_name == "img" {
+                    write_span_start(&mut writer, spans.next_segment())?;
+                    is_image_wrapped = true;
+                }
+
+                writer.write(WriterEvent::StartElement {
+                    attributes: Cow::Owned(attributes.iter().map(|v| v.borrow()).collect()),
+                    name: name.borrow(),
+                    namespace: Cow::Owned(namespace),
+                })?;
+
+                if local_name == "body" {
+                    is_inside_body = true;
+
+                    writer.write(WriterEvent::start_element("div").attr("id", "book-columns"))?;
+                    writer.write(WriterEvent::start_element("div").attr("id", "book-inner"))?;
+                }
+            }
+
+            ReaderEvent::EndElement { name } => {
+                let local_name = name.local_name.to_lowercase();
+
+                if skip_depth != 0 {
+                    skip_depth -= 1;
+                }
+
+                match local_name.as_str() {
+                    "head" => {
+                        writer.write(
+                            WriterEvent::start_element("style")
+                                .attr("type", "text/css")
+                                .attr("class", "kobostylehacks"),
+                        )?;
+                        writer.write(WriterEvent::characters(KOBO_STYLE))?;
+                        writer.write(WriterEvent::end_element())?;
+                    }
+
+                    "body" => {
+                        is_inside_body = false;
+
+                        // book-inner and book-columns
+                        writer.write(WriterEvent::end_element())?;
+                        writer.write(WriterEvent::end_element())?;
+                    }
+
+                    _ => (),
+                }
+
+                writer.write(WriterEvent::end_element())?;
+
+                if local_name == "img" && is_image_wrapped {
+                    is_image_wrapped = false;
+                    writer.write(WriterEvent::end_element())?;
+                }
+            }
+
+            ReaderEvent::Characters(text) if is_inside_body && skip_depth == 0 => {
+                for sentence in split_sentences(&text) {
+                    if sentence.trim().is_empty() {
+                        writer.write(WriterEvent::characters(sentence))?;
+                    } else {
+                        write_span_start(&mut writer, spans.next_segment())?;
+                        writer.write(WriterEvent::characters(sentence))?;
+                        writer.write(WriterEvent::end_element())?;
+                    }
+                }
+            }
+
+            ReaderEvent::EndDocument => break,
+
+            v => {
+                if let Some(v) = v.as_writer_event() {
+                    writer.write(v)?;
+                }
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+fn write_span_start(writer: &mut EventWriter<&mut Vec<u8>>, id: String) -> Result<()> {
+    writer.write(
+        WriterEvent::start_element("span")
+            .attr("class", "koboSpan")
+            .attr("id", &id),
+    )?;
+
+    Ok(())
+}
+
+fn contains_bytes(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|v| v == needle)
+}
+
+#[derive(Default)]
+struct SpanCounter {
+    paragraph: usize,
+    segment: usize,
+}
+
+impl SpanCounter {
+    fn next_paragraph(&mut self) {
+        self.paragraph += 1;
+        self.segment = 0;
+    }
+
+    /// Returns the id of the next span. Eg. "kobo.3.1"
+    fn next_segment(&mut self) -> String {
+        // Text which is directly inside of the body.
+        if self.paragraph == 0 {
+            self.paragraph = 1;
+        }
+
+        self.segment += 1;
+
+        format!("kobo.{}.{}", self.paragraph, self.segment)
+    }
+}
+
+/// Split text after the sentence ending punctuation. The whitespace after a sentence stays with it.
+fn split_sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+
+    let mut start = 0;
+    let mut is_ending = false;
+    let mut is_after_whitespace = false;
+
+    for (index, ch) in text.char_indices() {
+        if is_after_whitespace && !ch.is_whitespace() {
+            sentences.push(&text[start..index]);
+            start = index;
+            is_ending = false;
+            is_after_whitespace = false;
+        }
+
+        if matches!(ch, '.' | '!' | '?' | '…') {
+            is_ending = true;
+        } else if is_ending && ch.is_whitespace() {
+            is_after_whitespace = true;
+        } else if !matches!(ch, '"' | '\'' | '”' | '’' | '»' | ')' | ']') {
+            // Closing quotes and brackets still belong to the sentence.
+            is_ending = false;
+        }
+    }
+
+    if start < text.len() {
+        sentences.push(&text[start..]);
+    }
+
+    sentences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sentences() {
+        assert_eq!(
+            split_sentences("One. Two? “Three!” Four… five"),
+            ["One. ", "Two? ", "“Three!” ", "Four… ", "five"]
+        );
+        assert_eq!(
+            split_sentences("Mr.Smith went  home. "),
+            ["Mr.Smith went  home. "]
+        );
+        assert_eq!(split_sentences("  "), ["  "]);
+    }
+
+    #[test]
+    fn content_document() {
+        let page = br#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head><title>Chapter 1</title></head>
+<body>
+<h1>Chapter 1</h1>
+<p>It was late. The <em>rain</em> had stopped.</p>
+<p><img src="map.png" alt="Map"/></p>
+<style>p { color: red; }</style>
+</body>
+</html>"#;
+
+        let output = String::from_utf8(convert_content_document(page).unwrap()).unwrap();
+
+        assert!(
+            output.contains(r#"<body><div id="book-columns"><div id="book-inner">"#),
+            "{output}"
+        );
+        assert!(
+            output.contains(r#"<h1><span class="koboSpan" id="kobo.1.1">Chapter 1</span></h1>"#),
+            "{output}"
+        );
+        assert!(output.contains(r#"<p><span class="koboSpan" id="kobo.2.1">It was late. </span><span class="koboSpan" id="kobo.2.2">The </span><em><span class="koboSpan" id="kobo.2.3">rain</span></em>"#), "{output}");
+        assert!(
+            output.contains(
+                r#"<span class="koboSpan" id="kobo.3.1"><img src="map.png" alt="Map" /></span>"#
+            ),
+            "{output}"
+        );
+        assert!(
+            output.contains("<style>p { color: red; }</style>"),
+            "{output}"
+        );
+        assert!(output.contains(r#"</div></div></body>"#), "{output}");
+        assert!(output.contains("kobostylehacks"), "{output}");
+
+        // Converting twice doesn't wrap the spans again.
+        assert_eq!(
+            convert_content_document(output.as_bytes()).unwrap(),
+            output.as_bytes()
+        );
+    }
+}
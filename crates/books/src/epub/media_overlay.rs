@@ -0,0 +1,214 @@
+// https://www.w3.org/TR/epub-33/#sec-media-overlays
+// https://www.w3.org/TR/SMIL3/smil-timing.html#q22
+
+use std::io::Read;
+
+use xml::reader::XmlEvent;
+
+use super::nav_document::resolve_href;
+use crate::Result;
+
+/// The Media Overlay (SMIL) of a chapter. A timeline of text fragments and the audio clips which narrate them.
+#[derive(Debug, Default)]
+pub struct MediaOverlay {
+    pub clips: Vec<OverlayClip>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverlayClip {
+    /// Relative to the package document. Includes the fragment.
+    pub text: String,
+    /// Relative to the package document.
+    pub audio: String,
+    /// In milliseconds.
+    pub begin: u64,
+    /// In milliseconds. None if the clip plays until the end of the audio file.
+    pub end: Option<u64>,
+}
+
+impl OverlayClip {
+    /// The element id the clip is narrating.
+    pub fn fragment(&self) -> Option<&str> {
+        self.text.split_once('#').map(|(_, v)| v)
+    }
+}
+
+impl MediaOverlay {
+    /// `path` is the location of the SMIL file relative to the package document.
+    pub fn parse<R: Read>(value: R, path: &str) -> Result<Self> {
+        let mut this = Self::default();
+
+        let reader = xml::EventReader::new(value);
+
+        // The text and audio of the <par> we're inside of.
+        let mut text = None;
+        let mut audio = None;
+
+        for event in reader {
+            match event? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => match name.local_name.as_str() {
+                    "par" => {
+                        text = None;
+                        audio = None;
+                    }
+
+                    "text" => {
+                        text = attributes
+                            .into_iter()
+                            .find(|v| v.name.local_name == "src")
+                            .map(|v| resolve_href(path, &v.value));
+                    }
+
+                    "audio" => {
+                        let mut src = None;
+                        let mut begin = 0;
+                        let mut end = None;
+
+                        for attr in attributes {
+                            match attr.name.local_name.as_str() {
+                                "src" => src = Some(resolve_href(path, &attr.value)),
+                                "clipBegin" => {
+                                    begin = parse_clock_value(&attr.value).unwrap_or_default()
+                                }
+                                "clipEnd" => end = parse_clock_value(&attr.value),
+                                _ => (),
+                            }
+                        }
+
+                        audio = src.map(|src| (src, begin, end));
+                    }
+
+                    _ => (),
+                },
+
+                XmlEvent::EndElement { name } if name.local_name == "par" => {
+                    if let Some((text, (audio, begin, end))) = text.take().zip(audio.take()) {
+                        this.clips.push(OverlayClip {
+                            text,
+                            audio,
+                            begin,
+                            end,
+                        });
+                    }
+                }
+
+                XmlEvent::EndDocument => break,
+
+                _ => (),
+            }
+        }
+
+        Ok(this)
+    }
+}
+
+/// Parse a SMIL clock value into milliseconds.
+///
+/// Eg. "0:00:01.500", "00:01.5", "1.5s", "1500ms", "0.5min", "1h" or "1.5"
+fn parse_clock_value(value: &str) -> Option<u64> {
+    let value = value.trim();
+
+    let seconds = if value.contains(':') {
+        let mut parts = value.rsplit(':');
+
+        let seconds = parts.next()?.parse::<f64>().ok()?;
+        let minutes = parts.next()?.parse::<f64>().ok()?;
+        let hours = parts.next().map_or(Some(0.0), |v| v.parse::<f64>().ok())?;
+
+        if parts.next().is_some() {
+            return None;
+        }
+
+        hours * 3600.0 + minutes * 60.0 + seconds
+    } else if let Some(value) = value.strip_suffix("ms") {
+        value.parse::<f64>().ok()? / 1000.0
+    } else if let Some(value) = value.strip_suffix("min") {
+        value.parse::<f64>().ok()? * 60.0
+    } else if let Some(value) = value.strip_suffix('h') {
+        value.parse::<f64>().ok()? * 3600.0
+    } else {
+        value
+            .strip_suffix('s')
+            .unwrap_or(value)
+            .parse::<f64>()
+            .ok()?
+    };
+
+    (seconds >= 0.0).then(|| (seconds * 1000.0).round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_values() {
+        assert_eq!(parse_clock_value("0:00:01.500"), Some(1_500));
+        assert_eq!(parse_clock_value("1:02:03"), Some(3_723_000));
+        assert_eq!(parse_clock_value("02:30.25"), Some(150_250));
+        assert_eq!(parse_clock_value("1.5s"), Some(1_500));
+        assert_eq!(parse_clock_value("250ms"), Some(250));
+        assert_eq!(parse_clock_value("0.5min"), Some(30_000));
+        assert_eq!(parse_clock_value("1h"), Some(3_600_000));
+        assert_eq!(parse_clock_value("12.345"), Some(12_345));
+        assert_eq!(parse_clock_value("abc"), None);
+    }
+
+    #[test]
+    fn chapter_timeline() {
+        let smil = br##"<?xml version="1.0" encoding="UTF-8"?>
+<smil xmlns="http://www.w3.org/ns/SMIL" xmlns:epub="http://www.idpf.org/2007/ops" version="3.0">
+<body>
+    <seq epub:textref="../Text/chapter1.xhtml" epub:type="bodymatter chapter">
+        <par id="p1">
+            <text src="../Text/chapter1.xhtml#s1"/>
+            <audio src="../Audio/chapter1.mp3" clipBegin="0:00:00.000" clipEnd="0:00:02.250"/>
+        </par>
+        <seq>
+            <par id="p2">
+                <audio src="../Audio/chapter1.mp3" clipBegin="2.25s" clipEnd="4800ms"/>
+                <text src="../Text/chapter1.xhtml#s2"/>
+            </par>
+        </seq>
+        <par id="p3">
+            <text src="../Text/chapter1.xhtml#img1"/>
+        </par>
+        <par id="p4">
+            <text src="../Text/chapter1.xhtml#s3"/>
+            <audio src="../Audio/chapter1b.mp3"/>
+        </par>
+    </seq>
+</body>
+</smil>"##;
+
+        let overlay = MediaOverlay::parse(&smil[..], "Overlays/chapter1.smil").unwrap();
+
+        assert_eq!(
+            overlay.clips,
+            [
+                OverlayClip {
+                    text: String::from("Text/chapter1.xhtml#s1"),
+                    audio: String::from("Audio/chapter1.mp3"),
+                    begin: 0,
+                    end: Some(2_250),
+                },
+                OverlayClip {
+                    text: String::from("Text/chapter1.xhtml#s2"),
+                    audio: String::from("Audio/chapter1.mp3"),
+                    begin: 2_250,
+                    end: Some(4_800),
+                },
+                OverlayClip {
+                    text: String::from("Text/chapter1.xhtml#s3"),
+                    audio: String::from("Audio/chapter1b.mp3"),
+                    begin: 0,
+                    end: None,
+                },
+            ]
+        );
+
+        assert_eq!(overlay.clips[1].fragment(), Some("s2"));
+    }
+}
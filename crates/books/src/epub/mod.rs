@@ -8,33 +8,48 @@
 use std::{
     borrow::Cow,
     fs::File,
-    io::{Cursor, Read},
+    io::{Cursor, Read, Seek, Write},
     path::{Path, PathBuf},
 };
 
-use binstall_zip::ZipArchive;
+use binstall_zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
 use common_local::sort::filename_sort;
 
+pub mod cfi;
 pub mod container;
 mod file_ncx;
+pub mod kepub;
+mod media_overlay;
 mod modifier;
+mod nav_document;
 mod package_document;
+mod package_writer;
+mod rendition;
+mod sanitize;
 
-use crate::{BookSearch, Result};
+use crate::{markup, BookSearch, ChapterText, Error, ReadSeek, Result, TocItem};
 
-use self::file_ncx::FileNCX;
+use self::{
+    cfi::Cfi,
+    file_ncx::{FileNCX, NavPoint},
+};
 
 use super::Book;
 use container::*;
 
+pub use media_overlay::{MediaOverlay, OverlayClip};
 pub use modifier::*;
+pub use nav_document::{NavDocument, NavItem};
 pub use package_document::*;
+pub use package_writer::{MetadataUpdate, COVER_ID};
+pub use rendition::*;
+pub use sanitize::{sanitize_css, SanitizeElement, SanitizeOptions};
 
 // TODO: Ignore specific file entries? Eg. "META-INF/calibre_bookmarks.txt"
 // Would allow for better file hashing to compare against. Eg. One zip may have it, the other may not even though they're the same.
 
 pub struct EpubBook {
-    pub container: AbsContainer<File>,
+    pub container: AbsContainer<Box<dyn ReadSeek>>,
     pub package: PackageDocument,
 
     root_file_dir: PathBuf,
@@ -69,6 +84,313 @@ impl EpubBook {
             .find(|mani_item| mani_item.id == spine_item.idref)
     }
 
+    /// Parse the EPUB 3 Navigation Document if the manifest has one.
+    pub fn get_navigation(&mut self) -> Result<Option<NavDocument>> {
+        let Some(path) = self
+            .package
+            .manifest
+            .items
+            .iter()
+            .find(|v| {
+                v.properties
+                    .as_deref()
+                    .map_or(false, |v| v.split_whitespace().any(|v| v == "nav"))
+            })
+            .map(|v| v.href.clone())
+        else {
+            return Ok(None);
+        };
+
+        let value = self.get_path_contents(&path)?;
+
+        Ok(Some(NavDocument::parse(Cursor::new(value), &path)?))
+    }
+
+    /// The landmarks of the Navigation Document along with their epub:type. Eg. "bodymatter", "toc"
+    pub fn get_landmarks(&mut self) -> Result<Vec<(Option<String>, TocItem)>> {
+        let Some(nav) = self.get_navigation()? else {
+            return Ok(Vec::new());
+        };
+
+        Ok(nav
+            .landmarks
+            .into_iter()
+            .filter_map(|mut item| {
+                let type_of = item.type_of.take();
+
+                Some((type_of, self.create_toc_items(vec![item]).pop()?))
+            })
+            .collect())
+    }
+
+    /// The page list of the Navigation Document. Maps the page numbers of the print edition.
+    pub fn get_page_list(&mut self) -> Result<Vec<TocItem>> {
+        let Some(nav) = self.get_navigation()? else {
+            return Ok(Vec::new());
+        };
+
+        Ok(self.create_toc_items(nav.page_list))
+    }
+
+    /// Parse the Media Overlay of the current chapter if it has one.
+    pub fn get_media_overlay(&mut self) -> Result<Option<MediaOverlay>> {
+        let Some(path) = self
+            .get_manifest_item_by_spine(self.chapter)
+            .and_then(|v| v.media_overlay.as_deref())
+            .and_then(|id| self.package.manifest.get_item_by_id(id))
+            .map(|v| v.href.clone())
+        else {
+            return Ok(None);
+        };
+
+        let value = self.get_path_contents(&path)?;
+
+        Ok(Some(MediaOverlay::parse(Cursor::new(value), &path)?))
+    }
+
+    pub fn has_media_overlays(&self) -> bool {
+        self.package
+            .manifest
+            .items
+            .iter()
+            .any(|v| v.media_overlay.is_some())
+    }
+
+    /// Create a CFI for the position inside of the chapter. See [`Cfi::resolve`] for how the position is counted.
+    pub fn generate_cfi(&mut self, chapter: usize, position: usize) -> Result<Cfi> {
+        let spine_id = self
+            .package
+            .spine
+            .items
+            .get(chapter)
+            .ok_or(Error::MissingValueFor("spine item"))?
+            .id
+            .clone();
+
+        let document = self.read_spine_item(chapter)?;
+
+        Ok(Cfi::from_position(chapter, spine_id, &document, position))
+    }
+
+    /// Resolve the CFI into the chapter and the position inside of it.
+    pub fn resolve_cfi(&mut self, cfi: &Cfi) -> Result<(usize, usize)> {
+        let items = &self.package.spine.items;
+
+        // Some reading systems assert the idref instead of the id.
+        let chapter = match cfi.spine_id.as_deref() {
+            Some(id)
+                if items
+                    .get(cfi.spine_index)
+                    .map_or(true, |v| v.id.as_deref() != Some(id) && v.idref != id) =>
+            {
+                items
+                    .iter()
+                    .position(|v| v.id.as_deref() == Some(id) || v.idref == id)
+                    .unwrap_or(cfi.spine_index)
+            }
+
+            _ => cfi.spine_index,
+        };
+
+        if chapter >= items.len() {
+            return Err(Error::InvalidCfi(cfi.to_string()));
+        }
+
+        let document = self.read_spine_item(chapter)?;
+
+        let position = cfi
+            .resolve(&document)
+            .ok_or_else(|| Error::InvalidCfi(cfi.to_string()))?;
+
+        Ok((chapter, position))
+    }
+
+    fn read_spine_item(&mut self, index: usize) -> Result<String> {
+        let href = self
+            .get_manifest_item_by_spine(index)
+            .map(|v| v.href.clone())
+            .ok_or(Error::MissingValueFor("spine item"))?;
+
+        Ok(String::from_utf8_lossy(&self.get_path_contents(&href)?).into_owned())
+    }
+
+    /// The class to give the element which is currently being narrated. Set by the `media:active-class` meta.
+    pub fn get_media_overlay_active_class(&self) -> Option<&str> {
+        self.package
+            .metadata
+            .meta_items
+            .iter()
+            .find(|v| v.property == "media:active-class")
+            .and_then(|v| v.value.as_deref())
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+    }
+
+    /// Write a copy of the EPUB with the updated metadata.
+    ///
+    /// The `mimetype` file is written first and uncompressed as the OCF requires.
+    pub fn write_with_metadata<W: Write + Seek>(
+        &mut self,
+        update: &MetadataUpdate,
+        writer: W,
+    ) -> Result<()> {
+        self.write_copy(update, false, writer)
+    }
+
+    /// Write a copy of the EPUB as a KEPUB for Kobo devices. The metadata is updated as well.
+    ///
+    /// The content documents are converted with [`kepub::convert_content_document`].
+    pub fn write_kepub<W: Write + Seek>(
+        &mut self,
+        update: &MetadataUpdate,
+        writer: W,
+    ) -> Result<()> {
+        self.write_copy(update, true, writer)
+    }
+
+    fn write_copy<W: Write + Seek>(
+        &mut self,
+        update: &MetadataUpdate,
+        is_kepub: bool,
+        writer: W,
+    ) -> Result<()> {
+        let package_path = self.container.root_files()[0].full_path.clone();
+
+        let package = package_writer::rewrite_package(
+            self.container.archive.by_name(&package_path)?,
+            &self.package,
+            update,
+        )?;
+
+        // Full paths of the XHTML content documents.
+        let content_paths = if is_kepub {
+            self.package
+                .manifest
+                .items
+                .iter()
+                .filter(|v| v.media_type == "application/xhtml+xml")
+                .filter_map(|v| urlencoding::decode(&v.href).ok())
+                .map(|href| {
+                    self.root_file_dir
+                        .join(href.as_ref())
+                        .display()
+                        .to_string()
+                        .replace('\\', "/")
+                })
+                .collect::<Vec<_>>()
+        } else {
+            Vec::new()
+        };
+
+        let cover_path = update.cover_href().map(|href| {
+            self.root_file_dir
+                .join(href)
+                .display()
+                .to_string()
+                .replace('\\', "/")
+        });
+
+        let mut zip = ZipWriter::new(writer);
+
+        zip.start_file(
+            "mimetype",
+            FileOptions::default().compression_method(CompressionMethod::Stored),
+        )?;
+        zip.write_all(b"application/epub+zip")?;
+
+        for index in 0..self.container.archive.len() {
+            let file = self.container.archive.by_index_raw(index)?;
+
+            if file.name() == "mimetype"
+                || file.name() == package_path
+                || Some(file.name()) == cover_path.as_deref()
+            {
+                continue;
+            }
+
+            if content_paths.iter().any(|v| v == file.name()) {
+                let name = file.name().to_string();
+                drop(file);
+
+                let mut contents = Vec::new();
+                self.container
+                    .archive
+                    .by_name(&name)?
+                    .read_to_end(&mut contents)?;
+
+                // A chapter we can't parse is still better sent as-is than failing the whole book.
+                let converted = match kepub::convert_content_document(&contents) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        tracing::warn!("Unable to convert {name} into KEPUB: {e}");
+                        contents
+                    }
+                };
+
+                zip.start_file(name, FileOptions::default())?;
+                zip.write_all(&converted)?;
+
+                continue;
+            }
+
+            zip.raw_copy_file(file)?;
+        }
+
+        zip.start_file(package_path, FileOptions::default())?;
+        zip.write_all(&package)?;
+
+        if let Some((path, (_, contents))) = cover_path.zip(update.cover.as_ref()) {
+            zip.start_file(path, FileOptions::default())?;
+            zip.write_all(contents)?;
+        }
+
+        zip.finish()?;
+
+        Ok(())
+    }
+
+    /// Returns the spine index and fragment of a href relative to the package document.
+    fn get_spine_position_by_href(&self, href: &str) -> Option<(usize, Option<String>)> {
+        let (path, fragment) = match href.split_once('#') {
+            Some((path, fragment)) => (path, Some(fragment).filter(|v| !v.is_empty())),
+            None => (href, None),
+        };
+
+        let path = urlencoding::decode(path).ok()?;
+
+        let item = self.package.manifest.items.iter().find(|v| {
+            v.href == path || urlencoding::decode(&v.href).map_or(false, |v| v == path)
+        })?;
+
+        Some((
+            self.package.spine.position_of_idref(&item.id)?,
+            fragment.map(|v| v.to_string()),
+        ))
+    }
+
+    fn create_toc_items(&self, items: Vec<NavItem>) -> Vec<TocItem> {
+        items
+            .into_iter()
+            .filter_map(|item| {
+                let children = self.create_toc_items(item.children);
+
+                let (chapter, fragment) = item
+                    .href
+                    .as_deref()
+                    .and_then(|v| self.get_spine_position_by_href(v))
+                    // Headings without a link go to their first child.
+                    .or_else(|| Some((children.first()?.chapter, None)))?;
+
+                Some(TocItem {
+                    title: item.title,
+                    chapter,
+                    fragment,
+                    children,
+                })
+            })
+            .collect()
+    }
+
     fn get_path_contents(&mut self, path: &str) -> Result<Vec<u8>> {
         // TODO: Optimize
         let path = if path.starts_with(&self.root_file_dir.display().to_string().replace('\\', "/"))
@@ -92,6 +414,37 @@ impl EpubBook {
             .by_name(&path)?
             .read_to_end(&mut buf)?;
 
+        if let Some(item) = self
+            .container
+            .encryption
+            .as_ref()
+            .and_then(|v| v.get_item_by_path(&path))
+        {
+            match &item.method {
+                EncryptionMethod::IdpfFont => {
+                    deobfuscate_idpf_font(&mut buf, &self.get_unique_id()?);
+                }
+
+                EncryptionMethod::AdobeFont => {
+                    let deobfuscated = self
+                        .package
+                        .metadata
+                        .dcmes_elements
+                        .get("identifier")
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|v| v.value.as_deref())
+                        .any(|id| deobfuscate_adobe_font(&mut buf, id));
+
+                    if !deobfuscated {
+                        return Err(Error::Encrypted(path.into_owned()));
+                    }
+                }
+
+                EncryptionMethod::Other(_) => return Err(Error::Encrypted(path.into_owned())),
+            }
+        }
+
         Ok(buf)
     }
 
@@ -101,6 +454,7 @@ impl EpubBook {
         page_path: PathBuf,
         prepend_to_urls: Option<&str>,
         add_css: Option<&[&str]>,
+        sanitize: Option<SanitizeOptions>,
     ) -> Result<Vec<u8>> {
         update_attributes_with(
             input,
@@ -169,64 +523,72 @@ impl EpubBook {
                             .ok()
                             .and_then(|v| String::from_utf8(v).ok())
                         {
-                            writer
-                                .write(xml::writer::XmlEvent::start_element("style"))
-                                .unwrap();
-                            writer
-                                .write(xml::writer::XmlEvent::characters(&cont))
-                                .unwrap();
-                            writer.write(xml::writer::XmlEvent::end_element()).unwrap();
-
-                            return true;
+                            writer.write(xml::writer::XmlEvent::start_element("style"))?;
+                            writer.write(xml::writer::XmlEvent::characters(
+                                &if sanitize.is_some() {
+                                    sanitize_css(&cont)
+                                } else {
+                                    Cow::Borrowed(cont.as_str())
+                                },
+                            ))?;
+                            writer.write(xml::writer::XmlEvent::end_element())?;
+
+                            return Ok(true);
                         }
                     }
                 }
 
-                false
+                Ok(false)
             },
             if let Some(v) = add_css { v } else { &[] },
+            sanitize,
         )
     }
 }
 
 impl Book for EpubBook {
-    fn get_table_of_contents(&mut self) -> Result<Option<Vec<(String, usize)>>> {
-        if let Some(path) = self
+    fn get_table_of_contents(&mut self) -> Result<Option<Vec<TocItem>>> {
+        let nav = match self.get_navigation() {
+            Ok(v) => v.filter(|v| !v.toc.is_empty()),
+            Err(e) => {
+                tracing::warn!("Unable to read EPUB navigation document: {e}");
+                None
+            }
+        };
+
+        let items = if let Some(nav) = nav {
+            nav.toc
+        } else if let Some(path) = self
             .package
             .manifest
-            .get_item_by_id("ncx")
+            .items
+            .iter()
+            .find(|v| v.id == "ncx" || v.media_type == "application/x-dtbncx+xml")
             .map(|v| v.href.clone())
         {
-            let value = self.read_path_as_bytes(&path, None, None)?;
+            let value = self.read_path_as_bytes(&path, None, None, None)?;
             let ncx_file = FileNCX::parse(Cursor::new(value))?;
 
-            Ok(Some(
-                ncx_file
-                    .nav_map
-                    .into_iter()
-                    .filter_map(|v| {
-                        let item = self.package.manifest.get_item_by_href(&v.content.src)?;
-
-                        Some((
-                            v.nav_label.text,
-                            self.package.spine.position_of_idref(&item.id)?,
-                        ))
-                    })
-                    .collect(),
-            ))
+            nav_points_to_items(&path, ncx_file.nav_map)
         } else {
-            // TODO: Would need to parse the file
-            // let toc = self.package.guide.find_toc().unwrap();
+            return Ok(None);
+        };
 
-            Ok(None)
-        }
+        Ok(Some(self.create_toc_items(items)))
     }
 
-    fn load_from_path(path: &str) -> Result<Self>
+    fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Self>
     where
         Self: Sized,
     {
-        let archive = ZipArchive::new(File::open(path)?)?;
+        Self::load_from_reader(File::open(path)?)
+    }
+
+    fn load_from_reader<R: ReadSeek + 'static>(reader: R) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let archive = ZipArchive::new(Box::new(reader) as Box<dyn ReadSeek>)?;
 
         let mut this = Self {
             container: AbsContainer::new(archive)?,
@@ -251,7 +613,7 @@ impl Book for EpubBook {
             .map(|v| v.href.clone())
             .collect::<Vec<_>>()
         {
-            if let Ok(asdf) = self.read_path_as_bytes(&href, None, None) {
+            if let Ok(asdf) = self.read_path_as_bytes(&href, None, None, None) {
                 hasher.update(&asdf);
             }
         }
@@ -379,6 +741,22 @@ impl Book for EpubBook {
         self.root_file_dir.as_path()
     }
 
+    fn get_rendition(&self) -> Rendition {
+        self.package.get_rendition()
+    }
+
+    fn get_chapter_rendition(&mut self) -> Result<Rendition> {
+        let mut rendition = self.package.get_spine_rendition(self.chapter);
+
+        if rendition.is_fixed_layout() {
+            if let Some(viewport) = rendition::read_viewport(&self.read_page_raw_as_bytes()?) {
+                rendition.viewport = Some(viewport);
+            }
+        }
+
+        Ok(rendition)
+    }
+
     fn get_page_path(&self) -> PathBuf {
         let item = self.get_manifest_item_by_spine(self.chapter);
         self.root_file_dir.join(item.unwrap().href.as_str())
@@ -394,17 +772,24 @@ impl Book for EpubBook {
         }
     }
 
+    fn read_page_as_text(&mut self) -> Result<Option<ChapterText>> {
+        let value = self.read_page_raw_as_bytes()?;
+
+        Ok(Some(markup::html_to_text(&String::from_utf8_lossy(&value))))
+    }
+
     fn read_path_as_bytes(
         &mut self,
         path: &str,
         prepend_to_urls: Option<&str>,
         add_css: Option<&[&str]>,
+        sanitize: Option<SanitizeOptions>,
     ) -> Result<Vec<u8>> {
-        if prepend_to_urls.is_some() || add_css.is_some() {
+        if prepend_to_urls.is_some() || add_css.is_some() || sanitize.is_some() {
             let page_path = PathBuf::from(path);
             let input = self.get_path_contents(path)?;
 
-            self.handle_update_attributes(&input, page_path, prepend_to_urls, add_css)
+            self.handle_update_attributes(&input, page_path, prepend_to_urls, add_css, sanitize)
         } else {
             self.get_path_contents(path)
         }
@@ -414,11 +799,12 @@ impl Book for EpubBook {
         &mut self,
         prepend_to_urls: Option<&str>,
         add_css: Option<&[&str]>,
+        sanitize: Option<SanitizeOptions>,
     ) -> Result<Vec<u8>> {
         let page_path = self.get_page_path();
         let input = self.read_page_raw_as_bytes()?;
 
-        self.handle_update_attributes(&input, page_path, prepend_to_urls, add_css)
+        self.handle_update_attributes(&input, page_path, prepend_to_urls, add_css, sanitize)
     }
 
     fn chapter_count(&self) -> usize {
@@ -457,6 +843,18 @@ impl Book for EpubBook {
     }
 }
 
+fn nav_points_to_items(ncx_path: &str, points: Vec<NavPoint>) -> Vec<NavItem> {
+    points
+        .into_iter()
+        .map(|point| NavItem {
+            title: point.nav_label.text.trim().to_string(),
+            href: Some(nav_document::resolve_href(ncx_path, &point.content.src)),
+            type_of: None,
+            children: nav_points_to_items(ncx_path, point.children),
+        })
+        .collect()
+}
+
 // TODO: General Conformance | https://www.w3.org/publishing/epub3/epub-packages.html#sec-conformance
 // TODO: Reading System Conformance | https://www.w3.org/publishing/epub3/epub-packages.html#sec-package-rs-conf
 
@@ -465,14 +863,151 @@ impl Book for EpubBook {
 // opf     http://www.idpf.org/2007/opf
 // rendition     http://www.idpf.org/vocab/rendition/#
 
-// TODO: Tests
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use binstall_zip::{write::FileOptions, ZipWriter};
+
+    use super::*;
+
+    #[test]
+    fn navigation() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+        for (name, contents) in [
+            ("mimetype", &b"application/epub+zip"[..]),
+            (
+                "META-INF/container.xml",
+                br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#,
+            ),
+            (
+                "OEBPS/content.opf",
+                br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="uid">urn:uuid:1234</dc:identifier>
+    <dc:title>Title</dc:title>
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+    <item id="one" href="one.xhtml" media-type="application/xhtml+xml"/>
+    <item id="two" href="two.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine><itemref idref="nav"/><itemref idref="one"/><itemref idref="two"/></spine>
+</package>"#,
+            ),
+            (
+                "OEBPS/nav.xhtml",
+                br#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<body>
+    <nav epub:type="toc"><ol><li><a href="one.xhtml">One</a></li></ol></nav>
+    <nav epub:type="landmarks">
+        <ol>
+            <li><a epub:type="toc" href="nav.xhtml">Contents</a></li>
+            <li><a epub:type="bodymatter" href="one.xhtml">Start</a></li>
+        </ol>
+    </nav>
+    <nav epub:type="page-list">
+        <ol>
+            <li><a href="one.xhtml#page1">1</a></li>
+            <li><a href="two.xhtml#page2">2</a></li>
+        </ol>
+    </nav>
+</body>
+</html>"#,
+            ),
+            ("OEBPS/one.xhtml", br#"<html xmlns="http://www.w3.org/1999/xhtml"><body><p id="page1">One</p></body></html>"#),
+            ("OEBPS/two.xhtml", br#"<html xmlns="http://www.w3.org/1999/xhtml"><body><p id="page2">Two</p></body></html>"#),
+        ] {
+            zip.start_file(name, FileOptions::default()).unwrap();
+            zip.write_all(contents).unwrap();
+        }
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
+        let source = zip.finish().unwrap().into_inner();
+        let mut book = EpubBook::load_from_reader(Cursor::new(source)).unwrap();
 
-//     #[test]
-//     fn load() {
-//         //
-//     }
-// }
+        let landmarks = book
+            .get_landmarks()
+            .unwrap()
+            .into_iter()
+            .map(|(type_of, item)| (type_of.unwrap(), item.title, item.chapter))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            landmarks,
+            [
+                (String::from("toc"), String::from("Contents"), 0),
+                (String::from("bodymatter"), String::from("Start"), 1)
+            ]
+        );
+
+        let pages = book.get_page_list().unwrap();
+
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].title, "1");
+        assert_eq!(pages[0].chapter, 1);
+        assert_eq!(pages[0].fragment.as_deref(), Some("page1"));
+        assert_eq!(pages[1].chapter, 2);
+        assert_eq!(pages[1].fragment.as_deref(), Some("page2"));
+    }
+
+    #[test]
+    fn kepub_keeps_malformed_chapter() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+        for (name, contents) in [
+            ("mimetype", &b"application/epub+zip"[..]),
+            (
+                "META-INF/container.xml",
+                br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#,
+            ),
+            (
+                "content.opf",
+                br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="uid">urn:uuid:1234</dc:identifier>
+    <dc:title>Title</dc:title>
+  </metadata>
+  <manifest>
+    <item id="one" href="one.xhtml" media-type="application/xhtml+xml"/>
+    <item id="two" href="two.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine><itemref idref="one"/><itemref idref="two"/></spine>
+</package>"#,
+            ),
+            ("one.xhtml", br#"<html xmlns="http://www.w3.org/1999/xhtml"><body><p>One</p></body></html>"#),
+            ("two.xhtml", br#"<html xmlns="http://www.w3.org/1999/xhtml"><body><p>Two</div></body></html>"#),
+        ] {
+            zip.start_file(name, FileOptions::default()).unwrap();
+            zip.write_all(contents).unwrap();
+        }
+
+        let source = zip.finish().unwrap().into_inner();
+        let mut book = EpubBook::load_from_reader(Cursor::new(source)).unwrap();
+
+        let mut output = Cursor::new(Vec::new());
+        book.write_kepub(&MetadataUpdate::default(), &mut output)
+            .unwrap();
+
+        let mut kepub = EpubBook::load_from_reader(Cursor::new(output.into_inner())).unwrap();
+
+        let one = kepub
+            .read_path_as_string("one.xhtml", None, None, None)
+            .unwrap();
+        assert!(one.contains("koboSpan"), "{one}");
+
+        let two = kepub
+            .read_path_as_string("two.xhtml", None, None, None)
+            .unwrap();
+        assert!(two.contains("<p>Two</div>"), "{two}");
+    }
+}
@@ -9,20 +9,23 @@ use xml::{
     writer::XmlEvent as WriterEvent, EmitterConfig, EventWriter,
 };
 
-use crate::{Book, Result};
+use super::{sanitize_css, SanitizeElement, SanitizeOptions};
+use crate::Result;
 
 /// Based on https://github.com/danigm/epub-rs/blob/master/src/xmlutils.rs#L229
+///
+/// If `sanitize` is set, disallowed elements and attributes are removed before they're passed to the functions.
 pub fn update_attributes_with<B, F, S>(
     input: &[u8],
     book: &mut B,
     mut func_mod_attr: F,
     mut skip_and_insert: S,
     add_css: &[&str],
+    sanitize: Option<SanitizeOptions>,
 ) -> Result<Vec<u8>>
 where
-    B: Book,
     F: FnMut(&mut B, &OwnedName, OwnedAttribute) -> OwnedAttribute,
-    S: FnMut(&mut B, &OwnedName, &[OwnedAttribute], &mut EventWriter<&mut Vec<u8>>) -> bool,
+    S: FnMut(&mut B, &OwnedName, &[OwnedAttribute], &mut EventWriter<&mut Vec<u8>>) -> Result<bool>,
 {
     let reader = xml::ParserConfig::new()
         .add_entity("nbsp", " ")
@@ -37,20 +40,49 @@ where
 
     let mut skipping_name = None;
 
+    // How deep we are inside of an element removed by the sanitizer.
+    let mut sanitize_skip_depth = 0;
+    // The open elements and whether they were written. Unwrapped elements aren't.
+    let mut open_elements: Vec<(OwnedName, bool)> = Vec::new();
+
     for event in reader {
         match event {
             Err(e) => eprintln!("update_attributes_with: {e}"),
             Ok(v) => match v {
                 ReaderEvent::StartElement {
                     name,
-                    attributes,
+                    mut attributes,
                     namespace,
                 } => {
                     if skipping_name.is_some() {
                         continue;
                     }
 
-                    if skip_and_insert(book, &name, &attributes, &mut writer) {
+                    if sanitize_skip_depth != 0 {
+                        sanitize_skip_depth += 1;
+                        continue;
+                    }
+
+                    if let Some(options) = sanitize {
+                        match options.check_element(&name, &attributes) {
+                            SanitizeElement::Keep => (),
+                            SanitizeElement::Unwrap => {
+                                open_elements.push((name, false));
+                                continue;
+                            }
+                            SanitizeElement::Remove => {
+                                sanitize_skip_depth = 1;
+                                continue;
+                            }
+                        }
+
+                        attributes = attributes
+                            .into_iter()
+                            .filter_map(|attr| options.check_attribute(&name, attr))
+                            .collect();
+                    }
+
+                    if skip_and_insert(book, &name, &attributes, &mut writer)? {
                         skipping_name = Some(name.clone());
                         continue;
                     }
@@ -67,6 +99,8 @@ where
                             namespace: Cow::Owned(namespace),
                         })
                         .unwrap();
+
+                    open_elements.push((name, true));
                 }
 
                 ReaderEvent::EndElement { name } => {
@@ -78,6 +112,15 @@ where
                         continue;
                     }
 
+                    if sanitize_skip_depth != 0 {
+                        sanitize_skip_depth -= 1;
+                        continue;
+                    }
+
+                    if let Some((_, false)) = open_elements.pop() {
+                        continue;
+                    }
+
                     if name.local_name.to_lowercase() == "head" && !add_css.is_empty() {
                         // injecting here the extra css
                         let allcss = add_css.concat();
@@ -91,10 +134,30 @@ where
                 }
 
                 v => {
-                    if skipping_name.is_some() {
+                    if skipping_name.is_some() || sanitize_skip_depth != 0 {
                         continue;
                     }
 
+                    if sanitize.is_some() {
+                        match &v {
+                            // Eg. <?xml-stylesheet href="https://.." ?>
+                            ReaderEvent::ProcessingInstruction { .. } => continue,
+
+                            ReaderEvent::Characters(text) | ReaderEvent::CData(text)
+                                if open_elements.last().map_or(false, |(name, _)| {
+                                    name.local_name.eq_ignore_ascii_case("style")
+                                }) =>
+                            {
+                                writer
+                                    .write(WriterEvent::characters(&sanitize_css(text)))
+                                    .unwrap();
+                                continue;
+                            }
+
+                            _ => (),
+                        }
+                    }
+
                     if let Some(v) = v.as_writer_event() {
                         writer.write(v).unwrap();
                     }
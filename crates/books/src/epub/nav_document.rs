@@ -0,0 +1,261 @@
+// https://www.w3.org/TR/epub-33/#sec-nav
+
+use std::{io::Read, path::PathBuf};
+
+use xml::reader::XmlEvent;
+
+use super::update_value_with_relative_internal_path;
+use crate::Result;
+
+/// The EPUB 3 Navigation Document.
+#[derive(Debug, Default)]
+pub struct NavDocument {
+    pub toc: Vec<NavItem>,
+    pub landmarks: Vec<NavItem>,
+    pub page_list: Vec<NavItem>,
+}
+
+#[derive(Debug, Default)]
+pub struct NavItem {
+    pub title: String,
+    /// Relative to the package document. Includes the fragment.
+    pub href: Option<String>,
+    /// The epub:type of the link. Used by the landmarks. Eg. "bodymatter", "toc"
+    pub type_of: Option<String>,
+
+    pub children: Vec<NavItem>,
+}
+
+#[derive(Clone, Copy)]
+enum NavType {
+    Toc,
+    Landmarks,
+    PageList,
+}
+
+impl NavType {
+    fn from_epub_type(value: &str) -> Option<Self> {
+        value.split_whitespace().find_map(|v| match v {
+            "toc" => Some(Self::Toc),
+            "landmarks" => Some(Self::Landmarks),
+            "page-list" => Some(Self::PageList),
+            _ => None,
+        })
+    }
+}
+
+impl NavDocument {
+    /// `path` is the location of the navigation document relative to the package document.
+    pub fn parse<R: Read>(value: R, path: &str) -> Result<Self> {
+        let mut this = Self::default();
+
+        let reader = xml::ParserConfig::new()
+            .add_entity("nbsp", " ")
+            .add_entity("copy", "©")
+            .add_entity("reg", "®")
+            .create_reader(value);
+
+        let mut nav_type = None;
+
+        // The <ol> elements and <li> elements we're currently inside of.
+        let mut lists: Vec<Vec<NavItem>> = Vec::new();
+        let mut items: Vec<NavItem> = Vec::new();
+
+        // Element depth inside of the <a> or <span> label.
+        let mut label_depth = 0;
+
+        for event in reader {
+            match event? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => {
+                    if label_depth != 0 {
+                        label_depth += 1;
+                        continue;
+                    }
+
+                    match name.local_name.as_str() {
+                        "nav" => {
+                            nav_type = attributes
+                                .iter()
+                                .find(|v| is_epub_type(&v.name))
+                                .and_then(|v| NavType::from_epub_type(&v.value));
+                        }
+
+                        _ if nav_type.is_none() => (),
+
+                        "ol" => lists.push(Vec::new()),
+
+                        "li" => items.push(NavItem::default()),
+
+                        "a" | "span" => {
+                            if let Some(item) = items
+                                .last_mut()
+                                .filter(|v| v.title.is_empty() && v.href.is_none())
+                            {
+                                for attr in attributes {
+                                    if attr.name.local_name == "href" {
+                                        item.href = Some(resolve_href(path, &attr.value));
+                                    } else if is_epub_type(&attr.name) {
+                                        item.type_of = Some(attr.value);
+                                    }
+                                }
+
+                                label_depth = 1;
+                            }
+                        }
+
+                        _ => (),
+                    }
+                }
+
+                XmlEvent::Characters(value)
+                | XmlEvent::CData(value)
+                | XmlEvent::Whitespace(value)
+                    if label_depth != 0 =>
+                {
+                    if let Some(item) = items.last_mut() {
+                        item.title.push_str(&value);
+                    }
+                }
+
+                XmlEvent::EndElement { name } => {
+                    if label_depth != 0 {
+                        label_depth -= 1;
+                        continue;
+                    }
+
+                    match name.local_name.as_str() {
+                        "nav" => {
+                            nav_type = None;
+                            lists.clear();
+                            items.clear();
+                        }
+
+                        "ol" => {
+                            let Some(list) = lists.pop() else {
+                                continue;
+                            };
+
+                            if let Some(item) = items.last_mut() {
+                                item.children.extend(list);
+                            } else {
+                                match nav_type {
+                                    Some(NavType::Toc) => this.toc.extend(list),
+                                    Some(NavType::Landmarks) => this.landmarks.extend(list),
+                                    Some(NavType::PageList) => this.page_list.extend(list),
+                                    None => (),
+                                }
+                            }
+                        }
+
+                        "li" => {
+                            if let Some((mut item, list)) = items.pop().zip(lists.last_mut()) {
+                                item.title =
+                                    item.title.split_whitespace().collect::<Vec<_>>().join(" ");
+
+                                list.push(item);
+                            }
+                        }
+
+                        _ => (),
+                    }
+                }
+
+                XmlEvent::EndDocument => break,
+
+                _ => (),
+            }
+        }
+
+        Ok(this)
+    }
+}
+
+fn is_epub_type(name: &xml::name::OwnedName) -> bool {
+    name.local_name == "type" && name.prefix.as_deref() == Some("epub")
+}
+
+/// Resolve a href which is relative to `base` into one relative to the package document.
+pub(super) fn resolve_href(base: &str, href: &str) -> String {
+    let (path, fragment) = match href.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (href, None),
+    };
+
+    let mut value = if path.is_empty() {
+        base.to_string()
+    } else {
+        update_value_with_relative_internal_path(PathBuf::from(base), path, None)
+    };
+
+    if let Some(fragment) = fragment {
+        value.push('#');
+        value.push_str(fragment);
+    }
+
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_toc() {
+        let nav = br##"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<body>
+    <nav epub:type="toc">
+        <h1>Contents</h1>
+        <ol>
+            <li><a href="part1.xhtml">Part <b>One</b></a>
+                <ol>
+                    <li><a href="chapter1.xhtml#start">Chapter 1</a></li>
+                    <li><a href="../Text/chapter2.xhtml">Chapter&nbsp;2</a></li>
+                </ol>
+            </li>
+            <li><span>Appendices</span>
+                <ol><li><a href="#notes">Notes</a></li></ol>
+            </li>
+        </ol>
+    </nav>
+    <nav epub:type="landmarks" hidden="">
+        <ol><li><a epub:type="bodymatter" href="chapter1.xhtml">Start</a></li></ol>
+    </nav>
+    <nav epub:type="page-list" hidden="">
+        <ol><li><a href="chapter1.xhtml#page1">1</a></li></ol>
+    </nav>
+</body>
+</html>"##;
+
+        let nav = NavDocument::parse(&nav[..], "Text/nav.xhtml").unwrap();
+
+        assert_eq!(nav.toc.len(), 2);
+        assert_eq!(nav.toc[0].title, "Part One");
+        assert_eq!(nav.toc[0].href.as_deref(), Some("Text/part1.xhtml"));
+        assert_eq!(nav.toc[0].children.len(), 2);
+        assert_eq!(
+            nav.toc[0].children[0].href.as_deref(),
+            Some("Text/chapter1.xhtml#start")
+        );
+        assert_eq!(nav.toc[0].children[1].title, "Chapter 2");
+        assert_eq!(
+            nav.toc[0].children[1].href.as_deref(),
+            Some("Text/chapter2.xhtml")
+        );
+
+        assert_eq!(nav.toc[1].title, "Appendices");
+        assert_eq!(nav.toc[1].href, None);
+        assert_eq!(
+            nav.toc[1].children[0].href.as_deref(),
+            Some("Text/nav.xhtml#notes")
+        );
+
+        assert_eq!(nav.landmarks.len(), 1);
+        assert_eq!(nav.landmarks[0].type_of.as_deref(), Some("bodymatter"));
+
+        assert_eq!(nav.page_list.len(), 1);
+        assert_eq!(nav.page_list[0].title, "1");
+    }
+}
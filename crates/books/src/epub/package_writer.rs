@@ -0,0 +1,766 @@
+// https://www.w3.org/TR/epub-33/#sec-pkg-metadata
+// https://idpf.org/epub/20/spec/OPF_2.0.1_draft.htm#Section2.2
+
+use std::{borrow::Cow, collections::HashSet, io::Read};
+
+use xml::{
+    attribute::OwnedAttribute,
+    name::OwnedName,
+    namespace::Namespace,
+    reader::XmlEvent,
+    writer::{EventWriter, XmlEvent as WriterEvent},
+    EmitterConfig, ParserConfig,
+};
+
+use super::PackageDocument;
+use crate::Result;
+
+const DC_NAMESPACE: &str = "http://purl.org/dc/elements/1.1/";
+const OPF_NAMESPACE: &str = "http://www.idpf.org/2007/opf";
+
+/// Manifest ID of the cover image we add.
+pub static COVER_ID: &str = "bookie-cover";
+
+/// Values to write into the package document. `None` keeps the values already in the file.
+#[derive(Debug, Default, Clone)]
+pub struct MetadataUpdate {
+    pub title: Option<String>,
+    pub creators: Option<Vec<String>>,
+    /// Scheme and value. Eg. ("isbn", "9780000000000")
+    ///
+    /// Replaces the identifiers with the same scheme. The unique identifier is never touched.
+    pub identifiers: Vec<(String, String)>,
+    /// Series name and position in the series.
+    pub series: Option<(String, Option<f64>)>,
+    /// Image file extension and contents.
+    pub cover: Option<(String, Vec<u8>)>,
+}
+
+impl MetadataUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.creators.is_none()
+            && self.identifiers.is_empty()
+            && self.series.is_none()
+            && self.cover.is_none()
+    }
+
+    /// The href of the new cover image relative to the package document.
+    pub fn cover_href(&self) -> Option<String> {
+        self.cover
+            .as_ref()
+            .map(|(ext, _)| format!("{COVER_ID}.{}", ext.to_lowercase()))
+    }
+}
+
+/// A direct child of the metadata element along with everything inside of it.
+struct MetadataEntry {
+    name: OwnedName,
+    attributes: Vec<OwnedAttribute>,
+    text: String,
+    /// Removed regardless of what it contains.
+    remove: bool,
+
+    events: Vec<XmlEvent>,
+    /// Depth of the element in the document.
+    depth: usize,
+}
+
+impl MetadataEntry {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|v| v.name.local_name == name)
+            .map(|v| v.value.as_str())
+    }
+}
+
+/// Rewrite the package document with the updated metadata.
+///
+/// Everything which isn't being replaced is written back as-is.
+pub(super) fn rewrite_package<R: Read>(
+    value: R,
+    package: &PackageDocument,
+    update: &MetadataUpdate,
+) -> Result<Vec<u8>> {
+    let is_epub3 = package.attributes.version.starts_with('3');
+    let unique_id = package.attributes.unique_identifier.as_str();
+
+    // IDs of the elements we're removing. Used to remove the <meta refines=".."> elements.
+    let mut removed_ids = HashSet::new();
+
+    if update.title.is_some() {
+        removed_ids.extend(dcmes_ids(package, "title"));
+    }
+
+    if update.creators.is_some() {
+        removed_ids.extend(dcmes_ids(package, "creator"));
+    }
+
+    if update.series.is_some() {
+        removed_ids.extend(
+            package
+                .metadata
+                .meta_items
+                .iter()
+                .filter(|v| v.property == "belongs-to-collection")
+                .filter_map(|v| v.id.clone()),
+        );
+    }
+
+    let reader = ParserConfig::new()
+        .ignore_comments(false)
+        .create_reader(value);
+
+    let mut buffer = Vec::new();
+
+    let mut writer = EmitterConfig::new()
+        .perform_indent(false)
+        .autopad_comments(false)
+        .create_writer(&mut buffer);
+
+    // Element names from the root to the current element.
+    let mut stack: Vec<OwnedName> = Vec::new();
+    let mut entry: Option<MetadataEntry> = None;
+
+    // Held onto so it can be removed along with a removed element.
+    let mut whitespace: Option<String> = None;
+    // Whitespace in front of the metadata items. Used for the items we add.
+    let mut indent = String::from("\n    ");
+
+    for event in reader {
+        let event = event?;
+
+        // Currently inside of a metadata item. Store it until we know if we're keeping it.
+        if let Some(current) = entry.as_mut() {
+            match &event {
+                XmlEvent::StartElement { name, .. } => stack.push(name.clone()),
+                XmlEvent::EndElement { .. } => {
+                    stack.pop();
+                }
+                XmlEvent::Characters(v) | XmlEvent::CData(v) => current.text.push_str(v),
+                _ => (),
+            }
+
+            current.events.push(event);
+
+            if stack.len() < current.depth {
+                let current = entry.take().unwrap();
+                let whitespace = whitespace.take();
+
+                if is_replaced(&current, update, unique_id, &mut removed_ids) {
+                    continue;
+                }
+
+                if let Some(value) = whitespace {
+                    writer.write(WriterEvent::Characters(&value))?;
+                }
+
+                for event in &current.events {
+                    if let Some(event) = event.as_writer_event() {
+                        writer.write(event)?;
+                    }
+                }
+            }
+
+            continue;
+        }
+
+        let parent = stack.last().map(|v| v.local_name.clone());
+
+        match event {
+            XmlEvent::StartElement {
+                name,
+                attributes,
+                namespace,
+            } if matches!(
+                parent.as_deref(),
+                Some("metadata" | "dc-metadata" | "x-metadata")
+            ) && !matches!(name.local_name.as_str(), "dc-metadata" | "x-metadata") =>
+            {
+                if let Some(value) = whitespace.as_ref() {
+                    indent = value.clone();
+                }
+
+                stack.push(name.clone());
+
+                entry = Some(MetadataEntry {
+                    name: name.clone(),
+                    attributes: attributes.clone(),
+                    text: String::new(),
+                    remove: false,
+                    events: vec![XmlEvent::StartElement {
+                        name,
+                        attributes,
+                        namespace,
+                    }],
+                    depth: stack.len(),
+                });
+            }
+
+            XmlEvent::StartElement {
+                name,
+                mut attributes,
+                namespace,
+            } => {
+                if update.cover.is_some()
+                    && parent.as_deref() == Some("manifest")
+                    && name.local_name == "item"
+                {
+                    // Our previous cover image. It's replaced below.
+                    if attributes
+                        .iter()
+                        .any(|v| v.name.local_name == "id" && v.value == COVER_ID)
+                    {
+                        stack.push(name.clone());
+
+                        entry = Some(MetadataEntry {
+                            name,
+                            attributes,
+                            text: String::new(),
+                            remove: true,
+                            events: Vec::new(),
+                            depth: stack.len(),
+                        });
+
+                        continue;
+                    }
+
+                    // Only one item can be the cover image.
+                    if let Some(attr) = attributes
+                        .iter_mut()
+                        .find(|v| v.name.local_name == "properties")
+                    {
+                        attr.value = attr
+                            .value
+                            .split_whitespace()
+                            .filter(|v| *v != "cover-image")
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                    }
+
+                    attributes.retain(|v| v.name.local_name != "properties" || !v.value.is_empty());
+                }
+
+                flush_whitespace(&mut writer, &mut whitespace)?;
+
+                writer.write(WriterEvent::StartElement {
+                    name: name.borrow(),
+                    attributes: Cow::Owned(attributes.iter().map(|v| v.borrow()).collect()),
+                    namespace: Cow::Borrowed(&namespace),
+                })?;
+
+                stack.push(name);
+            }
+
+            XmlEvent::EndElement { name } => {
+                stack.pop();
+
+                match name.local_name.as_str() {
+                    "metadata" => write_metadata(&mut writer, update, is_epub3, &indent)?,
+
+                    "manifest" => {
+                        if let Some((href, (ext, _))) =
+                            update.cover_href().zip(update.cover.as_ref())
+                        {
+                            let mut element = WriterEvent::start_element("item")
+                                .attr("id", COVER_ID)
+                                .attr("href", &href)
+                                .attr("media-type", image_media_type(ext));
+
+                            if is_epub3 {
+                                element = element.attr("properties", "cover-image");
+                            }
+
+                            writer.write(WriterEvent::Characters(&indent))?;
+                            writer.write(element)?;
+                            writer.write(WriterEvent::end_element())?;
+                        }
+                    }
+
+                    _ => (),
+                }
+
+                flush_whitespace(&mut writer, &mut whitespace)?;
+
+                writer.write(WriterEvent::EndElement {
+                    name: Some(name.borrow()),
+                })?;
+            }
+
+            XmlEvent::Whitespace(value) => {
+                flush_whitespace(&mut writer, &mut whitespace)?;
+                whitespace = Some(value);
+            }
+
+            event => {
+                flush_whitespace(&mut writer, &mut whitespace)?;
+
+                if let Some(event) = event.as_writer_event() {
+                    writer.write(event)?;
+                }
+            }
+        }
+    }
+
+    drop(writer);
+
+    Ok(buffer)
+}
+
+fn dcmes_ids<'a>(package: &'a PackageDocument, name: &str) -> impl Iterator<Item = String> + 'a {
+    package
+        .metadata
+        .dcmes_elements
+        .get(name)
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.id.clone())
+}
+
+/// Returns true if the metadata item will be replaced by a value in the update.
+fn is_replaced(
+    entry: &MetadataEntry,
+    update: &MetadataUpdate,
+    unique_id: &str,
+    removed_ids: &mut HashSet<String>,
+) -> bool {
+    if entry.remove {
+        return true;
+    }
+
+    let id = entry.attr("id");
+
+    // Books may bind the DC namespace to a prefix other than "dc".
+    let is_dc = entry.name.namespace.as_deref() == Some(DC_NAMESPACE);
+
+    let is_replaced = match (is_dc, entry.name.local_name.as_str()) {
+        (true, "title") => update.title.is_some(),
+        (true, "creator") => update.creators.is_some(),
+
+        (true, "identifier") if id != Some(unique_id) => {
+            let text = entry.text.trim().to_lowercase();
+
+            update.identifiers.iter().any(|(scheme, _)| {
+                let scheme = scheme.to_lowercase();
+
+                entry
+                    .attr("scheme")
+                    .is_some_and(|v| v.eq_ignore_ascii_case(&scheme))
+                    || text.starts_with(&format!("urn:{scheme}:"))
+                    || text.starts_with(&format!("{scheme}:"))
+            })
+        }
+
+        (_, "meta") => {
+            if let Some(refines) = entry.attr("refines") {
+                removed_ids.contains(refines.trim_start_matches('#'))
+            } else {
+                match (entry.attr("name"), entry.attr("property")) {
+                    (Some("cover"), _) => update.cover.is_some(),
+                    (Some("calibre:series" | "calibre:series_index"), _) => update.series.is_some(),
+                    (_, Some("belongs-to-collection")) => update.series.is_some(),
+                    _ => false,
+                }
+            }
+        }
+
+        _ => false,
+    };
+
+    if is_replaced {
+        if let Some(id) = id {
+            removed_ids.insert(id.to_string());
+        }
+    }
+
+    is_replaced
+}
+
+/// Write the updated values at the end of the metadata element.
+fn write_metadata<W: std::io::Write>(
+    writer: &mut EventWriter<W>,
+    update: &MetadataUpdate,
+    is_epub3: bool,
+    indent: &str,
+) -> Result<()> {
+    let mut namespace = Namespace::empty();
+    namespace.put("dc", DC_NAMESPACE);
+    namespace.put("opf", OPF_NAMESPACE);
+
+    if let Some(title) = update.title.as_deref() {
+        write_element(writer, indent, &namespace, "dc:title", &[], title)?;
+    }
+
+    for (index, name) in update.creators.iter().flatten().enumerate() {
+        if is_epub3 {
+            let id = format!("bookie-creator-{index}");
+            let refines = format!("#{id}");
+
+            write_element(
+                writer,
+                indent,
+                &namespace,
+                "dc:creator",
+                &[("id", &id)],
+                name,
+            )?;
+            write_element(
+                writer,
+                indent,
+                &namespace,
+                "meta",
+                &[
+                    ("refines", &refines),
+                    ("property", "role"),
+                    ("scheme", "marc:relators"),
+                ],
+                "aut",
+            )?;
+        } else {
+            write_element(
+                writer,
+                indent,
+                &namespace,
+                "dc:creator",
+                &[("opf:role", "aut")],
+                name,
+            )?;
+        }
+    }
+
+    for (scheme, value) in &update.identifiers {
+        if is_epub3 {
+            let value = format!("urn:{}:{value}", scheme.to_lowercase());
+
+            write_element(writer, indent, &namespace, "dc:identifier", &[], &value)?;
+        } else {
+            write_element(
+                writer,
+                indent,
+                &namespace,
+                "dc:identifier",
+                &[("opf:scheme", &scheme.to_uppercase())],
+                value,
+            )?;
+        }
+    }
+
+    if let Some((name, position)) = &update.series {
+        if is_epub3 {
+            write_element(
+                writer,
+                indent,
+                &namespace,
+                "meta",
+                &[
+                    ("property", "belongs-to-collection"),
+                    ("id", "bookie-series"),
+                ],
+                name,
+            )?;
+            write_element(
+                writer,
+                indent,
+                &namespace,
+                "meta",
+                &[
+                    ("refines", "#bookie-series"),
+                    ("property", "collection-type"),
+                ],
+                "series",
+            )?;
+
+            if let Some(position) = position {
+                write_element(
+                    writer,
+                    indent,
+                    &namespace,
+                    "meta",
+                    &[
+                        ("refines", "#bookie-series"),
+                        ("property", "group-position"),
+                    ],
+                    &position.to_string(),
+                )?;
+            }
+        }
+
+        // Calibre's series meta is understood by most readers.
+        write_element(
+            writer,
+            indent,
+            &namespace,
+            "meta",
+            &[("name", "calibre:series"), ("content", name)],
+            "",
+        )?;
+
+        if let Some(position) = position {
+            write_element(
+                writer,
+                indent,
+                &namespace,
+                "meta",
+                &[
+                    ("name", "calibre:series_index"),
+                    ("content", &position.to_string()),
+                ],
+                "",
+            )?;
+        }
+    }
+
+    if update.cover.is_some() {
+        write_element(
+            writer,
+            indent,
+            &namespace,
+            "meta",
+            &[("name", "cover"), ("content", COVER_ID)],
+            "",
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_element<W: std::io::Write>(
+    writer: &mut EventWriter<W>,
+    indent: &str,
+    namespace: &Namespace,
+    name: &str,
+    attributes: &[(&str, &str)],
+    value: &str,
+) -> Result<()> {
+    let mut element = WriterEvent::start_element(name);
+
+    if name.starts_with("dc:") || attributes.iter().any(|(n, _)| n.starts_with("opf:")) {
+        for (prefix, uri) in namespace {
+            element = element.ns(prefix, uri);
+        }
+    }
+
+    for (name, value) in attributes {
+        element = element.attr(*name, value);
+    }
+
+    writer.write(WriterEvent::Characters(indent))?;
+    writer.write(element)?;
+
+    if !value.is_empty() {
+        writer.write(WriterEvent::characters(value))?;
+    }
+
+    writer.write(WriterEvent::end_element())?;
+
+    Ok(())
+}
+
+fn flush_whitespace<W: std::io::Write>(
+    writer: &mut EventWriter<W>,
+    whitespace: &mut Option<String>,
+) -> Result<()> {
+    if let Some(value) = whitespace.take() {
+        writer.write(WriterEvent::Characters(&value))?;
+    }
+
+    Ok(())
+}
+
+pub(super) fn image_media_type(ext: &str) -> &'static str {
+    match ext.to_lowercase().as_str() {
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        _ => "image/jpeg",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrite_epub3_metadata() {
+        let opf = br##"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="uid">urn:uuid:1234</dc:identifier>
+    <dc:identifier id="isbn">urn:isbn:0000000000</dc:identifier>
+    <dc:title id="t1">Old Title</dc:title>
+    <meta refines="#t1" property="title-type">main</meta>
+    <dc:creator id="c1">Old Author</dc:creator>
+    <meta refines="#c1" property="role" scheme="marc:relators">aut</meta>
+    <dc:language>en</dc:language>
+    <meta property="dcterms:modified">2020-01-01T00:00:00Z</meta>
+  </metadata>
+  <manifest>
+    <item id="cover" href="cover.jpg" media-type="image/jpeg" properties="cover-image"/>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+  </manifest>
+  <spine><itemref idref="nav"/></spine>
+</package>"##;
+
+        let package = PackageDocument::parse(&opf[..]).unwrap();
+
+        let update = MetadataUpdate {
+            title: Some(String::from("New & Title")),
+            creators: Some(vec![String::from("Jane Doe")]),
+            identifiers: vec![(String::from("isbn"), String::from("9780000000001"))],
+            series: Some((String::from("Example"), Some(2.0))),
+            cover: Some((String::from("png"), Vec::new())),
+        };
+
+        let value = rewrite_package(&opf[..], &package, &update).unwrap();
+        let package = PackageDocument::parse(&value[..]).unwrap();
+        let value = String::from_utf8(value).unwrap();
+
+        assert!(!value.contains("Old Title"));
+        assert!(!value.contains("Old Author"));
+        assert!(!value.contains("title-type"));
+        assert!(!value.contains("urn:isbn:0000000000"));
+        assert!(value.contains("urn:uuid:1234"));
+        assert!(value.contains("dcterms:modified"));
+
+        let metadata = &package.metadata;
+        assert_eq!(
+            metadata.dcmes_elements["title"][0].value.as_deref(),
+            Some("New & Title")
+        );
+        assert_eq!(metadata.get_creators(), vec!["Jane Doe"]);
+        assert_eq!(metadata.dcmes_elements["identifier"].len(), 2);
+        assert!(metadata
+            .meta_items
+            .iter()
+            .any(|v| v.property == "group-position" && v.value.as_deref() == Some("2")));
+
+        let covers = package
+            .manifest
+            .items
+            .iter()
+            .filter(|v| v.properties.as_deref() == Some("cover-image"))
+            .collect::<Vec<_>>();
+
+        assert_eq!(covers.len(), 1);
+        assert_eq!(covers[0].href, "bookie-cover.png");
+        assert_eq!(covers[0].media_type, "image/png");
+    }
+
+    #[test]
+    fn dc_namespace_prefix() {
+        let opf = br##"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+  <metadata xmlns:dc11="http://purl.org/dc/elements/1.1/" xmlns:x="http://example.com/">
+    <dc11:identifier id="uid">urn:uuid:1234</dc11:identifier>
+    <dc11:title>Old Title</dc11:title>
+    <x:title>Not Dublin Core</x:title>
+  </metadata>
+  <manifest/>
+  <spine/>
+</package>"##;
+
+        let package = PackageDocument::parse(&opf[..]).unwrap();
+
+        let update = MetadataUpdate {
+            title: Some(String::from("New Title")),
+            ..MetadataUpdate::default()
+        };
+
+        let value = rewrite_package(&opf[..], &package, &update).unwrap();
+        let value = String::from_utf8(value).unwrap();
+
+        assert!(!value.contains("Old Title"), "{value}");
+        assert!(value.contains("New Title"), "{value}");
+        assert!(value.contains("Not Dublin Core"), "{value}");
+        assert!(value.contains("urn:uuid:1234"), "{value}");
+    }
+
+    #[test]
+    fn round_trip_epub() {
+        use std::io::{Cursor, Write};
+
+        use binstall_zip::{write::FileOptions, ZipWriter};
+
+        use crate::{epub::EpubBook, Book, BookSearch};
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+        for (name, contents) in [
+            ("mimetype", &b"application/epub+zip"[..]),
+            (
+                "META-INF/container.xml",
+                br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#,
+            ),
+            (
+                "OEBPS/content.opf",
+                br#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="uid">urn:uuid:1234</dc:identifier>
+    <dc:title>Old Title</dc:title>
+    <dc:creator>Old Author</dc:creator>
+    <dc:language>en</dc:language>
+  </metadata>
+  <manifest>
+    <item id="chapter" href="chapter.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine><itemref idref="chapter"/></spine>
+</package>"#,
+            ),
+            (
+                "OEBPS/chapter.xhtml",
+                br#"<html xmlns="http://www.w3.org/1999/xhtml"><body><p>Hello</p></body></html>"#,
+            ),
+        ] {
+            zip.start_file(name, FileOptions::default()).unwrap();
+            zip.write_all(contents).unwrap();
+        }
+
+        let source = zip.finish().unwrap().into_inner();
+
+        let update = MetadataUpdate {
+            title: Some(String::from("New Title")),
+            creators: Some(vec![String::from("Jane Doe"), String::from("John Doe")]),
+            identifiers: vec![(String::from("isbn"), String::from("9780000000001"))],
+            series: None,
+            cover: Some((String::from("PNG"), b"png".to_vec())),
+        };
+
+        let mut written = Cursor::new(Vec::new());
+        EpubBook::load_from_reader(Cursor::new(source))
+            .unwrap()
+            .write_with_metadata(&update, &mut written)
+            .unwrap();
+
+        let mut book = EpubBook::load_from_reader(Cursor::new(written.into_inner())).unwrap();
+
+        assert_eq!(
+            book.find(BookSearch::Title),
+            Some(vec![String::from("New Title")])
+        );
+        assert_eq!(
+            book.find(BookSearch::Creator),
+            Some(vec![String::from("Jane Doe"), String::from("John Doe")])
+        );
+        assert_eq!(book.get_unique_id().unwrap(), "urn:uuid:1234");
+
+        let cover = book.find(BookSearch::CoverImage).unwrap();
+        assert_eq!(cover, ["bookie-cover.png"]);
+        assert_eq!(
+            book.read_path_as_bytes("OEBPS/bookie-cover.png", None, None, None)
+                .unwrap(),
+            b"png"
+        );
+
+        // The content is left untouched.
+        assert_eq!(book.chapter_count(), 1);
+        assert!(String::from_utf8(book.read_page_raw_as_bytes().unwrap())
+            .unwrap()
+            .contains("Hello"));
+    }
+}
@@ -0,0 +1,233 @@
+// https://www.w3.org/TR/epub-33/#sec-fixed-layouts
+
+use xml::reader::XmlEvent;
+
+pub use common_local::reader::{
+    PageSpread, Rendition, RenditionLayout, RenditionOrientation, RenditionSpread, Viewport,
+};
+
+use super::PackageDocument;
+
+impl PackageDocument {
+    /// The book-wide rendition properties.
+    pub fn get_rendition(&self) -> Rendition {
+        let mut rendition = Rendition::default();
+
+        for item in &self.metadata.meta_items {
+            // Refinements only apply to other elements.
+            if item.refines.is_some() {
+                continue;
+            }
+
+            let value = item.value.as_deref().unwrap_or_default();
+
+            match item.property.as_str() {
+                "rendition:layout" => {
+                    if let Some(v) = RenditionLayout::from_value(value) {
+                        rendition.layout = v;
+                    }
+                }
+
+                "rendition:spread" => {
+                    if let Some(v) = RenditionSpread::from_value(value) {
+                        rendition.spread = v;
+                    }
+                }
+
+                "rendition:orientation" => {
+                    if let Some(v) = RenditionOrientation::from_value(value) {
+                        rendition.orientation = v;
+                    }
+                }
+
+                // Older books use the iBooks and Kindle <meta name=".." content=".." /> elements.
+                "" => match (item.get("name"), item.get("content")) {
+                    (Some("fixed-layout"), Some("true")) => {
+                        rendition.layout = RenditionLayout::PrePaginated;
+                    }
+
+                    (Some("original-resolution"), Some(content)) => {
+                        rendition.viewport = content.split_once('x').and_then(|(w, h)| {
+                            Some(Viewport {
+                                width: w.trim().parse().ok()?,
+                                height: h.trim().parse().ok()?,
+                            })
+                        });
+                    }
+
+                    _ => (),
+                },
+
+                _ => (),
+            }
+        }
+
+        rendition
+    }
+
+    /// The rendition of a spine item. Its properties override the book-wide ones.
+    pub fn get_spine_rendition(&self, index: usize) -> Rendition {
+        let mut rendition = self.get_rendition();
+
+        let Some(properties) = self
+            .spine
+            .items
+            .get(index)
+            .and_then(|v| v.properties.as_deref())
+        else {
+            return rendition;
+        };
+
+        for property in properties.split_whitespace() {
+            match property {
+                "page-spread-left" | "rendition:page-spread-left" => {
+                    rendition.page_spread = Some(PageSpread::Left);
+                }
+
+                "page-spread-right" | "rendition:page-spread-right" => {
+                    rendition.page_spread = Some(PageSpread::Right);
+                }
+
+                "rendition:page-spread-center" => {
+                    rendition.page_spread = Some(PageSpread::Center);
+                }
+
+                _ => {
+                    let Some(property) = property.strip_prefix("rendition:") else {
+                        continue;
+                    };
+
+                    if let Some(v) = property
+                        .strip_prefix("layout-")
+                        .and_then(RenditionLayout::from_value)
+                    {
+                        rendition.layout = v;
+                    } else if let Some(v) = property
+                        .strip_prefix("spread-")
+                        .and_then(RenditionSpread::from_value)
+                    {
+                        rendition.spread = v;
+                    } else if let Some(v) = property
+                        .strip_prefix("orientation-")
+                        .and_then(RenditionOrientation::from_value)
+                    {
+                        rendition.orientation = v;
+                    }
+                }
+            }
+        }
+
+        rendition
+    }
+}
+
+/// Read the viewport of a fixed layout content document.
+///
+/// XHTML uses `<meta name="viewport" content="width=1200, height=1600" />` and SVG uses its `viewBox`.
+pub(super) fn read_viewport(contents: &[u8]) -> Option<Viewport> {
+    let reader = xml::ParserConfig::new()
+        .add_entity("nbsp", " ")
+        .create_reader(contents);
+
+    for event in reader {
+        let XmlEvent::StartElement {
+            name, attributes, ..
+        } = event.ok()?
+        else {
+            continue;
+        };
+
+        let get = |name: &str| {
+            attributes
+                .iter()
+                .find(|v| v.name.local_name == name)
+                .map(|v| v.value.as_str())
+        };
+
+        match name.local_name.as_str() {
+            "meta" if get("name") == Some("viewport") => {
+                return parse_viewport_content(get("content")?);
+            }
+
+            "svg" => {
+                let mut view_box = get("viewBox")?
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|v| !v.is_empty())
+                    .skip(2)
+                    .map(|v| v.parse::<f32>().ok());
+
+                return Some(Viewport {
+                    width: view_box.next()??.round() as u32,
+                    height: view_box.next()??.round() as u32,
+                });
+            }
+
+            // The viewport has to be inside of the <head>.
+            "body" => return None,
+
+            _ => (),
+        }
+    }
+
+    None
+}
+
+/// Eg. "width=1200, height=1600"
+fn parse_viewport_content(value: &str) -> Option<Viewport> {
+    let mut width = None;
+    let mut height = None;
+
+    for (key, value) in value.split([',', ';']).filter_map(|v| v.split_once('=')) {
+        let value = value.trim().trim_end_matches("px").parse::<f32>().ok();
+
+        match key.trim() {
+            "width" => width = value,
+            "height" => height = value,
+            _ => (),
+        }
+    }
+
+    Some(Viewport {
+        width: width?.round() as u32,
+        height: height?.round() as u32,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn viewports() {
+        let page = br#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head>
+    <title>Page 1</title>
+    <meta name="viewport" content="width=1200, height=1600"/>
+</head>
+<body><img src="page1.jpg"/></body>
+</html>"#;
+
+        assert_eq!(
+            read_viewport(page),
+            Some(Viewport {
+                width: 1200,
+                height: 1600
+            })
+        );
+
+        let svg = br#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 800.5 1200"></svg>"#;
+
+        assert_eq!(
+            read_viewport(svg),
+            Some(Viewport {
+                width: 801,
+                height: 1200
+            })
+        );
+
+        let reflowable = br#"<html xmlns="http://www.w3.org/1999/xhtml"><head><title>A</title></head><body><svg viewBox="0 0 10 10"/></body></html>"#;
+
+        assert_eq!(read_viewport(reflowable), None);
+    }
+}
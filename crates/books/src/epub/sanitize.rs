@@ -0,0 +1,445 @@
+// https://www.w3.org/TR/epub-33/#sec-scripted-content
+// https://cheatsheetseries.owasp.org/cheatsheets/XSS_Filter_Evasion_Cheat_Sheet.html
+
+use std::borrow::Cow;
+
+use xml::{attribute::OwnedAttribute, name::OwnedName};
+
+const XHTML_NAMESPACE: &str = "http://www.w3.org/1999/xhtml";
+const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+const MATHML_NAMESPACE: &str = "http://www.w3.org/1998/Math/MathML";
+
+/// The HTML elements which are kept as is. Anything else is unwrapped (the children are kept).
+const ALLOWED_ELEMENTS: &str = "\
+    html head title meta link style body article aside footer header h1 h2 h3 h4 h5 h6 \
+    hgroup main nav section address blockquote dd div dl dt figcaption figure hr li menu ol \
+    p pre ul a abbr b bdi bdo br cite code data dfn em i kbd mark q rb rp rt rtc ruby s \
+    samp small span strong sub sup time u var wbr del ins area audio canvas img map picture \
+    source track video svg math caption col colgroup table tbody td tfoot th thead tr \
+    button datalist fieldset form input label legend meter optgroup option output progress \
+    select textarea details dialog summary acronym big center font nobr strike tt";
+
+/// The elements which are removed along with everything inside of them.
+const REMOVED_ELEMENTS: &str = "\
+    applet base embed frame frameset iframe object param portal handler listener";
+
+/// The attributes kept on HTML elements.
+const ALLOWED_ATTRIBUTES: &str = "\
+    accesskey class dir hidden id lang role style tabindex title translate alt charset \
+    content coords height href hreflang ismap media name rel shape sizes src srcset target \
+    type usemap width cite datetime reversed start value autoplay controls default kind \
+    label loop muted playsinline poster preload srclang abbr align axis bgcolor border \
+    cellpadding cellspacing char charoff colspan frame headers nowrap rowspan rules scope \
+    span summary valign accept autocomplete checked cols disabled for high low max \
+    maxlength method min minlength multiple open optimum pattern placeholder readonly \
+    required rows selected size step wrap clear color compact face noshade";
+
+/// Attributes which contain a URL.
+const URL_ATTRIBUTES: &str = "\
+    action background cite data formaction href longdesc lowsrc manifest ping poster src \
+    srcset";
+
+/// How to sanitize content documents before they're displayed in the reader.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SanitizeOptions {
+    /// Keep `<script>` elements and event handler attributes for EPUB3 scripted content.
+    pub allow_scripts: bool,
+}
+
+/// What to do with an element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SanitizeElement {
+    Keep,
+    /// Remove the element but keep its children.
+    Unwrap,
+    /// Remove the element and its children.
+    Remove,
+}
+
+impl SanitizeOptions {
+    pub fn check_element(
+        &self,
+        name: &OwnedName,
+        attributes: &[OwnedAttribute],
+    ) -> SanitizeElement {
+        let local_name = name.local_name.to_lowercase();
+
+        if local_name == "script" {
+            return if self.allow_scripts {
+                SanitizeElement::Keep
+            } else {
+                SanitizeElement::Remove
+            };
+        }
+
+        if has_word(REMOVED_ELEMENTS, &local_name) {
+            return SanitizeElement::Remove;
+        }
+
+        match name.namespace.as_deref() {
+            None | Some(XHTML_NAMESPACE) => {
+                if local_name == "noscript" {
+                    // Its contents are the fallback for when scripts are disabled.
+                    if self.allow_scripts {
+                        SanitizeElement::Remove
+                    } else {
+                        SanitizeElement::Unwrap
+                    }
+                } else if has_word(ALLOWED_ELEMENTS, &local_name) {
+                    SanitizeElement::Keep
+                } else {
+                    SanitizeElement::Unwrap
+                }
+            }
+
+            Some(SVG_NAMESPACE) => {
+                // <set attributeName="href" to="javascript:.." /> would bypass the attribute checks.
+                let is_animating_url = matches!(
+                    local_name.as_str(),
+                    "animate" | "animatemotion" | "animatetransform" | "set"
+                ) && attributes.iter().any(|v| {
+                    v.name.local_name == "attributeName"
+                        && v.value.to_lowercase().trim_start_matches("xlink:") == "href"
+                });
+
+                if is_animating_url {
+                    SanitizeElement::Remove
+                } else {
+                    SanitizeElement::Keep
+                }
+            }
+
+            Some(MATHML_NAMESPACE) => SanitizeElement::Keep,
+
+            // Eg. <epub:switch>
+            Some(_) => SanitizeElement::Unwrap,
+        }
+    }
+
+    /// Returns None if the attribute should be removed.
+    pub fn check_attribute(
+        &self,
+        element: &OwnedName,
+        mut attr: OwnedAttribute,
+    ) -> Option<OwnedAttribute> {
+        let local_name = attr.name.local_name.to_lowercase();
+
+        if local_name.starts_with("on") {
+            return self.allow_scripts.then_some(attr);
+        }
+
+        // Only the HTML attributes go through the allowlist. SVG and MathML have too many presentation attributes.
+        let is_html = matches!(element.namespace.as_deref(), None | Some(XHTML_NAMESPACE));
+
+        let is_allowed = match attr.name.prefix.as_deref() {
+            // Namespace declarations, epub:type, xml:lang and xlink:href
+            Some(_) => true,
+            None => {
+                !is_html
+                    || has_word(ALLOWED_ATTRIBUTES, &local_name)
+                    || local_name.starts_with("data-")
+                    || local_name.starts_with("aria-")
+                    || local_name == "xmlns"
+            }
+        };
+
+        if !is_allowed {
+            return None;
+        }
+
+        if local_name == "style" {
+            attr.value = sanitize_css(&attr.value).into_owned();
+        } else if local_name == "srcset" {
+            // Eg. "image.jpg 1x, https://example.com/image-2x.jpg 2x"
+            let is_safe = attr
+                .value
+                .split(',')
+                .filter_map(|v| v.split_whitespace().next())
+                .all(|v| check_url(v, false) == UrlKind::Local);
+
+            if !is_safe {
+                return None;
+            }
+        } else if has_word(URL_ATTRIBUTES, &local_name) {
+            let element_name = element.local_name.to_lowercase();
+
+            // Links are only followed when they're clicked. Everything else is fetched by the browser.
+            let is_navigation =
+                local_name == "href" && matches!(element_name.as_str(), "a" | "area");
+
+            match check_url(&attr.value, is_navigation) {
+                UrlKind::Local | UrlKind::Navigation => (),
+                UrlKind::Remote | UrlKind::Unsafe => return None,
+            }
+
+            // Forms would be submitted outside of the book.
+            if matches!(local_name.as_str(), "action" | "formaction" | "ping") {
+                return None;
+            }
+        }
+
+        Some(attr)
+    }
+}
+
+fn has_word(list: &str, value: &str) -> bool {
+    list.split_whitespace().any(|v| v == value)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UrlKind {
+    /// Inside of the book or an inline data URL of a media type.
+    Local,
+    /// A remote link which is only opened when clicked.
+    Navigation,
+    Remote,
+    /// Eg. "javascript:" or "data:text/html"
+    Unsafe,
+}
+
+fn check_url(value: &str, is_navigation: bool) -> UrlKind {
+    // Browsers ignore whitespace and control characters inside of the scheme. Eg. "java\tscript:"
+    let value = value
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_ascii_control())
+        .collect::<String>()
+        .to_lowercase();
+
+    if value.starts_with("//") || value.starts_with("\\\\") {
+        return if is_navigation {
+            UrlKind::Navigation
+        } else {
+            UrlKind::Remote
+        };
+    }
+
+    let Some((scheme, rest)) = value.split_once(':') else {
+        return UrlKind::Local;
+    };
+
+    // A colon after a path separator, query or fragment isn't a scheme. Eg. "chapter.xhtml#note:1"
+    let is_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+
+    if !is_scheme {
+        return UrlKind::Local;
+    }
+
+    match scheme {
+        "data" => {
+            let is_media = ["image/", "audio/", "video/", "font/"]
+                .iter()
+                .any(|v| rest.starts_with(v));
+
+            if is_media && !is_navigation {
+                UrlKind::Local
+            } else {
+                UrlKind::Unsafe
+            }
+        }
+
+        "http" | "https" | "ftp" => {
+            if is_navigation {
+                UrlKind::Navigation
+            } else {
+                UrlKind::Remote
+            }
+        }
+
+        "mailto" | "tel" if is_navigation => UrlKind::Navigation,
+
+        _ => UrlKind::Unsafe,
+    }
+}
+
+/// Remove remote `url()`s, `@import`s and `expression()`s from a stylesheet or style attribute.
+pub fn sanitize_css(value: &str) -> Cow<'_, str> {
+    // ASCII only so the indexes match the original value.
+    let lowercase = value.to_ascii_lowercase();
+
+    if !lowercase.contains("url(")
+        && !lowercase.contains("@import")
+        && !lowercase.contains("expression(")
+    {
+        return Cow::Borrowed(value);
+    }
+
+    let mut output = String::with_capacity(value.len());
+    let mut index = 0;
+
+    while index < value.len() {
+        let rest = &lowercase[index..];
+
+        if rest.starts_with("url(") {
+            let end = rest.find(')').map_or(value.len(), |v| index + v + 1);
+            let url = value[index + 4..end]
+                .trim_end_matches(')')
+                .trim()
+                .trim_matches(|c| c == '"' || c == '\'');
+
+            if check_url(url, false) == UrlKind::Local {
+                output.push_str(&value[index..end]);
+            } else {
+                output.push_str("url()");
+            }
+
+            index = end;
+        } else if rest.starts_with("@import") {
+            let end = rest.find(';').map_or(value.len(), |v| index + v + 1);
+            // Eg. @import url("a.css") screen; or @import 'a.css';
+            let import = value[index + 7..end].trim().trim_end_matches(';').trim();
+            let import = if import
+                .get(..4)
+                .is_some_and(|v| v.eq_ignore_ascii_case("url("))
+            {
+                import[4..].split(')').next().unwrap_or_default()
+            } else {
+                import
+            };
+
+            let url = import
+                .trim()
+                .trim_start_matches(['"', '\''])
+                .split(['"', '\''])
+                .next()
+                .unwrap_or_default();
+
+            if check_url(url.trim(), false) == UrlKind::Local {
+                output.push_str(&value[index..end]);
+            }
+
+            index = end;
+        } else if rest.starts_with("expression(") {
+            output.push_str("none(");
+            index += "expression(".len();
+        } else {
+            let ch = value[index..].chars().next().unwrap();
+            output.push(ch);
+            index += ch.len_utf8();
+        }
+    }
+
+    Cow::Owned(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::epub::update_attributes_with;
+
+    #[test]
+    fn urls() {
+        assert_eq!(check_url("../Images/cover.jpg", false), UrlKind::Local);
+        assert_eq!(check_url("chapter.xhtml#note:1", true), UrlKind::Local);
+        assert_eq!(
+            check_url("data:image/png;base64,AAAA", false),
+            UrlKind::Local
+        );
+        assert_eq!(
+            check_url("https://example.com/a.png", false),
+            UrlKind::Remote
+        );
+        assert_eq!(check_url("//example.com/a.png", false), UrlKind::Remote);
+        assert_eq!(check_url("https://example.com", true), UrlKind::Navigation);
+        assert_eq!(check_url(" java\tscript:alert(1)", true), UrlKind::Unsafe);
+        assert_eq!(check_url("data:text/html,<script>", false), UrlKind::Unsafe);
+    }
+
+    #[test]
+    fn css() {
+        assert_eq!(
+            sanitize_css("background: url('../Images/bg.png'); color: red"),
+            "background: url('../Images/bg.png'); color: red"
+        );
+        assert_eq!(
+            sanitize_css("background: URL(\"https://example.com/track.png\")"),
+            "background: url()"
+        );
+        assert_eq!(
+            sanitize_css("@import url(https://example.com/a.css);\np { color: red }"),
+            "\np { color: red }"
+        );
+        assert_eq!(
+            sanitize_css("@import \"local.css\";"),
+            "@import \"local.css\";"
+        );
+    }
+
+    #[test]
+    fn css_non_ascii() {
+        assert_eq!(sanitize_css("@import \"éé.css\";"), "@import \"éé.css\";");
+        assert_eq!(
+            sanitize_css("@import url(\"https://example.com/ü.css\");ü"),
+            "ü"
+        );
+        assert_eq!(
+            sanitize_css("content: \"é\"; background: url(ä.png)"),
+            "content: \"é\"; background: url(ä.png)"
+        );
+    }
+
+    #[test]
+    fn content_documents() {
+        let page = br#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head>
+    <title>Chapter</title>
+    <base href="https://example.com/" />
+    <script type="text/javascript">alert(1)</script>
+</head>
+<body onload="alert(2)">
+    <section epub:type="chapter">
+        <p style="background: url(https://example.com/a.png)">Text <a href="javascript:alert(3)">link</a> <a href="https://example.com">site</a></p>
+        <img src="https://example.com/tracker.png" alt="tracker" />
+        <iframe src="https://example.com"><p>frame</p></iframe>
+        <blink>unwrapped</blink>
+        <noscript><p>fallback</p></noscript>
+    </section>
+</body>
+</html>"#;
+
+        let output = sanitize(page, SanitizeOptions::default());
+
+        assert!(!output.contains("script"), "{output}");
+        assert!(!output.contains("alert"), "{output}");
+        assert!(!output.contains("<base"), "{output}");
+        assert!(!output.contains("iframe"), "{output}");
+        assert!(!output.contains("frame<"), "{output}");
+        assert!(!output.contains("tracker.png"), "{output}");
+        assert!(!output.contains("blink"), "{output}");
+        assert!(output.contains("unwrapped"), "{output}");
+        assert!(output.contains("fallback"), "{output}");
+        assert!(output.contains(r#"href="https://example.com""#), "{output}");
+        assert!(output.contains(r#"epub:type="chapter""#), "{output}");
+        assert!(output.contains("background: url()"), "{output}");
+
+        let output = sanitize(
+            page,
+            SanitizeOptions {
+                allow_scripts: true,
+            },
+        );
+
+        assert!(output.contains("alert(1)"), "{output}");
+        assert!(output.contains(r#"onload="alert(2)""#), "{output}");
+        assert!(!output.contains("alert(3)"), "{output}");
+        assert!(!output.contains("fallback"), "{output}");
+    }
+
+    fn sanitize(input: &[u8], options: SanitizeOptions) -> String {
+        let output = update_attributes_with(
+            input,
+            &mut (),
+            |_, _, attr| attr,
+            |_, _, _, _| Ok(false),
+            &[],
+            Some(options),
+        )
+        .unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+}
@@ -1,10 +1,14 @@
 use std::{io, string::FromUtf8Error};
 
 use binstall_zip::result::ZipError;
-use mobi::MobiError;
+use id3::Error as Id3Error;
+use lopdf::Error as PdfError;
 use serde_xml_rs::Error as SerdeXmlError;
+use sevenz_rust::Error as SevenZError;
 use thiserror::Error as ThisError;
-use xml::reader::Error as XmlReaderError;
+#[cfg(feature = "rar")]
+use unrar::error::UnrarError;
+use xml::{reader::Error as XmlReaderError, writer::Error as XmlWriterError};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -16,8 +20,27 @@ pub enum Error {
     #[error("Zip Error: {0}")]
     Zip(#[from] ZipError),
 
+    #[cfg(feature = "rar")]
+    #[error("Rar Error: {0}")]
+    Rar(#[from] UnrarError),
+
+    #[error("7z Error: {0}")]
+    SevenZ(#[from] SevenZError),
+
+    #[error("PDF Error: {0}")]
+    Pdf(#[from] PdfError),
+
     #[error("Mobi Error: {0}")]
-    Mobi(#[from] MobiError),
+    Mobi(&'static str),
+
+    #[error("MP4 Error: {0}")]
+    Mp4(&'static str),
+
+    #[error("MP3 Error: {0}")]
+    Mp3(&'static str),
+
+    #[error("ID3 Error: {0}")]
+    Id3(#[from] Id3Error),
 
     #[error("Serde XML Error: {0}")]
     SerdeXml(#[from] SerdeXmlError),
@@ -25,12 +48,27 @@ pub enum Error {
     #[error("XML Reader Error: {0}")]
     XmlReader(#[from] XmlReaderError),
 
+    #[error("XML Writer Error: {0}")]
+    XmlWriter(#[from] XmlWriterError),
+
     #[error("FromUtf8 Error: {0}")]
     FromUtf8(#[from] FromUtf8Error),
 
     #[error("Missing Value For {0}")]
     MissingValueFor(&'static str),
 
+    #[error("Missing File {0}")]
+    MissingFile(String),
+
+    #[error("Invalid CFI: {0}")]
+    InvalidCfi(String),
+
     #[error("Missing Unique ID")]
     MissingUniqueId,
+
+    #[error("Unsupported: {0}")]
+    Unsupported(&'static str),
+
+    #[error("Resource is encrypted with DRM: {0}")]
+    Encrypted(String),
 }
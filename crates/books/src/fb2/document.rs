@@ -0,0 +1,148 @@
+use xml::reader::XmlEvent;
+
+use crate::Result;
+
+#[derive(Debug, Clone)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
+
+/// An element of the FictionBook document. Names are without their namespace prefix.
+#[derive(Debug, Clone, Default)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+impl Element {
+    /// Parse the whole document. Returns the root `<FictionBook>` element.
+    pub fn parse(value: &str) -> Result<Self> {
+        let reader = xml::ParserConfig::new()
+            .add_entity("nbsp", "\u{a0}")
+            .add_entity("copy", "©")
+            .add_entity("reg", "®")
+            .add_entity("laquo", "«")
+            .add_entity("raquo", "»")
+            .add_entity("mdash", "—")
+            .add_entity("ndash", "–")
+            .create_reader(value.as_bytes());
+
+        let mut stack = vec![Element::default()];
+
+        for event in reader {
+            match event? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => {
+                    stack.push(Element {
+                        name: name.local_name,
+                        attributes: attributes
+                            .into_iter()
+                            .map(|v| (v.name.local_name, v.value))
+                            .collect(),
+                        children: Vec::new(),
+                    });
+                }
+
+                XmlEvent::EndElement { .. } if stack.len() > 1 => {
+                    let element = stack.pop().unwrap();
+                    stack
+                        .last_mut()
+                        .unwrap()
+                        .children
+                        .push(Node::Element(element));
+                }
+
+                XmlEvent::Characters(text) | XmlEvent::CData(text) | XmlEvent::Whitespace(text) => {
+                    stack.last_mut().unwrap().children.push(Node::Text(text));
+                }
+
+                XmlEvent::EndDocument => break,
+
+                _ => (),
+            }
+        }
+
+        let document = stack.swap_remove(0);
+
+        Ok(document
+            .children
+            .into_iter()
+            .find_map(|v| match v {
+                Node::Element(v) => Some(v),
+                Node::Text(_) => None,
+            })
+            .unwrap_or_default())
+    }
+
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|v| match v {
+            Node::Element(v) => Some(v),
+            Node::Text(_) => None,
+        })
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.elements().find(|v| v.name == name)
+    }
+
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.elements().filter(move |v| v.name == name)
+    }
+
+    /// Follow a path of child element names. Eg. ["description", "title-info", "book-title"]
+    pub fn find_path(&self, path: &[&str]) -> Option<&Element> {
+        path.iter()
+            .try_fold(self, |element, name| element.child(name))
+    }
+
+    /// The text of the element and its children with the whitespace collapsed.
+    pub fn text(&self) -> String {
+        let mut value = String::new();
+        self.push_text(&mut value);
+
+        value.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    fn push_text(&self, value: &mut String) {
+        for child in &self.children {
+            match child {
+                Node::Text(text) => value.push_str(text),
+                Node::Element(element) => {
+                    element.push_text(value);
+                    // Paragraphs aren't separated by whitespace in the document.
+                    value.push(' ');
+                }
+            }
+        }
+    }
+
+    /// The text or `None` if it's empty.
+    pub fn non_empty_text(&self) -> Option<String> {
+        Some(self.text()).filter(|v| !v.is_empty())
+    }
+}
+
+/// An `<author>`, `<translator>` or similar element.
+pub fn person_name(element: &Element) -> Option<String> {
+    let name = ["first-name", "middle-name", "last-name"]
+        .into_iter()
+        .filter_map(|v| element.child(v)?.non_empty_text())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if name.is_empty() {
+        element.child("nickname")?.non_empty_text()
+    } else {
+        Some(name)
+    }
+}
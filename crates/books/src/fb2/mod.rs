@@ -0,0 +1,592 @@
+// For .fb2 and .fb2.zip files
+
+// http://www.fictionbook.org/index.php/Eng:XML_Schema_Fictionbook_2.1
+// https://wiki.mobileread.com/wiki/FB2
+
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+mod document;
+mod xhtml;
+
+use self::{
+    document::{person_name, Element},
+    xhtml::{image_path, Chapter},
+};
+use super::Book;
+use crate::{
+    epub::{update_attributes_with, update_value_with_relative_internal_path},
+    markup, BookSearch, ChapterText, Error, ReadSeek, Result, SanitizeOptions, TocItem,
+};
+
+/// An embedded `<binary>`. Decoded when it's requested.
+struct Binary {
+    id: String,
+    content_type: String,
+    data: String,
+}
+
+pub struct Fb2Book {
+    /// The `<description>` of the book.
+    description: Element,
+    /// The `<id>` of the document info.
+    document_id: Option<String>,
+
+    chapters: Vec<Chapter>,
+    binaries: Vec<Binary>,
+    toc: Vec<TocItem>,
+
+    /// The document after it's been decoded into UTF-8.
+    source: String,
+
+    pub chapter: usize,
+}
+
+impl Fb2Book {
+    fn parse(data: Vec<u8>) -> Result<Self> {
+        let source = decode_document(data);
+
+        let root = Element::parse(&source)?;
+
+        if root.name != "FictionBook" {
+            return Err(Error::MissingValueFor("FictionBook"));
+        }
+
+        let converted = xhtml::convert(&root);
+
+        let binaries = root
+            .children_named("binary")
+            .filter_map(|v| {
+                Some(Binary {
+                    id: v.attr("id")?.to_string(),
+                    content_type: v.attr("content-type").unwrap_or("image").to_string(),
+                    data: v.text(),
+                })
+            })
+            .collect();
+
+        let description = root.child("description").cloned().unwrap_or_default();
+
+        let document_id = description
+            .find_path(&["document-info", "id"])
+            .and_then(|v| v.non_empty_text());
+
+        Ok(Self {
+            description,
+            document_id,
+            chapters: converted.chapters,
+            binaries,
+            toc: converted.toc,
+            source,
+            chapter: 0,
+        })
+    }
+
+    fn title_info(&self) -> Option<&Element> {
+        self.description.child("title-info")
+    }
+
+    /// Books with an empty body don't have any chapters.
+    fn current_chapter(&self) -> Result<&Chapter> {
+        self.chapters
+            .get(self.chapter)
+            .ok_or_else(|| Error::MissingFile(format!("chapter {}", self.chapter)))
+    }
+
+    fn get_path_contents(&self, path: &str) -> Result<Vec<u8>> {
+        let path = path.trim_start_matches('/');
+
+        if let Some(chapter) = self.chapters.iter().find(|v| v.path == path) {
+            return Ok(chapter.contents.clone());
+        }
+
+        if let Some(binary) = self.find_binary(path) {
+            let data = binary
+                .data
+                .chars()
+                .filter(|v| !v.is_whitespace())
+                .collect::<String>();
+
+            return STANDARD
+                .decode(data)
+                .map_err(|_| Error::MissingFile(path.to_string()));
+        }
+
+        Err(Error::MissingFile(path.to_string()))
+    }
+
+    fn find_binary(&self, path: &str) -> Option<&Binary> {
+        let id = path.strip_prefix("images/")?;
+
+        self.binaries.iter().find(|v| v.id == id)
+    }
+
+    fn handle_update_attributes(
+        &mut self,
+        input: &[u8],
+        page_path: PathBuf,
+        prepend_to_urls: Option<&str>,
+        add_css: Option<&[&str]>,
+        sanitize: Option<SanitizeOptions>,
+    ) -> Result<Vec<u8>> {
+        update_attributes_with(
+            input,
+            self,
+            |book, element_name, mut attr| {
+                if element_name.local_name == "img" && attr.name.local_name == "src" {
+                    let path = update_value_with_relative_internal_path(
+                        page_path.clone(),
+                        &attr.value,
+                        None,
+                    );
+
+                    attr.value = match (book.find_binary(&path), book.get_path_contents(&path)) {
+                        (Some(binary), Ok(cont)) => format!(
+                            "data:{};charset=utf-8;base64,{}",
+                            binary.content_type,
+                            STANDARD.encode(cont)
+                        ),
+                        _ => update_value_with_relative_internal_path(
+                            page_path.clone(),
+                            &attr.value,
+                            prepend_to_urls,
+                        ),
+                    };
+                }
+
+                attr
+            },
+            |_, _, _, _| Ok(false),
+            if let Some(v) = add_css { v } else { &[] },
+            sanitize,
+        )
+    }
+}
+
+impl Book for Fb2Book {
+    fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::load_from_reader(File::open(path)?)
+    }
+
+    fn load_from_reader<R: ReadSeek + 'static>(mut reader: R) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        // A .fb2.zip only contains the .fb2 file.
+        if data.starts_with(b"PK\x03\x04") {
+            let mut archive = binstall_zip::ZipArchive::new(std::io::Cursor::new(data))?;
+
+            let name = archive
+                .file_names()
+                .find(|v| v.to_lowercase().ends_with(".fb2"))
+                .map(|v| v.to_string())
+                .ok_or_else(|| Error::MissingFile(String::from("*.fb2")))?;
+
+            let mut file = archive.by_name(&name)?;
+
+            data = Vec::new();
+            file.read_to_end(&mut data)?;
+        }
+
+        Self::parse(data)
+    }
+
+    fn get_table_of_contents(&mut self) -> Result<Option<Vec<TocItem>>> {
+        if self.toc.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(self.toc.clone()))
+        }
+    }
+
+    fn compute_hash(&mut self) -> Option<String> {
+        // Skip the description since it's the metadata.
+        let start = self.source.find("<body").unwrap_or_default();
+
+        Some(blake3::hash(&self.source.as_bytes()[start..]).to_string())
+    }
+
+    fn find(&self, search: BookSearch<'_>) -> Option<Vec<String>> {
+        let title_info = self.title_info()?;
+        let publish_info = self.description.child("publish-info");
+
+        let texts = |element: Option<&Element>, name: &str| {
+            let values = element
+                .into_iter()
+                .flat_map(|v| v.children_named(name))
+                .filter_map(|v| v.non_empty_text())
+                .collect::<Vec<_>>();
+
+            (!values.is_empty()).then_some(values)
+        };
+
+        let people = |name: &str| {
+            let values = title_info
+                .children_named(name)
+                .filter_map(person_name)
+                .collect::<Vec<_>>();
+
+            (!values.is_empty()).then_some(values)
+        };
+
+        match search {
+            BookSearch::Title => texts(Some(title_info), "book-title"),
+            BookSearch::Creator => people("author"),
+            BookSearch::Contributor => people("translator"),
+            BookSearch::Language => texts(Some(title_info), "lang"),
+            BookSearch::Publisher => texts(publish_info, "publisher"),
+            BookSearch::Source => texts(self.description.child("document-info"), "src-url"),
+
+            BookSearch::Identifier => {
+                let mut values = self.document_id.clone().into_iter().collect::<Vec<_>>();
+                values.extend(texts(publish_info, "isbn").unwrap_or_default());
+
+                (!values.is_empty()).then_some(values)
+            }
+
+            BookSearch::Date => {
+                let date = title_info.child("date").and_then(|v| {
+                    v.attr("value")
+                        .map(|v| v.to_string())
+                        .or(v.non_empty_text())
+                });
+
+                date.map(|v| vec![v])
+                    .or_else(|| texts(publish_info, "year"))
+            }
+
+            BookSearch::Description => texts(Some(title_info), "annotation"),
+
+            BookSearch::Subject => {
+                let mut values = texts(Some(title_info), "genre").unwrap_or_default();
+
+                values.extend(
+                    texts(Some(title_info), "keywords")
+                        .into_iter()
+                        .flatten()
+                        .flat_map(|v| {
+                            v.split(',')
+                                .map(|v| v.trim().to_string())
+                                .filter(|v| !v.is_empty())
+                                .collect::<Vec<_>>()
+                        }),
+                );
+
+                (!values.is_empty()).then_some(values)
+            }
+
+            BookSearch::CoverImage => {
+                let id = title_info
+                    .find_path(&["coverpage", "image"])?
+                    .attr("href")?
+                    .strip_prefix('#')?;
+
+                Some(vec![image_path(id)])
+            }
+
+            BookSearch::Format => Some(vec![String::from("application/x-fictionbook+xml")]),
+
+            // Lookup by title-info element name.
+            BookSearch::Other(v) => texts(Some(title_info), v),
+
+            BookSearch::Coverage | BookSearch::Relation | BookSearch::Rights | BookSearch::Type => {
+                None
+            }
+        }
+    }
+
+    fn get_unique_id(&self) -> Result<Cow<str>> {
+        if let Some(value) = self.document_id.as_deref() {
+            return Ok(Cow::Borrowed(value));
+        }
+
+        self.description
+            .find_path(&["publish-info", "isbn"])
+            .and_then(|v| v.non_empty_text())
+            .map(Cow::Owned)
+            .ok_or(Error::MissingValueFor("document-info id"))
+    }
+
+    fn get_files(&self) -> Vec<String> {
+        let mut files = self
+            .chapters
+            .iter()
+            .map(|v| v.path.clone())
+            .collect::<Vec<_>>();
+
+        files.extend(self.binaries.iter().map(|v| image_path(&v.id)));
+
+        files
+    }
+
+    fn get_root_file_dir(&self) -> &Path {
+        Path::new("")
+    }
+
+    fn get_page_path(&self) -> PathBuf {
+        self.current_chapter()
+            .map(|v| PathBuf::from(&v.path))
+            .unwrap_or_default()
+    }
+
+    fn read_page_raw_as_bytes(&mut self) -> Result<Vec<u8>> {
+        Ok(self.current_chapter()?.contents.clone())
+    }
+
+    fn read_page_as_text(&mut self) -> Result<Option<ChapterText>> {
+        let value = &self.current_chapter()?.contents;
+
+        Ok(Some(markup::html_to_text(&String::from_utf8_lossy(value))))
+    }
+
+    fn read_path_as_bytes(
+        &mut self,
+        path: &str,
+        prepend_to_urls: Option<&str>,
+        add_css: Option<&[&str]>,
+        sanitize: Option<SanitizeOptions>,
+    ) -> Result<Vec<u8>> {
+        let is_chapter = self
+            .chapters
+            .iter()
+            .any(|v| v.path == path.trim_start_matches('/'));
+
+        if is_chapter && (prepend_to_urls.is_some() || add_css.is_some() || sanitize.is_some()) {
+            let page_path = PathBuf::from(path);
+            let input = self.get_path_contents(path)?;
+
+            self.handle_update_attributes(&input, page_path, prepend_to_urls, add_css, sanitize)
+        } else {
+            self.get_path_contents(path)
+        }
+    }
+
+    fn read_page_as_bytes(
+        &mut self,
+        prepend_to_urls: Option<&str>,
+        add_css: Option<&[&str]>,
+        sanitize: Option<SanitizeOptions>,
+    ) -> Result<Vec<u8>> {
+        let page_path = self.get_page_path();
+        let input = self.read_page_raw_as_bytes()?;
+
+        self.handle_update_attributes(&input, page_path, prepend_to_urls, add_css, sanitize)
+    }
+
+    fn chapter_count(&self) -> usize {
+        self.chapters.len()
+    }
+
+    fn set_chapter(&mut self, value: usize) -> bool {
+        if value < self.chapter_count() {
+            self.chapter = value;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn next_chapter(&mut self) -> bool {
+        self.set_chapter(self.chapter + 1)
+    }
+
+    fn previous_chapter(&mut self) -> bool {
+        if self.chapter != 0 {
+            self.set_chapter(self.chapter - 1)
+        } else {
+            false
+        }
+    }
+
+    fn get_chapter(&self) -> usize {
+        self.chapter
+    }
+}
+
+/// Decode the document into UTF-8 using the encoding from its XML declaration. Eg. windows-1251
+fn decode_document(data: Vec<u8>) -> String {
+    let declaration_end = data
+        .windows(2)
+        .take(200)
+        .position(|v| v == b"?>")
+        .unwrap_or_default();
+
+    let declaration = String::from_utf8_lossy(&data[..declaration_end]).into_owned();
+
+    let label = declaration.split_once("encoding=").and_then(|(_, v)| {
+        let quote = v.chars().next()?;
+        v[1..].split(quote).next()
+    });
+
+    let encoding = label
+        .and_then(|v| encoding_rs::Encoding::for_label(v.as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8);
+
+    let (value, _, _) = encoding.decode(&data);
+
+    if encoding == encoding_rs::UTF_8 {
+        return value.into_owned();
+    }
+
+    // The XML parser only needs to know it's now UTF-8.
+    match label {
+        Some(label) => value.replacen(label, "utf-8", 1),
+        None => value.into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOOK: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<FictionBook xmlns="http://www.gribuser.ru/xml/fictionbook/2.0" xmlns:l="http://www.w3.org/1999/xlink">
+<description>
+  <title-info>
+    <genre>sf</genre>
+    <author><first-name>Jane</first-name><last-name>Doe</last-name></author>
+    <book-title>The Book</book-title>
+    <annotation><p>An example.</p></annotation>
+    <keywords>space, ships</keywords>
+    <date value="2001-02-03">2001</date>
+    <coverpage><image l:href="#cover.jpg"/></coverpage>
+    <lang>en</lang>
+    <translator><nickname>tr</nickname></translator>
+  </title-info>
+  <document-info><id>abc-123</id></document-info>
+  <publish-info><publisher>Pub</publisher><isbn>978-0</isbn></publish-info>
+</description>
+<body>
+  <title><p>The Book</p></title>
+  <epigraph><p>Words.</p><text-author>Someone</text-author></epigraph>
+  <section>
+    <title><p>Chapter 1</p></title>
+    <p>It <emphasis>was</emphasis> late.<a l:href="#n1" type="note">[1]</a></p>
+    <section id="part-a"><title><p>Part A</p></title><p>A</p></section>
+    <section><title><p>Part &amp; B</p></title><p>B</p></section>
+  </section>
+  <section>
+    <title><p>Chapter 2</p></title>
+    <image l:href="#cover.jpg"/>
+  </section>
+</body>
+<body name="notes">
+  <section id="n1"><title><p>1</p></title><p>A note.</p></section>
+</body>
+<binary id="cover.jpg" content-type="image/jpeg">/9j/
+4AAQ</binary>
+</FictionBook>"##;
+
+    #[test]
+    fn chapters_and_metadata() {
+        let mut book = Fb2Book::parse(BOOK.as_bytes().to_vec()).unwrap();
+
+        assert_eq!(book.chapter_count(), 4);
+        assert_eq!(
+            book.get_files(),
+            [
+                "text/part0000.xhtml",
+                "text/part0001.xhtml",
+                "text/part0002.xhtml",
+                "text/part0003.xhtml",
+                "images/cover.jpg"
+            ]
+        );
+
+        let toc = book.get_table_of_contents().unwrap().unwrap();
+        let titles = toc.iter().map(|v| v.title.as_str()).collect::<Vec<_>>();
+
+        assert_eq!(titles, ["The Book", "Chapter 1", "Chapter 2", "Notes"]);
+        assert_eq!(toc[1].chapter, 1);
+        assert_eq!(toc[1].fragment, None);
+        assert_eq!(toc[1].children.len(), 2);
+        assert_eq!(toc[1].children[0].fragment.as_deref(), Some("part-a"));
+        assert_eq!(toc[1].children[1].title, "Part & B");
+
+        book.set_chapter(1);
+        let page = book.read_page_raw_as_string().unwrap();
+
+        assert!(
+            page.contains(r#"<h1 class="title">Chapter 1</h1>"#),
+            "{page}"
+        );
+        assert!(page.contains("<p>It <em>was</em> late."), "{page}");
+        assert!(
+            page.contains(r#"<a href="part0003.xhtml#n1" class="note">[1]</a>"#),
+            "{page}"
+        );
+        assert!(
+            page.contains(r#"<h2 class="title">Part &amp; B</h2>"#),
+            "{page}"
+        );
+
+        book.set_chapter(2);
+        let page = book.read_page_raw_as_string().unwrap();
+
+        assert!(
+            page.contains(r#"<div class="image"><img src="../images/cover.jpg" alt=""/></div>"#),
+            "{page}"
+        );
+        assert_eq!(
+            book.read_path_as_bytes("images/cover.jpg", None, None, None)
+                .unwrap(),
+            [0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10]
+        );
+
+        assert_eq!(book.get_unique_id().unwrap(), "abc-123");
+        assert_eq!(book.find(BookSearch::Title).unwrap(), ["The Book"]);
+        assert_eq!(book.find(BookSearch::Creator).unwrap(), ["Jane Doe"]);
+        assert_eq!(book.find(BookSearch::Contributor).unwrap(), ["tr"]);
+        assert_eq!(book.find(BookSearch::Date).unwrap(), ["2001-02-03"]);
+        assert_eq!(
+            book.find(BookSearch::Identifier).unwrap(),
+            ["abc-123", "978-0"]
+        );
+        assert_eq!(
+            book.find(BookSearch::Subject).unwrap(),
+            ["sf", "space", "ships"]
+        );
+        assert_eq!(
+            book.find(BookSearch::CoverImage).unwrap(),
+            ["images/cover.jpg"]
+        );
+    }
+
+    #[test]
+    fn encoding() {
+        let (data, _, _) = encoding_rs::WINDOWS_1251.encode(
+            r#"<?xml version="1.0" encoding="windows-1251"?><FictionBook><description><title-info><book-title>Книга</book-title></title-info></description><body><section><p>Текст</p></section></body></FictionBook>"#,
+        );
+
+        let book = Fb2Book::parse(data.into_owned()).unwrap();
+
+        assert_eq!(book.find(BookSearch::Title).unwrap(), ["Книга"]);
+    }
+
+    #[test]
+    fn empty_body() {
+        let mut book = Fb2Book::parse(
+            br#"<?xml version="1.0" encoding="UTF-8"?><FictionBook><description><title-info><book-title>Empty</book-title></title-info></description><body></body></FictionBook>"#.to_vec(),
+        )
+        .unwrap();
+
+        assert_eq!(book.chapter_count(), 0);
+        assert_eq!(book.find(BookSearch::Title).unwrap(), ["Empty"]);
+        assert_eq!(book.get_page_path(), PathBuf::new());
+        assert!(book.read_page_raw_as_bytes().is_err());
+        assert!(book.read_page_as_bytes(None, None, None).is_err());
+        assert!(book.read_page_as_text().is_err());
+    }
+}
@@ -0,0 +1,455 @@
+//! Converts the FictionBook bodies into XHTML chapters.
+//!
+//! http://www.fictionbook.org/index.php/Eng:XML_Schema_Fictionbook_2.1
+
+use std::collections::HashMap;
+
+use common_local::TocItem;
+
+use super::document::{Element, Node};
+use crate::markup::{escape, wrap_in_xhtml};
+
+const DEFAULT_STYLE: &str = "\
+.title, .subtitle { text-align: center; }
+.epigraph, .cite { margin-left: 2em; font-style: italic; }
+.text-author { text-align: right; font-style: italic; }
+.poem { margin: 1em 2em; }
+.stanza { margin-bottom: 1em; }
+.verse { margin: 0; text-indent: 0; }
+.image { text-align: center; }
+.image img { max-width: 100%; }";
+
+pub struct Chapter {
+    pub path: String,
+    pub contents: Vec<u8>,
+}
+
+/// The chapters and table of contents of the book.
+pub struct Converted {
+    pub chapters: Vec<Chapter>,
+    pub toc: Vec<TocItem>,
+}
+
+/// The elements a chapter is made out of.
+enum ChapterSource<'a> {
+    /// A top level section of the main body.
+    Section(&'a Element),
+    /// The elements of the main body which aren't inside of a section. Eg. The book title and epigraph.
+    Loose(Vec<&'a Element>),
+    /// A whole body. Used for the notes and comments.
+    Body(&'a Element),
+}
+
+impl<'a> ChapterSource<'a> {
+    fn elements(&self) -> Vec<&'a Element> {
+        match self {
+            Self::Section(v) | Self::Body(v) => vec![*v],
+            Self::Loose(v) => v.clone(),
+        }
+    }
+}
+
+pub fn chapter_path(index: usize) -> String {
+    format!("text/part{index:04}.xhtml")
+}
+
+pub fn image_path(id: &str) -> String {
+    format!("images/{id}")
+}
+
+pub fn convert(root: &Element) -> Converted {
+    let mut sources = Vec::new();
+
+    for (index, body) in root.children_named("body").enumerate() {
+        // The first body is the main one. The others are notes or comments.
+        if index != 0 && body.attr("name").is_some() {
+            sources.push(ChapterSource::Body(body));
+            continue;
+        }
+
+        let mut loose = Vec::new();
+
+        for element in body.elements() {
+            if element.name == "section" {
+                if !loose.is_empty() {
+                    sources.push(ChapterSource::Loose(std::mem::take(&mut loose)));
+                }
+
+                sources.push(ChapterSource::Section(element));
+            } else {
+                loose.push(element);
+            }
+        }
+
+        if !loose.is_empty() {
+            sources.push(ChapterSource::Loose(loose));
+        }
+    }
+
+    // Which chapter each id is in so links can point to the right file.
+    let mut ids = HashMap::new();
+
+    for (index, source) in sources.iter().enumerate() {
+        for element in source.elements() {
+            collect_ids(element, index, &mut ids);
+        }
+    }
+
+    let stylesheet = root
+        .children_named("stylesheet")
+        .map(|v| v.text())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut chapters = Vec::new();
+    let mut toc = Vec::new();
+
+    for (index, source) in sources.iter().enumerate() {
+        let mut converter = Converter {
+            ids: &ids,
+            chapter: index,
+            output: String::new(),
+            generated_ids: 0,
+            paragraph_depth: 0,
+        };
+
+        let mut items = Vec::new();
+
+        let title = match source {
+            ChapterSource::Section(section) => {
+                converter.element(section, 0, &mut items);
+
+                // The chapter itself is linked to without a fragment.
+                if let [item] = items.as_mut_slice() {
+                    item.fragment = None;
+                }
+
+                section.child("title").and_then(|v| v.non_empty_text())
+            }
+
+            ChapterSource::Loose(elements) => {
+                for element in elements {
+                    converter.element(element, 0, &mut items);
+                }
+
+                let title = elements
+                    .iter()
+                    .find(|v| v.name == "title")
+                    .and_then(|v| v.non_empty_text());
+
+                items = title
+                    .clone()
+                    .map(|title| TocItem {
+                        title,
+                        chapter: index,
+                        fragment: None,
+                        children: Vec::new(),
+                    })
+                    .into_iter()
+                    .collect();
+
+                title
+            }
+
+            ChapterSource::Body(body) => {
+                for element in body.elements() {
+                    // Notes would fill the table of contents.
+                    converter.element(element, 0, &mut Vec::new());
+                }
+
+                let title = body
+                    .child("title")
+                    .and_then(|v| v.non_empty_text())
+                    .or_else(|| body.attr("name").map(capitalize));
+
+                items = title
+                    .clone()
+                    .map(|title| TocItem {
+                        title,
+                        chapter: index,
+                        fragment: None,
+                        children: Vec::new(),
+                    })
+                    .into_iter()
+                    .collect();
+
+                title
+            }
+        };
+
+        toc.append(&mut items);
+
+        let mut head = String::new();
+
+        if let Some(title) = title {
+            head.push_str("<title>");
+            head.push_str(&escape(&title));
+            head.push_str("</title>");
+        }
+
+        head.push_str("<style>");
+        head.push_str(&escape(DEFAULT_STYLE));
+
+        if !stylesheet.is_empty() {
+            head.push('\n');
+            head.push_str(&escape(&stylesheet));
+        }
+
+        head.push_str("</style>");
+
+        chapters.push(Chapter {
+            path: chapter_path(index),
+            contents: wrap_in_xhtml(&head, &converter.output).into_bytes(),
+        });
+    }
+
+    Converted { chapters, toc }
+}
+
+fn collect_ids(element: &Element, chapter: usize, ids: &mut HashMap<String, usize>) {
+    if let Some(id) = element.attr("id") {
+        ids.entry(id.to_string()).or_insert(chapter);
+    }
+
+    for child in element.elements() {
+        collect_ids(child, chapter, ids);
+    }
+}
+
+struct Converter<'a> {
+    ids: &'a HashMap<String, usize>,
+    chapter: usize,
+    output: String,
+
+    /// Used for the ids of sections which don't have one so they can be in the table of contents.
+    generated_ids: usize,
+    /// Images inside of paragraphs are inline.
+    paragraph_depth: usize,
+}
+
+impl<'a> Converter<'a> {
+    fn nodes(&mut self, element: &Element, depth: usize, toc: &mut Vec<TocItem>) {
+        for child in &element.children {
+            match child {
+                Node::Text(text) => self.output.push_str(&escape(text)),
+                Node::Element(child) => self.element(child, depth, toc),
+            }
+        }
+    }
+
+    fn element(&mut self, element: &Element, depth: usize, toc: &mut Vec<TocItem>) {
+        match element.name.as_str() {
+            "section" => {
+                let title = element.child("title").and_then(|v| v.non_empty_text());
+
+                let id = match element.attr("id") {
+                    Some(id) => Some(id.to_string()),
+                    None if title.is_some() => {
+                        self.generated_ids += 1;
+                        Some(format!("section-{}-{}", self.chapter, self.generated_ids))
+                    }
+                    None => None,
+                };
+
+                self.output.push_str(r#"<div class="section""#);
+
+                if let Some(id) = id.as_deref() {
+                    self.push_attr("id", id);
+                }
+
+                self.output.push('>');
+
+                let mut children = Vec::new();
+                self.nodes(element, depth + 1, &mut children);
+
+                self.output.push_str("</div>");
+
+                match title {
+                    Some(title) => toc.push(TocItem {
+                        title,
+                        chapter: self.chapter,
+                        fragment: id,
+                        children,
+                    }),
+                    None => toc.append(&mut children),
+                }
+            }
+
+            "title" => {
+                let level = depth.clamp(1, 6);
+
+                self.output
+                    .push_str(&format!(r#"<h{level} class="title">"#));
+
+                let mut is_first = true;
+
+                for line in element.elements().filter(|v| v.name == "p") {
+                    if !is_first {
+                        self.output.push_str("<br/>");
+                    }
+
+                    is_first = false;
+
+                    self.paragraph_depth += 1;
+                    self.nodes(line, depth, toc);
+                    self.paragraph_depth -= 1;
+                }
+
+                self.output.push_str(&format!("</h{level}>"));
+            }
+
+            "p" => self.wrap(element, "p", None, depth, toc),
+            "subtitle" => self.wrap(element, "p", Some("subtitle"), depth, toc),
+            "v" => self.wrap(element, "p", Some("verse"), depth, toc),
+            "text-author" => self.wrap(element, "p", Some("text-author"), depth, toc),
+            "date" => self.wrap(element, "p", Some("date"), depth, toc),
+
+            "epigraph" => self.wrap(element, "blockquote", Some("epigraph"), depth, toc),
+            "cite" => self.wrap(element, "blockquote", Some("cite"), depth, toc),
+            "poem" => self.wrap(element, "div", Some("poem"), depth, toc),
+            "stanza" => self.wrap(element, "div", Some("stanza"), depth, toc),
+            "annotation" => self.wrap(element, "div", Some("annotation"), depth, toc),
+
+            "emphasis" => self.wrap(element, "em", None, depth, toc),
+            "strong" => self.wrap(element, "strong", None, depth, toc),
+            "strikethrough" => self.wrap(element, "del", None, depth, toc),
+            "sub" => self.wrap(element, "sub", None, depth, toc),
+            "sup" => self.wrap(element, "sup", None, depth, toc),
+            "code" => self.wrap(element, "code", None, depth, toc),
+            "style" => self.wrap(element, "span", element.attr("name"), depth, toc),
+
+            "table" => self.wrap(element, "table", None, depth, toc),
+            "tr" => self.wrap(element, "tr", None, depth, toc),
+            "th" => self.wrap(element, "th", None, depth, toc),
+            "td" => self.wrap(element, "td", None, depth, toc),
+
+            "empty-line" => self.output.push_str(r#"<br class="empty-line"/>"#),
+
+            "a" => {
+                self.output.push_str("<a");
+
+                if let Some(href) = element.attr("href") {
+                    let href = match href.strip_prefix('#') {
+                        Some(id) => match self.ids.get(id) {
+                            Some(&chapter) => format!("{}#{id}", chapter_file_name(chapter)),
+                            None => href.to_string(),
+                        },
+                        None => href.to_string(),
+                    };
+
+                    self.push_attr("href", &href);
+                }
+
+                if element.attr("type") == Some("note") {
+                    self.push_attr("class", "note");
+                }
+
+                self.output.push('>');
+                self.nodes(element, depth, toc);
+                self.output.push_str("</a>");
+            }
+
+            "image" => {
+                let Some(id) = element.attr("href").and_then(|v| v.strip_prefix('#')) else {
+                    return;
+                };
+
+                let is_block = self.paragraph_depth == 0;
+
+                if is_block {
+                    self.output.push_str(r#"<div class="image""#);
+
+                    if let Some(id) = element.attr("id") {
+                        self.push_attr("id", id);
+                    }
+
+                    self.output.push('>');
+                }
+
+                self.output.push_str("<img");
+                self.push_attr("src", &format!("../{}", image_path(id)));
+                self.push_attr(
+                    "alt",
+                    element.attr("alt").or(element.attr("title")).unwrap_or(""),
+                );
+                self.output.push_str("/>");
+
+                if is_block {
+                    self.output.push_str("</div>");
+                }
+            }
+
+            // Unknown elements only keep their contents.
+            _ => self.nodes(element, depth, toc),
+        }
+    }
+
+    /// Write the element as `name` with the contents converted.
+    fn wrap(
+        &mut self,
+        element: &Element,
+        name: &str,
+        class: Option<&str>,
+        depth: usize,
+        toc: &mut Vec<TocItem>,
+    ) {
+        let is_paragraph = name == "p" || name == "td" || name == "th";
+
+        self.output.push('<');
+        self.output.push_str(name);
+
+        if let Some(class) = class {
+            self.push_attr("class", class);
+        }
+
+        for (attr, value) in &element.attributes {
+            if matches!(
+                attr.as_str(),
+                "id" | "colspan" | "rowspan" | "align" | "valign"
+            ) {
+                self.push_attr(attr, value);
+            }
+        }
+
+        self.output.push('>');
+
+        if is_paragraph {
+            self.paragraph_depth += 1;
+        }
+
+        self.nodes(element, depth, toc);
+
+        if is_paragraph {
+            self.paragraph_depth -= 1;
+        }
+
+        self.output.push_str("</");
+        self.output.push_str(name);
+        self.output.push('>');
+    }
+
+    fn push_attr(&mut self, name: &str, value: &str) {
+        self.output.push(' ');
+        self.output.push_str(name);
+        self.output.push_str("=\"");
+        self.output.push_str(&escape(value));
+        self.output.push('"');
+    }
+}
+
+fn chapter_file_name(index: usize) -> String {
+    chapter_path(index)
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn capitalize(value: &str) -> String {
+    let mut chars = value.chars();
+
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -1,19 +1,39 @@
 use std::{
     borrow::Cow,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    ops::Range,
     path::{Path, PathBuf},
 };
 
+pub mod audio;
 pub mod cb;
 pub mod epub;
+pub mod fb2;
 pub mod mobi;
+pub mod pdf;
+pub mod text;
+
+mod markup;
 
 pub mod error;
 pub use error::*;
 
-// TODO: path: &str -> path: &Path
+pub use common_local::{reader::Rendition, TocItem};
+pub use epub::SanitizeOptions;
+
+/// A source a book can be read from. Eg. A file, or an uploaded file kept in memory.
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
 
 pub trait Book: Send {
-    fn load_from_path(path: &str) -> Result<Self>
+    fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Self>
+    where
+        Self: Sized;
+
+    /// Load the book without it being on the filesystem. Eg. An uploaded file or a file inside of another archive.
+    fn load_from_reader<R: ReadSeek + 'static>(reader: R) -> Result<Self>
     where
         Self: Sized;
 
@@ -29,11 +49,42 @@ pub trait Book: Send {
         Ok(String::from_utf8(self.read_page_raw_as_bytes()?)?)
     }
 
+    /// Get the page as plain text. Returns `None` if the book has no text. Eg. Comic Books
+    fn read_page_as_text(&mut self) -> Result<Option<ChapterText>> {
+        Ok(None)
+    }
+
+    /// Get the plain text of every chapter. The current chapter is kept.
+    fn read_all_as_text(&mut self) -> Result<Vec<ChapterText>> {
+        let current = self.get_chapter();
+        let mut items = Vec::new();
+
+        for chapter in 0..self.chapter_count() {
+            self.set_chapter(chapter);
+
+            match self.read_page_as_text() {
+                Ok(Some(value)) => items.push(value),
+                Ok(None) => items.push(ChapterText::default()),
+                Err(e) => {
+                    self.set_chapter(current);
+                    return Err(e);
+                }
+            }
+        }
+
+        self.set_chapter(current);
+
+        Ok(items)
+    }
+
     /// Get the page with urls relative to the internal zip structure
+    ///
+    /// Scripts and remote resources are removed if `sanitize` is set.
     fn read_page_as_bytes(
         &mut self,
         prepend_to_urls: Option<&str>,
         add_css: Option<&[&str]>,
+        sanitize: Option<SanitizeOptions>,
     ) -> Result<Vec<u8>>;
 
     /// Get the page with urls relative to the internal zip structure
@@ -41,10 +92,13 @@ pub trait Book: Send {
         &mut self,
         prepend_to_urls: Option<&str>,
         add_css: Option<&[&str]>,
+        sanitize: Option<SanitizeOptions>,
     ) -> Result<String> {
-        Ok(String::from_utf8(
-            self.read_page_as_bytes(prepend_to_urls, add_css)?,
-        )?)
+        Ok(String::from_utf8(self.read_page_as_bytes(
+            prepend_to_urls,
+            add_css,
+            sanitize,
+        )?)?)
     }
 
     fn read_path_as_bytes(
@@ -52,6 +106,7 @@ pub trait Book: Send {
         path: &str,
         prepend_to_urls: Option<&str>,
         add_css: Option<&[&str]>,
+        sanitize: Option<SanitizeOptions>,
     ) -> Result<Vec<u8>>;
 
     fn read_path_as_string(
@@ -59,11 +114,13 @@ pub trait Book: Send {
         path: &str,
         prepend_to_urls: Option<&str>,
         add_css: Option<&[&str]>,
+        sanitize: Option<SanitizeOptions>,
     ) -> Result<String> {
         Ok(String::from_utf8(self.read_path_as_bytes(
             path,
             prepend_to_urls,
             add_css,
+            sanitize,
         )?)?)
     }
 
@@ -71,7 +128,8 @@ pub trait Book: Send {
         Vec::new()
     }
 
-    fn get_table_of_contents(&mut self) -> Result<Option<Vec<(String, usize)>>> {
+    /// The table of contents as a tree.
+    fn get_table_of_contents(&mut self) -> Result<Option<Vec<TocItem>>> {
         Ok(None)
     }
 
@@ -84,11 +142,50 @@ pub trait Book: Send {
 
     fn get_root_file_dir(&self) -> &Path;
 
+    /// How the book wants to be displayed. Reflowable unless the format says otherwise.
+    fn get_rendition(&self) -> Rendition {
+        Rendition::default()
+    }
+
+    /// The rendition of the current chapter. Fixed layout chapters include their viewport.
+    fn get_chapter_rendition(&mut self) -> Result<Rendition> {
+        Ok(self.get_rendition())
+    }
+
     fn find(&self, search: BookSearch<'_>) -> Option<Vec<String>>;
 
     fn compute_hash(&mut self) -> Option<String>;
 }
 
+/// The plain text of a chapter.
+///
+/// Offsets are in characters, not bytes, and are the same every time the chapter is read.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ChapterText {
+    /// Paragraphs are separated by an empty line. Line breaks inside of a paragraph are kept.
+    pub text: String,
+    /// The character range of each paragraph in the text.
+    pub paragraphs: Vec<Range<usize>>,
+}
+
+impl ChapterText {
+    pub fn char_count(&self) -> usize {
+        self.paragraphs.last().map(|v| v.end).unwrap_or_default()
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.text
+            .split_whitespace()
+            .filter(|v| v.chars().any(|c| c.is_alphanumeric()))
+            .count()
+    }
+
+    /// The paragraph containing the character offset.
+    pub fn paragraph_at(&self, offset: usize) -> Option<usize> {
+        self.paragraphs.iter().position(|v| v.contains(&offset))
+    }
+}
+
 pub enum BookSearch<'a> {
     // Required
     Title,
@@ -119,11 +216,297 @@ impl<'a> From<&'a str> for BookSearch<'a> {
     }
 }
 
-pub fn load_from_path(path: &str) -> Result<Option<Box<dyn Book>>> {
-    Ok(match path.rsplit_once('.').map(|v| v.1) {
-        Some("cbz") => Some(Box::new(cb::ComicBook::load_from_path(path)?)),
-        Some("epub") => Some(Box::new(epub::EpubBook::load_from_path(path)?)),
+/// The format of a book found from the first bytes of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookFormat {
+    Epub,
+    /// Zip archive which isn't an EPUB. Eg. cbz
+    Zip,
+    Rar,
+    SevenZ,
+    /// Optionally gzip compressed.
+    Tar,
+    /// Also AZW3. Plain PalmDOC files aren't supported.
+    Mobi,
+    Pdf,
+    /// FictionBook. Optionally zipped.
+    Fb2,
+    /// Standalone HTML document. Plain text and Markdown are only found by their extension.
+    Html,
+    /// M4B and M4A audio.
+    Mp4,
+    Mp3,
+}
+
+impl BookFormat {
+    /// Detect the format by its magic bytes. The reader is rewound to the start.
+    pub fn detect<R: Read + Seek>(reader: &mut R) -> Result<Option<Self>> {
+        reader.seek(SeekFrom::Start(0))?;
+
+        let mut header = Vec::new();
+        reader.by_ref().take(1024).read_to_end(&mut header)?;
+
+        let format = if header.starts_with(b"PK\x03\x04") {
+            // The mimetype file should be stored first and uncompressed.
+            if header.get(30..38) == Some(b"mimetype")
+                && header.get(38..58) == Some(b"application/epub+zip")
+            {
+                Some(Self::Epub)
+            } else {
+                reader.seek(SeekFrom::Start(0))?;
+
+                let names = binstall_zip::ZipArchive::new(&mut *reader)
+                    .map(|v| v.file_names().map(|v| v.to_string()).collect::<Vec<_>>())
+                    .unwrap_or_default();
+
+                Some(if names.iter().any(|v| v == "META-INF/container.xml") {
+                    Self::Epub
+                } else if names.iter().any(|v| v.to_lowercase().ends_with(".fb2")) {
+                    // A .fb2.zip
+                    Self::Fb2
+                } else {
+                    Self::Zip
+                })
+            }
+        } else if header.starts_with(b"Rar!\x1A\x07") {
+            Some(Self::Rar)
+        } else if header.starts_with(b"7z\xBC\xAF\x27\x1C") {
+            Some(Self::SevenZ)
+        } else if is_tar(&header) || (header.starts_with(&GZIP_MAGIC) && is_gzip_tar(reader)?) {
+            Some(Self::Tar)
+        } else if header.get(60..68) == Some(b"BOOKMOBI") {
+            Some(Self::Mobi)
+        } else if header.windows(12).any(|v| v == b"<FictionBook") {
+            Some(Self::Fb2)
+        } else if text::is_html(&header) {
+            Some(Self::Html)
+        } else if header.trim_ascii_start().starts_with(b"%PDF-") {
+            Some(Self::Pdf)
+        } else if has_text_bom(&header) {
+            // UTF-16LE starts with the bytes of an MPEG frame sync.
+            None
+        } else if header.get(4..8) == Some(b"ftyp") {
+            Some(Self::Mp4)
+        } else if header.starts_with(b"ID3") || is_mpeg_frame(&header) {
+            Some(Self::Mp3)
+        } else {
+            None
+        };
+
+        reader.seek(SeekFrom::Start(0))?;
+
+        Ok(format)
+    }
+}
+
+pub(crate) const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+fn has_text_bom(header: &[u8]) -> bool {
+    [&b"\xEF\xBB\xBF"[..], b"\xFF\xFE", b"\xFE\xFF"]
+        .iter()
+        .any(|bom| header.starts_with(bom))
+}
+
+/// A frame sync followed by a valid version, layer, bitrate and sample rate.
+fn is_mpeg_frame(header: &[u8]) -> bool {
+    let [0xFF, b1, b2, ..] = *header else {
+        return false;
+    };
+
+    b1 & 0xE0 == 0xE0
+        // Reserved version
+        && (b1 >> 3) & 0b11 != 0b01
+        // Reserved layer
+        && (b1 >> 1) & 0b11 != 0b00
+        // Invalid bitrate
+        && b2 >> 4 != 0b1111
+        // Reserved sample rate
+        && (b2 >> 2) & 0b11 != 0b11
+}
+
+fn is_tar(header: &[u8]) -> bool {
+    header.get(257..262) == Some(b"ustar")
+}
+
+/// Gzip is used for more than tar archives so the start of it is decompressed.
+fn is_gzip_tar<R: Read + Seek>(reader: &mut R) -> Result<bool> {
+    reader.seek(SeekFrom::Start(0))?;
+
+    let mut header = Vec::new();
+
+    // Not a valid gzip stream.
+    if flate2::read::GzDecoder::new(reader.by_ref())
+        .take(512)
+        .read_to_end(&mut header)
+        .is_err()
+    {
+        return Ok(false);
+    }
+
+    Ok(is_tar(&header))
+}
+
+pub fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Option<Box<dyn Book>>> {
+    let path = path.as_ref();
 
-        _ => None,
+    // A folder of audio tracks or images.
+    if path.is_dir() {
+        return Ok(if audio::is_audio_book_folder(path) {
+            Some(Box::new(audio::AudioBook::load_from_path(path)?))
+        } else if cb::is_image_folder(path) {
+            Some(Box::new(cb::ComicBook::load_from_path(path)?))
+        } else {
+            None
+        });
+    }
+
+    Ok(match BookFormat::detect(&mut File::open(path)?)? {
+        Some(BookFormat::Epub) => Some(Box::new(epub::EpubBook::load_from_path(path)?)),
+        Some(BookFormat::Zip | BookFormat::Rar | BookFormat::SevenZ | BookFormat::Tar) => {
+            Some(Box::new(cb::ComicBook::load_from_path(path)?))
+        }
+        Some(BookFormat::Mobi) => Some(Box::new(mobi::MobiBook::load_from_path(path)?)),
+        Some(BookFormat::Pdf) => Some(Box::new(pdf::PdfBook::load_from_path(path)?)),
+        Some(BookFormat::Fb2) => Some(Box::new(fb2::Fb2Book::load_from_path(path)?)),
+        Some(BookFormat::Html) => Some(Box::new(text::TextBook::load_from_path(path)?)),
+        Some(BookFormat::Mp4 | BookFormat::Mp3) => {
+            Some(Box::new(audio::AudioBook::load_from_path(path)?))
+        }
+
+        // Text files don't have any magic bytes.
+        None => match path
+            .extension()
+            .and_then(|v| text::TextFormat::from_extension(&v.to_string_lossy()))
+        {
+            Some(_) => Some(Box::new(text::TextBook::load_from_path(path)?)),
+            None => None,
+        },
+    })
+}
+
+pub fn load_from_reader<R: ReadSeek + 'static>(mut reader: R) -> Result<Option<Box<dyn Book>>> {
+    Ok(match BookFormat::detect(&mut reader)? {
+        Some(BookFormat::Epub) => Some(Box::new(epub::EpubBook::load_from_reader(reader)?)),
+        Some(BookFormat::Zip | BookFormat::Rar | BookFormat::SevenZ | BookFormat::Tar) => {
+            Some(Box::new(cb::ComicBook::load_from_reader(reader)?))
+        }
+        Some(BookFormat::Mobi) => Some(Box::new(mobi::MobiBook::load_from_reader(reader)?)),
+        Some(BookFormat::Pdf) => Some(Box::new(pdf::PdfBook::load_from_reader(reader)?)),
+        Some(BookFormat::Fb2) => Some(Box::new(fb2::Fb2Book::load_from_reader(reader)?)),
+        Some(BookFormat::Html) => Some(Box::new(text::TextBook::load_from_reader(reader)?)),
+        Some(BookFormat::Mp4 | BookFormat::Mp3) => {
+            Some(Box::new(audio::AudioBook::load_from_reader(reader)?))
+        }
+
+        None => None,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use binstall_zip::{write::FileOptions, CompressionMethod, ZipWriter};
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    fn detect(data: &[u8]) -> Option<BookFormat> {
+        BookFormat::detect(&mut Cursor::new(data)).unwrap()
+    }
+
+    fn fixture(name: &str) -> Vec<u8> {
+        std::fs::read(format!("{}/fixtures/{name}", env!("CARGO_MANIFEST_DIR"))).unwrap()
+    }
+
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+        for (name, contents) in files {
+            zip.start_file(
+                *name,
+                FileOptions::default().compression_method(CompressionMethod::Stored),
+            )
+            .unwrap();
+            zip.write_all(contents).unwrap();
+        }
+
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// A Palm Database header with the type and creator.
+    fn palm_database(type_creator: &[u8; 8]) -> Vec<u8> {
+        let mut data = vec![0; 78];
+        data[60..68].copy_from_slice(type_creator);
+        data
+    }
+
+    #[test]
+    fn detect_magic_bytes() {
+        assert_eq!(
+            detect(&zip(&[("mimetype", b"application/epub+zip")])),
+            Some(BookFormat::Epub)
+        );
+        assert_eq!(
+            detect(&zip(&[("META-INF/container.xml", b"")])),
+            Some(BookFormat::Epub)
+        );
+        assert_eq!(detect(&zip(&[("1.jpg", b"")])), Some(BookFormat::Zip));
+        assert_eq!(detect(&zip(&[("book.FB2", b"")])), Some(BookFormat::Fb2));
+
+        assert_eq!(detect(&fixture("comic.cbr")), Some(BookFormat::Rar));
+        assert_eq!(detect(&fixture("comic.cb7")), Some(BookFormat::SevenZ));
+        assert_eq!(detect(&fixture("comic.cbt")), Some(BookFormat::Tar));
+        assert_eq!(detect(&fixture("comic-gzip.cbt")), Some(BookFormat::Tar));
+
+        assert_eq!(detect(&palm_database(b"BOOKMOBI")), Some(BookFormat::Mobi));
+        assert_eq!(detect(b"%PDF-1.7\n"), Some(BookFormat::Pdf));
+        assert_eq!(detect(b"\r\n%PDF-1.4\n"), Some(BookFormat::Pdf));
+        assert_eq!(
+            detect(b"<?xml version=\"1.0\"?>\n<FictionBook>"),
+            Some(BookFormat::Fb2)
+        );
+        assert_eq!(
+            detect(b"<!DOCTYPE html><html></html>"),
+            Some(BookFormat::Html)
+        );
+        assert_eq!(detect(b"\0\0\0\x20ftypM4B \0\0\0\0"), Some(BookFormat::Mp4));
+        assert_eq!(detect(b"ID3\x04\0\0\0\0\0\0"), Some(BookFormat::Mp3));
+        assert_eq!(detect(b"\xFF\xFB\x90\x00"), Some(BookFormat::Mp3));
+        assert_eq!(detect(b"Plain text"), None);
+    }
+
+    #[test]
+    fn detect_text_bom() {
+        assert_eq!(detect(b"\xFF\xFEh\0i\0"), None);
+        assert_eq!(detect(b"\xFE\xFF\0h\0i"), None);
+        assert_eq!(detect(b"\xEF\xBB\xBFhi"), None);
+
+        // Reserved version and layer.
+        assert_eq!(detect(b"\xFF\xE9\x90\x00"), None);
+        assert_eq!(detect(b"\xFF\xF9\x90\x00"), None);
+    }
+
+    #[test]
+    fn detect_false_positives() {
+        // Only gzip compressed tar archives.
+        assert_eq!(detect(&gzip(b"Plain text")), None);
+        assert_eq!(detect(&[0x1F, 0x8B, 0, 0]), None);
+
+        // The PDF header has to be at the start.
+        assert_eq!(
+            detect(b"<html><body>%PDF-1.7</body></html>"),
+            Some(BookFormat::Html)
+        );
+        assert_eq!(detect(b"Notes about %PDF-1.7 files"), None);
+
+        // Plain PalmDOC isn't readable by the MOBI reader.
+        assert_eq!(detect(&palm_database(b"TEXtREAd")), None);
+    }
+}
@@ -0,0 +1,667 @@
+//! Helpers for turning loosely written HTML into XHTML the reader is able to parse.
+
+use std::borrow::Cow;
+
+use crate::ChapterText;
+
+pub(crate) const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Elements which implicitly close an open <p>
+const CLOSES_PARAGRAPH: [&str; 21] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "div",
+    "dl",
+    "fieldset",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "ol",
+    "p",
+    "table",
+    "ul",
+];
+
+/// Elements which start a new paragraph in the plain text.
+const TEXT_BLOCK_ELEMENTS: [&str; 32] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "caption",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "tr",
+    "td",
+    "ul",
+];
+
+/// Elements whose contents aren't part of the plain text.
+const TEXT_SKIPPED_ELEMENTS: [&str; 5] = ["head", "script", "style", "template", "rp"];
+
+/// Named entities which the XML parser doesn't know about.
+const NAMED_ENTITIES: [(&str, u32); 32] = [
+    ("nbsp", 160),
+    ("iexcl", 161),
+    ("cent", 162),
+    ("pound", 163),
+    ("yen", 165),
+    ("sect", 167),
+    ("copy", 169),
+    ("laquo", 171),
+    ("reg", 174),
+    ("deg", 176),
+    ("plusmn", 177),
+    ("para", 182),
+    ("middot", 183),
+    ("raquo", 187),
+    ("frac12", 189),
+    ("iquest", 191),
+    ("times", 215),
+    ("eacute", 233),
+    ("divide", 247),
+    ("ndash", 8211),
+    ("mdash", 8212),
+    ("lsquo", 8216),
+    ("rsquo", 8217),
+    ("sbquo", 8218),
+    ("ldquo", 8220),
+    ("rdquo", 8221),
+    ("bdquo", 8222),
+    ("dagger", 8224),
+    ("bull", 8226),
+    ("hellip", 8230),
+    ("euro", 8364),
+    ("trade", 8482),
+];
+
+/// Converts HTML into well-formed XHTML.
+///
+/// Closes void and unclosed elements, quotes attribute values, drops namespaced elements
+/// (eg. `<mbp:pagebreak/>`), comments and doctypes, and converts named entities into numeric ones.
+pub(crate) fn html_to_xhtml(input: &str) -> String {
+    let mut output = String::with_capacity(input.len() + input.len() / 8);
+    let mut stack: Vec<String> = Vec::new();
+
+    let mut rest = input;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("<!--") {
+            let end = after.find("-->").map_or(after.len(), |v| v + 3);
+            rest = &after[end..];
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            let end = rest.find('>').map_or(rest.len(), |v| v + 1);
+            rest = &rest[end..];
+        } else if let Some(after) = rest.strip_prefix("</") {
+            let end = after.find('>').map_or(after.len(), |v| v + 1);
+            let name = after[..end].trim_end_matches('>').trim().to_lowercase();
+            rest = &after[end..];
+
+            if let Some(pos) = stack.iter().rposition(|v| *v == name) {
+                for name in stack.drain(pos..).rev() {
+                    output.push_str("</");
+                    output.push_str(&name);
+                    output.push('>');
+                }
+            }
+        } else if rest.starts_with('<') && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic())
+        {
+            let (tag, consumed) = parse_tag(rest);
+            rest = &rest[consumed..];
+
+            // Namespaced elements would require their namespace to be declared.
+            if tag.name.contains(':') {
+                continue;
+            }
+
+            if CLOSES_PARAGRAPH.contains(&tag.name.as_str())
+                && stack.last().map(|v| v.as_str()) == Some("p")
+            {
+                stack.pop();
+                output.push_str("</p>");
+            }
+
+            output.push('<');
+            output.push_str(&tag.name);
+
+            for (name, value) in &tag.attributes {
+                output.push(' ');
+                output.push_str(name);
+                output.push_str("=\"");
+                push_escaped(&mut output, value, true);
+                output.push('"');
+            }
+
+            if tag.self_closing || VOID_ELEMENTS.contains(&tag.name.as_str()) {
+                output.push_str("/>");
+            } else if tag.name == "script" || tag.name == "style" {
+                output.push('>');
+
+                // Raw text until the closing tag.
+                let closing = format!("</{}", tag.name);
+                let end = find_ignore_case(rest, &closing).unwrap_or(rest.len());
+
+                push_escaped(&mut output, &rest[..end], false);
+
+                output.push_str("</");
+                output.push_str(&tag.name);
+                output.push('>');
+
+                rest = &rest[end..];
+                let end = rest.find('>').map_or(rest.len(), |v| v + 1);
+                rest = &rest[end..];
+            } else {
+                output.push('>');
+                stack.push(tag.name);
+            }
+        } else {
+            let first_len = rest.chars().next().map_or(1, |c| c.len_utf8());
+            let end = rest[first_len..]
+                .find('<')
+                .map_or(rest.len(), |v| v + first_len);
+            push_escaped(&mut output, &rest[..end], false);
+            rest = &rest[end..];
+        }
+    }
+
+    for name in stack.into_iter().rev() {
+        output.push_str("</");
+        output.push_str(&name);
+        output.push('>');
+    }
+
+    output
+}
+
+/// Wraps the head and body contents into a full XHTML document.
+pub(crate) fn wrap_in_xhtml(head: &str, body: &str) -> String {
+    format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="utf-8"?>"#,
+            "\n",
+            r#"<html xmlns="http://www.w3.org/1999/xhtml"><head>{}</head><body>{}</body></html>"#
+        ),
+        head, body
+    )
+}
+
+/// Converts (X)HTML into plain text.
+///
+/// Whitespace is collapsed, `<br>` becomes a new line and block elements are separated by an empty line.
+pub(crate) fn html_to_text(input: &str) -> ChapterText {
+    let mut builder = TextBuilder::default();
+    let mut pre_depth = 0usize;
+
+    let mut rest = input;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("<!--") {
+            let end = after.find("-->").map_or(after.len(), |v| v + 3);
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix("<![CDATA[") {
+            let end = after.find("]]>").unwrap_or(after.len());
+            builder.push_str(&after[..end], pre_depth != 0);
+            rest = &after[(end + 3).min(after.len())..];
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            let end = rest.find('>').map_or(rest.len(), |v| v + 1);
+            rest = &rest[end..];
+        } else if let Some(after) = rest.strip_prefix("</") {
+            let end = after.find('>').map_or(after.len(), |v| v + 1);
+            let name = after[..end].trim_end_matches('>').trim().to_lowercase();
+            rest = &after[end..];
+
+            if name == "pre" {
+                pre_depth = pre_depth.saturating_sub(1);
+            }
+
+            if TEXT_BLOCK_ELEMENTS.contains(&name.as_str()) {
+                builder.end_paragraph();
+            } else if name == "th" {
+                builder.push_str(" ", false);
+            }
+        } else if rest.starts_with('<') && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic())
+        {
+            let (tag, consumed) = parse_tag(rest);
+            rest = &rest[consumed..];
+
+            // Namespaced elements (eg. <svg:svg>) are skipped but their contents are kept.
+            let name = tag.name.rsplit(':').next().unwrap_or_default();
+
+            if TEXT_SKIPPED_ELEMENTS.contains(&name) {
+                if !tag.self_closing {
+                    let closing = format!("</{}", tag.name);
+                    let end = find_ignore_case(rest, &closing).unwrap_or(rest.len());
+                    rest = &rest[end..];
+                    let end = rest.find('>').map_or(rest.len(), |v| v + 1);
+                    rest = &rest[end..];
+                }
+            } else if name == "br" {
+                builder.push_line_break();
+            } else if TEXT_BLOCK_ELEMENTS.contains(&name) {
+                builder.end_paragraph();
+
+                if name == "pre" && !tag.self_closing {
+                    pre_depth += 1;
+                }
+            } else if matches!(name, "td" | "th") {
+                builder.push_str(" ", false);
+            }
+        } else {
+            let first_len = rest.chars().next().map_or(1, |c| c.len_utf8());
+            let end = rest[first_len..]
+                .find('<')
+                .map_or(rest.len(), |v| v + first_len);
+            builder.push_str(&decode_entities(&rest[..end]), pre_depth != 0);
+            rest = &rest[end..];
+        }
+    }
+
+    builder.end_paragraph();
+
+    builder.value
+}
+
+/// Converts text into paragraphs. Paragraphs are separated by empty lines.
+pub(crate) fn plain_to_text(input: &str) -> ChapterText {
+    let mut builder = TextBuilder::default();
+
+    for line in input.lines() {
+        if line.trim().is_empty() {
+            builder.end_paragraph();
+        } else {
+            builder.push_line_break();
+            builder.push_str(line, false);
+        }
+    }
+
+    builder.end_paragraph();
+
+    builder.value
+}
+
+#[derive(Default)]
+struct TextBuilder {
+    value: ChapterText,
+    /// Length of the text in characters.
+    length: usize,
+
+    paragraph: String,
+    pending_space: bool,
+}
+
+impl TextBuilder {
+    fn push_str(&mut self, value: &str, keep_whitespace: bool) {
+        for c in value.chars() {
+            if keep_whitespace {
+                self.paragraph.push(c);
+            } else if c.is_ascii_whitespace() {
+                self.pending_space = true;
+            } else {
+                if self.pending_space
+                    && !self.paragraph.is_empty()
+                    && !self.paragraph.ends_with('\n')
+                {
+                    self.paragraph.push(' ');
+                }
+
+                self.pending_space = false;
+                self.paragraph.push(c);
+            }
+        }
+    }
+
+    fn push_line_break(&mut self) {
+        self.pending_space = false;
+
+        if !self.paragraph.is_empty() {
+            self.paragraph.push('\n');
+        }
+    }
+
+    fn end_paragraph(&mut self) {
+        self.pending_space = false;
+
+        let paragraph = std::mem::take(&mut self.paragraph);
+        let paragraph = paragraph.trim_matches(|c: char| c == '\n' || c == '\r');
+
+        if paragraph.trim().is_empty() {
+            return;
+        }
+
+        if !self.value.text.is_empty() {
+            self.value.text.push_str("\n\n");
+            self.length += 2;
+        }
+
+        let start = self.length;
+
+        self.value.text.push_str(paragraph);
+        self.length += paragraph.chars().count();
+
+        self.value.paragraphs.push(start..self.length);
+    }
+}
+
+/// Replaces the entities with the characters they represent.
+pub(crate) fn decode_entities(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(pos) = rest.find('&') {
+        output.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&len| len != 0 && len < 10)
+            .and_then(|len| {
+                let name = &rest[1..1 + len];
+
+                let c = if let Some(code) = name.strip_prefix("#x").or(name.strip_prefix("#X")) {
+                    u32::from_str_radix(code, 16).ok().and_then(char::from_u32)
+                } else if let Some(code) = name.strip_prefix('#') {
+                    code.parse().ok().and_then(char::from_u32)
+                } else {
+                    match name {
+                        "amp" => Some('&'),
+                        "lt" => Some('<'),
+                        "gt" => Some('>'),
+                        "quot" => Some('"'),
+                        "apos" => Some('\''),
+                        _ => NAMED_ENTITIES
+                            .iter()
+                            .find(|(v, _)| *v == name)
+                            .and_then(|(_, code)| char::from_u32(*code)),
+                    }
+                };
+
+                Some((c?, len + 2))
+            });
+
+        if let Some((c, len)) = decoded {
+            output.push(c);
+            rest = &rest[len..];
+        } else {
+            output.push('&');
+            rest = &rest[1..];
+        }
+    }
+
+    output.push_str(rest);
+
+    output
+}
+
+pub(crate) struct Tag {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub self_closing: bool,
+}
+
+/// Parses the start tag at the beginning of `input`. Returns the tag along with the amount of bytes consumed.
+pub(crate) fn parse_tag(input: &str) -> (Tag, usize) {
+    let bytes = input.as_bytes();
+
+    let mut pos = 1;
+
+    while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() && !matches!(bytes[pos], b'/' | b'>')
+    {
+        pos += 1;
+    }
+
+    let mut tag = Tag {
+        name: input[1..pos].to_lowercase(),
+        attributes: Vec::new(),
+        self_closing: false,
+    };
+
+    loop {
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+
+        match bytes.get(pos) {
+            None => break,
+            Some(b'>') => {
+                pos += 1;
+                break;
+            }
+            Some(b'/') => {
+                pos += 1;
+
+                if bytes.get(pos) == Some(&b'>') {
+                    tag.self_closing = true;
+                    pos += 1;
+                    break;
+                }
+
+                continue;
+            }
+            _ => (),
+        }
+
+        let name_start = pos;
+
+        while pos < bytes.len()
+            && !bytes[pos].is_ascii_whitespace()
+            && !matches!(bytes[pos], b'=' | b'>' | b'/')
+        {
+            pos += 1;
+        }
+
+        let name = input[name_start..pos].to_lowercase();
+
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+
+        let value = if bytes.get(pos) == Some(&b'=') {
+            pos += 1;
+
+            while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+
+            match bytes.get(pos) {
+                Some(&quote @ (b'"' | b'\'')) => {
+                    let start = pos + 1;
+                    let end = input[start..]
+                        .find(quote as char)
+                        .map_or(input.len(), |v| start + v);
+
+                    pos = (end + 1).min(input.len());
+
+                    input[start..end].to_string()
+                }
+
+                _ => {
+                    let start = pos;
+
+                    while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() && bytes[pos] != b'>'
+                    {
+                        pos += 1;
+                    }
+
+                    input[start..pos].to_string()
+                }
+            }
+        } else {
+            // Boolean attribute
+            name.clone()
+        };
+
+        if is_valid_attribute_name(&name) && !tag.attributes.iter().any(|(v, _)| *v == name) {
+            tag.attributes.push((name, value));
+        }
+    }
+
+    (tag, pos)
+}
+
+fn is_valid_attribute_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    let Some(first) = chars.next() else {
+        return false;
+    };
+
+    // Only allow xml:lang for namespaced attributes since others won't be declared.
+    if name.contains(':') {
+        return name == "xml:lang";
+    }
+
+    (first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Escapes text so it can be placed in an element or attribute value.
+pub(crate) fn escape(value: &str) -> Cow<'_, str> {
+    if !value.contains(['&', '<', '>', '"']) {
+        return Cow::Borrowed(value);
+    }
+
+    Cow::Owned(
+        value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;"),
+    )
+}
+
+/// Escapes the value while keeping any valid entities.
+fn push_escaped(output: &mut String, value: &str, is_attribute: bool) {
+    let mut rest = value;
+
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '&' => {
+                let entity_len = rest[1..]
+                    .find(';')
+                    .filter(|&len| {
+                        len != 0
+                            && len < 10
+                            && rest[1..1 + len]
+                                .chars()
+                                .all(|c| c.is_ascii_alphanumeric() || c == '#')
+                    });
+
+                if let Some(len) = entity_len {
+                    let name = &rest[1..1 + len];
+
+                    if name.starts_with('#') || ["amp", "lt", "gt", "quot", "apos"].contains(&name) {
+                        output.push_str(&rest[..len + 2]);
+                    } else if let Some((_, code)) = NAMED_ENTITIES.iter().find(|(v, _)| *v == name) {
+                        output.push_str(&format!("&#{code};"));
+                    } else {
+                        output.push_str("&amp;");
+                        output.push_str(&rest[1..len + 2]);
+                    }
+
+                    rest = &rest[len + 2..];
+                    continue;
+                }
+
+                output.push_str("&amp;");
+            }
+
+            '<' => output.push_str("&lt;"),
+            '"' if is_attribute => output.push_str("&quot;"),
+            c => output.push(c),
+        }
+
+        rest = &rest[c.len_utf8()..];
+    }
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|v| v.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closes_void_and_open_elements() {
+        assert_eq!(
+            html_to_xhtml("<p>One<br>Two<p>Three"),
+            "<p>One<br/>Two</p><p>Three</p>"
+        );
+    }
+
+    #[test]
+    fn quotes_attributes_and_escapes() {
+        assert_eq!(
+            html_to_xhtml(r#"<a href=part1.html#top title='A "B"'>Q&A &mdash; &amp;</a>"#),
+            r#"<a href="part1.html#top" title="A &quot;B&quot;">Q&amp;A &#8212; &amp;</a>"#
+        );
+    }
+
+    #[test]
+    fn plain_text_paragraphs() {
+        let value = html_to_text(
+            r#"<?xml version="1.0"?><html><head><title>Skip</title><style>p {}</style></head>
+<body><h1>Chapter  One</h1>
+<p>Caf&eacute;   is
+  <i>open</i>.<br/>Come&#160;in &amp; sit.</p>
+<div><p>Last</p></div><script>var a = 1;</script></body></html>"#,
+        );
+
+        assert_eq!(
+            value.text,
+            "Chapter One\n\nCafé is open.\nCome\u{a0}in & sit.\n\nLast"
+        );
+        assert_eq!(value.paragraphs, vec![0..11, 13..41, 43..47]);
+        assert_eq!(value.word_count(), 9);
+
+        let chars = value.text.chars().collect::<Vec<_>>();
+        let last = &value.paragraphs[2];
+        assert_eq!(chars[last.clone()].iter().collect::<String>(), "Last");
+    }
+
+    #[test]
+    fn drops_namespaced_elements() {
+        assert_eq!(
+            html_to_xhtml("<div>A<mbp:pagebreak/>B</div>"),
+            "<div>AB</div>"
+        );
+    }
+}
@@ -0,0 +1,326 @@
+// https://wiki.mobileread.com/wiki/PalmDOC
+// https://wiki.mobileread.com/wiki/MOBI#HUFF_and_CDIC
+
+use super::reader::{read_u16, read_u32};
+use crate::{Error, Result};
+
+pub const NO_COMPRESSION: u16 = 1;
+pub const PALM_DOC: u16 = 2;
+pub const HUFF_CDIC: u16 = 17480;
+
+/// PalmDOC LZ77 decompression.
+pub fn palmdoc_decompress(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(data.len() * 2);
+
+    let mut i = 0;
+
+    while i < data.len() {
+        let c = data[i];
+        i += 1;
+
+        match c {
+            // Copy the next 1-8 bytes as-is.
+            0x01..=0x08 => {
+                let end = (i + c as usize).min(data.len());
+                output.extend_from_slice(&data[i..end]);
+                i = end;
+            }
+
+            // Literal
+            0x00 | 0x09..=0x7F => output.push(c),
+
+            // Distance / Length pair.
+            0x80..=0xBF => {
+                if i >= data.len() {
+                    break;
+                }
+
+                let pair = ((c as usize) << 8) | data[i] as usize;
+                i += 1;
+
+                let distance = (pair >> 3) & 0x07FF;
+                let length = (pair & 0x07) + 3;
+
+                if distance == 0 || distance > output.len() {
+                    continue;
+                }
+
+                // Byte by byte since the copy may overlap what we're writing.
+                for _ in 0..length {
+                    output.push(output[output.len() - distance]);
+                }
+            }
+
+            // Space + character.
+            0xC0..=0xFF => {
+                output.push(b' ');
+                output.push(c ^ 0x80);
+            }
+        }
+    }
+
+    output
+}
+
+/// Huffman/CDIC decompression used by Mobipocket files.
+///
+/// Based on https://github.com/kevinhendricks/KindleUnpack/blob/master/lib/mobi_uncompress.py
+pub struct HuffCdicReader {
+    /// (code length, is terminal, max code)
+    dict1: Vec<(u32, bool, u64)>,
+
+    min_code: [u64; 33],
+    max_code: [u64; 33],
+
+    /// (phrase, is already decompressed)
+    dictionary: Vec<Option<(Vec<u8>, bool)>>,
+}
+
+impl HuffCdicReader {
+    pub fn new(huff: &[u8], cdics: &[&[u8]]) -> Result<Self> {
+        if huff.len() < 16 || &huff[0..8] != b"HUFF\x00\x00\x00\x18" {
+            return Err(Error::Mobi("Invalid HUFF header"));
+        }
+
+        let off1 = read_u32(huff, 8)? as usize;
+        let off2 = read_u32(huff, 12)? as usize;
+
+        let mut dict1 = Vec::with_capacity(256);
+
+        for i in 0..256 {
+            let v = read_u32(huff, off1 + i * 4)?;
+
+            let code_len = v & 0x1F;
+            let term = v & 0x80 != 0;
+            let max_code = v >> 8;
+
+            if code_len == 0 {
+                return Err(Error::Mobi("Invalid HUFF code length"));
+            }
+
+            let max_code = ((max_code as u64 + 1) << (32 - code_len)) - 1;
+
+            dict1.push((code_len, term, max_code));
+        }
+
+        let mut min_code = [0; 33];
+        let mut max_code = [0; 33];
+
+        for code_len in 1..33 {
+            let min = read_u32(huff, off2 + (code_len - 1) * 8)? as u64;
+            let max = read_u32(huff, off2 + (code_len - 1) * 8 + 4)? as u64;
+
+            min_code[code_len] = min << (32 - code_len);
+            max_code[code_len] = ((max + 1) << (32 - code_len)) - 1;
+        }
+
+        let mut this = Self {
+            dict1,
+            min_code,
+            max_code,
+            dictionary: Vec::new(),
+        };
+
+        for cdic in cdics {
+            this.load_cdic(cdic)?;
+        }
+
+        Ok(this)
+    }
+
+    fn load_cdic(&mut self, cdic: &[u8]) -> Result<()> {
+        if cdic.len() < 16 || &cdic[0..8] != b"CDIC\x00\x00\x00\x10" {
+            return Err(Error::Mobi("Invalid CDIC header"));
+        }
+
+        let phrases = read_u32(cdic, 8)? as usize;
+        let bits = read_u32(cdic, 12)?;
+
+        if bits > 32 {
+            return Err(Error::Mobi("Invalid CDIC code length"));
+        }
+
+        let count =
+            (1u64 << bits).min(phrases.saturating_sub(self.dictionary.len()) as u64) as usize;
+
+        for i in 0..count {
+            let offset = read_u16(cdic, 16 + i * 2)? as usize;
+            let blen = read_u16(cdic, 16 + offset)? as usize;
+
+            let start = 18 + offset;
+            let end = start + (blen & 0x7FFF);
+
+            let phrase = cdic
+                .get(start..end)
+                .ok_or(Error::Mobi("Invalid CDIC phrase"))?;
+
+            self.dictionary
+                .push(Some((phrase.to_vec(), blen & 0x8000 != 0)));
+        }
+
+        Ok(())
+    }
+
+    pub fn unpack(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut bits_left = data.len() as i64 * 8;
+
+        let mut padded = data.to_vec();
+        padded.extend_from_slice(&[0; 8]);
+
+        let mut pos = 0;
+        let mut x = read_u64(&padded, pos);
+        let mut n: i64 = 32;
+
+        let mut output = Vec::new();
+
+        loop {
+            if n <= 0 {
+                pos += 4;
+                x = read_u64(&padded, pos);
+                n += 32;
+            }
+
+            let code = (x >> n) & 0xFFFF_FFFF;
+
+            let (mut code_len, term, mut max_code) = self.dict1[(code >> 24) as usize];
+
+            if !term {
+                while (code_len as usize) < 32 && code < self.min_code[code_len as usize] {
+                    code_len += 1;
+                }
+
+                max_code = self.max_code[code_len as usize];
+            }
+
+            n -= code_len as i64;
+            bits_left -= code_len as i64;
+
+            if bits_left < 0 {
+                break;
+            }
+
+            let index = (max_code.wrapping_sub(code) >> (32 - code_len)) as usize;
+
+            let (phrase, is_unpacked) = self
+                .dictionary
+                .get_mut(index)
+                .and_then(|v| v.take())
+                .ok_or(Error::Mobi("Invalid HUFF dictionary index"))?;
+
+            let phrase = if is_unpacked {
+                phrase
+            } else {
+                self.unpack(&phrase)?
+            };
+
+            output.extend_from_slice(&phrase);
+
+            self.dictionary[index] = Some((phrase, true));
+        }
+
+        Ok(output)
+    }
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+
+    if let Some(v) = data.get(offset..offset + 8) {
+        bytes.copy_from_slice(v);
+    }
+
+    u64::from_be_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palmdoc_literals_and_spaces() {
+        // "ab" + " c" (0xE3 = ' ' + 'c')
+        assert_eq!(palmdoc_decompress(&[b'a', b'b', 0xE3]), b"ab c");
+    }
+
+    #[test]
+    fn palmdoc_raw_bytes() {
+        assert_eq!(palmdoc_decompress(&[0x03, 0xFF, 0x00, 0x80]), [0xFF, 0x00, 0x80]);
+    }
+
+    #[test]
+    fn palmdoc_back_reference() {
+        // "abc" then copy 3 bytes from a distance of 3 -> "abcabc"
+        let pair: u16 = 0x8000 | (3 << 3);
+
+        assert_eq!(
+            palmdoc_decompress(&[b'a', b'b', b'c', (pair >> 8) as u8, pair as u8]),
+            b"abcabc"
+        );
+    }
+
+    /// Every code is a terminal 8 bit one. Byte `c` decodes to phrase `255 - c`.
+    fn huff() -> Vec<u8> {
+        let mut data = b"HUFF\x00\x00\x00\x18".to_vec();
+        data.extend(24u32.to_be_bytes());
+        data.extend((24 + 1024u32).to_be_bytes());
+        data.extend([0; 8]);
+
+        for _ in 0..256 {
+            data.extend(((255u32 << 8) | 0x80 | 8).to_be_bytes());
+        }
+
+        data.extend([0; 256]);
+
+        data
+    }
+
+    /// (phrase, is already decompressed)
+    fn cdic(phrases: &[(&[u8], bool)], bits: u32) -> Vec<u8> {
+        let mut data = b"CDIC\x00\x00\x00\x10".to_vec();
+        data.extend((phrases.len() as u32).to_be_bytes());
+        data.extend(bits.to_be_bytes());
+
+        let mut contents = Vec::new();
+
+        for (phrase, is_unpacked) in phrases {
+            data.extend(((phrases.len() * 2 + contents.len()) as u16).to_be_bytes());
+
+            let flag = if *is_unpacked { 0x8000 } else { 0 };
+            contents.extend((phrase.len() as u16 | flag).to_be_bytes());
+            contents.extend_from_slice(phrase);
+        }
+
+        data.extend(contents);
+
+        data
+    }
+
+    #[test]
+    fn huff_cdic() {
+        let cdic = cdic(
+            &[
+                (b"Hello", true),
+                (b" ", true),
+                (b"world", true),
+                // "Hello " which is compressed itself.
+                (&[255, 254], false),
+            ],
+            8,
+        );
+
+        let mut reader = HuffCdicReader::new(&huff(), &[&cdic]).unwrap();
+
+        assert_eq!(reader.unpack(&[255, 254, 253]).unwrap(), b"Hello world");
+        assert_eq!(reader.unpack(&[252, 253]).unwrap(), b"Hello world");
+        // The nested phrase is cached after the first use.
+        assert_eq!(reader.unpack(&[252, 252]).unwrap(), b"Hello Hello ");
+
+        assert!(reader.unpack(&[0]).is_err());
+    }
+
+    #[test]
+    fn huff_cdic_invalid() {
+        assert!(HuffCdicReader::new(b"HUFF", &[]).is_err());
+        assert!(HuffCdicReader::new(&huff(), &[&cdic(&[(b"a", true)], 33)]).is_err());
+    }
+}
@@ -0,0 +1,345 @@
+// https://wiki.mobileread.com/wiki/MOBI#Index_records
+
+// Based on https://github.com/kevinhendricks/KindleUnpack/blob/master/lib/mobi_index.py
+
+use std::collections::HashMap;
+
+use super::reader::{read_u16, read_u32, MobiReader};
+use crate::{Error, Result};
+
+pub const NULL_INDEX: u32 = 0xFFFF_FFFF;
+
+#[derive(Debug)]
+pub struct IndexEntry {
+    pub text: Vec<u8>,
+    pub tags: HashMap<u8, Vec<u32>>,
+}
+
+impl IndexEntry {
+    pub fn text_as_string(&self) -> String {
+        String::from_utf8_lossy(&self.text).into_owned()
+    }
+
+    pub fn tag(&self, tag: u8) -> Option<&[u32]> {
+        self.tags.get(&tag).map(|v| v.as_slice())
+    }
+
+    pub fn tag_value(&self, tag: u8, index: usize) -> Option<u32> {
+        self.tags.get(&tag)?.get(index).copied()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Index {
+    pub entries: Vec<IndexEntry>,
+    /// The CNCX strings keyed by their offset.
+    pub cncx: HashMap<u32, String>,
+}
+
+struct IndexHeader {
+    length: u32,
+    idxt_start: u32,
+    count: u32,
+    cncx_count: u32,
+}
+
+struct TagInfo {
+    tag: u8,
+    values_per_entry: u32,
+    mask: u8,
+    end_flag: u8,
+}
+
+impl Index {
+    /// Read the INDX records starting at the absolute record `record_index`.
+    pub fn read(reader: &MobiReader, record_index: usize) -> Result<Self> {
+        let mut this = Self::default();
+
+        let data = reader.record(record_index)?;
+        let header = parse_indx_header(data)?;
+
+        // Read the CNCX records which are located directly after the index records.
+        let mut record_offset = 0;
+
+        for i in 0..header.cncx_count as usize {
+            let data = reader.record(record_index + header.count as usize + 1 + i)?;
+
+            read_cncx(data, record_offset, &mut this.cncx);
+
+            record_offset += 0x10000;
+        }
+
+        let (control_byte_count, tag_table) = read_tag_section(header.length as usize, data)?;
+
+        for i in record_index + 1..record_index + 1 + header.count as usize {
+            let data = reader.record(i)?;
+            let entry_header = parse_indx_header(data)?;
+
+            let idxt_pos = entry_header.idxt_start as usize;
+
+            let mut positions = Vec::with_capacity(entry_header.count as usize + 1);
+
+            for j in 0..entry_header.count as usize {
+                positions.push(read_u16(data, idxt_pos + 4 + j * 2)? as usize);
+            }
+
+            // The last entry ends before the IDXT tag.
+            positions.push(idxt_pos);
+
+            for pos in positions.windows(2) {
+                let (start, end) = (pos[0], pos[1]);
+
+                let text_length = *data
+                    .get(start)
+                    .ok_or(Error::Mobi("Invalid index entry"))? as usize;
+
+                let text = data
+                    .get(start + 1..start + 1 + text_length)
+                    .ok_or(Error::Mobi("Invalid index entry"))?
+                    .to_vec();
+
+                let tags = read_tag_map(
+                    control_byte_count,
+                    &tag_table,
+                    data,
+                    start + 1 + text_length,
+                    end,
+                )?;
+
+                this.entries.push(IndexEntry { text, tags });
+            }
+        }
+
+        Ok(this)
+    }
+
+    pub fn cncx_string(&self, offset: u32) -> Option<&str> {
+        self.cncx.get(&offset).map(|v| v.as_str())
+    }
+}
+
+fn parse_indx_header(data: &[u8]) -> Result<IndexHeader> {
+    if data.get(0..4) != Some(b"INDX") {
+        return Err(Error::Mobi("Invalid INDX header"));
+    }
+
+    // len, nul1, type, gen, start, count, code, lng, total, ordt, ligt, nligt, nctoc
+    Ok(IndexHeader {
+        length: read_u32(data, 4)?,
+        idxt_start: read_u32(data, 20)?,
+        count: read_u32(data, 24)?,
+        cncx_count: read_u32(data, 52)?,
+    })
+}
+
+fn read_cncx(data: &[u8], record_offset: u32, cncx: &mut HashMap<u32, String>) {
+    let mut offset = 0;
+
+    while offset < data.len() {
+        if data[offset] == 0 {
+            break;
+        }
+
+        let start = offset;
+
+        let Some((consumed, length)) = read_variable_width_value(data, offset) else {
+            break;
+        };
+
+        offset += consumed;
+
+        let Some(name) = data.get(offset..offset + length as usize) else {
+            break;
+        };
+
+        offset += length as usize;
+
+        cncx.insert(
+            start as u32 + record_offset,
+            String::from_utf8_lossy(name).into_owned(),
+        );
+    }
+}
+
+fn read_tag_section(start: usize, data: &[u8]) -> Result<(usize, Vec<TagInfo>)> {
+    let mut tags = Vec::new();
+
+    if data.get(start..start + 4) != Some(b"TAGX") {
+        return Ok((0, tags));
+    }
+
+    let first_entry_offset = read_u32(data, start + 4)? as usize;
+    let control_byte_count = read_u32(data, start + 8)? as usize;
+
+    for i in (12..first_entry_offset).step_by(4) {
+        let pos = start + i;
+
+        let bytes = data
+            .get(pos..pos + 4)
+            .ok_or(Error::Mobi("Invalid TAGX section"))?;
+
+        tags.push(TagInfo {
+            tag: bytes[0],
+            values_per_entry: bytes[1] as u32,
+            mask: bytes[2],
+            end_flag: bytes[3],
+        });
+    }
+
+    Ok((control_byte_count, tags))
+}
+
+fn read_tag_map(
+    control_byte_count: usize,
+    tag_table: &[TagInfo],
+    data: &[u8],
+    start: usize,
+    end: usize,
+) -> Result<HashMap<u8, Vec<u32>>> {
+    // (tag, value count, value bytes, values per entry)
+    let mut tags = Vec::new();
+
+    let mut control_byte_index = 0;
+    let mut data_start = start + control_byte_count;
+
+    for info in tag_table {
+        if info.end_flag == 0x01 {
+            control_byte_index += 1;
+            continue;
+        }
+
+        let control_byte = *data
+            .get(start + control_byte_index)
+            .ok_or(Error::Mobi("Invalid index control byte"))?;
+
+        let mut mask = info.mask;
+        let mut value = control_byte & mask;
+
+        if value == 0 {
+            continue;
+        }
+
+        if value == mask {
+            if mask.count_ones() > 1 {
+                // A variable width value follows which defines the amount of bytes (not values) for the tag.
+                let (consumed, value) = read_variable_width_value(data, data_start)
+                    .ok_or(Error::Mobi("Invalid index entry"))?;
+
+                data_start += consumed;
+
+                tags.push((info.tag, None, Some(value), info.values_per_entry));
+            } else {
+                tags.push((info.tag, Some(1), None, info.values_per_entry));
+            }
+        } else {
+            while mask & 0x01 == 0 {
+                mask >>= 1;
+                value >>= 1;
+            }
+
+            tags.push((info.tag, Some(value as u32), None, info.values_per_entry));
+        }
+    }
+
+    let mut tag_map = HashMap::new();
+
+    for (tag, value_count, value_bytes, values_per_entry) in tags {
+        let mut values = Vec::new();
+
+        if let Some(value_count) = value_count {
+            for _ in 0..value_count * values_per_entry {
+                let (consumed, value) = read_variable_width_value(data, data_start)
+                    .ok_or(Error::Mobi("Invalid index entry"))?;
+
+                data_start += consumed;
+                values.push(value);
+            }
+        } else if let Some(value_bytes) = value_bytes {
+            let mut total_consumed = 0;
+
+            while total_consumed < value_bytes as usize && data_start < end {
+                let (consumed, value) = read_variable_width_value(data, data_start)
+                    .ok_or(Error::Mobi("Invalid index entry"))?;
+
+                data_start += consumed;
+                total_consumed += consumed;
+                values.push(value);
+            }
+        }
+
+        tag_map.insert(tag, values);
+    }
+
+    Ok(tag_map)
+}
+
+/// Forward-encoded variable width value. The high bit marks the last byte.
+///
+/// Returns (bytes consumed, value)
+fn read_variable_width_value(data: &[u8], offset: usize) -> Option<(usize, u32)> {
+    let mut value: u32 = 0;
+    let mut consumed = 0;
+
+    loop {
+        let v = *data.get(offset + consumed)?;
+        consumed += 1;
+
+        value = (value << 7) | (v & 0x7F) as u32;
+
+        if v & 0x80 != 0 {
+            return Some((consumed, value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+    use crate::mobi::reader::MobiHeader;
+
+    #[test]
+    fn variable_width_value() {
+        assert_eq!(read_variable_width_value(&[0x81], 0), Some((1, 1)));
+        assert_eq!(
+            read_variable_width_value(&[0x00, 0x01, 0x82], 1),
+            Some((2, 130))
+        );
+        // Missing the last byte.
+        assert_eq!(read_variable_width_value(&[0x01], 0), None);
+    }
+
+    #[test]
+    fn ncx_index() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/book.mobi");
+
+        let reader = MobiReader::new(File::open(path).unwrap()).unwrap();
+        let header = MobiHeader::parse(&reader, 0).unwrap();
+
+        let index = Index::read(&reader, header.ncx_index.unwrap()).unwrap();
+
+        let labels = index
+            .entries
+            .iter()
+            .map(|v| index.cncx_string(v.tag_value(3, 0).unwrap()).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(labels, ["Chapter One", "Scene", "Chapter Two"]);
+        assert_eq!(index.entries[1].text_as_string(), "1");
+
+        // Only the nested entry has a parent.
+        assert_eq!(index.entries[0].tag(21), None);
+        assert_eq!(index.entries[1].tag(21), Some(&[0][..]));
+        assert_eq!(index.entries[2].tag(21), None);
+
+        // Positions are increasing inside of the text.
+        let positions = index
+            .entries
+            .iter()
+            .map(|v| v.tag_value(1, 0).unwrap())
+            .collect::<Vec<_>>();
+
+        assert!(positions.windows(2).all(|v| v[0] < v[1]));
+    }
+}
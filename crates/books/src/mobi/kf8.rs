@@ -0,0 +1,383 @@
+// https://wiki.mobileread.com/wiki/KF8
+
+// Based on https://github.com/kevinhendricks/KindleUnpack/blob/master/lib/mobi_k8proc.py
+
+use std::sync::OnceLock;
+
+use regex::bytes::{Captures, Regex};
+
+use super::{
+    index::Index,
+    reader::{read_u32, MobiHeader, MobiReader},
+    Section,
+};
+use crate::{Error, Result};
+
+const BASE_32: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUV";
+
+/// Matches the `kindle:` links which point to positions, resources and flows.
+static LINK_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// Matches the id (or name) attribute of a tag.
+static ID_REGEX: OnceLock<Regex> = OnceLock::new();
+
+#[derive(Debug)]
+pub struct Fragment {
+    /// Position inside the text where the fragment is inserted into its skeleton.
+    pub insert_pos: usize,
+    pub length: usize,
+}
+
+struct Skeleton {
+    fragment_count: usize,
+    start_pos: usize,
+    length: usize,
+}
+
+pub struct Kf8Content {
+    pub sections: Vec<Section>,
+    pub fragments: Vec<Fragment>,
+    /// Flow 0 is the main text. The others are referenced with `kindle:flow:XXXX`
+    pub flows: Vec<Vec<u8>>,
+}
+
+/// Rebuilds the XHTML files from the skeleton and fragment indexes.
+pub fn build_content<F>(
+    reader: &MobiReader,
+    header: &MobiHeader,
+    text: Vec<u8>,
+    resource_path: F,
+) -> Result<Kf8Content>
+where
+    F: Fn(usize) -> Option<String>,
+{
+    let mut flows = read_flows(reader, header, text)?;
+
+    let skeletons = match header.skeleton_index {
+        Some(index) => Index::read(reader, index)?
+            .entries
+            .into_iter()
+            .map(|entry| {
+                Ok(Skeleton {
+                    fragment_count: entry
+                        .tag_value(1, 0)
+                        .ok_or(Error::Mobi("Invalid skeleton entry"))?
+                        as usize,
+                    start_pos: entry
+                        .tag_value(6, 0)
+                        .ok_or(Error::Mobi("Invalid skeleton entry"))?
+                        as usize,
+                    length: entry
+                        .tag_value(6, 1)
+                        .ok_or(Error::Mobi("Invalid skeleton entry"))?
+                        as usize,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?,
+
+        None => return Err(Error::Mobi("Missing KF8 skeleton index")),
+    };
+
+    let fragments = match header.fragment_index {
+        Some(index) => Index::read(reader, index)?
+            .entries
+            .into_iter()
+            .map(|entry| {
+                Ok(Fragment {
+                    insert_pos: entry
+                        .text_as_string()
+                        .parse()
+                        .map_err(|_| Error::Mobi("Invalid fragment insert position"))?,
+                    length: entry
+                        .tag_value(6, 1)
+                        .ok_or(Error::Mobi("Invalid fragment entry"))?
+                        as usize,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?,
+
+        None => Vec::new(),
+    };
+
+    let text = &flows[0];
+
+    // (start pos, end pos, contents)
+    let mut parts = Vec::with_capacity(skeletons.len());
+
+    let mut fragment_index = 0;
+
+    for skeleton in &skeletons {
+        let mut base_pos = skeleton.start_pos + skeleton.length;
+
+        let mut contents = text
+            .get(skeleton.start_pos..base_pos)
+            .ok_or(Error::Mobi("Invalid skeleton position"))?
+            .to_vec();
+
+        for _ in 0..skeleton.fragment_count {
+            let Some(fragment) = fragments.get(fragment_index) else {
+                break;
+            };
+
+            let slice = text
+                .get(base_pos..base_pos + fragment.length)
+                .ok_or(Error::Mobi("Invalid fragment position"))?;
+
+            let insert_at = fragment
+                .insert_pos
+                .saturating_sub(skeleton.start_pos)
+                .min(contents.len());
+
+            contents.splice(insert_at..insert_at, slice.iter().copied());
+
+            base_pos += fragment.length;
+            fragment_index += 1;
+        }
+
+        parts.push((skeleton.start_pos, base_pos, contents));
+    }
+
+    if parts.is_empty() {
+        return Err(Error::Mobi("Missing KF8 skeleton entries"));
+    }
+
+    let link_regex = LINK_REGEX.get_or_init(|| {
+        Regex::new(concat!(
+            r"kindle:pos:fid:([0-9A-Va-v]{4}):off:([0-9A-Va-v]{10})",
+            r#"|kindle:embed:([0-9A-Va-v]{4})(?:\?mime=[^'"\)]*)?"#,
+            r#"|kindle:flow:([0-9A-Va-v]{4})(?:\?mime=[^'"\)]*)?"#,
+        ))
+        .unwrap()
+    });
+
+    let replace_link = |caps: &Captures| -> Vec<u8> {
+        if let (Some(fid), Some(offset)) = (caps.get(1), caps.get(2)) {
+            let fid = from_base_32(fid.as_bytes());
+            let offset = from_base_32(offset.as_bytes());
+
+            let Some(fragment) = fragments.get(fid) else {
+                return Vec::new();
+            };
+
+            let pos = fragment.insert_pos + offset;
+
+            let (index, (start, _, contents)) = parts
+                .iter()
+                .enumerate()
+                .find(|(_, (start, end, _))| *start <= pos && pos < *end)
+                .or_else(|| {
+                    parts.iter().enumerate().find(|(_, (start, end, _))| {
+                        *start <= fragment.insert_pos && fragment.insert_pos < *end
+                    })
+                })
+                .unwrap_or((0, &parts[0]));
+
+            let mut link = section_file_name(index);
+
+            if let Some(id) = find_id_before(contents, pos.saturating_sub(*start)) {
+                link.push('#');
+                link.push_str(&id);
+            }
+
+            link.into_bytes()
+        } else if let Some(number) = caps.get(3) {
+            resource_path(from_base_32(number.as_bytes()))
+                .map(|v| format!("../{v}").into_bytes())
+                .unwrap_or_default()
+        } else if let Some(number) = caps.get(4) {
+            let number = from_base_32(number.as_bytes());
+
+            format!("../{}", flow_path(number, flows.get(number))).into_bytes()
+        } else {
+            Vec::new()
+        }
+    };
+
+    let sections = parts
+        .iter()
+        .enumerate()
+        .map(|(index, (start, end, contents))| Section {
+            path: format!("text/{}", section_file_name(index)),
+            start: *start,
+            end: *end,
+            contents: link_regex.replace_all(contents, &replace_link).into_owned(),
+        })
+        .collect();
+
+    // CSS files may also reference images and fonts.
+    let updated_flows = flows
+        .iter()
+        .map(|flow| link_regex.replace_all(flow, &replace_link).into_owned())
+        .collect::<Vec<_>>();
+
+    flows = updated_flows;
+
+    Ok(Kf8Content {
+        sections,
+        fragments,
+        flows,
+    })
+}
+
+pub fn section_file_name(index: usize) -> String {
+    format!("part{index:04}.xhtml")
+}
+
+pub fn flow_path(number: usize, flow: Option<&Vec<u8>>) -> String {
+    let is_svg = flow.is_some_and(|v| v[..v.len().min(256)].windows(4).any(|v| v == b"<svg"));
+
+    format!(
+        "flows/flow{number:04}.{}",
+        if is_svg { "svg" } else { "css" }
+    )
+}
+
+/// Splits the text into flows using the FDST record.
+fn read_flows(reader: &MobiReader, header: &MobiHeader, text: Vec<u8>) -> Result<Vec<Vec<u8>>> {
+    let Some(fdst_index) = header.fdst_index else {
+        return Ok(vec![text]);
+    };
+
+    let data = reader.record(fdst_index)?;
+
+    if data.get(0..4) != Some(b"FDST") {
+        return Ok(vec![text]);
+    }
+
+    let offset = read_u32(data, 4)? as usize;
+    let count = read_u32(data, 8)? as usize;
+
+    let mut flows = Vec::with_capacity(count);
+
+    for i in 0..count {
+        let start = read_u32(data, offset + i * 8)? as usize;
+        let end = read_u32(data, offset + i * 8 + 4)? as usize;
+
+        flows.push(
+            text.get(start..end.min(text.len()))
+                .unwrap_or_default()
+                .to_vec(),
+        );
+    }
+
+    if flows.is_empty() {
+        flows.push(text);
+    }
+
+    Ok(flows)
+}
+
+/// Finds the closest id (or name) attribute of the tags before `pos`.
+fn find_id_before(contents: &[u8], pos: usize) -> Option<String> {
+    let mut pos = pos.min(contents.len());
+
+    // If we're inside of a tag, include it.
+    let next_gt = contents[pos..].iter().position(|&v| v == b'>');
+    let next_lt = contents[pos..].iter().position(|&v| v == b'<');
+
+    if let Some(gt) = next_gt {
+        if next_lt.is_none_or(|lt| lt == 0 || gt < lt) {
+            pos += gt + 1;
+        }
+    }
+
+    let id_regex = ID_REGEX
+        .get_or_init(|| Regex::new(r#"(?i)^<[^>]*\s(?:id|name)\s*=\s*['"]([^'"]*)['"]"#).unwrap());
+
+    let mut end = pos;
+
+    while let Some(start) = contents[..end].iter().rposition(|&v| v == b'<') {
+        let tag = &contents[start..end];
+
+        if tag.starts_with(b"<body") {
+            return None;
+        }
+
+        if !tag.starts_with(b"<meta") {
+            if let Some(caps) = id_regex.captures(tag) {
+                return Some(String::from_utf8_lossy(&caps[1]).into_owned());
+            }
+        }
+
+        end = start;
+    }
+
+    None
+}
+
+fn from_base_32(value: &[u8]) -> usize {
+    value.iter().fold(0, |acc, &c| {
+        let c = c.to_ascii_uppercase();
+        acc * 32 + BASE_32.iter().position(|&v| v == c).unwrap_or(0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+
+    #[test]
+    fn base_32() {
+        assert_eq!(from_base_32(b"0000"), 0);
+        assert_eq!(from_base_32(b"000V"), 31);
+        assert_eq!(from_base_32(b"0010"), 32);
+        assert_eq!(from_base_32(b"001a"), 42);
+    }
+
+    #[test]
+    fn id_before_position() {
+        let contents = br#"<body><p id="a">One</p><p name="b">Two</p></body>"#;
+
+        assert_eq!(find_id_before(contents, 0), None);
+        // Inside of the tag
+        assert_eq!(find_id_before(contents, 8).as_deref(), Some("a"));
+        assert_eq!(find_id_before(contents, 23).as_deref(), Some("b"));
+        assert_eq!(find_id_before(contents, 999).as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn skeletons_and_fragments() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/book.azw3");
+
+        let reader = MobiReader::new(File::open(path).unwrap()).unwrap();
+        let header = MobiHeader::parse(&reader, 0).unwrap();
+        let text = header.read_text(&reader).unwrap();
+
+        let content = build_content(&reader, &header, text, |number| {
+            Some(format!("images/{number:05}.png"))
+        })
+        .unwrap();
+
+        assert_eq!(content.fragments.len(), 2);
+        assert_eq!(content.flows.len(), 2);
+        assert_eq!(content.flows[1], b"p { color: red; }");
+
+        let sections = &content.sections;
+
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].path, "text/part0000.xhtml");
+        assert_eq!(sections[1].path, "text/part0001.xhtml");
+
+        // Each fragment is inserted into its skeleton and the links point to the rebuilt files.
+        assert_eq!(
+            String::from_utf8_lossy(&sections[0].contents),
+            concat!(
+                r#"<html><head><link rel="stylesheet" href="../flows/flow0001.css"/></head>"#,
+                r#"<body><p id="a">One</p><p><a href="part0001.xhtml#b">Two</a>"#,
+                r#"<img src="../images/00001.png"/></p></body></html>"#,
+            )
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&sections[1].contents),
+            r#"<html><head></head><body><p id="b">Two</p></body></html>"#
+        );
+
+        // Sections cover the skeleton and its fragments inside of the text.
+        assert_eq!(sections[0].start, 0);
+        assert_eq!(sections[0].end, sections[1].start);
+        assert!(sections[1].start <= content.fragments[1].insert_pos);
+        assert!(content.fragments[1].insert_pos < sections[1].end);
+    }
+}
@@ -0,0 +1,162 @@
+// https://wiki.mobileread.com/wiki/MOBI#Mobipocket_Tags
+
+use std::sync::OnceLock;
+
+use regex::bytes::{Captures, Regex};
+
+use super::{kf8::section_file_name, reader::ENCODING_CP1252, Section};
+use crate::markup::{html_to_xhtml, wrap_in_xhtml};
+
+/// Matches the `filepos` links to positions inside of the text.
+static FILEPOS_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// Matches the `recindex` of images.
+static RECINDEX_REGEX: OnceLock<Regex> = OnceLock::new();
+
+static PAGEBREAK_REGEX: OnceLock<Regex> = OnceLock::new();
+
+static HEAD_REGEX: OnceLock<Regex> = OnceLock::new();
+
+static GUIDE_REGEX: OnceLock<Regex> = OnceLock::new();
+
+static BODY_START_REGEX: OnceLock<Regex> = OnceLock::new();
+
+static BODY_END_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// Splits the MOBI 7 HTML into XHTML sections at each `<mbp:pagebreak/>`.
+///
+/// `filepos` links are turned into anchors and `recindex` images into resource paths.
+pub fn build_sections<F>(text: &[u8], encoding: u32, resource_path: F) -> Vec<Section>
+where
+    F: Fn(usize) -> Option<String>,
+{
+    let filepos_regex = FILEPOS_REGEX
+        .get_or_init(|| Regex::new(r#"(?i)\bfilepos\s*=\s*['"]?0*(\d+)['"]?"#).unwrap());
+    let recindex_regex = RECINDEX_REGEX
+        .get_or_init(|| Regex::new(r#"(?i)\brecindex\s*=\s*['"]?0*(\d+)['"]?"#).unwrap());
+    let pagebreak_regex =
+        PAGEBREAK_REGEX.get_or_init(|| Regex::new(r"(?i)<mbp:pagebreak[^>]*>").unwrap());
+    let head_regex =
+        HEAD_REGEX.get_or_init(|| Regex::new(r"(?is)<head[^>]*>(.*?)</head\s*>").unwrap());
+    let guide_regex =
+        GUIDE_REGEX.get_or_init(|| Regex::new(r"(?is)<guide[^>]*>.*?</guide\s*>").unwrap());
+
+    let mut targets = filepos_regex
+        .captures_iter(text)
+        .filter_map(|caps| parse_number(&caps[1]))
+        .collect::<Vec<_>>();
+
+    targets.sort_unstable();
+    targets.dedup();
+
+    let head = head_regex
+        .captures(text)
+        .map(|caps| guide_regex.replace_all(&caps[1], &b""[..]).into_owned())
+        .map(|v| html_to_xhtml(&decode_text(&v, encoding)))
+        .unwrap_or_default();
+
+    let body_start = BODY_START_REGEX
+        .get_or_init(|| Regex::new(r"(?i)<body[^>]*>").unwrap())
+        .find(text)
+        .map_or(0, |v| v.end());
+
+    let body_end = BODY_END_REGEX
+        .get_or_init(|| Regex::new(r"(?i)</body\s*>").unwrap())
+        .find_iter(text)
+        .last()
+        .map(|v| v.start())
+        .filter(|&v| v >= body_start)
+        .unwrap_or(text.len());
+
+    // The (start, end) of each section inside of the text.
+    let mut ranges = Vec::new();
+
+    let mut start = body_start;
+
+    for found in pagebreak_regex.find_iter(&text[body_start..body_end]) {
+        ranges.push((start, body_start + found.start()));
+        start = body_start + found.end();
+    }
+
+    ranges.push((start, body_end));
+
+    ranges.retain(|&(start, end)| text[start..end].iter().any(|v| !v.is_ascii_whitespace()));
+
+    if ranges.is_empty() {
+        ranges.push((body_start, body_end));
+    }
+
+    let section_for_pos = |pos: usize| {
+        ranges
+            .iter()
+            .position(|&(_, end)| pos < end)
+            .unwrap_or(ranges.len() - 1)
+    };
+
+    ranges
+        .iter()
+        .enumerate()
+        .map(|(index, &(start, end))| {
+            let mut contents = Vec::with_capacity(end - start);
+
+            let mut last = start;
+
+            for &target in targets.iter().filter(|&&v| start <= v && v < end) {
+                // Don't place the anchor inside of a tag.
+                let insert_at = match text[last..target].iter().rposition(|&v| v == b'<') {
+                    Some(lt) if !text[last + lt..target].contains(&b'>') => last + lt,
+                    _ => target,
+                };
+
+                contents.extend_from_slice(&text[last..insert_at]);
+                contents.extend_from_slice(format!(r#"<a id="filepos{target}"></a>"#).as_bytes());
+
+                last = insert_at;
+            }
+
+            contents.extend_from_slice(&text[last..end]);
+
+            let contents = filepos_regex.replace_all(&contents, |caps: &Captures| {
+                let pos = parse_number(&caps[1]).unwrap_or_default();
+
+                format!(
+                    r#"href="{}#filepos{pos}""#,
+                    section_file_name(section_for_pos(pos))
+                )
+                .into_bytes()
+            });
+
+            let contents = recindex_regex.replace_all(&contents, |caps: &Captures| {
+                let path = parse_number(&caps[1])
+                    .and_then(&resource_path)
+                    .unwrap_or_default();
+
+                format!(r#"src="../{path}""#).into_bytes()
+            });
+
+            let body = html_to_xhtml(&decode_text(&contents, encoding));
+
+            Section {
+                path: format!("text/{}", section_file_name(index)),
+                start,
+                end,
+                contents: wrap_in_xhtml(&head, &body).into_bytes(),
+            }
+        })
+        .collect()
+}
+
+fn decode_text(value: &[u8], encoding: u32) -> String {
+    if encoding == ENCODING_CP1252 {
+        encoding_rs::WINDOWS_1252
+            .decode_without_bom_handling(value)
+            .0
+            .into_owned()
+    } else {
+        String::from_utf8_lossy(value).into_owned()
+    }
+}
+
+fn parse_number(value: &[u8]) -> Option<usize> {
+    std::str::from_utf8(value).ok()?.parse().ok()
+}
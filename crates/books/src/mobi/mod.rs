@@ -1,122 +1,695 @@
-// For .mobi and .prc files
+// For .mobi, .prc, .azw and .azw3 files
 
 // https://wiki.mobileread.com/wiki/MOBI
+// https://wiki.mobileread.com/wiki/KF8
 
 use std::{
     borrow::Cow,
+    fs::File,
+    io::Read,
     path::{Path, PathBuf},
 };
 
-use mobi::Mobi;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::read::ZlibDecoder;
 
+mod compression;
+mod index;
+mod kf8;
+mod mobi7;
+mod reader;
+
+use self::{
+    index::{Index, NULL_INDEX},
+    kf8::Fragment,
+    reader::*,
+};
 use super::Book;
-use crate::{BookSearch, Result};
+use crate::{
+    epub::{sanitize_css, update_attributes_with, update_value_with_relative_internal_path},
+    markup, BookSearch, ChapterText, Error, ReadSeek, Result, SanitizeOptions, TocItem,
+};
+
+/// A section of the book. MOBI files don't store their chapters separately so we create them.
+struct Section {
+    path: String,
+
+    /// Where the section is located inside of the uncompressed text.
+    start: usize,
+    end: usize,
 
-pub struct EpubBook {
-    reader: Mobi,
+    contents: Vec<u8>,
 }
 
-impl Book for EpubBook {
-    fn load_from_path(path: &str) -> Result<Self>
-    where
-        Self: Sized,
-    {
-        let this = Self {
-            reader: Mobi::from_path(path)?,
+pub struct MobiBook {
+    reader: MobiReader,
+    /// The header the text was read from. For combination files this is the KF8 one.
+    header: MobiHeader,
+
+    /// The record index of the first image. Resources are numbered from it starting at 1.
+    first_resource: usize,
+
+    sections: Vec<Section>,
+    flows: Vec<Vec<u8>>,
+    toc: Vec<TocItem>,
+
+    pub chapter: usize,
+}
+
+impl MobiBook {
+    fn parse(reader: MobiReader) -> Result<Self> {
+        let header = MobiHeader::parse(&reader, 0)?;
+
+        let first_resource = header.first_resource.unwrap_or(reader.record_count());
+
+        let header = match header.exth.get_u32(EXTH_KF8_BOUNDARY) {
+            Some(boundary) if boundary != NULL_INDEX && header.version < 8 => {
+                MobiHeader::parse(&reader, boundary as usize)?
+            }
+            _ => header,
+        };
+
+        let text = header.read_text(&reader)?;
+
+        let resource_path = |number| resource_path(&reader, first_resource, number);
+
+        let (sections, fragments, flows) = if header.is_kf8() {
+            let content = kf8::build_content(&reader, &header, text, resource_path)?;
+
+            (content.sections, content.fragments, content.flows)
+        } else {
+            let sections = mobi7::build_sections(&text, header.text_encoding, resource_path);
+
+            (sections, Vec::new(), Vec::new())
+        };
+
+        let toc = match read_table_of_contents(&reader, &header, &sections, &fragments) {
+            Ok(v) => v,
+            Err(e) => {
+                tracing::warn!("Unable to read MOBI table of contents: {e}");
+                Vec::new()
+            }
         };
 
-        // Testing
+        Ok(Self {
+            reader,
+            header,
+            first_resource,
+            sections,
+            flows,
+            toc,
+            chapter: 0,
+        })
+    }
 
-        let records = this.reader.raw_records();
-        let records = records.range(this.reader.readable_records_range());
+    /// Books without any text don't have any sections.
+    fn current_section(&self) -> Result<&Section> {
+        self.sections
+            .get(self.chapter)
+            .ok_or_else(|| Error::MissingFile(format!("chapter {}", self.chapter)))
+    }
 
-        println!("Records: {}", records.len());
+    fn get_path_contents(&self, path: &str) -> Result<Vec<u8>> {
+        let path = path.trim_start_matches('/');
+
+        if let Some(section) = self.sections.iter().find(|v| v.path == path) {
+            return Ok(section.contents.clone());
+        }
 
-        let rec = &records[0];
+        let (dir, file_name) = path.rsplit_once('/').unwrap_or(("", path));
 
-        let mut recompiled = Vec::new();
+        let number = file_name
+            .split('.')
+            .next()
+            .map(|v| v.trim_start_matches(|c: char| c.is_ascii_alphabetic()))
+            .and_then(|v| v.parse::<usize>().ok());
 
-        // TODO: For some reason random u8's are combined. We remove the space and 96 (tilde) from it. For some reason it always equals 96 after subtracting space and the correct char.
-        for v in rec.content {
-            if *v < 160 {
-                recompiled.push(*v);
-            } else {
-                recompiled.push(32);
-                recompiled.push(*v - 128);
+        match (dir, number) {
+            ("flows", Some(number)) if number != 0 => {
+                if let Some(flow) = self.flows.get(number) {
+                    return Ok(flow.clone());
+                }
             }
+
+            ("images" | "fonts", Some(number)) if number != 0 => {
+                let data = self.reader.record(self.first_resource + number - 1)?;
+
+                return if data.starts_with(b"FONT") {
+                    decode_font(data)
+                } else {
+                    Ok(data.to_vec())
+                };
+            }
+
+            _ => (),
         }
 
-        println!("{:?}", &recompiled[0..128]);
+        Err(Error::Mobi("File not found"))
+    }
+
+    fn handle_update_attributes(
+        &mut self,
+        input: &[u8],
+        page_path: PathBuf,
+        prepend_to_urls: Option<&str>,
+        add_css: Option<&[&str]>,
+        sanitize: Option<SanitizeOptions>,
+    ) -> Result<Vec<u8>> {
+        update_attributes_with(
+            input,
+            self,
+            |book, element_name, mut attr| {
+                attr.value = match (
+                    element_name.local_name.as_str(),
+                    attr.name.local_name.as_str(),
+                ) {
+                    ("link", "href") | ("image", "href") => {
+                        update_value_with_relative_internal_path(
+                            page_path.clone(),
+                            &attr.value,
+                            prepend_to_urls,
+                        )
+                    }
+                    ("img", "src") => {
+                        let path = update_value_with_relative_internal_path(
+                            page_path.clone(),
+                            &attr.value,
+                            None,
+                        );
+
+                        if let Ok(cont) = book.get_path_contents(&path) {
+                            let b64 = STANDARD.encode(cont);
 
-        std::fs::write(
-            "./app/testing.html",
-            String::from_utf8_lossy(&recompiled).to_string(),
+                            if let Some((_, type_of)) = attr.value.rsplit_once('.') {
+                                format!("data:image/{};charset=utf-8;base64,{}", type_of, b64)
+                            } else {
+                                format!("data:image;charset=utf-8;base64,{}", b64)
+                            }
+                        } else {
+                            update_value_with_relative_internal_path(
+                                page_path.clone(),
+                                &attr.value,
+                                prepend_to_urls,
+                            )
+                        }
+                    }
+                    _ => return attr,
+                };
+                attr
+            },
+            |book, name, attrs, writer| {
+                if name.local_name == "link"
+                    && attrs.iter().any(|v| {
+                        v.name.local_name == "rel" && v.value.to_lowercase() == "stylesheet"
+                    })
+                {
+                    if let Some(attr) = attrs.iter().find(|a| a.name.local_name == "href") {
+                        let path = update_value_with_relative_internal_path(
+                            page_path.clone(),
+                            &attr.value,
+                            None,
+                        );
+
+                        if let Some(cont) = book
+                            .get_path_contents(&path)
+                            .ok()
+                            .and_then(|v| String::from_utf8(v).ok())
+                        {
+                            writer.write(xml::writer::XmlEvent::start_element("style"))?;
+                            writer.write(xml::writer::XmlEvent::characters(
+                                &if sanitize.is_some() {
+                                    sanitize_css(&cont)
+                                } else {
+                                    Cow::Borrowed(cont.as_str())
+                                },
+                            ))?;
+                            writer.write(xml::writer::XmlEvent::end_element())?;
+
+                            return Ok(true);
+                        }
+                    }
+                }
+
+                Ok(false)
+            },
+            if let Some(v) = add_css { v } else { &[] },
+            sanitize,
         )
-        .unwrap();
+    }
+}
 
-        // std::fs::write("./app/testing.html", &this.reader.content_as_string_lossy()).unwrap();
+impl Book for MobiBook {
+    fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::load_from_reader(File::open(path)?)
+    }
 
-        Ok(this)
+    fn load_from_reader<R: ReadSeek + 'static>(reader: R) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::parse(MobiReader::new(reader)?)
+    }
+
+    fn get_table_of_contents(&mut self) -> Result<Option<Vec<TocItem>>> {
+        if self.toc.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(self.toc.clone()))
+        }
     }
 
     fn compute_hash(&mut self) -> Option<String> {
-        None
+        let mut hasher = blake3::Hasher::new();
+
+        // Skip the header record since it also contains the metadata.
+        for index in 1..self.reader.record_count() {
+            if let Ok(record) = self.reader.record(index) {
+                hasher.update(record);
+            }
+        }
+
+        Some(hasher.finalize().to_string())
     }
 
-    fn find(&self, _search: BookSearch<'_>) -> Option<Vec<String>> {
-        None
+    fn find(&self, search: BookSearch<'_>) -> Option<Vec<String>> {
+        let exth = &self.header.exth;
+
+        match search {
+            BookSearch::Title => exth.get_strings(EXTH_UPDATED_TITLE).or_else(|| {
+                let name = self.header.full_name.trim();
+
+                (!name.is_empty()).then(|| vec![name.to_string()])
+            }),
+
+            BookSearch::Identifier => {
+                let mut values = exth.get_strings(EXTH_ISBN).unwrap_or_default();
+                values.extend(exth.get_strings(EXTH_ASIN).unwrap_or_default());
+
+                (!values.is_empty()).then_some(values)
+            }
+
+            BookSearch::CoverImage => {
+                let offset = exth.get_u32(EXTH_COVER_OFFSET)?;
+
+                if offset == NULL_INDEX {
+                    return None;
+                }
+
+                Some(vec![resource_path(
+                    &self.reader,
+                    self.first_resource,
+                    offset as usize + 1,
+                )?])
+            }
+
+            BookSearch::Language => exth.get_strings(EXTH_LANGUAGE),
+            BookSearch::Contributor => exth.get_strings(EXTH_CONTRIBUTOR),
+            BookSearch::Creator => exth.get_strings(EXTH_AUTHOR),
+            BookSearch::Date => exth.get_strings(EXTH_PUBLISHED_DATE),
+            BookSearch::Description => exth.get_strings(EXTH_DESCRIPTION),
+            BookSearch::Publisher => exth.get_strings(EXTH_PUBLISHER),
+            BookSearch::Rights => exth.get_strings(EXTH_RIGHTS),
+            BookSearch::Source => exth.get_strings(EXTH_SOURCE),
+            BookSearch::Subject => exth.get_strings(EXTH_SUBJECT),
+
+            // Lookup by EXTH record type.
+            BookSearch::Other(v) => exth.get_strings(v.parse().ok()?),
+
+            BookSearch::Coverage | BookSearch::Format | BookSearch::Relation | BookSearch::Type => {
+                None
+            }
+        }
     }
 
     fn get_unique_id(&self) -> Result<Cow<str>> {
-        todo!()
+        let exth = &self.header.exth;
+
+        if let Some(value) = exth
+            .get_strings(EXTH_ASIN)
+            .or_else(|| exth.get_strings(EXTH_ISBN))
+            .and_then(|v| v.into_iter().next())
+        {
+            return Ok(Cow::Owned(value));
+        }
+
+        Ok(Cow::Owned(self.header.unique_id.to_string()))
+    }
+
+    fn get_files(&self) -> Vec<String> {
+        let mut files = self
+            .sections
+            .iter()
+            .map(|v| v.path.clone())
+            .collect::<Vec<_>>();
+
+        files.extend(
+            self.flows
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(number, flow)| kf8::flow_path(number, Some(flow))),
+        );
+
+        files.extend(
+            (1..=self
+                .reader
+                .record_count()
+                .saturating_sub(self.first_resource))
+                .filter_map(|number| resource_path(&self.reader, self.first_resource, number)),
+        );
+
+        files
     }
 
     fn get_root_file_dir(&self) -> &Path {
-        todo!()
+        Path::new("")
     }
 
     fn get_page_path(&self) -> PathBuf {
-        todo!()
+        self.current_section()
+            .map(|v| PathBuf::from(&v.path))
+            .unwrap_or_default()
     }
 
     fn read_page_raw_as_bytes(&mut self) -> Result<Vec<u8>> {
-        todo!()
+        Ok(self.current_section()?.contents.clone())
+    }
+
+    fn read_page_as_text(&mut self) -> Result<Option<ChapterText>> {
+        let value = &self.current_section()?.contents;
+
+        Ok(Some(markup::html_to_text(&String::from_utf8_lossy(value))))
     }
 
     fn read_path_as_bytes(
         &mut self,
-        _path: &str,
-        _prepend_to_urls: Option<&str>,
-        _add_css: Option<&[&str]>,
+        path: &str,
+        prepend_to_urls: Option<&str>,
+        add_css: Option<&[&str]>,
+        sanitize: Option<SanitizeOptions>,
     ) -> Result<Vec<u8>> {
-        todo!()
+        if prepend_to_urls.is_some() || add_css.is_some() || sanitize.is_some() {
+            let page_path = PathBuf::from(path);
+            let input = self.get_path_contents(path)?;
+
+            self.handle_update_attributes(&input, page_path, prepend_to_urls, add_css, sanitize)
+        } else {
+            self.get_path_contents(path)
+        }
     }
 
     fn read_page_as_bytes(
         &mut self,
-        _prepend_to_urls: Option<&str>,
-        _add_css: Option<&[&str]>,
+        prepend_to_urls: Option<&str>,
+        add_css: Option<&[&str]>,
+        sanitize: Option<SanitizeOptions>,
     ) -> Result<Vec<u8>> {
-        todo!()
+        let page_path = self.get_page_path();
+        let input = self.read_page_raw_as_bytes()?;
+
+        self.handle_update_attributes(&input, page_path, prepend_to_urls, add_css, sanitize)
     }
 
     fn chapter_count(&self) -> usize {
-        todo!()
+        self.sections.len()
     }
 
-    fn set_chapter(&mut self, _value: usize) -> bool {
-        todo!()
+    fn set_chapter(&mut self, value: usize) -> bool {
+        if value < self.chapter_count() {
+            self.chapter = value;
+            true
+        } else {
+            false
+        }
     }
 
     fn next_chapter(&mut self) -> bool {
-        todo!()
+        self.set_chapter(self.chapter + 1)
     }
 
     fn previous_chapter(&mut self) -> bool {
-        todo!()
+        if self.chapter != 0 {
+            self.set_chapter(self.chapter - 1)
+        } else {
+            false
+        }
     }
 
     fn get_chapter(&self) -> usize {
-        todo!()
+        self.chapter
+    }
+}
+
+/// The path of the resource (image or font) if the record is one.
+fn resource_path(reader: &MobiReader, first_resource: usize, number: usize) -> Option<String> {
+    if number == 0 {
+        return None;
+    }
+
+    let data = reader.record(first_resource + number - 1).ok()?;
+
+    let image_ext = if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("jpg")
+    } else if data.starts_with(b"\x89PNG") {
+        Some("png")
+    } else if data.starts_with(b"GIF8") {
+        Some("gif")
+    } else if data.starts_with(b"BM") {
+        Some("bmp")
+    } else {
+        None
+    };
+
+    if let Some(ext) = image_ext {
+        Some(format!("images/{number:05}.{ext}"))
+    } else if data.starts_with(b"FONT") {
+        let ext = match decode_font(data).ok()?.get(0..4) {
+            Some(b"OTTO") => "otf",
+            _ => "ttf",
+        };
+
+        Some(format!("fonts/{number:05}.{ext}"))
+    } else {
+        None
+    }
+}
+
+/// Fonts may be compressed and obfuscated.
+///
+/// https://wiki.mobileread.com/wiki/KF8#Embedded_Fonts
+fn decode_font(data: &[u8]) -> Result<Vec<u8>> {
+    let decoded_size = read_u32(data, 4)? as usize;
+    let flags = read_u32(data, 8)?;
+    let data_offset = read_u32(data, 12)? as usize;
+    let xor_key_length = read_u32(data, 16)? as usize;
+    let xor_key_offset = read_u32(data, 20)? as usize;
+
+    let mut font = data
+        .get(data_offset..)
+        .ok_or(Error::Mobi("Invalid font record"))?
+        .to_vec();
+
+    if flags & 0x0002 != 0 && xor_key_length != 0 {
+        let key = data
+            .get(xor_key_offset..xor_key_offset + xor_key_length)
+            .ok_or(Error::Mobi("Invalid font record"))?;
+
+        for (i, v) in font.iter_mut().take(1040).enumerate() {
+            *v ^= key[i % key.len()];
+        }
+    }
+
+    if flags & 0x0001 != 0 {
+        let mut buf = Vec::with_capacity(decoded_size);
+        ZlibDecoder::new(font.as_slice()).read_to_end(&mut buf)?;
+        font = buf;
+    }
+
+    Ok(font)
+}
+
+fn read_table_of_contents(
+    reader: &MobiReader,
+    header: &MobiHeader,
+    sections: &[Section],
+    fragments: &[Fragment],
+) -> Result<Vec<TocItem>> {
+    let Some(ncx_index) = header.ncx_index else {
+        return Ok(Vec::new());
+    };
+
+    let index = Index::read(reader, ncx_index)?;
+
+    // The item along with the index of its parent entry.
+    let mut items = index
+        .entries
+        .iter()
+        .map(|entry| {
+            let label = entry.tag_value(3, 0).and_then(|v| index.cncx_string(v))?;
+
+            // KF8 stores the position as a fragment id + offset.
+            let pos = match entry.tag(6) {
+                Some(&[fid, offset]) if !fragments.is_empty() => fragments
+                    .get(fid as usize)
+                    .map(|v| v.insert_pos + offset as usize),
+                _ => entry.tag_value(1, 0).map(|v| v as usize),
+            }?;
+
+            let chapter = sections
+                .iter()
+                .position(|v| v.start <= pos && pos < v.end)?;
+
+            Some((
+                TocItem {
+                    title: label.to_string(),
+                    chapter,
+                    fragment: None,
+                    children: Vec::new(),
+                },
+                entry.tag_value(21, 0).map(|v| v as usize),
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    // Children are always after their parent so we can nest them in reverse.
+    for i in (0..items.len()).rev() {
+        let Some(parent) = items[i].as_ref().and_then(|v| v.1).filter(|&v| v < i) else {
+            continue;
+        };
+
+        if items[parent].is_some() {
+            let (item, _) = items[i].take().unwrap();
+
+            items[parent].as_mut().unwrap().0.children.insert(0, item);
+        }
+    }
+
+    Ok(items.into_iter().flatten().map(|v| v.0).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str) -> MobiBook {
+        MobiBook::load_from_path(format!("{}/fixtures/{name}", env!("CARGO_MANIFEST_DIR"))).unwrap()
+    }
+
+    #[test]
+    fn mobi7() {
+        let mut book = load("book.mobi");
+
+        assert_eq!(
+            book.get_files(),
+            [
+                "text/part0000.xhtml",
+                "text/part0001.xhtml",
+                "images/00001.png"
+            ]
+        );
+        assert_eq!(book.chapter_count(), 2);
+
+        assert_eq!(book.find(BookSearch::Title).unwrap(), ["The Book"]);
+        assert_eq!(
+            book.find(BookSearch::Creator).unwrap(),
+            ["Jane Doe", "John Roe"]
+        );
+        assert_eq!(book.find(BookSearch::Publisher).unwrap(), ["Publisher"]);
+        assert_eq!(book.find(BookSearch::Language).unwrap(), ["en"]);
+        assert_eq!(book.find(BookSearch::Date), None);
+        assert_eq!(
+            book.find(BookSearch::CoverImage).unwrap(),
+            ["images/00001.png"]
+        );
+        assert_eq!(book.get_unique_id().unwrap(), "9780000000000");
+
+        let toc = book.get_table_of_contents().unwrap().unwrap();
+
+        assert_eq!(toc.len(), 2);
+        assert_eq!(toc[0].title, "Chapter One");
+        assert_eq!(toc[0].chapter, 0);
+        assert_eq!(toc[0].children.len(), 1);
+        assert_eq!(toc[0].children[0].title, "Scene");
+        assert_eq!(toc[1].title, "Chapter Two");
+        assert_eq!(toc[1].chapter, 1);
+
+        let page = book.read_page_raw_as_string().unwrap();
+
+        // CP1252 text is decoded and the filepos link points to the anchor in the next section.
+        assert!(page.contains("<title>Book</title>"), "{page}");
+        assert!(
+            page.contains("<p>Café <a href=\"part0001.xhtml#filepos"),
+            "{page}"
+        );
+        assert!(!page.contains("guide"), "{page}");
+
+        assert!(book.next_chapter());
+        let page = book.read_page_raw_as_string().unwrap();
+
+        assert!(page.contains("<a id=\"filepos"), "{page}");
+        assert!(
+            page.contains(r#"<img src="../images/00001.png"/>"#),
+            "{page}"
+        );
+        assert!(!book.next_chapter());
+
+        assert_eq!(
+            book.read_path_as_bytes("images/00001.png", None, None, None)
+                .unwrap(),
+            b"\x89PNG\r\n\x1a\nimage"
+        );
+        assert!(book
+            .read_path_as_bytes("images/00002.png", None, None, None)
+            .is_err());
+    }
+
+    #[test]
+    fn kf8() {
+        let mut book = load("book.azw3");
+
+        assert_eq!(
+            book.get_files(),
+            [
+                "text/part0000.xhtml",
+                "text/part0001.xhtml",
+                "flows/flow0001.css",
+                "images/00001.png"
+            ]
+        );
+
+        assert_eq!(book.find(BookSearch::Title).unwrap(), ["KF8 Book"]);
+        assert_eq!(book.find(BookSearch::CoverImage), None);
+        assert_eq!(book.get_unique_id().unwrap(), "B000000000");
+
+        let toc = book.get_table_of_contents().unwrap().unwrap();
+        let chapters = toc
+            .iter()
+            .map(|v| (v.title.as_str(), v.chapter))
+            .collect::<Vec<_>>();
+
+        assert_eq!(chapters, [("One", 0), ("Two", 1)]);
+
+        assert_eq!(
+            book.read_path_as_bytes("flows/flow0001.css", None, None, None)
+                .unwrap(),
+            b"p { color: red; }"
+        );
+
+        // The stylesheet is inlined when the page is served.
+        let page = String::from_utf8(book.read_page_as_bytes(None, None, None).unwrap()).unwrap();
+
+        assert!(page.contains("<style>p { color: red; }</style>"), "{page}");
+        assert!(
+            page.contains(r#"<a href="part0001.xhtml#b">Two</a>"#),
+            "{page}"
+        );
+        assert!(page.contains("data:image/png;"), "{page}");
+
+        let hash = book.compute_hash();
+        assert!(hash.is_some());
+        assert_ne!(hash, load("book.mobi").compute_hash());
     }
 }
@@ -1,58 +1,496 @@
-// TODO: Was going to create my own. Decided against it for now.
+// https://wiki.mobileread.com/wiki/PDB#Palm_Database_Format
+// https://wiki.mobileread.com/wiki/MOBI#MOBI_Header
 
+use std::{collections::HashMap, io::Read};
 
-use std::io::{Read, Seek, SeekFrom};
+use super::{
+    compression::{palmdoc_decompress, HuffCdicReader, HUFF_CDIC, NO_COMPRESSION, PALM_DOC},
+    index::NULL_INDEX,
+};
+use crate::{Error, Result};
 
-use crate::Result;
+pub const ENCODING_CP1252: u32 = 1252;
 
+// EXTH Record Types
+pub const EXTH_AUTHOR: u32 = 100;
+pub const EXTH_PUBLISHER: u32 = 101;
+pub const EXTH_DESCRIPTION: u32 = 103;
+pub const EXTH_ISBN: u32 = 104;
+pub const EXTH_SUBJECT: u32 = 105;
+pub const EXTH_PUBLISHED_DATE: u32 = 106;
+pub const EXTH_CONTRIBUTOR: u32 = 108;
+pub const EXTH_RIGHTS: u32 = 109;
+pub const EXTH_SOURCE: u32 = 112;
+pub const EXTH_ASIN: u32 = 113;
+pub const EXTH_KF8_BOUNDARY: u32 = 121;
+pub const EXTH_COVER_OFFSET: u32 = 201;
+pub const EXTH_UPDATED_TITLE: u32 = 503;
+pub const EXTH_LANGUAGE: u32 = 524;
 
-pub struct MobiReader<D: Read + Seek> {
-    data: D
+/// The Palm Database container which every MOBI file is stored in.
+pub struct MobiReader {
+    data: Vec<u8>,
+    record_offsets: Vec<usize>,
 }
 
-impl<D: Read + Seek> MobiReader<D> {
-    pub fn new(data: D) -> Result<Self> {
-        let mut this = Self {
-            data
+impl MobiReader {
+    pub fn new<R: Read>(mut reader: R) -> Result<Self> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        if data.len() < 78 {
+            return Err(Error::Mobi("File too small"));
+        }
+
+        if &data[60..64] != b"BOOK" {
+            return Err(Error::Mobi("Invalid Palm Database type"));
+        }
+
+        let record_count = read_u16(&data, 76)? as usize;
+
+        let mut record_offsets = Vec::with_capacity(record_count);
+
+        for i in 0..record_count {
+            record_offsets.push(read_u32(&data, 78 + i * 8)? as usize);
+        }
+
+        Ok(Self {
+            data,
+            record_offsets,
+        })
+    }
+
+    pub fn record_count(&self) -> usize {
+        self.record_offsets.len()
+    }
+
+    pub fn record(&self, index: usize) -> Result<&[u8]> {
+        let start = *self
+            .record_offsets
+            .get(index)
+            .ok_or(Error::Mobi("Record index out of bounds"))?;
+
+        let end = self
+            .record_offsets
+            .get(index + 1)
+            .copied()
+            .unwrap_or(self.data.len());
+
+        self.data
+            .get(start..end)
+            .ok_or(Error::Mobi("Invalid record offset"))
+    }
+}
+
+/// The PalmDOC + MOBI header stored inside the first record of a book.
+///
+/// Combination files (MOBI 7 + KF8) contain two of them. Every record index is made absolute.
+#[derive(Debug)]
+pub struct MobiHeader {
+    /// The absolute record index this header is located at.
+    pub start: usize,
+
+    pub compression: u16,
+    pub text_length: usize,
+    pub text_record_count: usize,
+    pub encryption: u16,
+
+    pub text_encoding: u32,
+    pub unique_id: u32,
+    pub version: u32,
+
+    pub full_name: String,
+
+    pub first_resource: Option<usize>,
+    pub huff_record: Option<usize>,
+    pub huff_record_count: usize,
+
+    pub extra_flags: u16,
+
+    pub ncx_index: Option<usize>,
+
+    // KF8 only
+    pub fdst_index: Option<usize>,
+    pub fragment_index: Option<usize>,
+    pub skeleton_index: Option<usize>,
+
+    pub exth: Exth,
+}
+
+impl MobiHeader {
+    pub fn parse(reader: &MobiReader, start: usize) -> Result<Self> {
+        let data = reader.record(start)?;
+
+        if data.get(16..20) != Some(b"MOBI") {
+            return Err(Error::Mobi("Missing MOBI header"));
+        }
+
+        let header_length = read_u32(data, 20)? as usize;
+        let version = read_u32(data, 36)?;
+
+        let full_name = {
+            let offset = read_u32(data, 84)? as usize;
+            let length = read_u32(data, 88)? as usize;
+
+            data.get(offset..offset + length)
+                .map(|v| String::from_utf8_lossy(v).into_owned())
+                .unwrap_or_default()
+        };
+
+        let index_at = |offset: usize| -> Result<Option<usize>> {
+            if offset + 4 > 16 + header_length {
+                return Ok(None);
+            }
+
+            let value = read_u32(data, offset)?;
+
+            Ok((value != NULL_INDEX).then_some(start + value as usize))
+        };
+
+        let exth = if read_u32(data, 128)? & 0x40 != 0 {
+            Exth::parse(data.get(16 + header_length..).unwrap_or_default())?
+        } else {
+            Exth::default()
+        };
+
+        let is_kf8 = version >= 8;
+
+        Ok(Self {
+            start,
+
+            compression: read_u16(data, 0)?,
+            text_length: read_u32(data, 4)? as usize,
+            text_record_count: read_u16(data, 8)? as usize,
+            encryption: read_u16(data, 12)?,
+
+            text_encoding: read_u32(data, 28)?,
+            unique_id: read_u32(data, 32)?,
+            version,
+
+            full_name,
+
+            first_resource: index_at(108)?,
+            huff_record: index_at(112)?,
+            huff_record_count: read_u32(data, 116)? as usize,
+
+            extra_flags: if header_length >= 0xE4 {
+                read_u16(data, 0xF2)?
+            } else {
+                0
+            },
+
+            ncx_index: index_at(0xF4)?,
+
+            fdst_index: if is_kf8 { index_at(0xC0)? } else { None },
+            fragment_index: if is_kf8 { index_at(0xF8)? } else { None },
+            skeleton_index: if is_kf8 { index_at(0xFC)? } else { None },
+
+            exth,
+        })
+    }
+
+    pub fn is_kf8(&self) -> bool {
+        self.version >= 8
+    }
+
+    /// Decompress and concatenate every text record.
+    pub fn read_text(&self, reader: &MobiReader) -> Result<Vec<u8>> {
+        if self.encryption != 0 {
+            return Err(Error::Mobi("DRM Protected"));
+        }
+
+        let mut huff = match (self.compression, self.huff_record) {
+            (HUFF_CDIC, Some(huff_record)) => {
+                let cdics = (1..self.huff_record_count)
+                    .map(|i| reader.record(huff_record + i))
+                    .collect::<Result<Vec<_>>>()?;
+
+                Some(HuffCdicReader::new(reader.record(huff_record)?, &cdics)?)
+            }
+
+            (HUFF_CDIC, None) => return Err(Error::Mobi("Missing HUFF record")),
+
+            (NO_COMPRESSION | PALM_DOC, _) => None,
+
+            _ => return Err(Error::Mobi("Unknown compression")),
         };
 
-        this.verify()?;
+        let records = (1..=self.text_record_count)
+            .map(|i| reader.record(self.start + i))
+            .collect::<Result<Vec<_>>>()?;
+
+        // The length comes from the file. A corrupt one shouldn't cause a huge allocation.
+        let mut text =
+            Vec::with_capacity(self.text_length.min(records.iter().map(|v| v.len()).sum()));
+
+        for record in records {
+            let record = &record[..record
+                .len()
+                .saturating_sub(trailing_entries_size(record, self.extra_flags))];
+
+            match huff.as_mut() {
+                Some(huff) => text.extend(huff.unpack(record)?),
+                None if self.compression == PALM_DOC => text.extend(palmdoc_decompress(record)),
+                None => text.extend_from_slice(record),
+            }
+        }
+
+        text.truncate(self.text_length);
+
+        Ok(text)
+    }
+}
+
+/// Extended Header. Contains the metadata of the book.
+#[derive(Debug, Default)]
+pub struct Exth {
+    pub records: HashMap<u32, Vec<Vec<u8>>>,
+}
+
+impl Exth {
+    fn parse(data: &[u8]) -> Result<Self> {
+        let mut this = Self::default();
+
+        if data.get(0..4) != Some(b"EXTH") {
+            return Ok(this);
+        }
+
+        let count = read_u32(data, 8)?;
+
+        let mut offset = 12;
+
+        for _ in 0..count {
+            let type_of = read_u32(data, offset)?;
+            let length = read_u32(data, offset + 4)? as usize;
+
+            if length < 8 {
+                break;
+            }
+
+            let value = data
+                .get(offset + 8..offset + length)
+                .ok_or(Error::Mobi("Invalid EXTH record"))?;
+
+            this.records
+                .entry(type_of)
+                .or_default()
+                .push(value.to_vec());
+
+            offset += length;
+        }
 
         Ok(this)
     }
 
-    fn verify(&mut self) -> Result<()> {
-        let name = read_next_to_vec(&mut self.data, 32)?;
-        println!("{:?}", std::str::from_utf8(&name));
+    pub fn get_strings(&self, type_of: u32) -> Option<Vec<String>> {
+        let values = self
+            .records
+            .get(&type_of)?
+            .iter()
+            .map(|v| String::from_utf8_lossy(v).trim().to_string())
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<_>>();
+
+        if values.is_empty() {
+            None
+        } else {
+            Some(values)
+        }
+    }
+
+    pub fn get_u32(&self, type_of: u32) -> Option<u32> {
+        let value = self.records.get(&type_of)?.first()?;
+
+        Some(read_u32(value, 0).ok()).flatten()
+    }
+}
+
+/// Size of the extra data appended to the end of each text record.
+///
+/// https://wiki.mobileread.com/wiki/MOBI#Variable-width_integers
+fn trailing_entries_size(data: &[u8], flags: u16) -> usize {
+    let mut size = 0;
+
+    let mut flags_left = flags >> 1;
 
-        // let compression = read_u16(&mut self.data)?;
-        // let unused = read_u16(&mut self.data)?;
+    while flags_left != 0 {
+        if flags_left & 1 != 0 {
+            size += trailing_entry_size(&data[..data.len().saturating_sub(size)]);
+        }
 
-        // println!("{:?}", compression);
-        // println!("{:?}", unused);
+        flags_left >>= 1;
+    }
 
-        Ok(())
+    // Multibyte character overlap
+    if flags & 1 != 0 {
+        if let Some(v) = data.len().checked_sub(size + 1).map(|i| data[i]) {
+            size += (v & 0x3) as usize + 1;
+        }
     }
+
+    size.min(data.len())
 }
 
-fn read_next_to_vec<D: Read + Seek>(data: &mut D, len: usize) -> Result<Vec<u8>> {
-    let mut read = vec![0; len];
+/// Backward-encoded variable width value. The high bit marks the first byte.
+fn trailing_entry_size(data: &[u8]) -> usize {
+    let mut bit_pos = 0;
+    let mut result = 0;
 
-    data.read_exact(&mut read)?;
+    for &v in data.iter().rev() {
+        result |= ((v & 0x7F) as usize) << bit_pos;
+        bit_pos += 7;
 
-    data.seek(SeekFrom::Current(len as i64))?;
+        if v & 0x80 != 0 || bit_pos >= 28 {
+            break;
+        }
+    }
 
-    Ok(read.to_vec())
+    result
 }
 
-fn read_u16<D: Read + Seek>(data: &mut D) -> Result<u16> {
-    let mut bytes = [0; 2];
-    data.read_exact(&mut bytes)?;
-    Ok(u16::from_be_bytes(bytes))
+pub(super) fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .ok_or(Error::Mobi("Unexpected end of record"))?;
+
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
 }
 
-fn read_u8<D: Read + Seek>(data: &mut D) -> Result<u8> {
-    let mut bytes = [0; 1];
-    data.read_exact(&mut bytes)?;
-    Ok(u8::from_be_bytes(bytes))
-}
\ No newline at end of file
+pub(super) fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    let bytes = data
+        .get(offset..offset + 2)
+        .ok_or(Error::Mobi("Unexpected end of record"))?;
+
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+
+    fn read_fixture(name: &str) -> MobiReader {
+        let path = format!("{}/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+
+        MobiReader::new(File::open(path).unwrap()).unwrap()
+    }
+
+    fn exth_record(records: &[(u32, &[u8])]) -> Vec<u8> {
+        let mut body = Vec::new();
+
+        for (type_of, value) in records {
+            body.extend(type_of.to_be_bytes());
+            body.extend((value.len() as u32 + 8).to_be_bytes());
+            body.extend_from_slice(value);
+        }
+
+        let mut data = b"EXTH".to_vec();
+        data.extend((body.len() as u32 + 12).to_be_bytes());
+        data.extend((records.len() as u32).to_be_bytes());
+        data.extend(body);
+
+        data
+    }
+
+    #[test]
+    fn exth_records() {
+        let exth = Exth::parse(&exth_record(&[
+            (EXTH_AUTHOR, b"Jane Doe"),
+            (EXTH_AUTHOR, b" John Roe "),
+            (EXTH_SUBJECT, b"  "),
+            (EXTH_COVER_OFFSET, &2u32.to_be_bytes()),
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            exth.get_strings(EXTH_AUTHOR).unwrap(),
+            ["Jane Doe", "John Roe"]
+        );
+        assert_eq!(exth.get_strings(EXTH_SUBJECT), None);
+        assert_eq!(exth.get_strings(EXTH_PUBLISHER), None);
+        assert_eq!(exth.get_u32(EXTH_COVER_OFFSET), Some(2));
+
+        // Not an EXTH header
+        assert!(Exth::parse(b"ABCD").unwrap().records.is_empty());
+
+        // The record claims to be longer than the data.
+        let mut data = exth_record(&[(EXTH_AUTHOR, b"Jane Doe")]);
+        data.truncate(data.len() - 2);
+
+        assert!(Exth::parse(&data).is_err());
+    }
+
+    #[test]
+    fn trailing_entries() {
+        // A 3 byte trailing entry whose size is stored in its last byte.
+        assert_eq!(trailing_entries_size(b"text\xAA\xBB\x83", 0b10), 3);
+        // Multibyte overlap of one extra byte.
+        assert_eq!(trailing_entries_size(b"textX\x01", 0b01), 2);
+        // The multibyte bytes are located before the trailing entries.
+        assert_eq!(trailing_entries_size(b"textX\x01\xAA\xBB\x83", 0b11), 5);
+        assert_eq!(trailing_entries_size(b"text", 0), 0);
+        // Never larger than the record.
+        assert_eq!(trailing_entries_size(b"\xFF", 0b10), 1);
+    }
+
+    #[test]
+    fn mobi7_header_and_text() {
+        let reader = read_fixture("book.mobi");
+        let header = MobiHeader::parse(&reader, 0).unwrap();
+
+        assert!(!header.is_kf8());
+        assert_eq!(header.compression, PALM_DOC);
+        assert_eq!(header.text_encoding, ENCODING_CP1252);
+        assert_eq!(header.text_record_count, 2);
+        assert_eq!(header.extra_flags, 0b11);
+        assert_eq!(header.full_name, "Full Name");
+        assert_eq!(header.first_resource, Some(6));
+        assert_eq!(header.ncx_index, Some(3));
+        assert_eq!(header.huff_record, None);
+        assert_eq!(header.skeleton_index, None);
+        assert_eq!(
+            header.exth.get_strings(EXTH_UPDATED_TITLE).unwrap(),
+            ["The Book"]
+        );
+
+        let text = header.read_text(&reader).unwrap();
+
+        // Both records are joined without their trailing entries.
+        assert_eq!(text.len(), header.text_length);
+        assert!(text.starts_with(b"<html><head><title>Book</title>"));
+        assert!(text.ends_with(b"<img recindex=\"00001\"/></p></body></html>"));
+        assert!(!text.windows(2).any(|v| v == b"\xAA\xBB"));
+    }
+
+    #[test]
+    fn kf8_header_and_huff_cdic_text() {
+        let reader = read_fixture("book.azw3");
+        let header = MobiHeader::parse(&reader, 0).unwrap();
+
+        assert!(header.is_kf8());
+        assert_eq!(header.compression, HUFF_CDIC);
+        assert_eq!(header.huff_record, Some(11));
+        assert_eq!(header.huff_record_count, 2);
+        assert_eq!(header.skeleton_index, Some(3));
+        assert_eq!(header.fragment_index, Some(5));
+        assert_eq!(header.fdst_index, Some(10));
+
+        let text = header.read_text(&reader).unwrap();
+
+        assert_eq!(text.len(), header.text_length);
+        assert!(text.starts_with(b"<html><head><link rel=\"stylesheet\""));
+        assert!(text.ends_with(b"<p id=\"b\">Two</p>p { color: red; }"));
+    }
+
+    #[test]
+    fn invalid_palm_database() {
+        assert!(MobiReader::new(&b"BOOKMOBI"[..]).is_err());
+
+        let mut data =
+            std::fs::read(format!("{}/fixtures/book.mobi", env!("CARGO_MANIFEST_DIR"))).unwrap();
+        data[60..64].copy_from_slice(b"TEXt");
+
+        assert!(MobiReader::new(data.as_slice()).is_err());
+    }
+}
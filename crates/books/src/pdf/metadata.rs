@@ -0,0 +1,224 @@
+// https://opensource.adobe.com/dc-acrobatsdk/docs/pdfstandards/PDF32000_2008.pdf (14.3 Metadata)
+
+use std::collections::HashMap;
+
+use lopdf::{decode_text_string, Document, Object};
+use xml::reader::XmlEvent;
+
+/// The Document Information Dictionary and XMP metadata of a PDF.
+#[derive(Debug, Default)]
+pub struct PdfMetadata {
+    /// Info dictionary. Eg. "Title", "Author", "CreationDate"
+    pub info: HashMap<String, String>,
+
+    /// XMP properties with their namespace prefix. Eg. "dc:title", "xmp:CreateDate"
+    pub xmp: HashMap<String, Vec<String>>,
+
+    /// The natural language specified in the catalog.
+    pub language: Option<String>,
+}
+
+impl PdfMetadata {
+    pub fn parse(document: &Document) -> Self {
+        let mut this = Self::default();
+
+        if let Ok(info) = document
+            .trailer
+            .get(b"Info")
+            .and_then(|v| document.dereference(v))
+            .and_then(|(_, v)| v.as_dict())
+        {
+            for (key, value) in info.iter() {
+                let value = document.dereference(value).map_or(value, |(_, v)| v);
+
+                if let Some(value) = decode_text(value) {
+                    this.info
+                        .insert(String::from_utf8_lossy(key).into_owned(), value);
+                }
+            }
+        }
+
+        if let Ok(catalog) = document.catalog() {
+            this.language = catalog.get(b"Lang").ok().and_then(decode_text);
+
+            if let Ok(stream) = catalog
+                .get(b"Metadata")
+                .and_then(Object::as_reference)
+                .and_then(|id| document.get_object(id))
+                .and_then(Object::as_stream)
+            {
+                let content = stream
+                    .decompressed_content()
+                    .unwrap_or_else(|_| stream.content.clone());
+
+                this.xmp = parse_xmp(&content);
+            }
+        }
+
+        this
+    }
+
+    /// Prefer the XMP value since the Info dictionary is deprecated in PDF 2.0
+    pub fn get(&self, xmp_key: &str, info_key: Option<&str>) -> Option<Vec<String>> {
+        if let Some(values) = self.xmp.get(xmp_key).filter(|v| !v.is_empty()) {
+            return Some(values.clone());
+        }
+
+        Some(vec![self.info.get(info_key?)?.clone()])
+    }
+
+    pub fn get_date(&self) -> Option<Vec<String>> {
+        if let Some(values) = self
+            .xmp
+            .get("dc:date")
+            .or_else(|| self.xmp.get("xmp:CreateDate"))
+        {
+            return Some(values.clone());
+        }
+
+        Some(vec![parse_pdf_date(self.info.get("CreationDate")?)?])
+    }
+}
+
+fn decode_text(value: &Object) -> Option<String> {
+    let value = match value {
+        Object::Name(v) => String::from_utf8_lossy(v).into_owned(),
+        _ => decode_text_string(value).ok()?,
+    };
+
+    let value = value.trim().trim_matches('\0').to_string();
+
+    (!value.is_empty()).then_some(value)
+}
+
+/// Converts a PDF date (D:YYYYMMDDHHmmSSOHH'mm) into YYYY-MM-DD
+fn parse_pdf_date(value: &str) -> Option<String> {
+    let value = value.trim_start_matches("D:");
+
+    let year = value
+        .get(0..4)
+        .filter(|v| v.chars().all(|c| c.is_ascii_digit()))?;
+    let month = value.get(4..6).unwrap_or("01");
+    let day = value.get(6..8).unwrap_or("01");
+
+    Some(format!("{year}-{month}-{day}"))
+}
+
+/// Reads the properties out of the RDF. Container (rdf:Bag, rdf:Seq, rdf:Alt) values are flattened.
+fn parse_xmp(content: &[u8]) -> HashMap<String, Vec<String>> {
+    let mut properties: HashMap<String, Vec<String>> = HashMap::new();
+
+    let reader = xml::ParserConfig::new()
+        .trim_whitespace(true)
+        .ignore_comments(true)
+        .create_reader(content);
+
+    // Property currently being read along with its text.
+    let mut current: Option<(String, String)> = None;
+    let mut depth = 0;
+    let mut property_depth = 0;
+
+    for event in reader {
+        let Ok(event) = event else {
+            break;
+        };
+
+        match event {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                depth += 1;
+
+                let prefix = name.prefix.as_deref().unwrap_or_default();
+
+                if name.local_name == "Description" && prefix == "rdf" {
+                    // Properties can also be stored as attributes.
+                    for attr in attributes {
+                        if let Some(prefix) = attr.name.prefix.as_deref() {
+                            if prefix != "rdf" && prefix != "xmlns" {
+                                properties
+                                    .entry(format!("{prefix}:{}", attr.name.local_name))
+                                    .or_default()
+                                    .push(attr.value);
+                            }
+                        }
+                    }
+                } else if current.is_none()
+                    && !prefix.is_empty()
+                    && prefix != "rdf"
+                    && prefix != "x"
+                {
+                    current = Some((format!("{prefix}:{}", name.local_name), String::new()));
+                    property_depth = depth;
+                }
+            }
+
+            XmlEvent::Characters(value) | XmlEvent::CData(value) => {
+                if let Some((_, text)) = current.as_mut() {
+                    text.push_str(&value);
+                }
+            }
+
+            XmlEvent::EndElement { name } => {
+                if let Some((key, text)) = current.as_mut() {
+                    let text = std::mem::take(text);
+
+                    // Each rdf:li is its own value.
+                    if (name.local_name == "li" || depth == property_depth)
+                        && !text.trim().is_empty()
+                    {
+                        properties
+                            .entry(key.clone())
+                            .or_default()
+                            .push(text.trim().to_string());
+                    }
+
+                    if depth == property_depth {
+                        current = None;
+                    }
+                }
+
+                depth -= 1;
+            }
+
+            _ => (),
+        }
+    }
+
+    properties
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xmp_properties() {
+        let xmp = br#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+            <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+                <rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmp:CreateDate="2020-05-01">
+                    <dc:title><rdf:Alt><rdf:li xml:lang="x-default">A Title</rdf:li></rdf:Alt></dc:title>
+                    <dc:creator><rdf:Seq><rdf:li>First</rdf:li><rdf:li>Second</rdf:li></rdf:Seq></dc:creator>
+                    <dc:format>application/pdf</dc:format>
+                </rdf:Description>
+            </rdf:RDF>
+        </x:xmpmeta>"#;
+
+        let props = parse_xmp(xmp);
+
+        assert_eq!(props["dc:title"], vec!["A Title"]);
+        assert_eq!(props["dc:creator"], vec!["First", "Second"]);
+        assert_eq!(props["dc:format"], vec!["application/pdf"]);
+        assert_eq!(props["xmp:CreateDate"], vec!["2020-05-01"]);
+    }
+
+    #[test]
+    fn pdf_date() {
+        assert_eq!(
+            parse_pdf_date("D:20210304120000Z").as_deref(),
+            Some("2021-03-04")
+        );
+        assert_eq!(parse_pdf_date("D:1999").as_deref(), Some("1999-01-01"));
+        assert_eq!(parse_pdf_date("yesterday"), None);
+    }
+}
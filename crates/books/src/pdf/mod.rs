@@ -0,0 +1,795 @@
+// For .pdf files
+
+// https://opensource.adobe.com/dc-acrobatsdk/docs/pdfstandards/PDF32000_2008.pdf
+
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+mod metadata;
+
+use self::metadata::PdfMetadata;
+use super::Book;
+use crate::{markup, BookSearch, ChapterText, Error, ReadSeek, Result, SanitizeOptions, TocItem};
+
+/// We don't render pages. Each page is passed through as either its single JPEG image (scanned
+/// documents) or as a standalone single page PDF.
+pub struct PdfBook {
+    /// Empty when loaded from a reader.
+    file_name: String,
+    /// Hash of the whole file.
+    hash: String,
+
+    document: Document,
+    metadata: PdfMetadata,
+
+    pages: Vec<ObjectId>,
+    toc: Vec<TocItem>,
+
+    pub chapter: usize,
+}
+
+impl PdfBook {
+    fn parse(file_name: String, data: &[u8]) -> Result<Self> {
+        let mut document = Document::load_mem(data)?;
+
+        if document.is_encrypted() && document.decrypt("").is_err() {
+            return Err(Error::Pdf(lopdf::Error::Decryption(
+                lopdf::encryption::DecryptionError::IncorrectPassword,
+            )));
+        }
+
+        let pages = document.get_pages().into_values().collect::<Vec<_>>();
+
+        let toc = read_outline(&document, &pages);
+
+        Ok(Self {
+            file_name,
+            hash: blake3::hash(data).to_string(),
+
+            metadata: PdfMetadata::parse(&document),
+            document,
+
+            pages,
+            toc,
+
+            chapter: 0,
+        })
+    }
+
+    /// The JPEG of the page if the page only consists of it.
+    fn get_page_image(&self, index: usize) -> Option<&[u8]> {
+        let page = self.document.get_dictionary(*self.pages.get(index)?).ok()?;
+
+        let resources = self.get_inherited(page, b"Resources")?.as_dict().ok()?;
+
+        let xobjects = resources
+            .get_deref(b"XObject", &self.document)
+            .and_then(Object::as_dict)
+            .ok()?;
+
+        if xobjects.len() != 1 || resources.has(b"Font") {
+            return None;
+        }
+
+        let (_, xobject) = xobjects.iter().next()?;
+
+        let stream = self
+            .document
+            .dereference(xobject)
+            .and_then(|(_, v)| v.as_stream())
+            .ok()?;
+
+        let is_jpeg = stream.dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Image")
+            && stream
+                .filters()
+                .ok()?
+                .iter()
+                .map(|v| v.as_str())
+                .eq(["DCTDecode"]);
+
+        is_jpeg.then_some(stream.content.as_slice())
+    }
+
+    /// Page attributes such as Resources can be inherited from the parent page tree nodes.
+    fn get_inherited<'a>(&'a self, mut page: &'a Dictionary, key: &[u8]) -> Option<&'a Object> {
+        let mut seen = HashSet::new();
+
+        loop {
+            if let Ok(value) = page.get_deref(key, &self.document) {
+                return Some(value);
+            }
+
+            let parent = page.get(b"Parent").and_then(Object::as_reference).ok()?;
+
+            if !seen.insert(parent) {
+                return None;
+            }
+
+            page = self.document.get_dictionary(parent).ok()?;
+        }
+    }
+
+    /// Create a new PDF only containing the page.
+    ///
+    /// Only the objects the page references are copied over rather than the whole document.
+    fn get_page_as_pdf(&self, index: usize) -> Result<Vec<u8>> {
+        let page_id = *self
+            .pages
+            .get(index)
+            .ok_or(Error::MissingValueFor("page"))?;
+
+        let source = self.document.get_dictionary(page_id)?;
+        let mut page = source.clone();
+
+        // Copy inherited attributes onto the page since the parents aren't copied.
+        for key in [
+            &b"Resources"[..],
+            &b"MediaBox"[..],
+            &b"CropBox"[..],
+            &b"Rotate"[..],
+        ] {
+            if !page.has(key) {
+                if let Some(value) = self.get_inherited(source, key) {
+                    page.set(key.to_vec(), value.clone());
+                }
+            }
+        }
+
+        strip_external_references(&mut page);
+
+        let mut document = Document::with_version(self.document.version.clone());
+        document.max_id = self.document.max_id;
+
+        let mut pending = Vec::new();
+        push_references(&Object::Dictionary(page.clone()), &mut pending);
+
+        while let Some(id) = pending.pop() {
+            if id == page_id || document.objects.contains_key(&id) {
+                continue;
+            }
+
+            let Some(mut object) = self.document.objects.get(&id).cloned() else {
+                continue;
+            };
+
+            match &mut object {
+                Object::Dictionary(dict) => strip_external_references(dict),
+                Object::Stream(stream) => strip_external_references(&mut stream.dict),
+                _ => (),
+            }
+
+            push_references(&object, &mut pending);
+
+            document.objects.insert(id, object);
+        }
+
+        let pages_id = document.new_object_id();
+
+        page.set("Parent", pages_id);
+        document.objects.insert(page_id, Object::Dictionary(page));
+
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(Dictionary::from_iter([
+                ("Type", Object::Name(b"Pages".to_vec())),
+                ("Kids", Object::Array(vec![page_id.into()])),
+                ("Count", Object::Integer(1)),
+            ])),
+        );
+
+        let catalog_id = document.add_object(Dictionary::from_iter([
+            ("Type", Object::Name(b"Catalog".to_vec())),
+            ("Pages", pages_id.into()),
+        ]));
+
+        document.trailer.set("Root", catalog_id);
+
+        let mut buf = Vec::new();
+        document.save_to(&mut buf)?;
+
+        Ok(buf)
+    }
+
+    fn page_path(&self, index: usize) -> String {
+        let ext = if self.get_page_image(index).is_some() {
+            "jpg"
+        } else {
+            "pdf"
+        };
+
+        format!("pages/{:04}.{ext}", index + 1)
+    }
+
+    fn get_path_contents(&self, path: &str) -> Result<Vec<u8>> {
+        let index = path
+            .trim_start_matches('/')
+            .strip_prefix("pages/")
+            .and_then(|v| v.split('.').next())
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|&v| v != 0 && v <= self.pages.len())
+            .ok_or_else(|| Error::MissingFile(path.to_string()))?
+            - 1;
+
+        if path.ends_with(".jpg") {
+            if let Some(image) = self.get_page_image(index) {
+                return Ok(image.to_vec());
+            }
+        }
+
+        self.get_page_as_pdf(index)
+    }
+}
+
+impl Book for PdfBook {
+    fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let path = path.as_ref();
+
+        Self::parse(
+            path.file_name()
+                .map(|v| v.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            &std::fs::read(path)?,
+        )
+    }
+
+    fn load_from_reader<R: ReadSeek + 'static>(mut reader: R) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        Self::parse(String::new(), &data)
+    }
+
+    fn get_table_of_contents(&mut self) -> Result<Option<Vec<TocItem>>> {
+        if self.toc.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(self.toc.clone()))
+        }
+    }
+
+    fn compute_hash(&mut self) -> Option<String> {
+        Some(self.hash.clone())
+    }
+
+    fn find(&self, search: BookSearch<'_>) -> Option<Vec<String>> {
+        let meta = &self.metadata;
+
+        match search {
+            BookSearch::Title => meta.get("dc:title", Some("Title")),
+            BookSearch::Creator => meta.get("dc:creator", Some("Author")),
+            BookSearch::Description => meta.get("dc:description", Some("Subject")),
+            BookSearch::Date => meta.get_date(),
+
+            BookSearch::Subject => meta.get("dc:subject", None).or_else(|| {
+                Some(
+                    meta.info
+                        .get("Keywords")?
+                        .split([',', ';'])
+                        .map(|v| v.trim().to_string())
+                        .filter(|v| !v.is_empty())
+                        .collect(),
+                )
+            }),
+
+            BookSearch::Language => meta
+                .get("dc:language", None)
+                .or_else(|| Some(vec![meta.language.clone()?])),
+
+            BookSearch::Identifier => {
+                let mut values = meta.get("dc:identifier", None).unwrap_or_default();
+                values.extend(meta.get("xmp:Identifier", None).unwrap_or_default());
+                values.extend(meta.get("prism:isbn", None).unwrap_or_default());
+
+                (!values.is_empty()).then_some(values)
+            }
+
+            BookSearch::Contributor => meta.get("dc:contributor", None),
+            BookSearch::Coverage => meta.get("dc:coverage", None),
+            BookSearch::Publisher => meta.get("dc:publisher", None),
+            BookSearch::Relation => meta.get("dc:relation", None),
+            BookSearch::Rights => meta.get("dc:rights", None),
+            BookSearch::Source => meta.get("dc:source", None),
+            BookSearch::Type => meta.get("dc:type", None),
+            BookSearch::Format => Some(vec![String::from("application/pdf")]),
+
+            // Only use the first page if it's an image. Otherwise it'd be a PDF.
+            BookSearch::CoverImage => self
+                .get_page_image(0)
+                .is_some()
+                .then(|| vec![self.page_path(0)]),
+
+            BookSearch::Other(v) => meta
+                .info
+                .get(v)
+                .map(|v| vec![v.clone()])
+                .or_else(|| meta.xmp.get(v).cloned()),
+        }
+    }
+
+    fn get_unique_id(&self) -> Result<Cow<str>> {
+        // The first file identifier is permanent. The second one changes on every update.
+        if let Some(id) = self
+            .document
+            .trailer
+            .get(b"ID")
+            .and_then(Object::as_array)
+            .ok()
+            .and_then(|v| v.first())
+            .and_then(|v| v.as_str().ok())
+            .filter(|v| !v.is_empty())
+        {
+            return Ok(Cow::Owned(
+                id.iter().map(|v| format!("{v:02x}")).collect::<String>(),
+            ));
+        }
+
+        if let Some(id) = self
+            .metadata
+            .xmp
+            .get("xmpMM:DocumentID")
+            .and_then(|v| v.first())
+        {
+            return Ok(Cow::Borrowed(id.as_str()));
+        }
+
+        if self.file_name.is_empty() {
+            return Err(Error::MissingUniqueId);
+        }
+
+        Ok(Cow::Borrowed(self.file_name.as_str()))
+    }
+
+    fn get_files(&self) -> Vec<String> {
+        (0..self.pages.len()).map(|i| self.page_path(i)).collect()
+    }
+
+    fn get_root_file_dir(&self) -> &Path {
+        Path::new("")
+    }
+
+    fn get_page_path(&self) -> PathBuf {
+        PathBuf::from(self.page_path(self.chapter))
+    }
+
+    fn read_page_raw_as_bytes(&mut self) -> Result<Vec<u8>> {
+        self.get_path_contents(&self.page_path(self.chapter))
+    }
+
+    fn read_page_as_text(&mut self) -> Result<Option<ChapterText>> {
+        // Page numbers start at 1.
+        // Not every PDF has text we're able to extract. Eg. Invalid ToUnicode maps or scanned pages.
+        Ok(self
+            .document
+            .extract_text(&[self.chapter as u32 + 1])
+            .ok()
+            .map(|v| markup::plain_to_text(&v)))
+    }
+
+    fn read_path_as_bytes(
+        &mut self,
+        path: &str,
+        _prepend_to_urls: Option<&str>,
+        _add_css: Option<&[&str]>,
+        _sanitize: Option<SanitizeOptions>,
+    ) -> Result<Vec<u8>> {
+        self.get_path_contents(path)
+    }
+
+    fn read_page_as_bytes(
+        &mut self,
+        _prepend_to_urls: Option<&str>,
+        _add_css: Option<&[&str]>,
+        _sanitize: Option<SanitizeOptions>,
+    ) -> Result<Vec<u8>> {
+        self.read_page_raw_as_bytes()
+    }
+
+    fn chapter_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    fn set_chapter(&mut self, value: usize) -> bool {
+        if value < self.chapter_count() {
+            self.chapter = value;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn next_chapter(&mut self) -> bool {
+        self.set_chapter(self.chapter + 1)
+    }
+
+    fn previous_chapter(&mut self) -> bool {
+        if self.chapter != 0 {
+            self.set_chapter(self.chapter - 1)
+        } else {
+            false
+        }
+    }
+
+    fn get_chapter(&self) -> usize {
+        self.chapter
+    }
+}
+
+/// Remove keys which point outside of a page. Eg. the page tree, link destinations and actions.
+fn strip_external_references(dict: &mut Dictionary) {
+    for key in [&b"Parent"[..], &b"P"[..], &b"Dest"[..], &b"A"[..]] {
+        dict.remove(key);
+    }
+
+    // Annotations are usually direct objects inside of the page.
+    for (_, value) in dict.iter_mut() {
+        strip_nested_references(value);
+    }
+}
+
+fn strip_nested_references(object: &mut Object) {
+    match object {
+        Object::Dictionary(dict) => strip_external_references(dict),
+        Object::Array(values) => values.iter_mut().for_each(strip_nested_references),
+        _ => (),
+    }
+}
+
+fn push_references(object: &Object, pending: &mut Vec<ObjectId>) {
+    match object {
+        Object::Reference(id) => pending.push(*id),
+        Object::Array(values) => values.iter().for_each(|v| push_references(v, pending)),
+        Object::Dictionary(dict) => dict.iter().for_each(|(_, v)| push_references(v, pending)),
+        Object::Stream(stream) => stream
+            .dict
+            .iter()
+            .for_each(|(_, v)| push_references(v, pending)),
+        _ => (),
+    }
+}
+
+/// Reads the document outline into the table of contents.
+fn read_outline(document: &Document, pages: &[ObjectId]) -> Vec<TocItem> {
+    let Some(first) = document
+        .catalog()
+        .and_then(|v| v.get_deref(b"Outlines", document))
+        .and_then(Object::as_dict)
+        .and_then(|v| v.get(b"First"))
+        .and_then(Object::as_reference)
+        .ok()
+    else {
+        return Vec::new();
+    };
+
+    read_outline_items(document, pages, first, &mut HashSet::new())
+}
+
+fn read_outline_items(
+    document: &Document,
+    pages: &[ObjectId],
+    first: ObjectId,
+    seen: &mut HashSet<ObjectId>,
+) -> Vec<TocItem> {
+    let mut items = Vec::new();
+    let mut next = Some(first);
+
+    while let Some(id) = next.take() {
+        if !seen.insert(id) {
+            break;
+        }
+
+        let Ok(item) = document.get_dictionary(id) else {
+            break;
+        };
+
+        next = item.get(b"Next").and_then(Object::as_reference).ok();
+
+        let children = match item.get(b"First").and_then(Object::as_reference) {
+            Ok(child) => read_outline_items(document, pages, child, seen),
+            Err(_) => Vec::new(),
+        };
+
+        let title = item
+            .get_deref(b"Title", document)
+            .ok()
+            .and_then(|v| lopdf::decode_text_string(v).ok());
+
+        let dest = item.get_deref(b"Dest", document).ok().or_else(|| {
+            let action = item
+                .get_deref(b"A", document)
+                .and_then(Object::as_dict)
+                .ok()?;
+
+            action.get_deref(b"D", document).ok()
+        });
+
+        let chapter = dest
+            .and_then(|v| resolve_dest(document, v, pages))
+            // Items without a destination go to their first child.
+            .or_else(|| Some(children.first()?.chapter));
+
+        if let Some((title, chapter)) = title.zip(chapter) {
+            items.push(TocItem {
+                title: title.trim().to_string(),
+                chapter,
+                fragment: None,
+                children,
+            });
+        }
+    }
+
+    items
+}
+
+/// Resolve an explicit or named destination into a page index.
+fn resolve_dest(document: &Document, dest: &Object, pages: &[ObjectId]) -> Option<usize> {
+    match dest {
+        Object::Array(values) => {
+            let page = values.first()?;
+
+            match page {
+                Object::Reference(id) => pages.iter().position(|v| v == id),
+                // Remote go-to actions use the page number.
+                Object::Integer(v) => Some(*v as usize).filter(|&v| v < pages.len()),
+                _ => None,
+            }
+        }
+
+        Object::Dictionary(dict) => {
+            resolve_dest(document, dict.get_deref(b"D", document).ok()?, pages)
+        }
+
+        Object::Name(name) | Object::String(name, _) => {
+            let catalog = document.catalog().ok()?;
+
+            // PDF 1.1 dictionary of names.
+            let found = catalog
+                .get_deref(b"Dests", document)
+                .and_then(Object::as_dict)
+                .and_then(|v| v.get_deref(name, document))
+                .ok()
+                // PDF 1.2 name tree.
+                .or_else(|| {
+                    let tree = catalog
+                        .get_deref(b"Names", document)
+                        .and_then(Object::as_dict)
+                        .and_then(|v| v.get_deref(b"Dests", document))
+                        .and_then(Object::as_dict)
+                        .ok()?;
+
+                    find_in_name_tree(document, tree, name, 0)
+                })?;
+
+            match found {
+                Object::Name(_) | Object::String(..) => None,
+                _ => resolve_dest(document, found, pages),
+            }
+        }
+
+        _ => None,
+    }
+}
+
+fn find_in_name_tree<'a>(
+    document: &'a Document,
+    node: &'a Dictionary,
+    name: &[u8],
+    depth: usize,
+) -> Option<&'a Object> {
+    if depth > 32 {
+        return None;
+    }
+
+    if let Ok(names) = node
+        .get_deref(b"Names", document)
+        .and_then(Object::as_array)
+    {
+        for pair in names.chunks(2) {
+            if let [key, value] = pair {
+                if document.dereference(key).ok()?.1.as_str().ok() == Some(name) {
+                    return document.dereference(value).ok().map(|v| v.1);
+                }
+            }
+        }
+    }
+
+    for kid in node
+        .get_deref(b"Kids", document)
+        .and_then(Object::as_array)
+        .ok()?
+    {
+        let kid = document.dereference(kid).ok()?.1.as_dict().ok()?;
+
+        if let Some(found) = find_in_name_tree(document, kid, name, depth + 1) {
+            return Some(found);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use lopdf::{dictionary, Stream, StringFormat};
+
+    use super::*;
+
+    const JPEG: &[u8] = b"\xFF\xD8\xFF\xE0not really a jpeg";
+
+    /// A scanned first page and a text second page which inherits its resources.
+    fn build_pdf() -> Vec<u8> {
+        let mut doc = Document::with_version("1.5");
+
+        let pages_id = doc.new_object_id();
+
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+
+        let image_id = doc.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => 1,
+                "Height" => 1,
+                "ColorSpace" => "DeviceRGB",
+                "BitsPerComponent" => 8,
+                "Filter" => "DCTDecode",
+            },
+            JPEG.to_vec(),
+        ));
+
+        let scan_content_id = doc.add_object(Stream::new(
+            dictionary! {},
+            b"q 1 0 0 1 0 0 cm /Im0 Do Q".to_vec(),
+        ));
+
+        let scan_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Resources" => dictionary! { "XObject" => dictionary! { "Im0" => image_id } },
+            "Contents" => scan_content_id,
+        });
+
+        let text_content_id = doc.add_object(Stream::new(
+            dictionary! {},
+            b"BT /F1 12 Tf 10 10 Td (Hello) Tj ET".to_vec(),
+        ));
+
+        let text_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => text_content_id,
+            "Annots" => vec![Object::Dictionary(dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "Link",
+                "Rect" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+                "Dest" => vec![scan_id.into(), "Fit".into()],
+            })],
+        });
+
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![scan_id.into(), text_id.into()],
+                "Count" => 2,
+                "MediaBox" => vec![0.into(), 0.into(), 100.into(), 100.into()],
+                "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+            }),
+        );
+
+        let outlines_id = doc.new_object_id();
+
+        let chapter_id = doc.add_object(dictionary! {
+            "Title" => Object::String(b"Chapter".to_vec(), StringFormat::Literal),
+            "Parent" => outlines_id,
+            "Dest" => vec![text_id.into(), "Fit".into()],
+        });
+
+        doc.objects.insert(
+            outlines_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Outlines",
+                "First" => chapter_id,
+                "Last" => chapter_id,
+                "Count" => 1,
+            }),
+        );
+
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+            "Outlines" => outlines_id,
+        });
+
+        let info_id = doc.add_object(dictionary! {
+            "Title" => Object::String(b"A Title".to_vec(), StringFormat::Literal),
+        });
+
+        doc.trailer.set("Root", catalog_id);
+        doc.trailer.set("Info", info_id);
+
+        let mut buf = Vec::new();
+        doc.save_to(&mut buf).unwrap();
+
+        buf
+    }
+
+    #[test]
+    fn pages() {
+        let mut book = PdfBook::parse(String::from("test.pdf"), &build_pdf()).unwrap();
+
+        assert_eq!(book.chapter_count(), 2);
+        assert_eq!(book.get_files(), ["pages/0001.jpg", "pages/0002.pdf"]);
+
+        assert_eq!(
+            book.find(BookSearch::Title),
+            Some(vec![String::from("A Title")])
+        );
+        assert_eq!(
+            book.find(BookSearch::CoverImage),
+            Some(vec![String::from("pages/0001.jpg")])
+        );
+        assert_eq!(book.get_unique_id().unwrap(), "test.pdf");
+
+        assert_eq!(
+            book.get_table_of_contents().unwrap(),
+            Some(vec![TocItem {
+                title: String::from("Chapter"),
+                chapter: 1,
+                fragment: None,
+                children: Vec::new(),
+            }])
+        );
+
+        // Scanned pages are passed through as their image.
+        assert_eq!(book.read_page_raw_as_bytes().unwrap(), JPEG);
+
+        assert!(book
+            .read_path_as_bytes("pages/0003.pdf", None, None, None)
+            .is_err());
+    }
+
+    #[test]
+    fn single_page_pdf() {
+        let mut book = PdfBook::parse(String::new(), &build_pdf()).unwrap();
+
+        book.set_chapter(1);
+
+        let page = Document::load_mem(&book.read_page_raw_as_bytes().unwrap()).unwrap();
+
+        let pages = page.get_pages();
+        assert_eq!(pages.len(), 1);
+
+        let dict = page.get_dictionary(pages[&1]).unwrap();
+
+        // Inherited attributes are copied onto the page.
+        assert!(dict.has(b"MediaBox"));
+        assert!(dict
+            .get_deref(b"Resources", &page)
+            .and_then(Object::as_dict)
+            .unwrap()
+            .has(b"Font"));
+
+        // The other page and its image aren't copied through the link annotation.
+        assert!(!page
+            .objects
+            .values()
+            .any(|v| v.as_stream().is_ok_and(|v| v.content == JPEG)));
+
+        assert_eq!(page.extract_text(&[1]).unwrap().trim(), "Hello");
+    }
+}
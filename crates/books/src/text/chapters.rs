@@ -0,0 +1,247 @@
+use std::ops::Range;
+
+use regex::Regex;
+
+use crate::markup::{escape, html_to_text, parse_tag};
+
+/// Elements which only wrap the whole document. Their contents are split instead.
+const WRAPPER_ELEMENTS: [&str; 4] = ["div", "article", "main", "section"];
+
+pub struct Chapter {
+    pub title: Option<String>,
+    /// The XHTML contents of the body.
+    pub body: String,
+}
+
+/// A direct child of the element being split.
+struct Child {
+    range: Range<usize>,
+    /// `None` for text.
+    name: Option<String>,
+    /// Where the contents are inside of the range.
+    inner: Range<usize>,
+}
+
+/// Split XHTML at its top level headings.
+///
+/// The highest heading level which appears at least twice starts each chapter. Eg. A single `<h1>`
+/// book title followed by `<h2>` chapters is split at the `<h2>`s.
+pub fn split_html(body: &str) -> Vec<Chapter> {
+    let mut body = body;
+    let mut children = top_level_children(body);
+
+    // Descend into a lone wrapper. Eg. <div class="content">...</div>
+    loop {
+        let mut elements = children.iter().filter(|v| match v.name.as_deref() {
+            Some(_) => true,
+            None => !body[v.range.clone()].trim().is_empty(),
+        });
+
+        match (elements.next(), elements.next()) {
+            (Some(child), None)
+                if child
+                    .name
+                    .as_deref()
+                    .map_or(false, |v| WRAPPER_ELEMENTS.contains(&v)) =>
+            {
+                body = &body[child.inner.clone()];
+                children = top_level_children(body);
+            }
+
+            _ => break,
+        }
+    }
+
+    let level_of = |child: &Child| match child.name.as_deref().map(str::as_bytes) {
+        Some(&[b'h', level @ b'1'..=b'6']) => Some(level - b'0'),
+        _ => None,
+    };
+
+    let split_level = (1..=6).find(|&level| {
+        children
+            .iter()
+            .filter(|v| level_of(v) == Some(level))
+            .count()
+            >= 2
+    });
+
+    let mut chapters = Vec::new();
+    let mut current = Chapter {
+        title: None,
+        body: String::new(),
+    };
+
+    for child in &children {
+        let contents = &body[child.range.clone()];
+
+        if split_level.is_some() && level_of(child) == split_level {
+            if !current.body.trim().is_empty() {
+                chapters.push(current);
+            }
+
+            current = Chapter {
+                title: Some(html_to_text(contents).text).filter(|v| !v.is_empty()),
+                body: String::new(),
+            };
+        } else if current.title.is_none() && level_of(child).is_some() {
+            // Unsplit documents are still titled by their first heading.
+            current.title = Some(html_to_text(contents).text).filter(|v| !v.is_empty());
+        }
+
+        current.body.push_str(contents);
+    }
+
+    if !current.body.trim().is_empty() || chapters.is_empty() {
+        chapters.push(current);
+    }
+
+    chapters
+}
+
+/// The elements and text directly inside of the XHTML.
+fn top_level_children(value: &str) -> Vec<Child> {
+    let mut children = Vec::new();
+
+    let mut depth = 0usize;
+    // The start of the current top level element along with its name and where its contents start.
+    let mut open: Option<(usize, String, usize)> = None;
+    let mut pos = 0;
+
+    while pos < value.len() {
+        let rest = &value[pos..];
+
+        if let Some(after) = rest.strip_prefix("</") {
+            let end = pos + 2 + after.find('>').map_or(after.len(), |v| v + 1);
+
+            depth = depth.saturating_sub(1);
+
+            if depth == 0 {
+                if let Some((start, name, inner_start)) = open.take() {
+                    children.push(Child {
+                        range: start..end,
+                        name: Some(name),
+                        inner: inner_start..pos,
+                    });
+                }
+            }
+
+            pos = end;
+        } else if rest.starts_with('<') && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic())
+        {
+            let (tag, consumed) = parse_tag(rest);
+
+            if depth == 0 {
+                if tag.self_closing {
+                    children.push(Child {
+                        range: pos..pos + consumed,
+                        name: Some(tag.name),
+                        inner: pos + consumed..pos + consumed,
+                    });
+                } else {
+                    open = Some((pos, tag.name, pos + consumed));
+                }
+            }
+
+            if !tag.self_closing {
+                depth += 1;
+            }
+
+            pos += consumed;
+        } else {
+            let first_len = rest.chars().next().map_or(1, |c| c.len_utf8());
+            let end = rest[first_len..]
+                .find('<')
+                .map_or(value.len(), |v| pos + first_len + v);
+
+            if depth == 0 {
+                children.push(Child {
+                    range: pos..end,
+                    name: None,
+                    inner: pos..end,
+                });
+            }
+
+            pos = end;
+        }
+    }
+
+    children
+}
+
+/// Split plain text into chapters by lines which look like chapter headings. Eg. "Chapter 12", "PART II: The Return"
+///
+/// Paragraphs are separated by empty lines.
+pub fn split_plain(value: &str) -> Vec<Chapter> {
+    let heading_regex = Regex::new(
+        r"(?i)^(?:(?:chapter|part|book)\s+(?:\d+|[ivxlcdm]+|one|two|three|four|five|six|seven|eight|nine|ten|eleven|twelve|thirteen|fourteen|fifteen|sixteen|seventeen|eighteen|nineteen|twenty)\b|(?:prologue|epilogue|preface|foreword|introduction|afterword)(?:$|\s*[:.\-–—]))",
+    )
+    .unwrap();
+
+    let mut paragraphs = Vec::new();
+    let mut lines = Vec::new();
+
+    for line in value.lines() {
+        if line.trim().is_empty() {
+            if !lines.is_empty() {
+                paragraphs.push(std::mem::take(&mut lines));
+            }
+        } else {
+            lines.push(line.trim_end());
+        }
+    }
+
+    if !lines.is_empty() {
+        paragraphs.push(lines);
+    }
+
+    let is_heading = |lines: &[&str]| {
+        let line = lines[0].trim();
+
+        lines.len() == 1 && line.chars().count() <= 80 && heading_regex.is_match(line)
+    };
+
+    let is_split = paragraphs.iter().filter(|v| is_heading(v)).count() >= 2;
+
+    let mut chapters = Vec::new();
+    let mut current = Chapter {
+        title: None,
+        body: String::new(),
+    };
+
+    for lines in &paragraphs {
+        if is_split && is_heading(lines) {
+            if !current.body.is_empty() {
+                chapters.push(current);
+            }
+
+            let title = lines[0].trim();
+
+            current = Chapter {
+                title: Some(title.to_string()),
+                body: format!("<h1>{}</h1>", escape(title)),
+            };
+        } else if lines.len() == 1 && is_scene_break(lines[0]) {
+            current.body.push_str("<hr/>");
+        } else {
+            current.body.push_str("<p>");
+            current.body.push_str(&escape(&lines.join("\n")));
+            current.body.push_str("</p>");
+        }
+    }
+
+    if !current.body.is_empty() || chapters.is_empty() {
+        chapters.push(current);
+    }
+
+    chapters
+}
+
+/// Eg. "* * *" or "----"
+fn is_scene_break(line: &str) -> bool {
+    let line = line.trim();
+
+    line.chars().filter(|v| !v.is_whitespace()).count() >= 3
+        && line
+            .chars()
+            .all(|v| v.is_whitespace() || matches!(v, '*' | '-' | '#' | '~' | '='))
+}
@@ -0,0 +1,709 @@
+// For single file .txt, .md and .html books
+
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use encoding_rs::Encoding;
+use pulldown_cmark::{html::push_html, Options, Parser};
+
+mod chapters;
+
+use self::chapters::{split_html, split_plain, Chapter};
+use super::Book;
+use crate::{
+    epub::update_attributes_with,
+    markup::{self, escape, html_to_text, html_to_xhtml, parse_tag, wrap_in_xhtml},
+    BookSearch, ChapterText, Error, ReadSeek, Result, SanitizeOptions, TocItem,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextFormat {
+    Plain,
+    Markdown,
+    Html,
+}
+
+impl TextFormat {
+    pub fn from_extension(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "txt" | "text" => Some(Self::Plain),
+            "md" | "markdown" => Some(Self::Markdown),
+            "html" | "htm" | "xhtml" => Some(Self::Html),
+            _ => None,
+        }
+    }
+
+    fn mime_type(self) -> &'static str {
+        match self {
+            Self::Plain => "text/plain",
+            Self::Markdown => "text/markdown",
+            Self::Html => "text/html",
+        }
+    }
+}
+
+/// A section of the book. The document is split into chapters by its headings.
+struct Section {
+    path: String,
+    title: Option<String>,
+    contents: Vec<u8>,
+}
+
+pub struct TextBook {
+    file_name: String,
+    format: TextFormat,
+
+    /// From the HTML `<head>` or the Markdown front matter. Keys are lowercase.
+    metadata: Vec<(String, String)>,
+    sections: Vec<Section>,
+
+    data: Vec<u8>,
+
+    pub chapter: usize,
+}
+
+impl TextBook {
+    pub fn load_from_bytes(data: Vec<u8>, format: TextFormat, file_name: String) -> Result<Self> {
+        let text = decode_text(&data, format);
+
+        let mut metadata = Vec::new();
+        let mut head = String::new();
+
+        let chapters = match format {
+            TextFormat::Plain => split_plain(&text),
+
+            TextFormat::Markdown => {
+                let (front_matter, text) = split_front_matter(&text);
+
+                metadata = read_front_matter(front_matter);
+
+                let options = Options::ENABLE_TABLES
+                    | Options::ENABLE_FOOTNOTES
+                    | Options::ENABLE_STRIKETHROUGH
+                    | Options::ENABLE_TASKLISTS
+                    | Options::ENABLE_HEADING_ATTRIBUTES;
+
+                let mut html = String::new();
+                push_html(&mut html, Parser::new_ext(text, options));
+
+                split_html(&html_to_xhtml(&html))
+            }
+
+            TextFormat::Html => {
+                let xhtml = html_to_xhtml(&text);
+
+                let head_contents = element_contents(&xhtml, "head").unwrap_or_default();
+
+                metadata = read_head_metadata(&xhtml, head_contents);
+
+                // Only the styles are kept. Linked files aren't next to the book.
+                let mut rest = head_contents;
+
+                while let Some(start) = rest.find("<style") {
+                    let end = rest[start..]
+                        .find("</style>")
+                        .map_or(rest.len(), |v| start + v + 8);
+
+                    head.push_str(&rest[start..end]);
+                    rest = &rest[end..];
+                }
+
+                split_html(element_contents(&xhtml, "body").unwrap_or(&xhtml))
+            }
+        };
+
+        let sections = chapters
+            .into_iter()
+            .enumerate()
+            .map(|(index, Chapter { title, body })| {
+                let mut chapter_head = String::new();
+
+                if let Some(title) = title.as_deref() {
+                    chapter_head.push_str("<title>");
+                    chapter_head.push_str(&escape(title));
+                    chapter_head.push_str("</title>");
+                }
+
+                chapter_head.push_str(&head);
+
+                Section {
+                    path: format!("text/part{index:04}.xhtml"),
+                    title,
+                    contents: wrap_in_xhtml(&chapter_head, &body).into_bytes(),
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            file_name,
+            format,
+            metadata,
+            sections,
+            data,
+            chapter: 0,
+        })
+    }
+
+    fn get_values(&self, keys: &[&str]) -> Option<Vec<String>> {
+        let values = self
+            .metadata
+            .iter()
+            .filter(|(key, _)| keys.contains(&key.as_str()))
+            .map(|(_, value)| value.clone())
+            .collect::<Vec<_>>();
+
+        (!values.is_empty()).then_some(values)
+    }
+
+    /// Empty files don't have any sections.
+    fn current_section(&self) -> Result<&Section> {
+        self.sections
+            .get(self.chapter)
+            .ok_or_else(|| Error::MissingFile(format!("chapter {}", self.chapter)))
+    }
+
+    fn get_path_contents(&self, path: &str) -> Result<Vec<u8>> {
+        let path = path.trim_start_matches('/');
+
+        self.sections
+            .iter()
+            .find(|v| v.path == path)
+            .map(|v| v.contents.clone())
+            .ok_or_else(|| Error::MissingFile(path.to_string()))
+    }
+
+    fn handle_update_attributes(
+        &mut self,
+        input: &[u8],
+        add_css: Option<&[&str]>,
+        sanitize: Option<SanitizeOptions>,
+    ) -> Result<Vec<u8>> {
+        update_attributes_with(
+            input,
+            self,
+            |_, _, attr| attr,
+            |_, _, _, _| Ok(false),
+            if let Some(v) = add_css { v } else { &[] },
+            sanitize,
+        )
+    }
+}
+
+impl Book for TextBook {
+    fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let path = path.as_ref();
+
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+
+        let format = path
+            .extension()
+            .and_then(|v| TextFormat::from_extension(&v.to_string_lossy()))
+            .unwrap_or_else(|| sniff_format(&data));
+
+        let file_name = path
+            .file_name()
+            .map(|v| v.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        Self::load_from_bytes(data, format, file_name)
+    }
+
+    fn load_from_reader<R: ReadSeek + 'static>(mut reader: R) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        let format = sniff_format(&data);
+
+        Self::load_from_bytes(data, format, String::new())
+    }
+
+    fn get_table_of_contents(&mut self) -> Result<Option<Vec<TocItem>>> {
+        let items = self
+            .sections
+            .iter()
+            .enumerate()
+            .filter_map(|(chapter, section)| {
+                Some(TocItem {
+                    title: section.title.clone()?,
+                    chapter,
+                    fragment: None,
+                    children: Vec::new(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if items.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(items))
+        }
+    }
+
+    fn compute_hash(&mut self) -> Option<String> {
+        Some(blake3::hash(&self.data).to_string())
+    }
+
+    fn find(&self, search: BookSearch<'_>) -> Option<Vec<String>> {
+        match search {
+            BookSearch::Title => self.get_values(&["title"]),
+            BookSearch::Creator => self.get_values(&["author", "authors", "creator"]),
+            BookSearch::Contributor => self.get_values(&["contributor", "contributors"]),
+            BookSearch::Language => self.get_values(&["language", "lang"]),
+            BookSearch::Description => self.get_values(&["description", "summary"]),
+            BookSearch::Date => self.get_values(&["date", "published"]),
+            BookSearch::Publisher => self.get_values(&["publisher"]),
+            BookSearch::Identifier => self.get_values(&["identifier", "isbn"]),
+            BookSearch::Rights => self.get_values(&["rights", "copyright", "license"]),
+            BookSearch::Source => self.get_values(&["source", "url"]),
+
+            BookSearch::Subject => {
+                let values = self
+                    .get_values(&["keywords", "tags", "subject", "subjects"])?
+                    .iter()
+                    .flat_map(|v| v.split(','))
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty())
+                    .collect::<Vec<_>>();
+
+                (!values.is_empty()).then_some(values)
+            }
+
+            BookSearch::Format => Some(vec![self.format.mime_type().to_string()]),
+
+            BookSearch::Other(v) => self.get_values(&[v.to_lowercase().as_str()]),
+
+            BookSearch::CoverImage
+            | BookSearch::Coverage
+            | BookSearch::Relation
+            | BookSearch::Type => None,
+        }
+    }
+
+    fn get_unique_id(&self) -> Result<Cow<str>> {
+        if let Some(value) = self.get_values(&["identifier", "isbn"]) {
+            return Ok(Cow::Owned(value.into_iter().next().unwrap()));
+        }
+
+        // Loaded from a reader.
+        if self.file_name.is_empty() {
+            return Err(Error::MissingUniqueId);
+        }
+
+        Ok(Cow::Borrowed(self.file_name.as_str()))
+    }
+
+    fn get_files(&self) -> Vec<String> {
+        self.sections.iter().map(|v| v.path.clone()).collect()
+    }
+
+    fn get_root_file_dir(&self) -> &Path {
+        Path::new("")
+    }
+
+    fn get_page_path(&self) -> PathBuf {
+        self.current_section()
+            .map(|v| PathBuf::from(&v.path))
+            .unwrap_or_default()
+    }
+
+    fn read_page_raw_as_bytes(&mut self) -> Result<Vec<u8>> {
+        Ok(self.current_section()?.contents.clone())
+    }
+
+    fn read_page_as_text(&mut self) -> Result<Option<ChapterText>> {
+        let value = &self.current_section()?.contents;
+
+        Ok(Some(markup::html_to_text(&String::from_utf8_lossy(value))))
+    }
+
+    fn read_path_as_bytes(
+        &mut self,
+        path: &str,
+        _prepend_to_urls: Option<&str>,
+        add_css: Option<&[&str]>,
+        sanitize: Option<SanitizeOptions>,
+    ) -> Result<Vec<u8>> {
+        let input = self.get_path_contents(path)?;
+
+        if add_css.is_some() || sanitize.is_some() {
+            self.handle_update_attributes(&input, add_css, sanitize)
+        } else {
+            Ok(input)
+        }
+    }
+
+    fn read_page_as_bytes(
+        &mut self,
+        _prepend_to_urls: Option<&str>,
+        add_css: Option<&[&str]>,
+        sanitize: Option<SanitizeOptions>,
+    ) -> Result<Vec<u8>> {
+        let input = self.read_page_raw_as_bytes()?;
+
+        self.handle_update_attributes(&input, add_css, sanitize)
+    }
+
+    fn chapter_count(&self) -> usize {
+        self.sections.len()
+    }
+
+    fn set_chapter(&mut self, value: usize) -> bool {
+        if value < self.chapter_count() {
+            self.chapter = value;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn next_chapter(&mut self) -> bool {
+        self.set_chapter(self.chapter + 1)
+    }
+
+    fn previous_chapter(&mut self) -> bool {
+        if self.chapter != 0 {
+            self.set_chapter(self.chapter - 1)
+        } else {
+            false
+        }
+    }
+
+    fn get_chapter(&self) -> usize {
+        self.chapter
+    }
+}
+
+/// Whether the start of the file looks like an HTML document.
+pub(crate) fn is_html(header: &[u8]) -> bool {
+    let header = String::from_utf8_lossy(header).to_lowercase();
+
+    header.contains("<!doctype html") || header.contains("<html")
+}
+
+fn sniff_format(data: &[u8]) -> TextFormat {
+    if is_html(&data[..data.len().min(1024)]) {
+        TextFormat::Html
+    } else {
+        TextFormat::Plain
+    }
+}
+
+/// Decode the text using its byte order mark, declared charset or a guess. Eg. windows-1252
+fn decode_text(data: &[u8], format: TextFormat) -> String {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(data) {
+        let (value, _) = encoding.decode_without_bom_handling(&data[bom_length..]);
+        return value.into_owned();
+    }
+
+    if let Ok(value) = std::str::from_utf8(data) {
+        return value.to_string();
+    }
+
+    let declared = if format == TextFormat::Html {
+        let header = String::from_utf8_lossy(&data[..data.len().min(1024)]).to_lowercase();
+
+        header.split_once("charset=").and_then(|(_, v)| {
+            let label = v
+                .trim_start_matches(['"', '\''])
+                .split(|c: char| matches!(c, '"' | '\'' | ';' | '/' | '>') || c.is_whitespace())
+                .next()?;
+
+            Encoding::for_label(label.as_bytes())
+        })
+    } else {
+        None
+    };
+
+    let encoding = declared.unwrap_or_else(|| {
+        let mut detector = chardetng::EncodingDetector::new();
+        detector.feed(data, true);
+        detector.guess(None, true)
+    });
+
+    let (value, _, _) = encoding.decode(data);
+
+    value.into_owned()
+}
+
+/// The contents of the first element with the name.
+fn element_contents<'a>(xhtml: &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("<{name}");
+
+    // Skip longer names. Eg. <header> when looking for <head>
+    let start = xhtml.match_indices(&open).map(|(v, _)| v).find(|&v| {
+        xhtml[v + open.len()..].starts_with(|c: char| c == '>' || c == '/' || c.is_whitespace())
+    })?;
+    let start = start + xhtml[start..].find('>')? + 1;
+    let end = xhtml[start..]
+        .rfind(&format!("</{name}>"))
+        .map_or(xhtml.len(), |v| start + v);
+
+    Some(&xhtml[start..end])
+}
+
+/// The `<title>`, `<meta>` values and language of the document.
+fn read_head_metadata(xhtml: &str, head: &str) -> Vec<(String, String)> {
+    let mut items = Vec::new();
+
+    if let Some(start) = xhtml.find("<html") {
+        let (tag, _) = parse_tag(&xhtml[start..]);
+
+        if let Some((_, value)) = tag
+            .attributes
+            .into_iter()
+            .find(|(name, _)| name == "lang" || name == "xml:lang")
+        {
+            items.push((String::from("language"), value));
+        }
+    }
+
+    let mut rest = head;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+
+        if !rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            rest = &rest[1..];
+            continue;
+        }
+
+        let (tag, consumed) = parse_tag(rest);
+        rest = &rest[consumed..];
+
+        let attr = |name: &str| {
+            tag.attributes
+                .iter()
+                .find(|(v, _)| v == name)
+                .map(|(_, v)| v.as_str())
+        };
+
+        let item = match tag.name.as_str() {
+            "title" => {
+                let end = rest.find("</title>").unwrap_or(rest.len());
+
+                Some((String::from("title"), &rest[..end]))
+            }
+
+            // Eg. <meta name="author" content="..."> or <meta name="DC.creator" content="...">
+            "meta" => {
+                attr("name")
+                    .or(attr("property"))
+                    .zip(attr("content"))
+                    .map(|(name, content)| {
+                        let name = name.to_lowercase();
+                        let name = name.rsplit(['.', ':']).next().unwrap_or_default();
+
+                        (name.to_string(), content)
+                    })
+            }
+
+            _ => None,
+        };
+
+        if let Some((name, value)) = item {
+            let value = html_to_text(value).text;
+
+            if !value.is_empty() {
+                items.push((name, value));
+            }
+        }
+    }
+
+    items
+}
+
+/// Split the YAML front matter from the rest of the Markdown.
+fn split_front_matter(value: &str) -> (&str, &str) {
+    let Some(rest) = value
+        .strip_prefix("---\n")
+        .or_else(|| value.strip_prefix("---\r\n"))
+    else {
+        return ("", value);
+    };
+
+    let mut offset = 0;
+
+    for line in rest.split_inclusive('\n') {
+        if matches!(line.trim_end(), "---" | "...") {
+            return (&rest[..offset], &rest[offset + line.len()..]);
+        }
+
+        offset += line.len();
+    }
+
+    ("", value)
+}
+
+/// Reads simple `key: value` front matter. Lists are either `[a, b]` or indented `- a` lines.
+fn read_front_matter(value: &str) -> Vec<(String, String)> {
+    let mut items = Vec::new();
+    let mut last_key: Option<String> = None;
+
+    for line in value.lines() {
+        if let Some(item) = line.trim_start().strip_prefix("- ") {
+            if let Some(key) = last_key.as_ref() {
+                items.push((key.clone(), unquote(item).to_string()));
+            }
+
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+
+        let key = key.trim().to_lowercase();
+        let value = value.trim();
+
+        last_key = Some(key.clone());
+
+        if let Some(list) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            items.extend(
+                list.split(',')
+                    .map(unquote)
+                    .filter(|v| !v.is_empty())
+                    .map(|v| (key.clone(), v.to_string())),
+            );
+        } else if !value.is_empty() {
+            items.push((key, unquote(value).to_string()));
+        }
+    }
+
+    items
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn titles(book: &mut TextBook) -> Vec<String> {
+        book.get_table_of_contents()
+            .unwrap()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|v| v.title)
+            .collect()
+    }
+
+    #[test]
+    fn plain_text() {
+        let text = "The Title\n\nCHAPTER I\n\nIt was a dark\nand stormy night.\n\n* * *\n\nChapter 2: The End\n\nDone & dusted.\n";
+
+        let mut book =
+            TextBook::load_from_bytes(text.as_bytes().to_vec(), TextFormat::Plain, String::new())
+                .unwrap();
+
+        assert_eq!(book.chapter_count(), 3);
+        assert_eq!(titles(&mut book), ["CHAPTER I", "Chapter 2: The End"]);
+
+        book.set_chapter(1);
+        let page = book.read_page_raw_as_string().unwrap();
+
+        assert!(
+            page.contains("<h1>CHAPTER I</h1><p>It was a dark\nand stormy night.</p><hr/>"),
+            "{page}"
+        );
+
+        book.set_chapter(2);
+        let page = book.read_page_raw_as_string().unwrap();
+
+        assert!(page.contains("<p>Done &amp; dusted.</p>"), "{page}");
+
+        // Not enough headings to split.
+        let book = TextBook::load_from_bytes(
+            b"Part of the story.\n\nChapter 1\n\nText".to_vec(),
+            TextFormat::Plain,
+            String::new(),
+        )
+        .unwrap();
+
+        assert_eq!(book.chapter_count(), 1);
+    }
+
+    #[test]
+    fn markdown() {
+        let text = "---\ntitle: \"A Story\"\nauthor: Jane Doe\ntags: [one, two]\n---\n# A Story\n\nIntro\n\n## First\n\nSome *text*.\n\n## Second\n\nMore.\n";
+
+        let mut book = TextBook::load_from_bytes(
+            text.as_bytes().to_vec(),
+            TextFormat::Markdown,
+            String::new(),
+        )
+        .unwrap();
+
+        assert_eq!(book.chapter_count(), 3);
+        assert_eq!(titles(&mut book), ["A Story", "First", "Second"]);
+        assert_eq!(book.find(BookSearch::Title).unwrap(), ["A Story"]);
+        assert_eq!(book.find(BookSearch::Creator).unwrap(), ["Jane Doe"]);
+        assert_eq!(book.find(BookSearch::Subject).unwrap(), ["one", "two"]);
+
+        book.set_chapter(1);
+        let page = book.read_page_raw_as_string().unwrap();
+
+        assert!(
+            page.contains("<h2>First</h2>\n<p>Some <em>text</em>.</p>"),
+            "{page}"
+        );
+    }
+
+    #[test]
+    fn html() {
+        let (data, _, _) = encoding_rs::WINDOWS_1252.encode(
+            r#"<!DOCTYPE html><html lang="fr"><head><meta charset="windows-1252"><title>Caf&eacute;</title><meta name="author" content="Zoë"><style>p { margin: 0 }</style><script src="x.js"></script></head>
+<body><div id="content"><h1>Un</h1><p>Première<br>ligne</p><h1>Deux</h1><p>Fin</p></div></body></html>"#,
+        );
+
+        let mut book =
+            TextBook::load_from_bytes(data.into_owned(), TextFormat::Html, String::new()).unwrap();
+
+        assert_eq!(book.chapter_count(), 2);
+        assert_eq!(titles(&mut book), ["Un", "Deux"]);
+        assert_eq!(book.find(BookSearch::Title).unwrap(), ["Café"]);
+        assert_eq!(book.find(BookSearch::Creator).unwrap(), ["Zoë"]);
+        assert_eq!(book.find(BookSearch::Language).unwrap(), ["fr"]);
+
+        let page = book.read_page_raw_as_string().unwrap();
+
+        assert!(page.contains("<style>p { margin: 0 }</style>"), "{page}");
+        assert!(!page.contains("x.js"), "{page}");
+        assert!(
+            page.contains("<h1>Un</h1><p>Première<br/>ligne</p>"),
+            "{page}"
+        );
+    }
+
+    #[test]
+    fn empty_file() {
+        for format in [TextFormat::Plain, TextFormat::Markdown, TextFormat::Html] {
+            let mut book = TextBook::load_from_bytes(Vec::new(), format, String::new()).unwrap();
+
+            // A single empty page.
+            assert_eq!(book.chapter_count(), 1);
+            assert_eq!(book.get_page_path(), PathBuf::from("text/part0000.xhtml"));
+            assert!(book.read_page_raw_as_bytes().is_ok());
+
+            assert!(!book.set_chapter(1));
+        }
+    }
+}
@@ -1,13 +1,17 @@
 use std::{collections::HashMap, path::PathBuf};
 
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime};
 use common::{api::QueryListResponse, BookId, Either, ImageId, MemberId, PersonId, Source};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    filter::FilterContainer, setup::Config, BasicLibrary, BookEdit, Chapter, Collection,
-    DisplayBookItem, DisplayItem, LibraryColl, LibraryId, MediaItem, Member, ModifyValuesBy,
-    Person, Poster, Progression, Result, SearchType,
+    filter::FilterContainer,
+    reader::Rendition,
+    setup::Config,
+    ws::{TaskId, TaskInfo, TaskOutcome},
+    AudioChapter, AudioTrack, BasicLibrary, BookEdit, Chapter, Collection, DisplayBookItem,
+    DisplayItem, LibraryColl, LibraryId, MediaItem, Member, ModifyValuesBy, Person, Poster,
+    Progression, Result, SearchType, TocItem,
 };
 
 // API Routes
@@ -19,6 +23,12 @@ pub type ApiGetFileByIdResponse = Option<self::GetFileByIdResponse>;
 pub type ApiGetFileResourceByIdResponse = String;
 /// GET     /file/{id}/pages/{pages}
 pub type ApiGetFilePagesByIdResponse = self::GetChaptersResponse;
+/// GET     /file/{id}/audio/{track}
+pub type ApiGetFileAudioByIdResponse = Vec<u8>;
+/// GET     /file/{id}/overlay/{chapter}
+pub type ApiGetFileOverlayByIdResponse = Option<self::MediaOverlayInfo>;
+/// GET     /file/{id}/cfi
+pub type ApiGetFileCfiByIdResponse = self::FileCfi;
 /// GET     /file/{id}/debug/{tail:.*}
 pub type ApiGetFileDebugByIdResponse = String;
 /// POST    /file/{id}/progress
@@ -98,6 +108,20 @@ pub type ApiPostUpdatePersonResponse = ();
 // Task
 /// POST    /task
 pub type ApiPostRunTaskResponse = ();
+/// GET     /tasks
+pub type ApiGetTasksResponse = self::GetTasksResponse;
+/// POST    /task/{id}/cancel
+pub type ApiPostCancelTaskResponse = String;
+
+// Trash
+/// GET     /trash
+pub type ApiGetTrashResponse = self::GetTrashResponse;
+/// POST    /trash/{id}/restore
+pub type ApiPostTrashRestoreResponse = ();
+/// DELETE  /trash/{id}
+pub type ApiDeleteTrashByIdResponse = ();
+/// DELETE  /trash
+pub type ApiDeleteTrashResponse = ();
 
 // Setup
 /// GET     /setup
@@ -176,6 +200,8 @@ pub struct GetLibrariesResponse {
 pub struct UpdateLibrary {
     pub name: Option<String>,
     pub is_public: Option<bool>,
+    pub allow_scripts: Option<bool>,
+    pub watch_directories: Option<bool>,
 
     pub add_directories: Vec<String>,
     pub remove_directories: Vec<String>,
@@ -197,11 +223,77 @@ pub struct LoadResourceQuery {
     pub configure_pages: bool,
 }
 
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct DownloadBookQuery {
+    /// Write the stored metadata into the file before sending it. Only used for EPUBs.
+    #[serde(default)]
+    pub embed_metadata: bool,
+    /// Convert the file into a KEPUB for Kobo devices. Only used for EPUBs.
+    #[serde(default)]
+    pub kepub: bool,
+}
+
+/// Either the `cfi` or the `chapter` is required.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct GetFileCfiQuery {
+    /// Resolve the CFI into the chapter and position.
+    pub cfi: Option<String>,
+
+    /// Create the CFI for the position inside of the chapter.
+    pub chapter: Option<usize>,
+    #[serde(default)]
+    pub char_pos: usize,
+}
+
+/// A position inside of an EPUB.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FileCfi {
+    pub cfi: String,
+    pub chapter: usize,
+    /// Counted the same way as the reader's `char_pos`.
+    pub char_pos: usize,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GetFileByIdResponse {
     pub media: MediaItem,
     pub progress: Option<Progression>,
-    pub toc: Vec<(String, usize)>,
+    pub toc: Vec<TocItem>,
+    /// Only set for audiobooks.
+    pub audio: Option<AudioBookInfo>,
+    /// The EPUB has Media Overlays which can be read aloud.
+    pub has_media_overlay: bool,
+    /// How the book wants to be displayed. Eg. Fixed layout EPUBs.
+    pub rendition: Rendition,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AudioBookInfo {
+    /// In milliseconds.
+    pub duration: u64,
+    pub narrators: Vec<String>,
+    pub tracks: Vec<AudioTrack>,
+    pub chapters: Vec<AudioChapter>,
+}
+
+/// The read-along timeline of a chapter.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MediaOverlayInfo {
+    /// The class given to the element being narrated.
+    pub active_class: String,
+    pub clips: Vec<MediaOverlayClip>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MediaOverlayClip {
+    /// The id of the element inside of the chapter.
+    pub fragment: String,
+    /// Resource path of the audio file.
+    pub audio: String,
+    /// In milliseconds.
+    pub begin: u64,
+    /// In milliseconds. None if the clip plays until the end of the audio file.
+    pub end: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -345,6 +437,9 @@ pub enum PostBookBody {
     UpdateBookBySource(Source),
 
     Edit(BookEdit),
+
+    /// Write the stored metadata into the EPUB files on disk.
+    EmbedMetadata,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -409,6 +504,27 @@ pub struct MetadataBookSearchItem {
 pub struct RunTaskBody {
     pub run_search: Option<LibraryId>,
     pub run_metadata: Option<LibraryId>,
+    /// Used with `run_search`. Re-reads every file instead of skipping unchanged ones.
+    #[serde(default)]
+    pub full_rescan: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GetTasksResponse {
+    pub running: Vec<(TaskId, TaskInfo)>,
+    /// The most recently finished tasks first.
+    pub history: Vec<TaskHistoryItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaskHistoryItem {
+    pub name: String,
+    pub outcome: TaskOutcome,
+    pub error: Option<String>,
+    pub items_processed: i64,
+
+    pub started_at: NaiveDateTime,
+    pub ended_at: NaiveDateTime,
 }
 
 #[derive(Deserialize)]
@@ -417,3 +533,20 @@ pub struct SimpleListQuery {
     pub limit: Option<i64>,
     pub query: Option<String>,
 }
+
+// Trash
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GetTrashResponse {
+    pub items: Vec<TrashItem>,
+    pub total: i64,
+
+    /// Days a file stays in the trash before it's purged.
+    pub retention_days: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrashItem {
+    pub file: MediaItem,
+    pub book: Option<DisplayItem>,
+}
@@ -3,9 +3,18 @@ use std::{
     sync::atomic::{AtomicUsize, Ordering},
 };
 
-use common::BookId;
+use common::{BookId, PersonId};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "backend")]
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    sqlite::{SqliteArgumentValue, SqliteValueRef},
+    Decode, Encode, Sqlite, Type,
+};
+
 pub static UNIQUE_ID: AtomicUsize = AtomicUsize::new(0);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -34,6 +43,58 @@ pub struct TaskInfo {
     pub name: String,
 
     pub current: Option<TaskType>,
+    pub progress: Option<TaskProgress>,
+}
+
+/// How far along a task is. `current` is the item being worked on, starting at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskProgress {
+    pub current: usize,
+    pub total: usize,
+}
+
+impl TaskProgress {
+    pub fn percentage(self) -> f32 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.current as f32 / self.total as f32 * 100.0
+        }
+    }
+}
+
+/// How a task ended.
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive,
+)]
+#[repr(i32)]
+pub enum TaskOutcome {
+    Finished = 1,
+    Failed = 2,
+    Cancelled = 3,
+}
+
+#[cfg(feature = "backend")]
+impl<'q> Encode<'q, Sqlite> for TaskOutcome {
+    fn encode_by_ref(&self, args: &mut Vec<SqliteArgumentValue<'q>>) -> IsNull {
+        args.push(SqliteArgumentValue::Int(*self as i32));
+
+        IsNull::No
+    }
+}
+
+#[cfg(feature = "backend")]
+impl<'r> Decode<'r, Sqlite> for TaskOutcome {
+    fn decode(value: SqliteValueRef<'r>) -> sqlx::Result<Self, BoxDynError> {
+        Ok(Self::try_from(<i32 as Decode<'r, Sqlite>>::decode(value)?).unwrap())
+    }
+}
+
+#[cfg(feature = "backend")]
+impl Type<Sqlite> for TaskOutcome {
+    fn type_info() -> <Sqlite as sqlx::Database>::TypeInfo {
+        <i32 as Type<Sqlite>>::type_info()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -65,6 +126,8 @@ pub enum WebsocketNotification {
         id: TaskId,
         type_of: TaskType,
         inserting: bool,
+        /// Left out when the progress hasn't changed.
+        progress: Option<TaskProgress>,
     },
 
     TaskEnd(TaskId),
@@ -80,6 +143,16 @@ impl WebsocketNotification {
             id,
             type_of,
             inserting,
+            progress: None,
+        }
+    }
+
+    pub fn update_task_progress(id: TaskId, type_of: TaskType, progress: TaskProgress) -> Self {
+        Self::TaskUpdate {
+            id,
+            type_of,
+            inserting: true,
+            progress: Some(progress),
         }
     }
 }
@@ -91,5 +164,10 @@ pub enum TaskType {
         subtitle: Option<String>,
     },
 
+    UpdatingPerson {
+        id: PersonId,
+        name: String,
+    },
+
     LibraryScan(String),
 }
@@ -380,6 +380,14 @@ impl MediaItem {
     pub fn is_comic_book(&self) -> bool {
         LibraryType::ComicBook.is_filetype_valid(&self.file_type)
     }
+
+    pub fn is_document(&self) -> bool {
+        LibraryType::Document.is_filetype_valid(&self.file_type)
+    }
+
+    pub fn is_audio_book(&self) -> bool {
+        LibraryType::AudioBook.is_filetype_valid(&self.file_type)
+    }
 }
 
 impl PartialEq for MediaItem {
@@ -388,16 +396,20 @@ impl PartialEq for MediaItem {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub enum Progression {
     Ebook {
         chapter: i64,
         char_pos: i64,
         page: i64,
+        /// EPUB Canonical Fragment Identifier of the position. Unlike the page it doesn't change with the screen size.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cfi: Option<String>,
     },
 
     AudioBook {
         chapter: i64,
+        /// Seconds from the start of the chapter's track.
         seek_pos: i64,
     },
 
@@ -412,6 +424,38 @@ pub struct Chapter {
     pub info: FileUnwrappedInfo,
 }
 
+/// An entry in a book's table of contents.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TocItem {
+    pub title: String,
+    /// The chapter (spine index) it points to.
+    pub chapter: usize,
+    /// The element id inside of the chapter.
+    pub fragment: Option<String>,
+
+    pub children: Vec<TocItem>,
+}
+
+/// A single audio file of an audiobook.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AudioTrack {
+    /// Relative to the audiobook folder. The file name for single file audiobooks.
+    pub path: String,
+    /// In milliseconds.
+    pub duration: u64,
+}
+
+/// An audiobook chapter. Chapters never span multiple tracks.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AudioChapter {
+    pub title: String,
+    /// Index of the track the chapter is in.
+    pub track: usize,
+    /// Milliseconds from the start of the track.
+    pub start: u64,
+    pub end: u64,
+}
+
 #[derive(
     Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive,
 )]
@@ -419,14 +463,21 @@ pub struct Chapter {
 pub enum LibraryType {
     Book = 1,
     ComicBook = 2,
-    // TODO: PDF's. What do they fall under? Documents?
+    Document = 3,
+    AudioBook = 4,
 }
 
 impl LibraryType {
     pub fn is_filetype_valid(self, value: &str) -> bool {
         match self {
-            LibraryType::Book => ["epub", "mobi", "azw", "azw3", "kfx"].contains(&value),
+            LibraryType::Book => [
+                "epub", "mobi", "azw", "azw3", "kfx", "fb2", "fb2.zip", "txt", "md", "markdown",
+                "html", "htm",
+            ]
+            .contains(&value),
             LibraryType::ComicBook => ["cbz", "cbr", "cbt", "cba", "cb7"].contains(&value),
+            LibraryType::Document => ["pdf"].contains(&value),
+            LibraryType::AudioBook => ["m4b", "m4a", "mp3"].contains(&value),
         }
     }
 }
@@ -523,6 +574,31 @@ pub struct LibraryColl {
     pub updated_at: i64,
 
     pub directories: Vec<String>,
+
+    /// Why the library directories couldn't be watched. They're scanned periodically instead.
+    #[serde(default)]
+    pub watch_error: Option<String>,
+}
+
+impl LibraryColl {
+    pub fn parse_settings_or_default(&self) -> Result<LibrarySettings> {
+        let Some(settings) = &self.settings else {
+            return Ok(LibrarySettings::default());
+        };
+
+        Ok(serde_json::from_str(settings)?)
+    }
+}
+
+/// Stored as JSON in the library `settings` column.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LibrarySettings {
+    /// Keep the scripts in EPUB3 scripted content. Otherwise they're removed before the book is displayed.
+    #[serde(default)]
+    pub allow_scripts: bool,
+    /// Watch the library directories for changes instead of waiting for a library scan.
+    #[serde(default)]
+    pub watch_directories: bool,
 }
 
 // TODO: Rename / remove
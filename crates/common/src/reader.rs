@@ -74,3 +74,94 @@ pub enum LayoutType {
     Scroll,
     Image,
 }
+
+/// How a book (or one of its chapters) wants to be displayed. From the EPUB rendition properties.
+///
+/// https://www.w3.org/TR/epub-33/#sec-fixed-layouts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rendition {
+    pub layout: RenditionLayout,
+    pub spread: RenditionSpread,
+    pub orientation: RenditionOrientation,
+    /// Which side of a two page spread the chapter is placed on.
+    pub page_spread: Option<PageSpread>,
+    /// The size, in CSS pixels, a fixed layout chapter was designed for.
+    pub viewport: Option<Viewport>,
+}
+
+impl Rendition {
+    pub fn is_fixed_layout(&self) -> bool {
+        self.layout == RenditionLayout::PrePaginated
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RenditionLayout {
+    #[default]
+    Reflowable,
+    PrePaginated,
+}
+
+impl RenditionLayout {
+    pub fn from_value(value: &str) -> Option<Self> {
+        match value.trim() {
+            "reflowable" => Some(Self::Reflowable),
+            "pre-paginated" => Some(Self::PrePaginated),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RenditionSpread {
+    None,
+    Landscape,
+    Both,
+    #[default]
+    Auto,
+}
+
+impl RenditionSpread {
+    pub fn from_value(value: &str) -> Option<Self> {
+        match value.trim() {
+            "none" => Some(Self::None),
+            "landscape" => Some(Self::Landscape),
+            // "portrait" is deprecated and treated as "both".
+            "both" | "portrait" => Some(Self::Both),
+            "auto" => Some(Self::Auto),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RenditionOrientation {
+    Landscape,
+    Portrait,
+    #[default]
+    Auto,
+}
+
+impl RenditionOrientation {
+    pub fn from_value(value: &str) -> Option<Self> {
+        match value.trim() {
+            "landscape" => Some(Self::Landscape),
+            "portrait" => Some(Self::Portrait),
+            "auto" => Some(Self::Auto),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PageSpread {
+    Left,
+    Right,
+    Center,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Viewport {
+    pub width: u32,
+    pub height: u32,
+}
@@ -29,6 +29,8 @@ pub struct Config {
     pub email: Option<ConfigEmail>,
     pub authenticators: Authenticators,
     pub searching: Searching,
+    pub trash: ConfigTrash,
+    pub watcher: ConfigWatcher,
 
     pub has_admin_account: bool,
     pub is_public_access: bool,
@@ -119,6 +121,24 @@ impl Default for Searching {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigTrash {
+    /// How many days removed files stay in the trash before they're purged.
+    pub retention_days: u32,
+}
+
+impl Default for ConfigTrash {
+    fn default() -> Self {
+        Self { retention_days: 30 }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ConfigWatcher {
+    /// Scan the libraries whose directories couldn't be watched every hour.
+    pub scan_unwatched: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LibraryConnection {
     pub pubid: Option<String>,
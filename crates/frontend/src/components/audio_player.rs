@@ -0,0 +1,271 @@
+use std::rc::Rc;
+
+use common_local::{api::AudioBookInfo, FileId, Progression};
+use web_sys::HtmlMediaElement;
+use yew::prelude::*;
+
+use crate::request;
+
+/// How often the position is saved while playing.
+const SAVE_INTERVAL_SECS: f64 = 15.0;
+
+#[derive(Properties, PartialEq)]
+pub struct Property {
+    pub file_id: FileId,
+    pub info: Rc<AudioBookInfo>,
+    pub progress: Option<Progression>,
+}
+
+pub enum Msg {
+    LoadedMetadata,
+    TimeUpdate,
+    Paused,
+    Ended,
+
+    SelectChapter(usize),
+
+    Ignore,
+}
+
+pub struct AudioPlayer {
+    track: usize,
+    chapter: usize,
+
+    /// Seconds to move to once the track is loaded.
+    pending_seek: Option<f64>,
+    /// Start playing once the track is loaded. Used when moving to a different track.
+    autoplay: bool,
+    last_saved: f64,
+
+    audio_ref: NodeRef,
+}
+
+impl Component for AudioPlayer {
+    type Message = Msg;
+    type Properties = Property;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let info = &ctx.props().info;
+
+        let (chapter, seek_pos) = match ctx.props().progress {
+            Some(Progression::AudioBook { chapter, seek_pos }) => (
+                (chapter.max(0) as usize).min(info.chapters.len().saturating_sub(1)),
+                seek_pos as f64,
+            ),
+            _ => (0, 0.0),
+        };
+
+        Self {
+            track: info.chapters.get(chapter).map_or(0, |v| v.track),
+            chapter,
+
+            pending_seek: Some(seek_pos),
+            autoplay: false,
+            last_saved: seek_pos,
+
+            audio_ref: NodeRef::default(),
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::Ignore => return false,
+
+            Msg::LoadedMetadata => {
+                let Some(audio) = self.audio_ref.cast::<HtmlMediaElement>() else {
+                    return false;
+                };
+
+                if let Some(seconds) = self.pending_seek.take() {
+                    audio.set_current_time(seconds);
+                }
+
+                if self.autoplay {
+                    self.autoplay = false;
+                    let _ = audio.play();
+                }
+
+                return false;
+            }
+
+            Msg::TimeUpdate => {
+                let Some(audio) = self.audio_ref.cast::<HtmlMediaElement>() else {
+                    return false;
+                };
+
+                let current = audio.current_time();
+
+                if (current - self.last_saved).abs() >= SAVE_INTERVAL_SECS {
+                    self.save_progress(ctx, current);
+                }
+
+                let chapter = self.find_chapter(ctx, current);
+
+                if chapter == self.chapter {
+                    return false;
+                }
+
+                self.chapter = chapter;
+            }
+
+            Msg::Paused => {
+                if let Some(audio) = self.audio_ref.cast::<HtmlMediaElement>() {
+                    self.save_progress(ctx, audio.current_time());
+                }
+
+                return false;
+            }
+
+            Msg::Ended => {
+                if self.track + 1 >= ctx.props().info.tracks.len() {
+                    let file_id = ctx.props().file_id;
+
+                    ctx.link().send_future(async move {
+                        let resp =
+                            request::update_book_progress(file_id, &Progression::Complete).await;
+
+                        if let Err(err) = resp.ok() {
+                            crate::display_error(err);
+                        }
+
+                        Msg::Ignore
+                    });
+
+                    return false;
+                }
+
+                self.track += 1;
+                self.pending_seek = Some(0.0);
+                self.autoplay = true;
+                self.chapter = self.find_chapter(ctx, 0.0);
+            }
+
+            Msg::SelectChapter(chapter) => {
+                let Some(item) = ctx.props().info.chapters.get(chapter) else {
+                    return false;
+                };
+
+                let seconds = item.start as f64 / 1000.0;
+
+                self.chapter = chapter;
+
+                if item.track == self.track {
+                    if let Some(audio) = self.audio_ref.cast::<HtmlMediaElement>() {
+                        audio.set_current_time(seconds);
+                    }
+                } else {
+                    self.track = item.track;
+                    self.pending_seek = Some(seconds);
+                    self.autoplay = true;
+                }
+
+                self.save_progress(ctx, seconds);
+            }
+        }
+
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let info = &ctx.props().info;
+
+        html! {
+            <div class="audio-player">
+                <audio
+                    controls=true
+                    preload="metadata"
+                    ref={ self.audio_ref.clone() }
+                    src={ format!("/api/file/{}/audio/{}", ctx.props().file_id, self.track) }
+                    onloadedmetadata={ ctx.link().callback(|_| Msg::LoadedMetadata) }
+                    ontimeupdate={ ctx.link().callback(|_| Msg::TimeUpdate) }
+                    onpause={ ctx.link().callback(|_| Msg::Paused) }
+                    onended={ ctx.link().callback(|_| Msg::Ended) }
+                />
+
+                <div class="audio-info">
+                    <span>{ format!("Length: {}", format_duration(info.duration)) }</span>
+                    {
+                        if info.narrators.is_empty() {
+                            html! {}
+                        } else {
+                            html! {
+                                <span>{ format!("Narrated by {}", info.narrators.join(", ")) }</span>
+                            }
+                        }
+                    }
+                </div>
+
+                <div class="audio-chapters">
+                    {
+                        for info.chapters.iter().enumerate().map(|(index, chapter)| {
+                            let class = if index == self.chapter {
+                                "audio-chapter active"
+                            } else {
+                                "audio-chapter"
+                            };
+
+                            html! {
+                                <div class={ class } onclick={ ctx.link().callback(move |_| Msg::SelectChapter(index)) }>
+                                    <span>{ chapter.title.clone() }</span>
+                                    <span>{ format_duration(chapter.end.saturating_sub(chapter.start)) }</span>
+                                </div>
+                            }
+                        })
+                    }
+                </div>
+            </div>
+        }
+    }
+}
+
+impl AudioPlayer {
+    /// The chapter playing at `seconds` into the current track.
+    fn find_chapter(&self, ctx: &Context<Self>, seconds: f64) -> usize {
+        let millis = (seconds * 1000.0) as u64;
+
+        ctx.props()
+            .info
+            .chapters
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, v)| v.track == self.track && v.start <= millis)
+            .map_or(self.chapter, |(index, _)| index)
+    }
+
+    fn save_progress(&mut self, ctx: &Context<Self>, seconds: f64) {
+        self.last_saved = seconds;
+
+        let file_id = ctx.props().file_id;
+        let progression = Progression::AudioBook {
+            chapter: self.chapter as i64,
+            seek_pos: seconds as i64,
+        };
+
+        ctx.link().send_future(async move {
+            let resp = request::update_book_progress(file_id, &progression).await;
+
+            if let Err(err) = resp.ok() {
+                crate::display_error(err);
+            }
+
+            Msg::Ignore
+        });
+    }
+}
+
+/// Eg. "1:02:03" or "4:05"
+fn format_duration(millis: u64) -> String {
+    let seconds = millis / 1000;
+
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            (seconds / 60) % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
@@ -115,6 +115,7 @@ impl Component for BookListComponent {
                         id: task_id,
                         type_of,
                         inserting,
+                        ..
                     } => {
                         if let TaskType::UpdatingBook { id: book_id, .. } = type_of {
                             if inserting {
@@ -71,6 +71,7 @@ pub enum BookPosterItemMsg {
     RemoveBookFromCollection(BookId, CollectionId),
 
     UnMatch(BookId),
+    EmbedMetadata(BookId),
     MarkAsRead,
     MarkAsUnread,
 
@@ -146,6 +147,14 @@ impl Component for BookPosterItem {
                 });
             }
 
+            BookPosterItemMsg::EmbedMetadata(book_id) => {
+                ctx.link().send_future(async move {
+                    request::update_book(book_id, &api::PostBookBody::EmbedMetadata).await;
+
+                    BookPosterItemMsg::Ignore
+                });
+            }
+
             BookPosterItemMsg::MarkAsRead => {
                 let file_id = ctx.props().progress.as_ref().unwrap().1.id;
 
@@ -218,7 +227,7 @@ impl Component for BookPosterItem {
                 </Link<BaseRoute>>
 
                 {
-                    if let Some(&(Progression::Ebook { chapter, .. }, ref file)) = ctx.props().progress.as_ref() {
+                    if let Some(&(Progression::Ebook { chapter, .. } | Progression::AudioBook { chapter, .. }, ref file)) = ctx.props().progress.as_ref() {
                         html! {
                             <div class="progress" title={ format!("Reading Chapter {}/{}", chapter + 1, file.chapter_count) }>
                                 <div class="prog-bar" style={ format!("width: {}%;", (chapter as f32 / file.chapter_count as f32 * 100.0) as i32) }></div>
@@ -270,7 +279,7 @@ impl Component for BookPosterItem {
 
                                         { book_id }
                                         { is_matched }
-                                        progress={ ctx.props().progress.as_ref().map(|v| v.0) }
+                                        progress={ ctx.props().progress.as_ref().map(|v| v.0.clone()) }
 
                                         event={ ctx.link().callback(move |e| {
                                             debug!("{e:?}");
@@ -281,6 +290,7 @@ impl Component for BookPosterItem {
                                                 DropdownInfoPopupEvent::AddToCollection(id) => BookPosterItemMsg::AddBookToCollection(book_id, id),
                                                 DropdownInfoPopupEvent::RemoveFromCollection(id) => BookPosterItemMsg::RemoveBookFromCollection(book_id, id),
                                                 DropdownInfoPopupEvent::RefreshMetadata => BookPosterItemMsg::RefreshBookById(book_id),
+                                                DropdownInfoPopupEvent::EmbedMetadata => BookPosterItemMsg::EmbedMetadata(book_id),
                                                 DropdownInfoPopupEvent::SearchFor => BookPosterItemMsg::ShowPopup(DisplayOverlayItem::SearchForBook { book_id, input_value: None }),
                                                 DropdownInfoPopupEvent::Info => BookPosterItemMsg::ShowPopup(DisplayOverlayItem::Info { book_id }),
                                                 DropdownInfoPopupEvent::MarkAsRead => BookPosterItemMsg::MarkAsRead,
@@ -469,6 +479,7 @@ impl PartialEq for DisplayOverlayItem {
 pub enum DropdownInfoPopupEvent {
     Closed,
     RefreshMetadata,
+    EmbedMetadata,
     AddToCollection(CollectionId),
     RemoveFromCollection(CollectionId),
     MarkAsUnread,
@@ -626,6 +637,13 @@ pub fn _dropdown_info(props: &DropdownInfoPopupProps) -> Html {
                     ) }>{ "Refresh Metadata" }</PopupClose>
                 </OwnerBarrier>
 
+                <OwnerBarrier>
+                    <PopupClose class="dropdown-item" onclick={ on_click_prevdef_cb(
+                        props.event.clone(),
+                        |cb, _| cb.emit(DropdownInfoPopupEvent::EmbedMetadata)
+                    ) }>{ "Write Metadata to File" }</PopupClose>
+                </OwnerBarrier>
+
                 {
                     if let Some(id) = book_list_ctx.collection_id {
                         html! {
@@ -650,14 +668,32 @@ pub fn _dropdown_info(props: &DropdownInfoPopupProps) -> Html {
                         use wasm_bindgen::UnwrapThrowExt;
 
                         html! {
-                            <PopupClose class="dropdown-item" onclick={
-                                Callback::from(move |_| {
-                                    window().open_with_url_and_target(
-                                        &request::get_download_path(Either::Left(book_id)),
-                                        "_blank"
-                                    ).unwrap_throw();
-                                })
-                            }>{ "Download" }</PopupClose>
+                            <>
+                                <PopupClose class="dropdown-item" onclick={
+                                    Callback::from(move |_| {
+                                        window().open_with_url_and_target(
+                                            &request::get_download_path(Either::Left(book_id)),
+                                            "_blank"
+                                        ).unwrap_throw();
+                                    })
+                                }>{ "Download" }</PopupClose>
+                                <PopupClose class="dropdown-item" onclick={
+                                    Callback::from(move |_| {
+                                        window().open_with_url_and_target(
+                                            &format!("{}?embed_metadata=true", request::get_download_path(Either::Left(book_id))),
+                                            "_blank"
+                                        ).unwrap_throw();
+                                    })
+                                }>{ "Download with Metadata" }</PopupClose>
+                                <PopupClose class="dropdown-item" onclick={
+                                    Callback::from(move |_| {
+                                        window().open_with_url_and_target(
+                                            &format!("{}?embed_metadata=true&kepub=true", request::get_download_path(Either::Left(book_id))),
+                                            "_blank"
+                                        ).unwrap_throw();
+                                    })
+                                }>{ "Download for Kobo (KEPUB)" }</PopupClose>
+                            </>
                         }
                     } else {
                         html! {}
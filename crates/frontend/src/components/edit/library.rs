@@ -66,6 +66,37 @@ pub fn _lib_edit(prop: &LibraryEditProperty) -> Html {
         })
     };
 
+    let on_change_allow_scripts = {
+        let library_update = library_update.clone();
+        let update = update.clone();
+
+        Callback::from(move |e: Event| {
+            let mut borrow = library_update.borrow_mut();
+            borrow.allow_scripts = Some(e.target_unchecked_into::<HtmlInputElement>().checked());
+
+            // Drop is needed since update() refreshes the state instantly and we're still borrowing.
+            drop(borrow);
+
+            update();
+        })
+    };
+
+    let on_change_watch_directories = {
+        let library_update = library_update.clone();
+        let update = update.clone();
+
+        Callback::from(move |e: Event| {
+            let mut borrow = library_update.borrow_mut();
+            borrow.watch_directories =
+                Some(e.target_unchecked_into::<HtmlInputElement>().checked());
+
+            // Drop is needed since update() refreshes the state instantly and we're still borrowing.
+            drop(borrow);
+
+            update();
+        })
+    };
+
     let on_add_directory = {
         let library_update = library_update.clone();
         let resp = resp.clone();
@@ -167,6 +198,7 @@ pub fn _lib_edit(prop: &LibraryEditProperty) -> Html {
                 {
                     if let Some(library) = resp.data.as_ref() {
                         let lib_update_ref = library_update.borrow();
+                        let settings = library.parse_settings_or_default().unwrap_or_default();
 
                         html! {
                             <>
@@ -190,6 +222,36 @@ pub fn _lib_edit(prop: &LibraryEditProperty) -> Html {
                                     <label class="form-check-label">{ "Is Library Public" }</label>
                                 </div>
 
+                                <div class="mb-3 form-check">
+                                    <input
+                                        class="form-check-input" type="checkbox"
+                                        checked={ lib_update_ref.allow_scripts.unwrap_or(settings.allow_scripts) }
+                                        onchange={ on_change_allow_scripts }
+                                    />
+                                    <label class="form-check-label">{ "Allow Scripts In Books" }</label>
+                                    <div class="form-text">{ "Only enable this if you trust everyone who can add books to this library." }</div>
+                                </div>
+
+                                <div class="mb-3 form-check">
+                                    <input
+                                        class="form-check-input" type="checkbox"
+                                        checked={ lib_update_ref.watch_directories.unwrap_or(settings.watch_directories) }
+                                        onchange={ on_change_watch_directories }
+                                    />
+                                    <label class="form-check-label">{ "Watch Directories For Changes" }</label>
+                                    <div class="form-text">{ "New, moved and removed books are picked up without a library scan." }</div>
+
+                                    {
+                                        if let Some(error) = library.watch_error.as_deref() {
+                                            html! {
+                                                <div class="label red">{ format!("Unable to watch directories: {error}") }</div>
+                                            }
+                                        } else {
+                                            html! {}
+                                        }
+                                    }
+                                </div>
+
                                 <div class="mb-3">
                                     <label class="form-label" for="asdf">{ "Library Type: " }</label>
                                     <SelectModule<LibraryType>
@@ -199,6 +261,8 @@ pub fn _lib_edit(prop: &LibraryEditProperty) -> Html {
                                     >
                                         <SelectItem<LibraryType> value={ LibraryType::Book } name="Book" />
                                         <SelectItem<LibraryType> value={ LibraryType::ComicBook } name="Comic Book" />
+                                        <SelectItem<LibraryType> value={ LibraryType::Document } name="Document" />
+                                        <SelectItem<LibraryType> value={ LibraryType::AudioBook } name="Audiobook" />
                                     </SelectModule<LibraryType>>
                                 </div>
 
@@ -1,3 +1,4 @@
+pub mod audio_player;
 pub mod book_list;
 mod book_list_item_info;
 pub mod book_poster_item;
@@ -10,6 +11,7 @@ pub mod popup;
 pub mod reader;
 mod sidebar;
 
+pub use audio_player::AudioPlayer;
 pub use book_list::{BookListComponent, BookListRequest, BookListScope};
 pub use book_list_item_info::BookListItemInfo;
 pub use book_poster_item::{BookPosterItem, DropdownInfoPopup, DropdownInfoPopupEvent};
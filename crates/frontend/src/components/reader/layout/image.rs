@@ -1,5 +1,5 @@
 use wasm_bindgen::{prelude::Closure, JsCast, UnwrapThrowExt};
-use web_sys::HtmlIFrameElement;
+use web_sys::{HtmlElement, HtmlIFrameElement};
 use yew::Context;
 
 use crate::{
@@ -53,6 +53,10 @@ impl ImageDisplay {
             )
             .unwrap_throw();
 
+        if self.class_name == "fixed-page" {
+            scale_fixed_pages(iframe);
+        }
+
         let link = ctx.link().clone();
 
         let function =
@@ -209,6 +213,39 @@ impl ImageDisplay {
     }
 }
 
+/// Scale each fixed layout page so its viewport fits inside of the reader.
+fn scale_fixed_pages(iframe: &HtmlIFrameElement) {
+    let document = iframe.content_document().unwrap_throw();
+
+    let pages = document
+        .query_selector_all(".fixed-layout-page > iframe[data-width][data-height]")
+        .unwrap_throw();
+
+    for index in 0..pages.length() {
+        let page = pages
+            .item(index)
+            .unwrap_throw()
+            .unchecked_into::<HtmlElement>();
+
+        let (Some(width), Some(height), Some(container)) = (
+            page.get_attribute("data-width")
+                .and_then(|v| v.parse::<f64>().ok()),
+            page.get_attribute("data-height")
+                .and_then(|v| v.parse::<f64>().ok()),
+            page.parent_element(),
+        ) else {
+            continue;
+        };
+
+        let scale = (container.client_width() as f64 / width)
+            .min(container.client_height() as f64 / height);
+
+        page.style()
+            .set_property("transform", &format!("scale({scale})"))
+            .unwrap_throw();
+    }
+}
+
 impl Clone for ImageDisplay {
     fn clone(&self) -> Self {
         Self {
@@ -6,7 +6,13 @@ use yew::Context;
 
 use super::{section::SectionContents, Reader};
 
-static PAGE_DISPLAYS: [&str; 4] = ["single-page", "double-page", "scrolling-page", "image-page"];
+static PAGE_DISPLAYS: [&str; 5] = [
+    "single-page",
+    "double-page",
+    "scrolling-page",
+    "image-page",
+    "fixed-page",
+];
 
 mod image;
 mod page;
@@ -48,6 +54,11 @@ impl LayoutDisplay {
         Self::Image(ImageDisplay::new("image-page", value))
     }
 
+    /// For fixed layout books. Each page is displayed on its own and scaled to fit its viewport.
+    pub fn new_fixed_page(value: PageMovement) -> Self {
+        Self::Image(ImageDisplay::new("fixed-page", value))
+    }
+
     pub fn as_type(&self) -> LayoutType {
         match self {
             LayoutDisplay::SinglePage(_) => LayoutType::Single,
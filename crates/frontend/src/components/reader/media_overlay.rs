@@ -0,0 +1,71 @@
+//! Reading a chapter aloud with the EPUB Media Overlay and highlighting the element being narrated.
+
+use common_local::api::{MediaOverlayClip, MediaOverlayInfo};
+use web_sys::Element;
+
+/// Two clips are treated as continuous if the next one starts within this many milliseconds.
+///
+/// Prevents seeking (and stuttering) between clips which follow each other in the same audio file.
+const CONTINUOUS_CLIP_GAP: u64 = 100;
+
+pub struct MediaOverlayState {
+    pub chapter: usize,
+    pub info: MediaOverlayInfo,
+
+    /// The clip being narrated.
+    pub clip: Option<usize>,
+
+    /// The element we gave the active class to.
+    highlighted: Option<Element>,
+}
+
+impl MediaOverlayState {
+    pub fn new(chapter: usize, info: MediaOverlayInfo) -> Self {
+        Self {
+            chapter,
+            info,
+            clip: None,
+            highlighted: None,
+        }
+    }
+
+    pub fn current_clip(&self) -> Option<&MediaOverlayClip> {
+        self.info.clips.get(self.clip?)
+    }
+
+    /// Whether the clip after the current one can keep playing without seeking the audio.
+    pub fn is_next_clip_continuous(&self) -> bool {
+        let Some(index) = self.clip else {
+            return false;
+        };
+
+        let (Some(current), Some(next)) =
+            (self.info.clips.get(index), self.info.clips.get(index + 1))
+        else {
+            return false;
+        };
+
+        current.audio == next.audio
+            && current
+                .end
+                .map_or(false, |end| next.begin.abs_diff(end) <= CONTINUOUS_CLIP_GAP)
+    }
+
+    /// Move the active class onto a new element.
+    pub fn set_highlight(&mut self, element: Option<Element>) {
+        if let Some(element) = self.highlighted.take() {
+            let _ = element.class_list().remove_1(&self.info.active_class);
+        }
+
+        if let Some(element) = element {
+            let _ = element.class_list().add_1(&self.info.active_class);
+            self.highlighted = Some(element);
+        }
+    }
+}
+
+impl Drop for MediaOverlayState {
+    fn drop(&mut self) {
+        self.set_highlight(None);
+    }
+}
@@ -1,8 +1,10 @@
 use std::{path::PathBuf, rc::Rc, time::Duration};
 
+use common::api::WrappingResponse;
 use common_local::{
-    reader::{LayoutType, ReaderLoadType},
-    Chapter, DisplayBookItem, MediaItem, Progression,
+    api::ApiGetFileOverlayByIdResponse,
+    reader::{LayoutType, ReaderLoadType, Rendition},
+    Chapter, DisplayBookItem, MediaItem, Progression, TocItem,
 };
 use gloo_timers::callback::{Interval, Timeout};
 use gloo_utils::{body, window};
@@ -11,7 +13,7 @@ use wasm_bindgen::{
     prelude::{wasm_bindgen, Closure},
     JsCast, UnwrapThrowExt,
 };
-use web_sys::{DomRect, Element, HtmlElement, HtmlIFrameElement};
+use web_sys::{DomRect, Element, HtmlElement, HtmlIFrameElement, HtmlMediaElement};
 use yew::{html::Scope, prelude::*};
 
 use crate::{
@@ -21,6 +23,7 @@ use crate::{
 
 pub mod color;
 pub mod layout;
+mod media_overlay;
 pub mod navbar;
 pub mod section;
 mod settings;
@@ -28,6 +31,7 @@ pub mod util;
 pub mod view_overlay;
 
 pub use self::layout::LayoutDisplay;
+use self::media_overlay::MediaOverlayState;
 use self::section::{SectionContents, SectionLoadProgress};
 pub use self::view_overlay::{DragType, OverlayEvent, ViewOverlay};
 pub use settings::*;
@@ -96,7 +100,12 @@ pub struct ReadingInfo {
     pub book: Option<DisplayBookItem>,
     pub file: Option<MediaItem>,
     pub chapters: LoadedChapters,
-    pub table_of_contents: Vec<(String, usize)>,
+    pub table_of_contents: Vec<TocItem>,
+    /// Element id to move to once the chapter is changed. Used by the table of contents.
+    pub jump_to_fragment: Option<String>,
+    /// The chapters can be read aloud with their Media Overlays.
+    pub has_media_overlay: bool,
+    pub rendition: Rendition,
 }
 
 impl ReadingInfo {
@@ -107,6 +116,13 @@ impl ReadingInfo {
     pub fn get_file(&self) -> &MediaItem {
         self.file.as_ref().unwrap()
     }
+
+    /// Comics, documents and fixed layout books are displayed a page at a time.
+    pub fn is_image_based(&self) -> bool {
+        let file = self.get_file();
+
+        file.is_comic_book() || file.is_document() || self.rendition.is_fixed_layout()
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -188,6 +204,12 @@ pub enum ReaderMsg {
     PreviousSection,
     SetSection(usize),
 
+    // Media Overlays
+    ToggleReadAloud,
+    MediaOverlayLoaded(usize, WrappingResponse<ApiGetFileOverlayByIdResponse>),
+    MediaOverlayTimeUpdate,
+    MediaOverlayEnded,
+
     Ignore,
 }
 
@@ -224,6 +246,11 @@ pub struct Reader {
 
     initial_progression_set: bool,
 
+    /// Are we reading the book aloud with its Media Overlays?
+    is_reading_aloud: bool,
+    media_overlay: Option<MediaOverlayState>,
+    media_overlay_audio: NodeRef,
+
     settings: SharedReaderSettings,
     _settings_listener: ContextHandle<SharedReaderSettings>,
     reading_info: UpdatableReadingInfo,
@@ -311,6 +338,10 @@ impl Component for Reader {
             initial_progression_set: false,
             handler: OverlayHandler::default(),
 
+            is_reading_aloud: false,
+            media_overlay: None,
+            media_overlay_audio: NodeRef::default(),
+
             settings,
             _settings_listener,
             reading_info,
@@ -329,14 +360,21 @@ impl Component for Reader {
 
                 self.cached_chapters = self.reading_info.borrow().chapters.chapters.clone();
 
-                let prog = self.reading_info.borrow().progress;
+                let prog = self.reading_info.borrow().progress.clone();
                 if let Some(Progression::Ebook {
                     chapter,
                     char_pos: -1,
                     page: -1,
+                    ..
                 }) = prog
                 {
                     self.set_section(chapter as usize, ctx);
+
+                    let fragment = self.reading_info.borrow_mut().jump_to_fragment.take();
+
+                    if let Some(fragment) = fragment {
+                        self.jump_to_fragment(chapter as usize, &fragment);
+                    }
                 }
 
                 self.load_surrounding_sections(ctx);
@@ -344,7 +382,7 @@ impl Component for Reader {
                 if self.are_all_sections_generated() {
                     // TODO: Ensure this works.
                     if self.settings.type_of == ReaderLoadType::Select {
-                        let prog = self.reading_info.borrow().progress;
+                        let prog = self.reading_info.borrow().progress.clone();
                         self.use_progression(prog, ctx);
                     }
                 }
@@ -938,6 +976,91 @@ impl Component for Reader {
                 self.drag_distance = 0;
             }
 
+            ReaderMsg::ToggleReadAloud => {
+                self.is_reading_aloud = !self.is_reading_aloud;
+
+                if !self.is_reading_aloud {
+                    if let Some(audio) = self.media_overlay_audio.cast::<HtmlMediaElement>() {
+                        audio.pause().unwrap_throw();
+                    }
+                } else if self
+                    .media_overlay
+                    .as_ref()
+                    .map_or(false, |v| v.chapter == self.viewing_section)
+                {
+                    if let Some(audio) = self.media_overlay_audio.cast::<HtmlMediaElement>() {
+                        let _ = audio.play();
+                    }
+                } else {
+                    self.load_media_overlay(self.viewing_section, ctx);
+                }
+            }
+
+            ReaderMsg::MediaOverlayLoaded(chapter, resp) => {
+                // Stopped while loading.
+                if !self.is_reading_aloud {
+                    return false;
+                }
+
+                match resp.ok() {
+                    Ok(Some(info)) if !info.clips.is_empty() => {
+                        self.media_overlay = Some(MediaOverlayState::new(chapter, info));
+                        self.play_media_overlay_clip(0);
+                    }
+
+                    // Chapters without narration are skipped.
+                    Ok(_) => self.next_media_overlay_chapter(chapter, ctx),
+
+                    Err(err) => {
+                        self.is_reading_aloud = false;
+                        crate::display_error(err);
+                    }
+                }
+            }
+
+            ReaderMsg::MediaOverlayTimeUpdate => {
+                let Some((audio, state)) = self
+                    .media_overlay_audio
+                    .cast::<HtmlMediaElement>()
+                    .zip(self.media_overlay.as_ref())
+                else {
+                    return false;
+                };
+
+                let millis = (audio.current_time() * 1000.0) as u64;
+
+                let Some((index, end)) = state.clip.zip(state.current_clip().and_then(|v| v.end))
+                else {
+                    return false;
+                };
+
+                if millis < end {
+                    return false;
+                }
+
+                if state.is_next_clip_continuous() {
+                    self.media_overlay.as_mut().unwrap().clip = Some(index + 1);
+                    self.highlight_media_overlay_clip();
+                } else {
+                    return Component::update(self, ctx, ReaderMsg::MediaOverlayEnded);
+                }
+            }
+
+            ReaderMsg::MediaOverlayEnded => {
+                let Some(state) = self.media_overlay.as_ref() else {
+                    return false;
+                };
+
+                let chapter = state.chapter;
+                let next_clip = state.clip.map_or(0, |v| v + 1);
+
+                if next_clip < state.info.clips.len() {
+                    self.play_media_overlay_clip(next_clip);
+                } else {
+                    self.next_media_overlay_chapter(chapter, ctx);
+                }
+            }
+
             ReaderMsg::UploadProgress => {
                 if self.section_frames.iter().all(|v| v.is_loaded()) {
                     debug!(">>> UploadProgress <<<");
@@ -1068,7 +1191,14 @@ impl Component for Reader {
                     </div>
                 </div>
 
-                { self.render_toolbar() }
+                { self.render_toolbar(ctx) }
+
+                <audio
+                    ref={ self.media_overlay_audio.clone() }
+                    preload="auto"
+                    ontimeupdate={ ctx.link().callback(|_| ReaderMsg::MediaOverlayTimeUpdate) }
+                    onended={ ctx.link().callback(|_| ReaderMsg::MediaOverlayEnded) }
+                />
 
                 {
                     if self.settings.show_progress {
@@ -1129,7 +1259,7 @@ impl Component for Reader {
         if self.are_all_sections_generated() {
             // TODO: Ensure this works.
             if self.settings.type_of == ReaderLoadType::Select {
-                let prog = self.reading_info.borrow().progress;
+                let prog = self.reading_info.borrow().progress.clone();
                 self.use_progression(prog, ctx);
             }
         }
@@ -1167,17 +1297,38 @@ impl Reader {
         // }
     }
 
-    fn render_toolbar(&self) -> Html {
-        if self.settings.display.is_scroll() {
-            html! {}
-        } else {
-            html! {
-                <div class="toolbar">
-                    <div class="d-flex w-100">
-                        <span>{ "Page " } { self.current_page_pos() + 1 } { "/" } { self.page_count() }</span>
-                    </div>
+    fn render_toolbar(&self, ctx: &Context<Self>) -> Html {
+        let has_media_overlay = self.reading_info.borrow().has_media_overlay;
+
+        if self.settings.display.is_scroll() && !has_media_overlay {
+            return html! {};
+        }
+
+        html! {
+            <div class="toolbar">
+                <div class="d-flex w-100">
+                    {
+                        if self.settings.display.is_scroll() {
+                            html! {}
+                        } else {
+                            html! {
+                                <span>{ "Page " } { self.current_page_pos() + 1 } { "/" } { self.page_count() }</span>
+                            }
+                        }
+                    }
+                    {
+                        if has_media_overlay {
+                            html! {
+                                <a onclick={ ctx.link().callback(|_| ReaderMsg::ToggleReadAloud) }>
+                                    { if self.is_reading_aloud { "Pause Narration" } else { "Read Aloud" } }
+                                </a>
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
                 </div>
-            }
+            </div>
         }
     }
 
@@ -1313,6 +1464,7 @@ impl Reader {
                 chapter,
                 char_pos,
                 page,
+                ..
             } = prog
             {
                 let chapter = chapter as usize;
@@ -1369,6 +1521,158 @@ impl Reader {
         }
     }
 
+    /// Move to the element with the id inside of the loaded chapter.
+    fn jump_to_fragment(&mut self, chapter: usize, fragment: &str) {
+        for sec in self.section_frames.iter_mut() {
+            let SectionLoadProgress::Loaded(section) = sec else {
+                continue;
+            };
+
+            if !section.get_chapters().iter().any(|v| v.value == chapter) {
+                continue;
+            }
+
+            let Some(element) = section
+                .find_elements(&format!(
+                    "div[data-section-id=\"{chapter}\"] [id=\"{}\"]",
+                    fragment.replace('"', "\\\"")
+                ))
+                .into_iter()
+                .next()
+            else {
+                return;
+            };
+
+            if self.settings.display.is_scroll() {
+                element.scroll_into_view();
+            } else if let Some(page) = section
+                .get_byte_position_of_element(&element)
+                .and_then(|pos| section.get_page_from_byte_position(pos))
+            {
+                self.cached_display.set_page(page, section);
+            }
+
+            return;
+        }
+    }
+
+    fn load_media_overlay(&self, chapter: usize, ctx: &Context<Self>) {
+        let file_id = self.reading_info.borrow().get_file().id;
+
+        ctx.link().send_future(async move {
+            ReaderMsg::MediaOverlayLoaded(
+                chapter,
+                request::get_book_overlay(file_id, chapter).await,
+            )
+        });
+    }
+
+    /// Continue reading aloud from the start of the next chapter.
+    fn next_media_overlay_chapter(&mut self, chapter: usize, ctx: &Context<Self>) {
+        self.media_overlay = None;
+
+        let next_chapter = chapter + 1;
+
+        if next_chapter < self.reading_info.borrow().get_file().chapter_count {
+            if self.set_section(next_chapter, ctx) {
+                self.upload_progress_and_emit(ctx);
+            }
+
+            self.load_media_overlay(next_chapter, ctx);
+        } else {
+            self.is_reading_aloud = false;
+        }
+    }
+
+    fn play_media_overlay_clip(&mut self, index: usize) {
+        let Some((audio, state)) = self
+            .media_overlay_audio
+            .cast::<HtmlMediaElement>()
+            .zip(self.media_overlay.as_mut())
+        else {
+            return;
+        };
+
+        let Some(clip) = state.info.clips.get(index) else {
+            return;
+        };
+
+        let src = format!(
+            "/api/file/{}/res/{}",
+            self.reading_info.borrow().get_file().id,
+            clip.audio
+        );
+
+        // The src is returned as an absolute URL.
+        if !audio.src().ends_with(&src) {
+            audio.set_src(&src);
+        }
+
+        audio.set_current_time(clip.begin as f64 / 1000.0);
+
+        let _ = audio.play();
+
+        state.clip = Some(index);
+
+        self.highlight_media_overlay_clip();
+    }
+
+    /// Give the element being narrated the active class and move to the page it's on.
+    fn highlight_media_overlay_clip(&mut self) {
+        let Some(state) = self.media_overlay.as_mut() else {
+            return;
+        };
+
+        let Some(fragment) = state
+            .current_clip()
+            .map(|v| v.fragment.replace('"', "\\\""))
+        else {
+            state.set_highlight(None);
+            return;
+        };
+
+        for sec in self.section_frames.iter_mut() {
+            let SectionLoadProgress::Loaded(section) = sec else {
+                continue;
+            };
+
+            if !section
+                .get_chapters()
+                .iter()
+                .any(|v| v.value == state.chapter)
+            {
+                continue;
+            }
+
+            let element = section
+                .find_elements(&format!(
+                    "div[data-section-id=\"{}\"] [id=\"{fragment}\"]",
+                    state.chapter
+                ))
+                .into_iter()
+                .next();
+
+            state.set_highlight(element.clone());
+
+            let Some(element) = element.filter(|_| state.chapter == self.viewing_section) else {
+                return;
+            };
+
+            if self.settings.display.is_scroll() {
+                element.scroll_into_view();
+            } else if let Some(page) = section
+                .get_byte_position_of_element(&element)
+                .and_then(|pos| section.get_page_from_byte_position(pos))
+                .filter(|page| *page != section.page_offset)
+            {
+                self.cached_display.set_page(page, section);
+                self.after_page_change();
+            }
+
+            return;
+        }
+    }
+
     fn are_all_sections_generated(&self) -> bool {
         self.section_frames.iter().all(|v| v.is_loaded())
     }
@@ -1376,12 +1680,12 @@ impl Reader {
     fn update_cached_pages(&mut self) {
         let mut total_page_pos = 0;
 
-        let is_comic_book = self.reading_info.borrow().get_file().is_comic_book();
+        let is_image_based = self.reading_info.borrow().is_image_based();
 
         // TODO: Verify if needed. Or can we do values_mut() we need to have it in asc order
         for chapter in 0..self.section_frames.len() {
             if let SectionLoadProgress::Loaded(ele) = &mut self.section_frames[chapter] {
-                let page_count = if is_comic_book {
+                let page_count = if is_image_based {
                     self.cached_chapters.len()
                 } else {
                     get_iframe_page_count(ele.get_iframe()).max(1)
@@ -1415,7 +1719,7 @@ impl Reader {
         self.set_page(0);
 
         // TODO: Move to Msg::GenerateIFrameLoaded so it's only in a single place.
-        let prog = self.reading_info.borrow().progress;
+        let prog = self.reading_info.borrow().progress.clone();
         self.use_progression(prog, ctx);
 
         info!("> All Frames Generated <");
@@ -1559,11 +1863,13 @@ impl Reader {
                     chapter,
                     char_pos,
                     page,
+                    cfi,
                 }) = state.progress.as_mut()
                 {
                     *chapter = next_section as i64;
                     *char_pos = 0;
                     *page = 0;
+                    *cfi = None;
 
                     true
                 } else {
@@ -1743,7 +2049,8 @@ impl Reader {
             v if v == last_page && chapter == self.section_frames.len().saturating_sub(1) => {
                 let value = Some(Progression::Complete);
 
-                self.reading_info.update(|state| state.progress = value);
+                self.reading_info
+                    .update(|state| state.progress = value.clone());
 
                 value
             }
@@ -1753,6 +2060,7 @@ impl Reader {
                     char_pos,
                     chapter: chapter as i64,
                     page,
+                    cfi: None,
                 });
 
                 let did_update = self.reading_info.update_if(|state| {
@@ -1760,7 +2068,7 @@ impl Reader {
                         false
                     } else {
                         // debug!("|> {:?}", state.progress);
-                        state.progress = value;
+                        state.progress = value.clone();
                         true
                     }
                 });
@@ -1,4 +1,4 @@
-use common_local::Progression;
+use common_local::{Progression, TocItem};
 use yew::{prelude::*, use_state_eq};
 use yew_router::prelude::Link;
 
@@ -134,31 +134,45 @@ fn render_toc(updatable_info: &UpdatableReadingInfo) -> Html {
                     }
                 } else {
                     html! {
-                        for reading_info.table_of_contents.iter().map(|&(ref name, section)| {
-                            html! {
-                                <button
-                                    type="button"
-                                    class="btn btn-secondary btn-sm mb-1"
-                                    onclick={
-                                        updatable_info.reform(move |_, state| {
-                                            if let Some(Progression::Ebook {
-                                                chapter,
-                                                char_pos,
-                                                page,
-                                            }) = state.progress.as_mut()
-                                            {
-                                                *chapter = section as i64;
-                                                *char_pos = -1;
-                                                *page = -1;
-                                            }
-                                        })
-                                    }
-                                >{ name }</button>
-                            }
-                        })
+                        for reading_info.table_of_contents.iter().map(|item| render_toc_item(updatable_info, item, 0))
                     }
                 }
             }
         </div>
     }
 }
+
+fn render_toc_item(updatable_info: &UpdatableReadingInfo, item: &TocItem, depth: usize) -> Html {
+    let section = item.chapter;
+    let fragment = item.fragment.clone();
+
+    html! {
+        <>
+            <button
+                type="button"
+                class="btn btn-secondary btn-sm mb-1 text-start"
+                style={ format!("margin-left: {}em;", depth) }
+                onclick={
+                    updatable_info.reform(move |_, state| {
+                        if let Some(Progression::Ebook {
+                            chapter,
+                            char_pos,
+                            page,
+                            cfi,
+                        }) = state.progress.as_mut()
+                        {
+                            *chapter = section as i64;
+                            *char_pos = -1;
+                            *page = -1;
+                            *cfi = None;
+                        }
+
+                        state.jump_to_fragment = fragment.clone();
+                    })
+                }
+            >{ &item.title }</button>
+
+            { for item.children.iter().map(|child| render_toc_item(updatable_info, child, depth + 1)) }
+        </>
+    }
+}
@@ -251,6 +251,41 @@ impl SectionContents {
         )
     }
 
+    /// Byte position of the element. Counted the same way as [`Self::get_page_from_byte_position`].
+    pub fn get_byte_position_of_element(&self, element: &Node) -> Option<usize> {
+        fn find_element(byte_count: &mut usize, element: &Node, cont: Node) -> bool {
+            if &cont == element {
+                return true;
+            }
+
+            if cont.node_type() == Node::TEXT_NODE {
+                let value = cont.unchecked_ref::<Text>().data();
+
+                if !value.trim().is_empty() {
+                    *byte_count += value.len();
+                }
+            }
+
+            let nodes = cont.child_nodes();
+            for index in 0..nodes.length() {
+                if find_element(byte_count, element, nodes.item(index).unwrap()) {
+                    return true;
+                }
+            }
+
+            false
+        }
+
+        let mut byte_count = 0;
+
+        find_element(
+            &mut byte_count,
+            element,
+            self.get_iframe_body()?.unchecked_into(),
+        )
+        .then_some(byte_count)
+    }
+
     // TODO: Handle images. Potentially add +100 bytes for each image?
     pub fn get_current_byte_pos(&self, is_vertical: bool) -> Option<(usize, usize)> {
         fn find_text_pos(
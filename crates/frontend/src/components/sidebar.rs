@@ -143,11 +143,12 @@ impl Sidebar {
             },
 
             Viewing::Settings => {
-                const ADMIN_LOCATIONS: [(&str, SettingsRoute); 4] = [
+                const ADMIN_LOCATIONS: [(&str, SettingsRoute); 5] = [
                     ("Tasks", SettingsRoute::AdminTasks),
                     ("Members", SettingsRoute::AdminMembers),
                     ("My Server", SettingsRoute::AdminMyServer),
                     ("Libraries", SettingsRoute::AdminLibraries),
+                    ("Trash", SettingsRoute::AdminTrash),
                 ];
 
                 const MEMBERS_LOCATIONS: [(&str, SettingsRoute); 1] =
@@ -249,6 +250,23 @@ impl Sidebar {
                                 { "Library Scan" }
                             </PopupClose>
 
+                            <PopupClose class="dropdown-item" onclick={ scope.callback_future(move |e: MouseEvent| {
+                                e.prevent_default();
+
+                                async move {
+                                    request::run_task(api::RunTaskBody {
+                                        run_search: Some(library_id),
+                                        full_rescan: true,
+
+                                        .. Default::default()
+                                    }).await;
+
+                                    Msg::Ignore
+                                }
+                            }) }>
+                                { "Full Library Rescan" }
+                            </PopupClose>
+
                             <PopupClose class="dropdown-item" onclick={ scope.callback(move |e: MouseEvent| {
                                 e.prevent_default();
                                 e.stop_propagation();
@@ -106,7 +106,7 @@ fn remove_error() {
 enum Msg {
     LoadServerSettings(WrappingResponse<PublicServerSettings>),
     LoadMemberSelf(WrappingResponse<api::GetMemberSelfResponse>),
-    GetTasksResponse(WrappingResponse<Vec<(TaskId, TaskInfo)>>),
+    GetTasksResponse(WrappingResponse<api::GetTasksResponse>),
     LibraryListResults(WrappingResponse<api::GetLibrariesResponse>),
 
     UpdateNavVis(bool),
@@ -197,7 +197,7 @@ impl Component for Model {
             Msg::GetTasksResponse(resp) => {
                 match resp.ok() {
                     Ok(resp) => {
-                        *RUNNING_TASKS.lock().unwrap() = HashMap::from_iter(resp);
+                        *RUNNING_TASKS.lock().unwrap() = HashMap::from_iter(resp.running);
                     }
 
                     Err(e) => display_error(e),
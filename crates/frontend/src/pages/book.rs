@@ -38,6 +38,7 @@ pub enum Msg {
     // Popup Events
     UpdateBook(BookId),
     UnMatchBook(BookId),
+    EmbedMetadata(BookId),
 
     Ignore,
 }
@@ -104,6 +105,14 @@ impl Component for BookPage {
                     Msg::Ignore
                 });
             }
+
+            Msg::EmbedMetadata(book_id) => {
+                ctx.link().send_future(async move {
+                    request::update_book(book_id, &api::PostBookBody::EmbedMetadata).await;
+
+                    Msg::Ignore
+                });
+            }
         }
 
         true
@@ -224,6 +233,7 @@ impl BookPage {
 
                                 DropdownInfoPopupEvent::Closed => Msg::ClosePopup,
                                 DropdownInfoPopupEvent::RefreshMetadata => Msg::UpdateBook(book_id),
+                                DropdownInfoPopupEvent::EmbedMetadata => Msg::EmbedMetadata(book_id),
                                 DropdownInfoPopupEvent::UnMatchBook => Msg::UnMatchBook(book_id),
                                 DropdownInfoPopupEvent::SearchFor => Msg::ShowPopup(LocalPopup::Poster(DisplayOverlayItem::SearchForBook { book_id, input_value: None })),
                                 DropdownInfoPopupEvent::Info => Msg::ShowPopup(LocalPopup::Poster(DisplayOverlayItem::Info { book_id })),
@@ -327,6 +337,15 @@ impl BookPage {
                                         html! {}
                                     }
                                 }
+                                {
+                                    if book.deleted_at.is_some() {
+                                        html! {
+                                            <span class="badge bg-danger">{ "In Trash" }</span>
+                                        }
+                                    } else {
+                                        html! {}
+                                    }
+                                }
                             </div>
 
                             <ExpandableContainerComponent max_expanded_lines=11 overflow_scroll=true>
@@ -364,6 +383,15 @@ impl BookPage {
                                             }) }
                                         >
                                             <h5>{ media.file_name.clone() }</h5>
+                                            {
+                                                if media.deleted_at.is_some() {
+                                                    html! {
+                                                        <div class="text-danger">{ "Missing From Disk" }</div>
+                                                    }
+                                                } else {
+                                                    html! {}
+                                                }
+                                            }
                                             <div><b>{ "File Size: " }</b>{ file_size_bytes_to_readable_string(media.file_size) }</div>
                                             <div><b>{ "File Type: " }</b>{ media.file_type.clone() }</div>
                                             {
@@ -9,6 +9,7 @@ use std::{
 use common::api::WrappingResponse;
 use common_local::{
     api::{self, GetChaptersResponse},
+    reader::Rendition,
     FileId,
 };
 use gloo_events::EventListener;
@@ -19,10 +20,13 @@ use web_sys::Element;
 use yew::{context::ContextHandle, prelude::*};
 
 use crate::{
-    components::reader::{
-        layout::PageMovement, navbar::ReaderNavbar, LayoutDisplay, LoadedChapters, OverlayEvent,
-        Reader, ReaderEvent, ReaderSettings, ReadingInfo, SharedInnerReaderSettings,
-        SharedReaderSettings, UpdatableReadingInfo,
+    components::{
+        reader::{
+            layout::PageMovement, navbar::ReaderNavbar, LayoutDisplay, LoadedChapters,
+            OverlayEvent, Reader, ReaderEvent, ReaderSettings, ReadingInfo,
+            SharedInnerReaderSettings, SharedReaderSettings, UpdatableReadingInfo,
+        },
+        AudioPlayer,
     },
     get_preferences, request,
     util::is_mobile_or_tablet,
@@ -69,6 +73,8 @@ pub struct ReadingBook {
 
     reader_settings: SharedReaderSettings,
     reading_info: UpdatableReadingInfo,
+    /// Only set for audiobooks.
+    audio: Option<Rc<api::AudioBookInfo>>,
 
     last_grabbed_count: usize,
     // TODO: Cache pages
@@ -128,11 +134,15 @@ impl Component for ReadingBook {
                     chapters: LoadedChapters::new(),
                     progress: None,
                     table_of_contents: Vec::new(),
+                    jump_to_fragment: None,
+                    has_media_overlay: false,
+                    rendition: Rendition::default(),
                     file: None,
                     book: None,
                 },
                 ctx.link().callback(Msg::ReadingInfoUpdated),
             ),
+            audio: None,
             last_grabbed_count: 0,
 
             _on_resize_event: {
@@ -238,14 +248,17 @@ impl Component for ReadingBook {
             },
 
             Msg::RetrieveBookFile(resp) => match resp.ok() {
-                Ok(Some(mut resp)) => {
+                Ok(Some(resp)) => {
                     // Get Chapters.
 
                     let file_id = resp.media.id;
 
                     let end = resp.media.chapter_count;
 
-                    if end != 0 {
+                    // Audiobooks are played instead of being rendered.
+                    if resp.audio.is_some() {
+                        self.state.update_nav_visibility.emit(true);
+                    } else if end != 0 {
                         ctx.link().send_future(async move {
                             Msg::RetrievePages(request::get_book_pages(file_id, 0, end).await)
                         });
@@ -264,12 +277,15 @@ impl Component for ReadingBook {
                         });
                     }
 
+                    self.audio = resp.audio.map(Rc::new);
+
                     self.reading_info.update(|store| {
                         store.file = Some(resp.media);
                         store.progress = resp.progress;
 
-                        resp.toc.sort_unstable_by(|a, b| a.1.cmp(&b.1));
                         store.table_of_contents = resp.toc;
+                        store.has_media_overlay = resp.has_media_overlay;
+                        store.rendition = resp.rendition;
                     });
 
                     self.update_settings();
@@ -314,6 +330,20 @@ impl Component for ReadingBook {
             }
         }
 
+        if let Some(audio) = self.audio.clone() {
+            let info = self.reading_info.borrow();
+
+            return html! {
+                <div class="reading-container">
+                    <AudioPlayer
+                        file_id={ ctx.props().id }
+                        info={ audio }
+                        progress={ info.progress.clone() }
+                    />
+                </div>
+            };
+        }
+
         let mut book_class = String::from("book");
 
         if is_full_screen() {
@@ -384,7 +414,9 @@ impl ReadingBook {
 
         let prefs = get_preferences().unwrap().unwrap_or_default();
 
-        let (general, text, image) = match (book.is_comic_book(), is_mobile_or_tablet()) {
+        let is_image_based = info.is_image_based();
+
+        let (general, text, image) = match (is_image_based, is_mobile_or_tablet()) {
             (true, true) => (
                 prefs.image_book.mobile.general,
                 None,
@@ -412,6 +444,10 @@ impl ReadingBook {
         // TODO: Remove this once we have a better way to handle this.
         if book.is_comic_book() {
             general.display = LayoutDisplay::new_image(PageMovement::RightToLeft);
+        } else if book.is_document() {
+            general.display = LayoutDisplay::new_image(PageMovement::LeftToRight);
+        } else if info.rendition.is_fixed_layout() {
+            general.display = LayoutDisplay::new_fixed_page(PageMovement::LeftToRight);
         }
 
         self.reader_settings = SharedReaderSettings::new(SharedInnerReaderSettings {
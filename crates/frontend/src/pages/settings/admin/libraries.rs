@@ -271,6 +271,8 @@ fn new_library(props: &NewLibraryProps) -> Html {
                     >
                         <SelectItem<LibraryType> value={ LibraryType::Book } name="Book" />
                         <SelectItem<LibraryType> value={ LibraryType::ComicBook } name="Comic Book" disabled=true />
+                        <SelectItem<LibraryType> value={ LibraryType::Document } name="Document" />
+                        <SelectItem<LibraryType> value={ LibraryType::AudioBook } name="Audiobook" disabled=true />
                     </SelectModule<LibraryType>>
                 </div>
 
@@ -2,8 +2,10 @@ pub mod libraries;
 pub mod members;
 pub mod myserver;
 pub mod tasks;
+pub mod trash;
 
 pub use libraries::AdminLibrariesPage;
 pub use members::AdminMembersPage;
 pub use myserver::AdminMyServerPage;
 pub use tasks::AdminTaskPage;
+pub use trash::AdminTrashPage;
@@ -1,15 +1,31 @@
-use common_local::ws::{TaskInfo, TaskType, WebsocketNotification};
+use std::collections::HashMap;
+
+use common::api::WrappingResponse;
+use common_local::{
+    api,
+    ws::{TaskId, TaskInfo, TaskOutcome, TaskType, WebsocketNotification},
+};
 use yew::prelude::*;
 use yew_agent::scope_ext::{AgentScopeExt, WorkerBridgeHandle};
 
-use crate::{services::WsEventBus, RUNNING_TASKS};
+use crate::{request, services::WsEventBus, RUNNING_TASKS};
+
+pub enum Msg {
+    // Request Results
+    TasksResults(Box<WrappingResponse<api::ApiGetTasksResponse>>),
+
+    // Events
+    Notification(WebsocketNotification),
+    Cancel(TaskId),
+}
 
 pub struct AdminTaskPage {
     // _producer: WorkerBridgeHandle<WsEventBus>,
+    history: Vec<api::TaskHistoryItem>,
 }
 
 impl Component for AdminTaskPage {
-    type Message = WebsocketNotification;
+    type Message = Msg;
     type Properties = ();
 
     fn create(ctx: &Context<Self>) -> Self {
@@ -17,46 +33,74 @@ impl Component for AdminTaskPage {
             // _producer: {
             //     let link = ctx.link().clone();
             //     ctx.link()
-            //         .bridge_worker(Callback::from(move |e| link.send_message(e)))
+            //         .bridge_worker(Callback::from(move |e| link.send_message(Msg::Notification(e))))
             // },
+            history: Vec::new(),
         }
     }
 
-    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
-            WebsocketNotification::TaskStart { id, name } => {
+            Msg::TasksResults(resp) => match resp.ok() {
+                Ok(resp) => {
+                    *RUNNING_TASKS.lock().unwrap() = HashMap::from_iter(resp.running);
+                    self.history = resp.history;
+                }
+
+                Err(err) => crate::display_error(err),
+            },
+
+            Msg::Cancel(id) => {
+                ctx.link().send_future(async move {
+                    request::cancel_task(id).await;
+
+                    Msg::TasksResults(Box::new(request::get_tasks().await))
+                });
+            }
+
+            Msg::Notification(WebsocketNotification::TaskStart { id, name }) => {
                 RUNNING_TASKS.lock().unwrap().insert(
                     id,
                     TaskInfo {
                         name,
                         current: None,
+                        progress: None,
                     },
                 );
             }
 
-            WebsocketNotification::TaskUpdate {
+            Msg::Notification(WebsocketNotification::TaskUpdate {
                 id,
                 type_of,
                 inserting,
-            } => {
+                progress,
+            }) => {
                 if let Some(info) = RUNNING_TASKS.lock().unwrap().get_mut(&id) {
                     if inserting {
                         info.current = Some(type_of);
                     } else {
                         info.current = None;
                     }
+
+                    if progress.is_some() {
+                        info.progress = progress;
+                    }
                 }
             }
 
-            WebsocketNotification::TaskEnd(id) => {
+            Msg::Notification(WebsocketNotification::TaskEnd(id)) => {
                 RUNNING_TASKS.lock().unwrap().remove(&id);
+
+                // Reload to show it in the history.
+                ctx.link()
+                    .send_future(async { Msg::TasksResults(Box::new(request::get_tasks().await)) });
             }
         }
 
         true
     }
 
-    fn view(&self, _ctx: &Context<Self>) -> Html {
+    fn view(&self, ctx: &Context<Self>) -> Html {
         // let member = get_member_self().unwrap();
 
         let tasks = RUNNING_TASKS.lock().unwrap();
@@ -76,10 +120,24 @@ impl Component for AdminTaskPage {
                                 }
                             } else {
                                 html! {
-                                    for tasks.values()
-                                        .map(|task| html! {
+                                    for tasks.iter()
+                                        .map(|(&id, task)| html! {
                                             <div>
-                                                <h4>{ task.name.clone() }</h4>
+                                                <h4>
+                                                    { task.name.clone() }
+                                                    <button class="btn btn-danger btn-sm ms-2" onclick={ ctx.link().callback(move |_| Msg::Cancel(id)) }>{ "Cancel" }</button>
+                                                </h4>
+
+                                                {
+                                                    for task.progress
+                                                        .map(|progress| html! {
+                                                            <div class="progress" title={ format!("{} / {}", progress.current, progress.total) }>
+                                                                <div class="progress-bar" style={ format!("width: {}%;", progress.percentage()) }>
+                                                                    { format!("{:.0}%", progress.percentage()) }
+                                                                </div>
+                                                            </div>
+                                                        })
+                                                }
 
                                                 {
                                                     for task.current.clone()
@@ -96,9 +154,55 @@ impl Component for AdminTaskPage {
                         }
                     </div>
                 </div>
+
+                <br />
+
+                <h2>{ "History" }</h2>
+
+                {
+                    if self.history.is_empty() {
+                        html! {
+                            <h4>{ "Nothing has ran yet" }</h4>
+                        }
+                    } else {
+                        html! {
+                            <table class="table table-dark">
+                                <thead>
+                                    <tr>
+                                        <th>{ "Task" }</th>
+                                        <th>{ "Outcome" }</th>
+                                        <th>{ "Items" }</th>
+                                        <th>{ "Started" }</th>
+                                        <th>{ "Ended" }</th>
+                                    </tr>
+                                </thead>
+                                <tbody>
+                                    {
+                                        for self.history.iter().map(|item| html! {
+                                            <tr>
+                                                <td>{ item.name.clone() }</td>
+                                                <td title={ item.error.clone().unwrap_or_default() }>{ render_outcome(item.outcome) }</td>
+                                                <td>{ item.items_processed }</td>
+                                                <td>{ item.started_at.format("%Y-%m-%d %H:%M:%S").to_string() }</td>
+                                                <td>{ item.ended_at.format("%Y-%m-%d %H:%M:%S").to_string() }</td>
+                                            </tr>
+                                        })
+                                    }
+                                </tbody>
+                            </table>
+                        }
+                    }
+                }
             </div>
         }
     }
+
+    fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
+        if first_render {
+            ctx.link()
+                .send_future(async { Msg::TasksResults(Box::new(request::get_tasks().await)) });
+        }
+    }
 }
 
 fn render_type_of(type_of: TaskType) -> String {
@@ -107,6 +211,16 @@ fn render_type_of(type_of: TaskType) -> String {
             subtitle.unwrap_or_else(|| format!("Updating {id:?}"))
         }
 
+        TaskType::UpdatingPerson { name, .. } => format!("Updating {name}"),
+
         TaskType::LibraryScan(file_name) => file_name,
     }
 }
+
+fn render_outcome(outcome: TaskOutcome) -> &'static str {
+    match outcome {
+        TaskOutcome::Finished => "Finished",
+        TaskOutcome::Failed => "Failed",
+        TaskOutcome::Cancelled => "Cancelled",
+    }
+}
@@ -0,0 +1,148 @@
+use chrono::{TimeZone, Utc};
+use common::api::WrappingResponse;
+use common_local::{api, util::file_size_bytes_to_readable_string, FileId};
+use yew::prelude::*;
+
+use crate::request;
+
+pub enum Msg {
+    // Request Results
+    TrashResults(Box<WrappingResponse<api::ApiGetTrashResponse>>),
+
+    // Events
+    Restore(FileId),
+    Purge(FileId),
+    PurgeAll,
+}
+
+pub struct AdminTrashPage {
+    resp: Option<api::GetTrashResponse>,
+}
+
+impl Component for AdminTrashPage {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self { resp: None }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::TrashResults(resp) => match resp.ok() {
+                Ok(resp) => self.resp = Some(resp),
+                Err(err) => crate::display_error(err),
+            },
+
+            Msg::Restore(id) => {
+                ctx.link().send_future(async move {
+                    request::restore_trash_item(id).await;
+
+                    Msg::TrashResults(Box::new(request::get_trash(None, None).await))
+                });
+            }
+
+            Msg::Purge(id) => {
+                ctx.link().send_future(async move {
+                    request::purge_trash_item(id).await;
+
+                    Msg::TrashResults(Box::new(request::get_trash(None, None).await))
+                });
+            }
+
+            Msg::PurgeAll => {
+                ctx.link().send_future(async move {
+                    request::purge_trash().await;
+
+                    Msg::TrashResults(Box::new(request::get_trash(None, None).await))
+                });
+            }
+        }
+
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let render = if let Some(resp) = self.resp.as_ref() {
+            html! {
+                <div>
+                    <h2>{ "Trash" }</h2>
+                    <p>{ format!("Files missing from disk are purged after {} days.", resp.retention_days) }</p>
+
+                    {
+                        if resp.items.is_empty() {
+                            html! {
+                                <h4>{ "Nothing in the trash" }</h4>
+                            }
+                        } else {
+                            html! {
+                                <>
+                                    <button class="btn btn-danger btn-sm mb-3" onclick={ ctx.link().callback(|_| Msg::PurgeAll) }>
+                                        { format!("Purge All ({})", resp.total) }
+                                    </button>
+
+                                    <table class="table table-dark">
+                                        <thead>
+                                            <tr>
+                                                <th>{ "Book" }</th>
+                                                <th>{ "Path" }</th>
+                                                <th>{ "Size" }</th>
+                                                <th>{ "Removed" }</th>
+                                                <th></th>
+                                            </tr>
+                                        </thead>
+                                        <tbody>
+                                            {
+                                                for resp.items.iter().map(|item| {
+                                                    let id = item.file.id;
+
+                                                    html! {
+                                                        <tr>
+                                                            <td>{ item.book.as_ref().map(|v| v.title.clone()).unwrap_or_else(|| item.file.file_name.clone()) }</td>
+                                                            <td>{ item.file.path.clone() }</td>
+                                                            <td>{ file_size_bytes_to_readable_string(item.file.file_size) }</td>
+                                                            <td>{ render_date(item.file.deleted_at) }</td>
+                                                            <td>
+                                                                <button class="btn btn-success btn-sm me-1" onclick={ ctx.link().callback(move |_| Msg::Restore(id)) }>{ "Restore" }</button>
+                                                                <button class="btn btn-danger btn-sm" onclick={ ctx.link().callback(move |_| Msg::Purge(id)) }>{ "Purge" }</button>
+                                                            </td>
+                                                        </tr>
+                                                    }
+                                                })
+                                            }
+                                        </tbody>
+                                    </table>
+                                </>
+                            }
+                        }
+                    }
+                </div>
+            }
+        } else {
+            html! {
+                <h1>{ "Loading..." }</h1>
+            }
+        };
+
+        html! {
+            <div class="view-container">
+                { render }
+            </div>
+        }
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
+        if first_render {
+            ctx.link().send_future(async {
+                Msg::TrashResults(Box::new(request::get_trash(None, None).await))
+            });
+        }
+    }
+}
+
+fn render_date(millis: Option<i64>) -> String {
+    millis
+        .and_then(|v| Utc.timestamp_millis_opt(v).single())
+        .map(|v| v.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
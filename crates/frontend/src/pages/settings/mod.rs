@@ -24,6 +24,9 @@ pub enum SettingsRoute {
     #[at("/settings/tasks")]
     AdminTasks,
 
+    #[at("/settings/trash")]
+    AdminTrash,
+
     // General Routes
     #[at("/settings/general")]
     MemberGeneral,
@@ -33,7 +36,11 @@ impl SettingsRoute {
     pub fn is_admin(&self) -> bool {
         matches!(
             self,
-            Self::AdminLibraries | Self::AdminMembers | Self::AdminMyServer | Self::AdminTasks
+            Self::AdminLibraries
+                | Self::AdminMembers
+                | Self::AdminMyServer
+                | Self::AdminTasks
+                | Self::AdminTrash
         )
     }
 }
@@ -45,6 +52,7 @@ pub fn switch_settings(route: SettingsRoute) -> Html {
         SettingsRoute::AdminMembers => html! { <AdminMembersPage /> },
         SettingsRoute::AdminMyServer => html! { <AdminMyServerPage /> },
         SettingsRoute::AdminTasks => html! { <AdminTaskPage /> },
+        SettingsRoute::AdminTrash => html! { <AdminTrashPage /> },
 
         // Members
         SettingsRoute::MemberGeneral => html! { <MemberGeneralPage /> },
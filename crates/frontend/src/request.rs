@@ -12,11 +12,8 @@ use wasm_bindgen_futures::JsFuture;
 use web_sys::{Headers, Request, RequestInit, RequestMode, Response};
 
 use common_local::{
-    api::*,
-    filter::FilterContainer,
-    setup::SetupConfig,
-    ws::{TaskId, TaskInfo},
-    CollectionId, FileId, LibraryId, MemberUpdate, Progression, PublicServerSettings, SearchType,
+    api::*, filter::FilterContainer, setup::SetupConfig, ws::TaskId, CollectionId, FileId,
+    LibraryId, MemberUpdate, Progression, PublicServerSettings, SearchType,
 };
 
 pub fn get_download_path(value: Either<BookId, FileId>) -> String {
@@ -308,6 +305,19 @@ pub async fn get_book_pages(
     .unwrap_or_else(def)
 }
 
+pub async fn get_book_overlay(
+    file_id: FileId,
+    chapter: usize,
+) -> WrappingResponse<ApiGetFileOverlayByIdResponse> {
+    fetch(
+        "GET",
+        &format!("/api/file/{file_id}/overlay/{chapter}"),
+        Option::<&()>::None,
+    )
+    .await
+    .unwrap_or_else(def)
+}
+
 pub fn compile_book_resource_path(
     file_id: FileId,
     location: &Path,
@@ -416,12 +426,68 @@ pub async fn run_task(value: RunTaskBody) -> WrappingResponse<String> {
         .unwrap_or_else(def)
 }
 
-pub async fn get_tasks() -> WrappingResponse<Vec<(TaskId, TaskInfo)>> {
+pub async fn get_tasks() -> WrappingResponse<ApiGetTasksResponse> {
     fetch("GET", "/api/tasks", Option::<&()>::None)
         .await
         .unwrap_or_else(def)
 }
 
+pub async fn cancel_task(id: TaskId) -> WrappingResponse<ApiPostCancelTaskResponse> {
+    fetch(
+        "POST",
+        &format!("/api/task/{id}/cancel"),
+        Option::<&()>::None,
+    )
+    .await
+    .unwrap_or_else(def)
+}
+
+// Trash
+
+pub async fn get_trash(
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> WrappingResponse<ApiGetTrashResponse> {
+    let mut url = String::from("/api/trash?");
+
+    if let Some(value) = offset {
+        url += "offset=";
+        url += &value.to_string();
+        url += "&";
+    }
+
+    if let Some(value) = limit {
+        url += "limit=";
+        url += &value.to_string();
+    }
+
+    fetch("GET", &url, Option::<&()>::None)
+        .await
+        .unwrap_or_else(def)
+}
+
+pub async fn restore_trash_item(id: FileId) -> WrappingResponse<String> {
+    fetch(
+        "POST",
+        &format!("/api/trash/{id}/restore"),
+        Option::<&()>::None,
+    )
+    .await
+    .unwrap_or_else(def)
+}
+
+pub async fn purge_trash_item(id: FileId) -> WrappingResponse<String> {
+    fetch("DELETE", &format!("/api/trash/{id}"), Option::<&()>::None)
+        .await
+        .unwrap_or_else(def)
+}
+
+pub async fn purge_trash() -> WrappingResponse<String> {
+    fetch("DELETE", "/api/trash", Option::<&()>::None)
+        .await
+        .unwrap_or_else(def)
+}
+
 // Login In
 
 pub async fn login_with_password(email: String, password: String) -> WrappingResponse<String> {
@@ -123,6 +123,11 @@ impl<V: 'static> UpdatableContext<V> {
         self.content.borrow()
     }
 
+    /// Mutate the value without notifying the listener.
+    pub fn borrow_mut(&self) -> std::cell::RefMut<'_, V> {
+        self.content.borrow_mut()
+    }
+
     pub fn set_updated(&mut self) {
         self.last_updated = Utc::now();
     }